@@ -1,11 +1,11 @@
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 
 use miette::{IntoDiagnostic, Result};
 use tokio::{
 	sync::{mpsc, watch},
 	time::sleep,
 };
-use watchexec::{event::Event, fs};
+use watchexec::{config::OverflowPolicy, event::Event, fs, metrics::Counters};
 
 // Run with: `env RUST_LOG=debug cargo run --example fs`,
 // then touch some files within the first 15 seconds, and afterwards.
@@ -16,6 +16,10 @@ async fn main() -> Result<()> {
 	let (ev_s, mut ev_r) = mpsc::channel::<Event>(1024);
 	let (er_s, mut er_r) = mpsc::channel(64);
 	let (wd_s, wd_r) = watch::channel(fs::WorkingData::default());
+	let (_sd_s, sd_r) = watch::channel(false);
+	let (_ps_s, ps_r) = watch::channel(false);
+	let (wp_s, _) = watch::channel(Vec::new());
+	let (ap_s, _) = watch::channel(0);
 
 	let mut wkd = fs::WorkingData::default();
 	wkd.pathset = vec![".".into()];
@@ -41,7 +45,18 @@ async fn main() -> Result<()> {
 		wd_s
 	});
 
-	fs::worker(wd_r, er_s, ev_s).await?;
+	fs::worker(
+		wd_r,
+		er_s,
+		ev_s,
+		sd_r,
+		ps_r,
+		wp_s,
+		ap_s,
+		OverflowPolicy::Block,
+		Arc::new(Counters::default()),
+	)
+	.await?;
 	wd_sh.await.into_diagnostic()?;
 
 	Ok(())
@@ -0,0 +1,65 @@
+use std::fs::read_to_string;
+
+use miette::{IntoDiagnostic, Result};
+use watchexec::{
+	action::{Action, Outcome},
+	config::{InitConfig, RuntimeConfig},
+	error::ReconfigError,
+	signal::source::MainSignal,
+	Watchexec,
+};
+
+// Run with: `env RUST_LOG=debug cargo run --example reload`, then edit `reload-paths.txt` (one
+// path per line) and send a SIGHUP to the printed PID to reload the set of watched paths.
+#[tokio::main]
+async fn main() -> Result<()> {
+	tracing_subscriber::fmt::init();
+	eprintln!("PID: {}", std::process::id());
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.pathset(read_paths());
+	runtime.command(["date"]);
+
+	let wx = Watchexec::new(InitConfig::default(), runtime.clone())?;
+	let w = wx.clone();
+
+	let config = runtime.clone();
+	runtime.on_action(move |action: Action| {
+		let mut config = config.clone();
+		let w = w.clone();
+		async move {
+			let sigs = action
+				.events
+				.iter()
+				.flat_map(|event| event.signals())
+				.collect::<Vec<_>>();
+
+			if sigs.contains(&MainSignal::Interrupt) || sigs.contains(&MainSignal::Terminate) {
+				action.outcome(Outcome::Exit);
+			} else if sigs.contains(&MainSignal::Hangup) {
+				let paths = read_paths();
+				eprintln!("Reloading, now watching: {:?}", paths);
+				config.pathset(paths).keep_action();
+				w.reconfigure(config)?;
+			} else {
+				action.outcome(Outcome::if_running(
+					Outcome::both(Outcome::Stop, Outcome::Start),
+					Outcome::Start,
+				));
+			}
+
+			Ok::<(), ReconfigError>(())
+		}
+	});
+
+	wx.reconfigure(runtime)?;
+	wx.main().await.into_diagnostic()??;
+
+	Ok(())
+}
+
+fn read_paths() -> Vec<String> {
+	read_to_string("reload-paths.txt")
+		.map(|s| s.lines().map(|l| l.trim().to_owned()).collect())
+		.unwrap_or_else(|_| vec![".".into()])
+}
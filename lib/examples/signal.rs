@@ -1,8 +1,9 @@
-use std::process::exit;
+use std::{process::exit, sync::Arc};
 
 use miette::Result;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, watch, Notify};
 use watchexec::{
+	config::OverflowPolicy,
 	event::{Event, Tag},
 	signal::{self, source::MainSignal},
 };
@@ -16,6 +17,7 @@ async fn main() -> Result<()> {
 
 	let (ev_s, mut ev_r) = mpsc::channel::<Event>(1024);
 	let (er_s, mut er_r) = mpsc::channel(64);
+	let (_sd_s, sd_r) = watch::channel(false);
 
 	tokio::spawn(async move {
 		while let Some(e) = ev_r.recv().await {
@@ -34,7 +36,15 @@ async fn main() -> Result<()> {
 	});
 
 	tracing::info!("PID is {}", std::process::id());
-	signal::source::worker(er_s.clone(), ev_s.clone()).await?;
+	signal::source::worker(
+		er_s.clone(),
+		ev_s.clone(),
+		sd_r,
+		OverflowPolicy::Block,
+		None,
+		Arc::new(Notify::new()),
+	)
+	.await?;
 
 	Ok(())
 }
@@ -1,46 +1,131 @@
 //! Processor responsible for receiving events, filtering them, and scheduling actions in response.
 
 use std::{
-	sync::Arc,
-	time::{Duration, Instant},
+	collections::{HashMap, HashSet, VecDeque},
+	mem::take,
+	path::{Path, PathBuf},
+	process::Stdio,
+	sync::{atomic::Ordering, Arc},
+	time::Duration,
 };
 
 use clearscreen::ClearScreen;
+use futures::FutureExt;
+use is_terminal::IsTerminal;
 use tokio::{
-	sync::{mpsc, watch},
-	time::timeout,
+	select, spawn,
+	sync::{broadcast, mpsc, watch, Mutex, MutexGuard, Notify},
+	time::{sleep, timeout, Instant},
 };
-use tracing::{debug, trace, warn};
+use tracing::{debug, info, trace, warn};
 
 use crate::{
-	command::Supervisor,
+	command::{merge_completions, Capture, EventsFile, Supervisor, SupervisorSet, Timeout},
 	error::{CriticalError, RuntimeError},
-	event::Event,
+	event::{filekind::FileEventKind, Event, Source, Tag},
+	filter::expr::{ExprFilterer, Filter},
 	handler::{rte, Handler},
+	metrics::{self, Counters},
+	paths::summarise_events_to_env,
+	signal::{process::SubSignal, source::MainSignal},
+	ShutdownReason,
 };
 
+use crash_loop::{CrashLoop, Guard};
 #[doc(inline)]
-pub use outcome::Outcome;
+pub use outcome::{Callback, CapturedOutput, OnBusyUpdate, Outcome, OutputCallback};
 #[doc(inline)]
 pub use workingdata::*;
 
+mod crash_loop;
 mod outcome;
 mod workingdata;
 
+/// The metadata key marking an event as followed by more of its batch.
+///
+/// This is how [`Watchexec::inject_events()`](crate::Watchexec::inject_events()) sends a batch.
+pub const BATCH_MORE: &str = "watchexec-batch-more";
+
+/// How long the worker waits for the next event of a batch before giving up on it.
+pub const BATCH_STALL: Duration = Duration::from_secs(1);
+
+/// How long to wait for the command to die when force-quitting.
+const FORCE_QUIT_GRACE: Duration = Duration::from_secs(1);
+
+/// How many events of a set of parallel commands can wait for the merger.
+const SET_EVENTS_BUFFER: usize = 64;
+
+/// How many events can be held while waiting for a configuration swap, on top of the event channel.
+const SWAP_BACKLOG: usize = 1024;
+
 /// The main worker of a Watchexec process.
 ///
 /// This is the main loop of the process. It receives events from the event channel, filters them,
 /// debounces them, obtains the desired outcome of an actioned event, calls the appropriate handlers
 /// and schedules processes as needed.
+///
+/// Process completion events go through the same pipeline as any other event, so handlers can react
+/// to them. To avoid endless loops, a command which was started by an action made only of
+/// completion events cannot have its own completion start another command: `Start` and `Restart`
+/// outcomes are then ignored (with a warning) until an action triggered by some other event starts
+/// one.
+///
+/// The ID of the running command (or its process group, if grouped) is published to `child_pid`,
+/// and reset to `None` once it ends. The outcome of every action, once resolved (and after the loop
+/// guard above has been applied), is published to `last_outcome` before it is applied.
+///
+/// Every event received is also sent to `observers` as is, before filtering. This never waits: if
+/// there are no observers the event is discarded, and observers which can't keep up miss events.
+///
+/// Events received, events filtered out, actions, and suppressed actions are counted in `metrics`.
+///
+/// Events carrying the [`BATCH_MORE`] metadata key (which is removed on receipt) are followed by
+/// more events of the same batch: no action is started until the batch ends, whatever the
+/// throttle, so the whole batch lands in one action. If no event arrives for [`BATCH_STALL`] in the
+/// middle of a batch, it's considered abandoned and the throttle applies again.
+///
+/// When `quit` is notified, the command is killed and the worker stops at once with
+/// [`CriticalError::Exit`] (for an interrupt), even in the middle of applying an outcome. This is
+/// how the signal worker [force-quits](crate::config::InitConfig#structfield.force_quit_window).
+///
+/// Applying an outcome, be it of an action or mapped from a signal, is done while holding `swap`.
+/// This is how [`Watchexec::reconfigure_atomic()`](crate::Watchexec::reconfigure_atomic()) keeps
+/// actions from running while it changes the configuration; events are still received meanwhile,
+/// up to a point.
+/// If the working data changed since the events of an action were filtered, they're filtered again
+/// before it's run, so an action never mixes events passed by an old configuration with a new one.
+///
+/// The worker stops when `shutdown` is set to `true`. This is only checked while waiting for events,
+/// so an action which is being applied will be completed first; events which have been collected
+/// but not yet actioned are discarded.
+#[allow(clippy::too_many_arguments)]
 pub async fn worker(
-	working: watch::Receiver<WorkingData>,
+	mut working: watch::Receiver<WorkingData>,
 	errors: mpsc::Sender<RuntimeError>,
 	events_tx: mpsc::Sender<Event>,
 	mut events: mpsc::Receiver<Event>,
+	mut shutdown: watch::Receiver<bool>,
+	child_pid: watch::Sender<Option<u32>>,
+	last_outcome: watch::Sender<Option<Outcome>>,
+	observers: broadcast::Sender<Event>,
+	metrics: Arc<Counters>,
+	quit: Arc<Notify>,
+	swap: Arc<Mutex<()>>,
 ) -> Result<(), CriticalError> {
+	let child_pid = Arc::new(child_pid);
 	let mut last = Instant::now();
+	let mut latest = last;
+	let mut last_action: Option<Instant> = None;
 	let mut set = Vec::new();
-	let mut process: Option<Supervisor> = None;
+	let mut backlog = VecDeque::new();
+	let mut seen = HashSet::new();
+	let mut compiled_filters = None;
+	let mut process: Option<SupervisorSet> = None;
+	let mut started_by_completion = false;
+	let mut in_batch = false;
+	let mut crash_loop = CrashLoop::default();
+	let mut delayed: Option<Delayed> = None;
+	let mut queued: Option<Delayed> = None;
 
 	let mut action_handler =
 		{ working.borrow().action_handler.take() }.ok_or(CriticalError::MissingHandler)?;
@@ -48,13 +133,115 @@ pub async fn worker(
 		{ working.borrow().pre_spawn_handler.take() }.ok_or(CriticalError::MissingHandler)?;
 	let mut post_spawn_handler =
 		{ working.borrow().post_spawn_handler.take() }.ok_or(CriticalError::MissingHandler)?;
+	let mut post_exit_handler =
+		{ working.borrow().post_exit_handler.take() }.ok_or(CriticalError::MissingHandler)?;
+	let mut spawned = HashMap::new();
+
+	if working.borrow().run_on_start {
+		debug!("run on start enabled, queueing initial empty event");
+		set.push(Event::default());
+		last = Instant::now();
+		latest = last;
+	}
 
 	loop {
-		let maxtime = if set.is_empty() {
+		let due = take_due(&mut delayed).or_else(|| take_queued(&mut queued, &process));
+		if let Some(due) = due {
+			let _swap = hold_swap(&swap, &mut events, &mut backlog).await;
+			let is_running = process.as_ref().map(|p| p.is_running()).unwrap_or(false);
+			let on_busy = working.borrow().on_busy;
+			let (outcome, later) = due.outcome.resolve(is_running).split_delay();
+			let (outcome, queue) = if is_running {
+				outcome.when_busy(&on_busy)
+			} else {
+				(outcome, None)
+			};
+			let only_completions = due.events.iter().all(Event::is_completion);
+			let outcome = if only_completions && started_by_completion {
+				outcome.without_start()
+			} else {
+				outcome
+			};
+			let mut w = working.borrow().clone();
+			let (outcome, backoff) =
+				guard_crash_loop(&mut crash_loop, &w, outcome, &observers, &errors, &metrics)
+					.await?;
+			schedule_delay(
+				&mut delayed,
+				&outcome,
+				later,
+				&due.events,
+				due.workdir.clone(),
+				&on_busy,
+			);
+			schedule_queue(
+				&mut queued,
+				&outcome,
+				queue,
+				&due.events,
+				due.workdir.clone(),
+			);
+			debug!(?outcome, "applying delayed or queued outcome");
+			last_outcome.send_replace(Some(outcome.clone()));
+
+			let previous_id = process.as_ref().map(|p| p.id());
+			if let Some(dir) = due.workdir {
+				w.workdir = Some(dir);
+			}
+
+			let rerr = if w.dry_run {
+				would_have_run(outcome, &observers)
+			} else {
+				let applied = select! {
+					res = async {
+						if let Some(delay) = backoff {
+							sleep(delay).await;
+						}
+						apply_outcome(
+							outcome,
+							due.events.clone(),
+							w,
+							&mut process,
+							&mut pre_spawn_handler,
+							&mut post_spawn_handler,
+							errors.clone(),
+							events_tx.clone(),
+							child_pid.clone(),
+						)
+						.await
+					} => Some(res),
+					_ = quit.notified() => None,
+				};
+				match applied {
+					Some(res) => res,
+					None => return force_quit(&mut process).await,
+				}
+			};
+
+			let current_id = process.as_ref().map(|p| p.id());
+			if current_id.is_some() && current_id != previous_id {
+				started_by_completion = only_completions;
+				crash_loop.started(Instant::now());
+			}
+			record_spawn(
+				&mut spawned,
+				&process,
+				previous_id,
+				&working.borrow(),
+				&due.events,
+			);
+			report_outcome_error(rerr, &due.events, &errors).await?;
+			continue;
+		}
+
+		let maxtime = if in_batch {
+			trace!("in the middle of a batch, waiting for the rest");
+			BATCH_STALL
+		} else if set.is_empty() {
 			trace!("nothing in set, waiting forever for next event");
 			Duration::from_secs(u64::MAX)
 		} else {
-			working.borrow().throttle.saturating_sub(last.elapsed())
+			remaining_wait(&working.borrow(), last, latest, last_action)
 		};
 
 		if maxtime.is_zero() {
@@ -67,33 +254,171 @@ pub async fn worker(
 			}
 		} else {
 			trace!(?maxtime, "waiting for event");
-			match timeout(maxtime, events.recv()).await {
+			let wait = delayed.as_ref().map_or(maxtime, |d| {
+				maxtime.min(d.at.saturating_duration_since(Instant::now()))
+			});
+			let recv = select! {
+				recv = timeout(wait, next_event(&mut backlog, &mut events)) => recv,
+				Ok(_) = shutdown.changed() => {
+					debug!("shutdown requested, stopping action worker");
+					break;
+				}
+				_ = quit.notified() => return force_quit(&mut process).await,
+				res = ended(&mut process), if queued.is_some() => {
+					if let Err(err) = res {
+						errors.send(err).await?;
+					}
+					trace!("command ended, applying the queued outcome");
+					continue;
+				}
+			};
+
+			match recv {
 				Err(_timeout) => {
+					if delayed.as_ref().map_or(false, |d| d.at <= Instant::now()) {
+						trace!("delayed outcome is due");
+						continue;
+					}
+
+					if in_batch {
+						warn!("batch of events stalled, no longer waiting for the rest");
+						in_batch = false;
+					}
 					trace!("timed out, cycling");
 					continue;
 				}
 				Ok(None) => break,
-				Ok(Some(event)) => {
-					trace!(?event, "got event");
+				Ok(Some(mut event)) => {
+					in_batch = event.metadata.remove(BATCH_MORE).is_some();
+					trace!(?event, %in_batch, "got event");
+					observers.send(event.clone()).ok();
+					metrics::add(&metrics.events_received, 1);
+
+					if let Some(exited) = exited(&mut spawned, &event) {
+						if let Some(h) = working.borrow().post_exit_handler.take() {
+							trace!("post-exit handler updated");
+							post_exit_handler = h;
+						}
+
+						debug!("running post-exit handler");
+						if let Err(err) = post_exit_handler
+							.handle(exited)
+							.map_err(|e| rte("action post-exit", e))
+						{
+							errors.send(err).await?;
+						}
+					}
+
+					let mapper = working.borrow().event_mapper.clone();
+					let event = match mapper(event) {
+						Some(event) => event,
+						None => {
+							trace!("event mapper dropped event");
+							metrics::add(&metrics.events_filtered, 1);
+							continue;
+						}
+					};
+
+					let mapped = signal_outcome(&working.borrow(), &event);
+					if let Some(outcome) = mapped {
+						let is_running = process.as_ref().map(|p| p.is_running()).unwrap_or(false);
+						let outcome = outcome.resolve(is_running);
+						debug!(?outcome, "applying outcome mapped from signal");
+						let _swap = hold_swap(&swap, &mut events, &mut backlog).await;
+						let w = working.borrow().clone();
+						let previous_id = process.as_ref().map(|p| p.id());
+						let events = Arc::new(vec![event]);
+						let rerr = if w.dry_run {
+							would_have_run(outcome, &observers)
+						} else {
+							let (outcome, later) = outcome.split_delay();
+							schedule_delay(
+								&mut delayed,
+								&outcome,
+								later,
+								&events,
+								None,
+								&w.on_busy,
+							);
+							schedule_queue(&mut queued, &outcome, None, &events, None);
+							let applied = select! {
+								res = apply_outcome(
+									outcome,
+									events.clone(),
+									w,
+									&mut process,
+									&mut pre_spawn_handler,
+									&mut post_spawn_handler,
+									errors.clone(),
+									events_tx.clone(),
+									child_pid.clone(),
+								) => Some(res),
+								_ = quit.notified() => None,
+							};
+							match applied {
+								Some(res) => res,
+								None => return force_quit(&mut process).await,
+							}
+						};
+						record_spawn(
+							&mut spawned,
+							&process,
+							previous_id,
+							&working.borrow(),
+							&events,
+						);
+						report_outcome_error(rerr, &events, &errors).await?;
+						continue;
+					}
 
 					if event.is_empty() {
 						trace!("empty event, by-passing filters");
+					} else if event.is_fs_lifecycle() {
+						trace!("fs watcher lifecycle event, by-passing filters");
 					} else {
-						let filtered = working.borrow().filterer.check_event(&event);
+						if set.is_empty() {
+							// the set is filtered again if the working data changes after this
+							working.borrow_and_update();
+						}
+
+						let filtered = passes(&working.borrow(), &mut compiled_filters, &event);
 						match filtered {
 							Err(err) => {
 								trace!(%err, "filter errored on event");
+								metrics::add(&metrics.events_filtered, 1);
 								errors.send(err).await?;
 								continue;
 							}
 							Ok(false) => {
-								trace!("filter rejected event");
+								trace!("filters rejected event");
+								metrics::add(&metrics.events_filtered, 1);
 								continue;
 							}
 							Ok(true) => {
-								trace!("filter passed event");
+								trace!("filters passed event");
 							}
 						}
+
+						if !passes_async(&working, &event).await {
+							trace!("async predicate rejected event");
+							metrics::add(&metrics.events_filtered, 1);
+							continue;
+						}
+					}
+
+					if set.is_empty() {
+						seen.clear();
+					}
+
+					if working.borrow().dedupe {
+						let pairs = path_kinds(&event);
+						if !pairs.is_empty() && pairs.iter().all(|pair| seen.contains(pair)) {
+							trace!(
+								"event only has path and kind pairs already in the batch, dropping"
+							);
+							continue;
+						}
+						seen.extend(pairs);
 					}
 
 					if set.is_empty() {
@@ -102,18 +427,65 @@ pub async fn worker(
 					}
 
 					set.push(event);
+					latest = Instant::now();
 
-					let elapsed = last.elapsed();
-					if elapsed < working.borrow().throttle {
-						trace!(?elapsed, "still within throttle window, cycling");
+					if in_batch {
+						trace!("more of the batch to come, cycling");
 						continue;
 					}
+
+					let remaining = remaining_wait(&working.borrow(), last, latest, last_action);
+					if !remaining.is_zero() {
+						trace!(?remaining, "still within throttle window, cycling");
+						continue;
+					}
+				}
+			}
+		}
+
+		let _swap = hold_swap(&swap, &mut events, &mut backlog).await;
+		if let Some(Ok(())) = working.changed().now_or_never() {
+			debug!("working data changed since the events were filtered, filtering them again");
+			let before = set.len();
+			let mut errs = Vec::new();
+			for event in take(&mut set) {
+				if event.is_empty() || event.is_fs_lifecycle() {
+					set.push(event);
+					continue;
 				}
+
+				let passed = passes(&working.borrow(), &mut compiled_filters, &event);
+				match passed {
+					Ok(true) if passes_async(&working, &event).await => set.push(event),
+					Ok(_) => {}
+					Err(err) => errs.push(err),
+				}
+			}
+			metrics::add(&metrics.events_filtered, (before - set.len()) as u64);
+			for err in errs {
+				errors.send(err).await?;
+			}
+			if set.is_empty() {
+				trace!("no events passed the new filters, skipping action");
+				last = Instant::now();
+				continue;
+			}
+		}
+
+		if working.borrow().drop_vanished {
+			let before = set.len();
+			set = drop_vanished(take(&mut set)).await;
+			metrics::add(&metrics.events_filtered, (before - set.len()) as u64);
+			if set.is_empty() {
+				trace!("all events were for vanished paths, skipping action");
+				last = Instant::now();
+				continue;
 			}
 		}
 
 		trace!("out of throttle, starting action process");
 		last = Instant::now();
+		last_action = Some(last);
 
 		let events = Arc::new(set.drain(..).collect());
 		let action = Action::new(Arc::clone(&events));
@@ -135,11 +507,20 @@ pub async fn worker(
 		}
 
 		debug!("running action handler");
+		metrics::add(&metrics.actions, 1);
 		let outcome = action.outcome.clone();
-		let err = action_handler
-			.handle(action)
-			.map_err(|e| rte("action worker", e));
+		let workdir = action.workdir.clone();
+		let err = {
+			// the handler's error isn't Send, so it must be dropped before awaiting
+			let res = action_handler.handle(action);
+			if let Err(Some(RuntimeError::Exit)) = res.as_ref().map_err(|err| err.downcast_ref()) {
+				debug!("action handler errored with an exit, stopping");
+				return Err(CriticalError::Exit(ShutdownReason::HandlerError));
+			}
+			res.map_err(|e| rte("action worker", e))
+		};
 		if let Err(err) = err {
+			metrics::add(&metrics.actions_suppressed, 1);
 			errors.send(err).await?;
 			debug!("action handler errored, skipping");
 			continue;
@@ -150,40 +531,677 @@ pub async fn worker(
 
 		let is_running = process.as_ref().map(|p| p.is_running()).unwrap_or(false);
 		let outcome = outcome.resolve(is_running);
-		debug!(?outcome, "outcome resolved");
-
-		let w = working.borrow().clone();
-		let rerr = apply_outcome(
-			outcome,
-			events,
-			w,
-			&mut process,
-			&mut pre_spawn_handler,
-			&mut post_spawn_handler,
-			errors.clone(),
-			events_tx.clone(),
-		)
-		.await;
-		if let Err(err) = rerr {
-			errors.send(err).await?;
+		let (outcome, later) = if working.borrow().dry_run {
+			(outcome, None)
+		} else {
+			outcome.split_delay()
+		};
+		let (outcome, queue) = if is_running {
+			outcome.when_busy(&working.borrow().on_busy)
+		} else {
+			(outcome, None)
+		};
+		debug!(?outcome, ?queue, "outcome resolved");
+
+		let only_completions = events.iter().all(Event::is_completion);
+		let outcome = if only_completions && started_by_completion {
+			let guarded = outcome.clone().without_start();
+			if guarded != outcome {
+				warn!("not starting command in response to the completion of a command that was itself started by a completion (loop guard)");
+				metrics::add(&metrics.actions_suppressed, 1);
+			}
+			guarded
+		} else {
+			outcome
+		};
+
+		let outcome = if is_running
+			&& queue.is_none()
+			&& !outcome.affects_process()
+			&& program_changed(&working.borrow(), &events)
+		{
+			debug!("command program changed, adding a restart");
+			Outcome::both(outcome, Outcome::Restart(None))
+		} else {
+			outcome
+		};
+		let mut w = working.borrow().clone();
+		let (outcome, backoff) =
+			guard_crash_loop(&mut crash_loop, &w, outcome, &observers, &errors, &metrics).await?;
+		if let Some(dir) = workdir.get() {
+			trace!(?dir, "action overrides working directory");
+			w.workdir = Some(dir.clone());
+		}
+
+		schedule_delay(
+			&mut delayed,
+			&outcome,
+			later,
+			&events,
+			workdir.get().cloned(),
+			&w.on_busy,
+		);
+		schedule_queue(
+			&mut queued,
+			&outcome,
+			queue,
+			&events,
+			workdir.get().cloned(),
+		);
+		last_outcome.send_replace(Some(outcome.clone()));
+
+		let previous_id = process.as_ref().map(|p| p.id());
+
+		let rerr = if w.dry_run {
+			would_have_run(outcome, &observers)
+		} else {
+			let applied = select! {
+				res = async {
+					if let Some(delay) = backoff {
+						sleep(delay).await;
+					}
+					apply_outcome(
+						outcome,
+						events.clone(),
+						w,
+						&mut process,
+						&mut pre_spawn_handler,
+						&mut post_spawn_handler,
+						errors.clone(),
+						events_tx.clone(),
+						child_pid.clone(),
+					)
+					.await
+				} => Some(res),
+				_ = quit.notified() => None,
+			};
+			match applied {
+				Some(res) => res,
+				None => return force_quit(&mut process).await,
+			}
+		};
+
+		let current_id = process.as_ref().map(|p| p.id());
+		if current_id.is_some() && current_id != previous_id {
+			started_by_completion = only_completions;
+			crash_loop.started(Instant::now());
 		}
+		record_spawn(
+			&mut spawned,
+			&process,
+			previous_id,
+			&working.borrow(),
+			&events,
+		);
+
+		report_outcome_error(rerr, &events, &errors).await?;
 	}
 
 	debug!("action worker finished");
 	Ok(())
 }
 
+/// Checks an outcome which starts the command against the crash loop guard.
+///
+/// This returns the outcome to apply, less its start if the guard gave up, and how long to back off
+/// before applying it, if at all. Backing off is announced with a [`Tag::RestartBackoff`] event to
+/// the `observers`, and giving up is reported as a [`RuntimeError::CrashLoop`].
+async fn guard_crash_loop(
+	crash_loop: &mut CrashLoop,
+	working: &WorkingData,
+	outcome: Outcome,
+	observers: &broadcast::Sender<Event>,
+	errors: &mpsc::Sender<RuntimeError>,
+	metrics: &Counters,
+) -> Result<(Outcome, Option<Duration>), CriticalError> {
+	if outcome.clone().without_start() == outcome {
+		return Ok((outcome, None));
+	}
+
+	match crash_loop.check(working, Instant::now()) {
+		Guard::Go => Ok((outcome, None)),
+		Guard::Backoff(delay) => {
+			warn!(?delay, "command seems to be in a crash loop, backing off");
+			observers
+				.send(Event {
+					tags: vec![Tag::Source(Source::Internal), Tag::RestartBackoff(delay)],
+					metadata: Default::default(),
+				})
+				.ok();
+			Ok((outcome, Some(delay)))
+		}
+		Guard::GiveUp(starts) => {
+			metrics::add(&metrics.actions_suppressed, 1);
+			errors
+				.send(RuntimeError::CrashLoop {
+					starts,
+					window: working.restart_window,
+				})
+				.await?;
+			Ok((outcome.without_start(), None))
+		}
+	}
+}
+
+/// An outcome put off by an [`Outcome::Delay`], or queued behind the running command by
+/// [`OnBusyUpdate::Queue`], with the action it came from.
+struct Delayed {
+	at: Instant,
+	outcome: Outcome,
+	events: Arc<Vec<Event>>,
+	workdir: Option<PathBuf>,
+}
+
+/// Takes the delayed outcome, if it's due.
+fn take_due(delayed: &mut Option<Delayed>) -> Option<Delayed> {
+	if delayed.as_ref()?.at > Instant::now() {
+		return None;
+	}
+
+	delayed.take()
+}
+
+/// Puts off the delayed part of an outcome, or cancels the pending one if the outcome now affects
+/// the command.
+///
+/// See [`Outcome::Delay`] for how a pending outcome is superseded.
+fn schedule_delay(
+	delayed: &mut Option<Delayed>,
+	now: &Outcome,
+	later: Option<(Duration, Outcome)>,
+	events: &Arc<Vec<Event>>,
+	workdir: Option<PathBuf>,
+	on_busy: &OnBusyUpdate,
+) {
+	let (after, outcome) = match later {
+		Some(later) => later,
+		None => {
+			if now.affects_process() && delayed.take().is_some() {
+				debug!("outcome affects the command, cancelling the delayed outcome");
+			}
+			return;
+		}
+	};
+
+	if delayed.is_some() {
+		if *on_busy == OnBusyUpdate::DoNothing {
+			debug!(?outcome, "an outcome is already delayed, dropping this one");
+			return;
+		}
+
+		debug!("superseding the delayed outcome");
+	}
+
+	debug!(?after, ?outcome, "delaying outcome");
+	*delayed = Some(Delayed {
+		at: Instant::now() + after,
+		outcome,
+		events: events.clone(),
+		workdir,
+	});
+}
+
+/// Takes the queued outcome, if the command has ended.
+fn take_queued(queued: &mut Option<Delayed>, process: &Option<SupervisorSet>) -> Option<Delayed> {
+	if process.as_ref().map_or(false, |p| p.is_running()) {
+		return None;
+	}
+
+	queued.take()
+}
+
+/// Queues the part of an outcome which waits for the command to end, or cancels the queued one if
+/// the outcome now affects the command.
+///
+/// See [`OnBusyUpdate::Queue`] for how a queued outcome is superseded.
+fn schedule_queue(
+	queued: &mut Option<Delayed>,
+	now: &Outcome,
+	later: Option<Outcome>,
+	events: &Arc<Vec<Event>>,
+	workdir: Option<PathBuf>,
+) {
+	let outcome = match later {
+		Some(later) => later,
+		None => {
+			if now.affects_process() && queued.take().is_some() {
+				debug!("outcome affects the command, cancelling the queued outcome");
+			}
+			return;
+		}
+	};
+
+	let events = match queued.take() {
+		Some(previous) => {
+			debug!("superseding the queued outcome");
+			Arc::new(
+				previous
+					.events
+					.iter()
+					.chain(events.iter())
+					.cloned()
+					.collect(),
+			)
+		}
+		None => events.clone(),
+	};
+
+	debug!(?outcome, "queueing outcome until the command ends");
+	*queued = Some(Delayed {
+		at: Instant::now(),
+		outcome,
+		events,
+		workdir,
+	});
+}
+
+/// Waits for the command to end, if there is one.
+async fn ended(process: &mut Option<SupervisorSet>) -> Result<(), RuntimeError> {
+	match process {
+		Some(process) => process.wait().await,
+		None => Ok(()),
+	}
+}
+
+/// Takes the next event, from those received while waiting for `swap` first.
+async fn next_event(
+	backlog: &mut VecDeque<Event>,
+	events: &mut mpsc::Receiver<Event>,
+) -> Option<Event> {
+	match backlog.pop_front() {
+		Some(event) => Some(event),
+		None => events.recv().await,
+	}
+}
+
+/// Waits for `swap`, receiving events into the `backlog` meanwhile.
+///
+/// This gives the event channel more room while a configuration swap is in progress, up to
+/// [`SWAP_BACKLOG`] events. Past that, events are left in the channel, so its senders wait or drop
+/// them as per the [`OverflowPolicy`](crate::config::OverflowPolicy), like when an action is
+/// being applied.
+async fn hold_swap<'s>(
+	swap: &'s Mutex<()>,
+	events: &mut mpsc::Receiver<Event>,
+	backlog: &mut VecDeque<Event>,
+) -> MutexGuard<'s, ()> {
+	let lock = swap.lock();
+	tokio::pin!(lock);
+	loop {
+		select! {
+			guard = &mut lock => return guard,
+			Some(event) = events.recv(), if backlog.len() < SWAP_BACKLOG => {
+				trace!("configuration is being swapped, holding event");
+				backlog.push_back(event);
+			}
+		}
+	}
+}
+
+/// Whether the event passes the filterer, the filter expressions, and the predicate.
+fn passes(
+	working: &WorkingData,
+	compiled_filters: &mut Option<ExprFilterer>,
+	event: &Event,
+) -> Result<bool, RuntimeError> {
+	Ok(working.filterer.check_event(event)?
+		&& check_filters(compiled_filters, &working.filters, event)?
+		&& (working.predicate)(event))
+}
+
+/// Whether the event passes the async predicate, if there's one.
+///
+/// See [`WorkingData::async_predicate`](WorkingData#structfield.async_predicate).
+async fn passes_async(working: &watch::Receiver<WorkingData>, event: &Event) -> bool {
+	let (predicate, limit) = {
+		let working = working.borrow();
+		(
+			working.async_predicate.clone(),
+			working.async_predicate_timeout,
+		)
+	};
+	let predicate = match predicate {
+		Some(predicate) => predicate,
+		None => return true,
+	};
+
+	match timeout(limit, predicate(event.clone())).await {
+		Ok(pass) => pass,
+		Err(_) => {
+			warn!(?limit, "async predicate timed out, passing event");
+			true
+		}
+	}
+}
+
+/// Keeps what the post-exit handler will need about a command, if a new one was spawned.
+///
+/// The command and grouping are taken from the latest working data, as the outcome was applied
+/// with it (less any per-action working directory, which isn't reported).
+fn record_spawn(
+	spawned: &mut HashMap<u32, PostExit>,
+	process: &Option<SupervisorSet>,
+	previous_id: Option<u32>,
+	working: &WorkingData,
+	events: &Arc<Vec<Event>>,
+) {
+	let id = match process.as_ref().map(|p| p.id()) {
+		Some(id) if Some(id) != previous_id => id,
+		_ => return,
+	};
+
+	spawned.insert(
+		id,
+		PostExit {
+			command: working.command.clone(),
+			events: events.clone(),
+			id,
+			grouped: working.grouped,
+			status: None,
+			duration: Duration::ZERO,
+		},
+	);
+}
+
+/// Checks the event against the filter expressions, compiling them first if they've changed.
+///
+/// See [`WorkingData::filters`](WorkingData#structfield.filters).
+fn check_filters(
+	compiled: &mut Option<ExprFilterer>,
+	filters: &[Filter],
+	event: &Event,
+) -> Result<bool, RuntimeError> {
+	if filters.is_empty() {
+		return Ok(true);
+	}
+
+	if compiled.as_ref().map_or(true, |c| c.filters() != filters) {
+		debug!(?filters, "compiling filter expressions");
+		*compiled = None;
+		let filterer =
+			ExprFilterer::new(filters.to_vec()).map_err(|err| RuntimeError::Filterer {
+				kind: "expr",
+				err: Box::new(err),
+			})?;
+		*compiled = Some(filterer);
+	}
+
+	Ok(compiled.as_ref().map_or(true, |c| c.check(event)))
+}
+
+/// The environment for the post-exit handler, if this is the completion of a command we spawned.
+///
+/// Each process tag follows the completion tag of its process: there are several pairs in the
+/// completion event of [parallel commands](WorkingData#structfield.parallel_commands).
+fn exited(spawned: &mut HashMap<u32, PostExit>, event: &Event) -> Option<PostExit> {
+	if !event.is_completion() {
+		return None;
+	}
+
+	let mut completion = None;
+	for tag in &event.tags {
+		match tag {
+			Tag::ProcessCompletion(status, duration) => completion = Some((*status, *duration)),
+			Tag::Process(id) => {
+				if let Some(mut exited) = spawned.remove(id) {
+					if let Some((status, duration)) = completion {
+						exited.status = status;
+						exited.duration = duration;
+					}
+					return Some(exited);
+				}
+			}
+			_ => {}
+		}
+	}
+	None
+}
+
+/// Kills the command, if there's one, and waits a little for it to go before exiting.
+///
+/// See [`InitConfig::force_quit_window`](crate::config::InitConfig#structfield.force_quit_window).
+async fn force_quit(process: &mut Option<SupervisorSet>) -> Result<(), CriticalError> {
+	warn!("force-quitting");
+	if let Some(p) = process.as_mut().filter(|p| p.is_running()) {
+		debug!("killing command before force-quitting");
+		p.kill().await;
+		if timeout(FORCE_QUIT_GRACE, p.wait()).await.is_err() {
+			warn!("command did not die in time, force-quitting anyway");
+		}
+	}
+
+	Err(CriticalError::Exit(ShutdownReason::Signal(
+		MainSignal::Interrupt,
+	)))
+}
+
+/// Reports the outcome of a dry run instead of applying it.
+///
+/// Exits still go through, as [`RuntimeError::Exit`].
+fn would_have_run(
+	outcome: Outcome,
+	observers: &broadcast::Sender<Event>,
+) -> Result<(), RuntimeError> {
+	info!(?outcome, "dry run, not applying outcome");
+	let exits = outcome.exits();
+	observers
+		.send(Event {
+			tags: vec![Tag::Source(Source::Internal), Tag::WouldHaveRun(outcome)],
+			metadata: Default::default(),
+		})
+		.ok();
+
+	if exits {
+		Err(RuntimeError::Exit)
+	} else {
+		Ok(())
+	}
+}
+
+/// Sends the error of applying an outcome on, or stops on [`Outcome::Exit`].
+///
+/// The exit is for the first signal in the events of the action, if there's one.
+async fn report_outcome_error(
+	rerr: Result<(), RuntimeError>,
+	events: &[Event],
+	errors: &mpsc::Sender<RuntimeError>,
+) -> Result<(), CriticalError> {
+	match rerr {
+		Ok(()) => Ok(()),
+		Err(RuntimeError::Exit) => {
+			let reason = events
+				.iter()
+				.flat_map(|event| event.signals())
+				.next()
+				.map_or(ShutdownReason::Requested, ShutdownReason::Signal);
+			debug!(?reason, "exit outcome, stopping");
+			Err(CriticalError::Exit(reason))
+		}
+		Err(err) => errors.send(err).await.map_err(CriticalError::from),
+	}
+}
+
+/// The outcome for a signal event from the [signal map](WorkingData#structfield.signal_map), if
+/// it's not to go through to the action handler.
+fn signal_outcome(working: &WorkingData, event: &Event) -> Option<Outcome> {
+	let unchanged =
+		working.signal_map.is_empty() && working.unmapped_signals == UnmappedSignals::Handler;
+	if unchanged || event.signals().next().is_none() {
+		return None;
+	}
+
+	let mut outcomes = Vec::new();
+	for signal in event.signals() {
+		match working.signal_map.get(&signal) {
+			Some(outcome) => outcomes.push(outcome.clone()),
+			None => match working.unmapped_signals {
+				UnmappedSignals::Handler => return None,
+				UnmappedSignals::Forward => outcomes.push(Outcome::Signal(signal.into())),
+				UnmappedSignals::Ignore => trace!(?signal, "ignoring unmapped signal"),
+			},
+		}
+	}
+
+	Some(
+		outcomes
+			.into_iter()
+			.reduce(Outcome::both)
+			.unwrap_or(Outcome::DoNothing),
+	)
+}
+
+/// The pairs of each path and each kind of an event, for deduplication.
+///
+/// Paths without a kind are paired with `None`.
+fn path_kinds(event: &Event) -> Vec<(PathBuf, Option<FileEventKind>)> {
+	let kinds: Vec<_> = event
+		.tags
+		.iter()
+		.filter_map(|tag| match tag {
+			Tag::FileEventKind(kind) => Some(Some(kind.clone())),
+			_ => None,
+		})
+		.collect();
+	let kinds = if kinds.is_empty() { vec![None] } else { kinds };
+
+	event
+		.paths()
+		.flat_map(|(path, _)| {
+			kinds
+				.iter()
+				.map(move |kind| (path.to_owned(), kind.clone()))
+		})
+		.collect()
+}
+
+/// Removes paths which no longer exist from the events, and sets the file type of the others.
+///
+/// Events which had paths but are left without any are dropped.
+async fn drop_vanished(events: Vec<Event>) -> Vec<Event> {
+	let mut kept = Vec::with_capacity(events.len());
+	for mut event in events {
+		let mut had_paths = false;
+		let mut tags = Vec::with_capacity(event.tags.len());
+		for tag in event.tags {
+			match tag {
+				Tag::Path { path, .. } => {
+					had_paths = true;
+					match tokio::fs::symlink_metadata(&path).await {
+						Ok(meta) => tags.push(Tag::Path {
+							path,
+							file_type: Some(meta.file_type().into()),
+						}),
+						Err(err) => trace!(?path, %err, "path vanished, dropping it"),
+					}
+				}
+				tag => tags.push(tag),
+			}
+		}
+
+		if had_paths && !tags.iter().any(|tag| matches!(tag, Tag::Path { .. })) {
+			trace!("all paths of event vanished, dropping it");
+			continue;
+		}
+
+		event.tags = tags;
+		kept.push(event);
+	}
+
+	kept
+}
+
+/// Resolves the working directory against ours, and checks that it's a usable directory.
+async fn resolve_workdir(dir: &Path) -> Result<PathBuf, RuntimeError> {
+	let path = std::env::current_dir()
+		.map(|cwd| cwd.join(dir))
+		.unwrap_or_else(|_| dir.to_owned());
+
+	match tokio::fs::metadata(&path).await {
+		Ok(meta) if meta.is_dir() => Ok(path),
+		Ok(_) => Err(RuntimeError::CommandWorkdir {
+			path,
+			err: std::io::Error::new(std::io::ErrorKind::Other, "not a directory"),
+		}),
+		Err(err) => Err(RuntimeError::CommandWorkdir { path, err }),
+	}
+}
+
+/// Whether any of the events is about the program of the command, if that's being watched.
+fn program_changed(working: &WorkingData, events: &[Event]) -> bool {
+	if !working.watch_command_binary {
+		return false;
+	}
+
+	working
+		.shell
+		.program_path(&working.command)
+		.map(|program| {
+			events
+				.iter()
+				.flat_map(Event::paths)
+				.any(|(path, _)| path == program)
+		})
+		.unwrap_or(false)
+}
+
+/// How long to wait before actioning the current set of events.
+///
+/// Without a rate limit, this is the throttle counted from the first event in the set (`first`).
+/// With one, it is the rest of the window since the last action, if any: the first event after a
+/// quiet window is actioned immediately. Either way, with a debounce, it is at least the rest of the
+/// debounce counted from the latest event in the set (`latest`).
+fn remaining_wait(
+	working: &WorkingData,
+	first: Instant,
+	latest: Instant,
+	last_action: Option<Instant>,
+) -> Duration {
+	let wait = match working.rate_limit {
+		Some(window) => last_action
+			.map(|at| window.saturating_sub(at.elapsed()))
+			.unwrap_or_default(),
+		None => working.throttle.saturating_sub(first.elapsed()),
+	};
+	match working.debounce {
+		Some(debounce) => wait.max(debounce.saturating_sub(latest.elapsed())),
+		None => wait,
+	}
+}
+
+/// Stops the process by sending it `signal`, then forcefully if it's still running after `grace`.
+async fn stop_process(
+	process: &mut SupervisorSet,
+	signal: SubSignal,
+	grace: Duration,
+) -> Result<(), RuntimeError> {
+	if signal != SubSignal::ForceStop {
+		trace!(?signal, ?grace, "asking process to stop");
+		process.signal(signal).await;
+		match timeout(grace, process.wait()).await {
+			Ok(res) => return res,
+			Err(_) => debug!(
+				?grace,
+				"process did not exit within grace period, forcing it to stop"
+			),
+		}
+	}
+
+	process.kill().await;
+	process.wait().await
+}
+
 #[allow(clippy::too_many_arguments)]
 #[async_recursion::async_recursion]
 async fn apply_outcome(
 	outcome: Outcome,
 	events: Arc<Vec<Event>>,
 	working: WorkingData,
-	process: &mut Option<Supervisor>,
+	process: &mut Option<SupervisorSet>,
 	pre_spawn_handler: &mut Box<dyn Handler<PreSpawn> + Send>,
 	post_spawn_handler: &mut Box<dyn Handler<PostSpawn> + Send>,
 	errors_c: mpsc::Sender<RuntimeError>,
 	events_c: mpsc::Sender<Event>,
+	child_pid: Arc<watch::Sender<Option<u32>>>,
 ) -> Result<(), RuntimeError> {
 	trace!(?outcome, "applying outcome");
 	match (process.as_mut(), outcome) {
@@ -192,66 +1210,146 @@ async fn apply_outcome(
 			return Err(RuntimeError::Exit);
 		}
 		(Some(p), Outcome::Stop) => {
-			p.kill().await;
-			p.wait().await?;
+			stop_process(p, working.stop_signal, working.stop_timeout).await?;
 			*process = None;
 		}
-		(None, o @ Outcome::Stop) | (None, o @ Outcome::Wait) | (None, o @ Outcome::Signal(_)) => {
+		(None, o @ Outcome::Stop)
+		| (None, o @ Outcome::Wait)
+		| (None, o @ Outcome::Signal(_))
+		| (None, o @ Outcome::Stdin(_)) => {
 			debug!(outcome=?o, "meaningless without a process, not doing anything");
 		}
-		(_, Outcome::Start) => {
+		(_, Outcome::Restart(grace)) => {
+			if let Some(p) = process.as_mut() {
+				if let Some(grace) = grace {
+					trace!(?grace, "asking process to terminate before restart");
+					stop_process(p, SubSignal::Terminate, grace).await?;
+				} else {
+					stop_process(p, working.stop_signal, working.stop_timeout).await?;
+				}
+			}
+
+			*process = None;
+			apply_outcome(
+				Outcome::Start,
+				events,
+				working,
+				process,
+				pre_spawn_handler,
+				post_spawn_handler,
+				errors_c,
+				events_c,
+				child_pid,
+			)
+			.await?;
+		}
+		(_, outcome @ (Outcome::Start | Outcome::Capture(_))) => {
+			let collect = match outcome {
+				Outcome::Capture(callback) => Some(callback),
+				_ => None,
+			};
+
 			if working.command.is_empty() {
 				warn!("tried to start a command without anything to run");
 			} else {
-				let command = working.shell.to_command(&working.command);
-				let (pre_spawn, command) =
-					PreSpawn::new(command, working.command.clone(), events.clone());
-
-				debug!("running pre-spawn handler");
-				pre_spawn_handler
-					.handle(pre_spawn)
-					.map_err(|e| rte("action pre-spawn", e))?;
-
-				let mut command = Arc::try_unwrap(command)
-					.map_err(|_| RuntimeError::HandlerLockHeld("pre-spawn"))?
-					.into_inner();
+				// with more commands, their events go through a merger so the set completes as one
+				let (set_events, merger) = if working.parallel_commands.is_empty() {
+					(events_c.clone(), None)
+				} else {
+					let (s, r) = mpsc::channel(SET_EVENTS_BUFFER);
+					(s, Some(r))
+				};
 
-				trace!("spawning supervisor for command");
-				let sup = Supervisor::spawn(
+				let main = match spawn_command(
+					&working.command,
+					&working,
+					&events,
+					pre_spawn_handler,
+					post_spawn_handler,
 					errors_c.clone(),
-					events_c.clone(),
-					&mut command,
-					working.grouped,
-				)?;
-
-				debug!("running post-spawn handler");
-				let post_spawn = PostSpawn {
-					command: working.command.clone(),
-					events: events.clone(),
-					id: sup.id(),
-					grouped: working.grouped,
+					set_events.clone(),
+					Some(child_pid.clone()),
+					collect,
+				)
+				.await?
+				{
+					Some(sup) => sup,
+					None => return Ok(()),
 				};
-				post_spawn_handler
-					.handle(post_spawn)
-					.map_err(|e| rte("action post-spawn", e))?;
+
+				let mut others = Vec::with_capacity(working.parallel_commands.len());
+				for command in &working.parallel_commands {
+					match spawn_command(
+						command,
+						&working,
+						&events,
+						pre_spawn_handler,
+						post_spawn_handler,
+						errors_c.clone(),
+						set_events.clone(),
+						None,
+						None,
+					)
+					.await
+					{
+						Ok(Some(sup)) => others.push(sup),
+						Ok(None) => {}
+						Err(err) => {
+							debug!(
+								?command,
+								"parallel command failed to start, running the others"
+							);
+							errors_c.send(err).await.ok();
+						}
+					}
+				}
+
+				if let Some(merger) = merger {
+					spawn(merge_completions(
+						merger,
+						events_c.clone(),
+						1 + others.len(),
+					));
+				}
 
 				// TODO: consider what we want to do for (previous) process if it's still running here?
-				*process = Some(sup);
+				*process = Some(SupervisorSet::new(main, others));
 			}
 		}
 
+		(_, Outcome::Callback(callback)) => {
+			trace!("running outcome callback");
+			(callback.0)(&events).await?;
+		}
 		(Some(p), Outcome::Signal(sig)) => {
 			p.signal(sig).await;
 		}
+		(Some(p), Outcome::Stdin(_)) if !p.is_running() => {
+			debug!("command is not running, not writing to its stdin");
+		}
+		(Some(p), Outcome::Stdin(bytes)) => {
+			if !p.write_stdin(bytes) {
+				warn!("the command's stdin isn't kept open, not writing to it");
+			}
+		}
 
 		(Some(p), Outcome::Wait) => {
 			p.wait().await?;
 		}
 
+		(_, o @ Outcome::Clear) | (_, o @ Outcome::Reset) if !std::io::stdout().is_terminal() => {
+			debug!(outcome=?o, "stdout is not a terminal, not clearing");
+		}
+
 		(_, Outcome::Clear) => {
 			clearscreen::clear()?;
 		}
 
+		(_, Outcome::LogLevel(level)) => {
+			debug!(%level, "changing log level");
+			working.log_level.set(level)?;
+		}
+
 		(_, Outcome::Reset) => {
 			for cs in [
 				ClearScreen::WindowsCooked,
@@ -274,6 +1372,7 @@ async fn apply_outcome(
 				post_spawn_handler,
 				errors_c,
 				events_c,
+				child_pid,
 			)
 			.await?;
 		}
@@ -287,6 +1386,7 @@ async fn apply_outcome(
 				post_spawn_handler,
 				errors_c,
 				events_c,
+				child_pid,
 			)
 			.await?;
 		}
@@ -301,6 +1401,7 @@ async fn apply_outcome(
 				post_spawn_handler,
 				errors_c.clone(),
 				events_c.clone(),
+				child_pid.clone(),
 			)
 			.await
 			{
@@ -319,6 +1420,24 @@ async fn apply_outcome(
 				post_spawn_handler,
 				errors_c,
 				events_c,
+				child_pid,
+			)
+			.await?;
+		}
+		(_, Outcome::Delay(after, outcome)) => {
+			// the worker splits delays off, so this is only reached if it didn't
+			trace!(?after, "waiting before applying delayed outcome");
+			sleep(after).await;
+			apply_outcome(
+				*outcome,
+				events,
+				working,
+				process,
+				pre_spawn_handler,
+				post_spawn_handler,
+				errors_c,
+				events_c,
+				child_pid,
 			)
 			.await?;
 		}
@@ -326,3 +1445,127 @@ async fn apply_outcome(
 
 	Ok(())
 }
+
+/// Spawns one command of an [`Outcome::Start`], going through the pre-spawn and post-spawn
+/// handlers.
+///
+/// Returns `None` if the pre-spawn handler vetoed the spawn. Only the main command publishes its
+/// ID to `child_pid`, and is given the callback to `collect` its output with, for an
+/// [`Outcome::Capture`].
+#[allow(clippy::too_many_arguments)]
+async fn spawn_command(
+	program: &[String],
+	working: &WorkingData,
+	events: &Arc<Vec<Event>>,
+	pre_spawn_handler: &mut Box<dyn Handler<PreSpawn> + Send>,
+	post_spawn_handler: &mut Box<dyn Handler<PostSpawn> + Send>,
+	errors_c: mpsc::Sender<RuntimeError>,
+	events_c: mpsc::Sender<Event>,
+	child_pid: Option<Arc<watch::Sender<Option<u32>>>>,
+	collect: Option<OutputCallback>,
+) -> Result<Option<Supervisor>, RuntimeError> {
+	let mut command = working.rlimits.wrap(working.shell.to_command(program));
+	if let Some(dir) = &working.workdir {
+		let dir = resolve_workdir(dir).await?;
+		trace!(?dir, "setting working directory for command");
+		command.current_dir(dir);
+	}
+	if working.environment {
+		for (kind, value) in summarise_events_to_env(events.iter()) {
+			let name = format!("{}{}_PATH", working.env_prefix, kind);
+			trace!(%name, ?value, "setting environment variable for command");
+			command.env(name, value);
+		}
+	}
+	let events_file = if working.events_to_file {
+		let file = EventsFile::write(events).await?;
+		command.env(format!("{}EVENTS_FILE", working.env_prefix), file.path());
+		Some(file)
+	} else {
+		None
+	};
+
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	let pty = if working.pty && collect.is_none() {
+		Some(crate::command::Pty::attach(&mut command).await?)
+	} else {
+		None
+	};
+	#[cfg(not(any(target_os = "linux", target_os = "android")))]
+	if working.pty && collect.is_none() {
+		warn!("pty is not supported on this platform, running the command without one");
+	}
+
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	let capture_output = working.capture_output && pty.is_none();
+	#[cfg(not(any(target_os = "linux", target_os = "android")))]
+	let capture_output = working.capture_output;
+	let capture = if let Some(callback) = collect {
+		Some(Capture::Collect {
+			limit: working.capture_limit,
+			callback,
+		})
+	} else {
+		capture_output.then(|| Capture::Lines {
+			cr_lines: working.output_cr_lines,
+		})
+	};
+	if capture.is_some() {
+		command.stdout(Stdio::piped()).stderr(Stdio::piped());
+	}
+	if working.keep_stdin {
+		command.stdin(Stdio::piped());
+	}
+
+	let (pre_spawn, command, vetoed) = PreSpawn::new(command, program.to_vec(), events.clone());
+
+	debug!("running pre-spawn handler");
+	pre_spawn_handler
+		.handle(pre_spawn)
+		.map_err(|e| rte("action pre-spawn", e))?;
+
+	if vetoed.load(Ordering::SeqCst) {
+		debug!("pre-spawn handler vetoed the spawn, not running the command");
+		return Ok(None);
+	}
+
+	let mut command = Arc::try_unwrap(command)
+		.map_err(|_| RuntimeError::HandlerLockHeld("pre-spawn"))?
+		.into_inner();
+
+	trace!("spawning supervisor for command");
+	let sup = Supervisor::spawn_reporting(
+		errors_c,
+		events_c,
+		&mut command,
+		working.grouped,
+		child_pid,
+		capture,
+		events_file,
+		working.command_timeout.map(|after| Timeout {
+			after,
+			signal: working.stop_signal,
+			grace: working.stop_timeout,
+		}),
+	)?;
+
+	// the command holds the slave side of the pty open until dropped
+	drop(command);
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	if let Some(pty) = pty {
+		pty.forward();
+	}
+
+	debug!("running post-spawn handler");
+	let post_spawn = PostSpawn {
+		command: program.to_vec(),
+		events: events.clone(),
+		id: sup.id(),
+		grouped: working.grouped,
+	};
+	post_spawn_handler
+		.handle(post_spawn)
+		.map_err(|e| rte("action post-spawn", e))?;
+
+	Ok(Some(sup))
+}
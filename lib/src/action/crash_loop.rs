@@ -0,0 +1,148 @@
+//! Detection of crash loops, where the command keeps being started over and over.
+
+use std::{collections::VecDeque, time::Duration};
+
+use tokio::time::Instant;
+
+use super::WorkingData;
+
+/// The first back-off, doubled on each one after it.
+const BACKOFF_BASE: Duration = Duration::from_secs(1);
+
+/// What to do about a start of the command.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum Guard {
+	/// Start it now.
+	Go,
+
+	/// Wait this long, then start it.
+	Backoff(Duration),
+
+	/// Don't start it: it was started this many times within the window, and backing off didn't
+	/// help.
+	GiveUp(usize),
+}
+
+/// The recent starts of the command, and how much it's been backed off.
+///
+/// See [`WorkingData::max_restarts`](WorkingData#structfield.max_restarts).
+#[derive(Debug, Default)]
+pub(super) struct CrashLoop {
+	starts: VecDeque<Instant>,
+	backoff: Option<Duration>,
+}
+
+impl CrashLoop {
+	/// Decides what to do about a start of the command at `now`.
+	pub fn check(&mut self, working: &WorkingData, now: Instant) -> Guard {
+		let max = match working.max_restarts {
+			Some(max) => max,
+			None => return Guard::Go,
+		};
+
+		let window = working.restart_window;
+		while let Some(start) = self.starts.front() {
+			if now.saturating_duration_since(*start) > window {
+				self.starts.pop_front();
+			} else {
+				break;
+			}
+		}
+
+		if self.starts.is_empty() {
+			// a whole window without a start: the loop, if there was one, is over
+			self.backoff = None;
+		}
+
+		// the first start doesn't count as a restart
+		if self.starts.len() <= max {
+			return Guard::Go;
+		}
+
+		let backoff = self.backoff.map_or(BACKOFF_BASE, |b| b.saturating_mul(2));
+		if backoff > window {
+			Guard::GiveUp(self.starts.len())
+		} else {
+			self.backoff = Some(backoff);
+			Guard::Backoff(backoff)
+		}
+	}
+
+	/// Records that the command was started at `now`.
+	pub fn started(&mut self, now: Instant) {
+		self.starts.push_back(now);
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	fn working(max: usize, window: Duration) -> WorkingData {
+		WorkingData {
+			max_restarts: Some(max),
+			restart_window: window,
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn disabled_always_goes() {
+		let working = WorkingData::default();
+		let mut crash = CrashLoop::default();
+		let now = Instant::now();
+		for _ in 0..100 {
+			assert_eq!(crash.check(&working, now), Guard::Go);
+			crash.started(now);
+		}
+	}
+
+	#[test]
+	fn backs_off_exponentially_then_gives_up() {
+		let working = working(2, Duration::from_secs(5));
+		let mut crash = CrashLoop::default();
+		let now = Instant::now();
+		for _ in 0..3 {
+			assert_eq!(crash.check(&working, now), Guard::Go);
+			crash.started(now);
+		}
+
+		assert_eq!(
+			crash.check(&working, now),
+			Guard::Backoff(Duration::from_secs(1))
+		);
+		crash.started(now);
+		assert_eq!(
+			crash.check(&working, now),
+			Guard::Backoff(Duration::from_secs(2))
+		);
+		crash.started(now);
+		assert_eq!(
+			crash.check(&working, now),
+			Guard::Backoff(Duration::from_secs(4))
+		);
+		crash.started(now);
+		assert_eq!(crash.check(&working, now), Guard::GiveUp(6));
+	}
+
+	#[test]
+	fn quiet_window_resets() {
+		let working = working(0, Duration::from_secs(5));
+		let mut crash = CrashLoop::default();
+		let now = Instant::now();
+		crash.started(now);
+		assert_eq!(
+			crash.check(&working, now),
+			Guard::Backoff(Duration::from_secs(1))
+		);
+		crash.started(now);
+
+		let later = now + Duration::from_secs(6);
+		assert_eq!(crash.check(&working, later), Guard::Go);
+		crash.started(later);
+		assert_eq!(
+			crash.check(&working, later),
+			Guard::Backoff(Duration::from_secs(1))
+		);
+	}
+}
@@ -1,4 +1,13 @@
-use crate::signal::process::SubSignal;
+use std::{fmt, sync::Arc, time::Duration};
+
+use futures::future::BoxFuture;
+use tracing::level_filters::LevelFilter;
+
+use crate::{
+	error::RuntimeError,
+	event::{Event, ProcessEnd},
+	signal::process::SubSignal,
+};
 
 /// The outcome to execute when an action is triggered.
 ///
@@ -18,7 +27,8 @@ pub enum Outcome {
 
 	/// If the command isn't running, start it.
 	///
-	/// This should be used with an `IfRunning`, and will warn if the command is running.
+	/// If it is running, what happens instead is up to the
+	/// [`on_busy`](crate::action::WorkingData#structfield.on_busy) setting.
 	Start,
 
 	/// Wait for command completion.
@@ -26,12 +36,37 @@ pub enum Outcome {
 	/// Does nothing if the command isn't running.
 	Wait,
 
+	/// Stop the command if it's running, wait for it to end, then start it again.
+	///
+	/// This is done as one step, so there's never two instances of the command at once. With a
+	/// grace period, the command is first sent [`SubSignal::Terminate`] and given that long to exit
+	/// on its own before being forcefully stopped; without one, it is stopped as described by the
+	/// [`stop_signal`](super::WorkingData#structfield.stop_signal) and
+	/// [`stop_timeout`](super::WorkingData#structfield.stop_timeout) settings (by default, it is
+	/// forcefully stopped at once).
+	///
+	/// The stopped command produces the usual completion event, and the pre-spawn and post-spawn
+	/// handlers run for the new one as for [`Start`](Outcome::Start). If the command isn't running,
+	/// this is just a `Start`.
+	Restart(Option<Duration>),
+
 	/// Send this signal to the command.
 	///
 	/// This does not wait for the command to complete.
 	Signal(SubSignal),
 
+	/// Write these bytes to the command's stdin.
+	///
+	/// This needs [`keep_stdin`](super::WorkingData#structfield.keep_stdin), and does nothing (with
+	/// a warning) without it, or nothing at all if the command isn't running. The bytes are written
+	/// in the background, in order with those of other `Stdin` outcomes, so this doesn't wait for
+	/// the command to read them.
+	Stdin(Vec<u8>),
+
 	/// Clear the (terminal) screen.
+	///
+	/// This does nothing if stdout isn't a terminal, so escape codes don't end up in logs or pipes.
+	/// Combine it with [`Start`](Outcome::Start) using [`Outcome::both()`] to clear before each run.
 	Clear,
 
 	/// Reset the (terminal) screen.
@@ -41,16 +76,154 @@ pub enum Outcome {
 	/// [`VtLeaveAlt`][clearscreen::ClearScreen::VtLeaveAlt],
 	/// [`VtWellDone`][clearscreen::ClearScreen::VtWellDone],
 	/// and [the default clear][clearscreen::ClearScreen::default()].
+	///
+	/// Like [`Clear`](Outcome::Clear), this does nothing if stdout isn't a terminal.
 	Reset,
 
 	/// Exit watchexec.
+	///
+	/// This is the clean way for the action handler to stop the runtime: the action worker ends
+	/// with [`CriticalError::Exit`](crate::error::CriticalError::Exit), and the main task shuts
+	/// down gracefully and resolves to the [`ShutdownReason`](crate::ShutdownReason), as with
+	/// [`Watchexec::shutdown()`]. Returning [`RuntimeError::Exit`](crate::error::RuntimeError::Exit)
+	/// from the action handler does the same, but with a different reason, and is meant for errors
+	/// rather than business logic.
+	///
+	/// [`Watchexec::shutdown()`]: crate::Watchexec::shutdown()
 	Exit,
 
+	/// Call back into the application with the events of the action, instead of (or as well as,
+	/// with [`Outcome::both()`]) running the command.
+	///
+	/// The action worker awaits the returned future before doing anything else, so long-running
+	/// work should rather be spawned from it. An error is sent to the error hook, like errors from
+	/// running the command are, and stops the rest of the outcome from being applied.
+	Callback(Callback),
+
+	/// Start the command as for [`Start`](Outcome::Start), capturing its output, and call back into
+	/// the application with it once the command ends.
+	///
+	/// The command's stdout and stderr are collected, up to
+	/// [`capture_limit`](super::WorkingData#structfield.capture_limit) bytes each, and passed to the
+	/// callback along with the exit status, as a [`CapturedOutput`]. This goes through the pre-spawn
+	/// and post-spawn handlers and the [`on_busy`](super::WorkingData#structfield.on_busy) setting
+	/// like a `Start` does, so watchexec still supervises the command: it can be stopped, restarted,
+	/// or time out as usual. Only the main command is captured, not the
+	/// [`parallel_commands`](super::WorkingData#structfield.parallel_commands). This takes
+	/// precedence over [`capture_output`](super::WorkingData#structfield.capture_output) and
+	/// [`pty`](super::WorkingData#structfield.pty) for this run.
+	///
+	/// The callback runs once the command has ended and its streams are closed, before the
+	/// completion event is sent, so a following [`Wait`](Outcome::Wait) also waits for it. Errors
+	/// are sent to the error hook. The callback isn't called if the command fails to start or can't
+	/// be waited on.
+	Capture(OutputCallback),
+
 	/// When command is running, do the first, otherwise the second.
+	///
+	/// This is resolved by the action worker as it applies the outcome, using its own view of
+	/// whether the command is running, so handlers don't need to (racily) check for themselves.
+	/// For example, `Outcome::if_running(Outcome::Restart(None), Outcome::Start)` restarts a
+	/// running command, or starts one otherwise.
 	IfRunning(Box<Outcome>, Box<Outcome>),
 
 	/// Do both outcomes in order.
 	Both(Box<Outcome>, Box<Outcome>),
+
+	/// Wait this long, then do the outcome.
+	///
+	/// The action worker doesn't wait there: it puts the outcome off and carries on taking in
+	/// events, then applies it once the delay has elapsed. It's only resolved (for
+	/// [`IfRunning`](Outcome::IfRunning) and the [`on_busy`](super::WorkingData#structfield.on_busy)
+	/// setting) then, against the state of the command at that time. In a [`Both`](Outcome::Both),
+	/// everything after the delay is put off with it: `Outcome::both(Outcome::Clear,
+	/// Outcome::delay(after, Outcome::Start))` clears at once, and starts later.
+	///
+	/// Only one outcome is put off at a time. While one is pending, a newer action's own delayed
+	/// outcome supersedes it, and a newer action which starts or stops the command cancels it, as
+	/// it was decided before that, so a stale delayed outcome never fires after a restart. With an
+	/// `on_busy` of [`DoNothing`](OnBusyUpdate::DoNothing), the pending one is kept instead, and
+	/// the delayed outcomes of newer actions are dropped until it fires. A pending outcome is
+	/// dropped on shutdown.
+	///
+	/// This differs from the [debounce](super::WorkingData#structfield.debounce) as the delay is
+	/// chosen by the handler for each action, and the events which come in meanwhile are actioned
+	/// as usual.
+	Delay(Duration, Box<Outcome>),
+
+	/// Change the log level, through the
+	/// [log level handler](crate::config::InitConfig#structfield.log_level_handler).
+	///
+	/// This takes effect straight away, without restarting anything. If no handler was given, a
+	/// [`RuntimeError::NoLogLevelHandler`] is issued instead.
+	LogLevel(LevelFilter),
+}
+
+/// A function to run in-process, for [`Outcome::Callback`].
+///
+/// Callbacks can't be compared, so two `Callback`s are equal only if they are clones of each other.
+#[derive(Clone)]
+pub struct Callback(
+	#[allow(clippy::type_complexity)]
+	pub  Arc<dyn Fn(&[Event]) -> BoxFuture<'static, Result<(), RuntimeError>> + Send + Sync>,
+);
+
+impl fmt::Debug for Callback {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Callback").finish_non_exhaustive()
+	}
+}
+
+impl PartialEq for Callback {
+	fn eq(&self, other: &Self) -> bool {
+		Arc::as_ptr(&self.0).cast::<()>() == Arc::as_ptr(&other.0).cast::<()>()
+	}
+}
+
+impl Eq for Callback {}
+
+/// A function to call with the output of the command, for [`Outcome::Capture`].
+///
+/// Like [`Callback`]s, two `OutputCallback`s are equal only if they are clones of each other.
+#[derive(Clone)]
+pub struct OutputCallback(
+	#[allow(clippy::type_complexity)]
+	pub  Arc<dyn Fn(CapturedOutput) -> BoxFuture<'static, Result<(), RuntimeError>> + Send + Sync>,
+);
+
+impl fmt::Debug for OutputCallback {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("OutputCallback").finish_non_exhaustive()
+	}
+}
+
+impl PartialEq for OutputCallback {
+	fn eq(&self, other: &Self) -> bool {
+		Arc::as_ptr(&self.0).cast::<()>() == Arc::as_ptr(&other.0).cast::<()>()
+	}
+}
+
+impl Eq for OutputCallback {}
+
+/// The output of a command run by [`Outcome::Capture`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct CapturedOutput {
+	/// How the command ended.
+	///
+	/// This is `None` if the exit status couldn't be obtained.
+	pub status: Option<ProcessEnd>,
+
+	/// What the command wrote to its stdout, up to the capture limit.
+	pub stdout: Vec<u8>,
+
+	/// What the command wrote to its stderr, up to the capture limit.
+	pub stderr: Vec<u8>,
+
+	/// Whether either stream went past the capture limit and was cut off.
+	///
+	/// The rest of the output is still read (so the command isn't blocked writing it), but dropped.
+	pub truncated: bool,
 }
 
 impl Default for Outcome {
@@ -59,6 +232,45 @@ impl Default for Outcome {
 	}
 }
 
+/// What to do when an action would start the command while it's still running.
+///
+/// This applies to a [`Start`](Outcome::Start) in the outcome of an action (once
+/// [`IfRunning`](Outcome::IfRunning)s are resolved) while the command is running, unless it comes
+/// after a [`Stop`](Outcome::Stop) or [`Wait`](Outcome::Wait). Outcomes which already say what to
+/// do with a running command, like [`Restart`](Outcome::Restart), are left alone.
+///
+/// Running several instances concurrently is out of scope here: the action worker supervises a
+/// single command at a time. The nearest is to start several commands at once, with
+/// [`parallel_commands`](super::WorkingData#structfield.parallel_commands).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum OnBusyUpdate {
+	/// Ignore the start: the running command is left to carry on.
+	DoNothing,
+
+	/// Wait for the running command to finish, then start it again.
+	///
+	/// The start (and whatever comes after it in the outcome) is queued, and the action worker
+	/// carries on taking in events and applying outcomes meanwhile, so a signal or an
+	/// [`Exit`](Outcome::Exit) still takes effect at once. Only one start is queued at a time: a
+	/// newer action which queues one supersedes it, and the queued start then comes with the events
+	/// of both actions. A newer action which restarts or stops the command cancels it. A queued
+	/// start is dropped on shutdown. This is the default.
+	Queue,
+
+	/// Stop the running command (as by [`Outcome::Restart`]) and start it anew.
+	Restart,
+
+	/// Send this signal to the running command instead of starting it.
+	Signal(SubSignal),
+}
+
+impl Default for OnBusyUpdate {
+	fn default() -> Self {
+		Self::Queue
+	}
+}
+
 impl Outcome {
 	/// Convenience function to create an outcome conditional on the state of the subprocess.
 	pub fn if_running(then: Outcome, otherwise: Outcome) -> Self {
@@ -70,6 +282,33 @@ impl Outcome {
 		Self::Both(Box::new(one), Box::new(two))
 	}
 
+	/// Convenience function to create a [`Callback`](Outcome::Callback) outcome.
+	pub fn callback(
+		f: impl Fn(&[Event]) -> BoxFuture<'static, Result<(), RuntimeError>> + Send + Sync + 'static,
+	) -> Self {
+		Self::Callback(Callback(Arc::new(f)))
+	}
+
+	/// Convenience function to create a [`Capture`](Outcome::Capture) outcome.
+	pub fn capture(
+		f: impl Fn(CapturedOutput) -> BoxFuture<'static, Result<(), RuntimeError>>
+			+ Send
+			+ Sync
+			+ 'static,
+	) -> Self {
+		Self::Capture(OutputCallback(Arc::new(f)))
+	}
+
+	/// Convenience function to create a [`Delay`](Outcome::Delay) outcome.
+	pub fn delay(after: Duration, then: Outcome) -> Self {
+		Self::Delay(after, Box::new(then))
+	}
+
+	/// Convenience function to restart the subprocess, with a grace period before force-stopping.
+	pub fn restart_with_grace(grace: Duration) -> Self {
+		Self::Restart(Some(grace))
+	}
+
 	/// Convenience function to wait for the subprocess to complete before executing the outcome.
 	pub fn wait(and_then: Outcome) -> Self {
 		Self::Both(Box::new(Outcome::Wait), Box::new(and_then))
@@ -84,12 +323,168 @@ impl Outcome {
 			(_, other) => other,
 		}
 	}
+
+	/// Whether this outcome starts, stops, or restarts the command (or exits).
+	pub(super) fn affects_process(&self) -> bool {
+		match self {
+			Self::Start | Self::Capture(_) | Self::Restart(_) | Self::Stop | Self::Exit => true,
+			Self::Both(one, two) | Self::IfRunning(one, two) => {
+				one.affects_process() || two.affects_process()
+			}
+			Self::Delay(_, then) => then.affects_process(),
+			_ => false,
+		}
+	}
+
+	/// Whether this outcome exits, once resolved.
+	pub(super) fn exits(&self) -> bool {
+		match self {
+			Self::Exit => true,
+			Self::Both(one, two) => one.exits() || two.exits(),
+			Self::Delay(_, then) => then.exits(),
+			_ => false,
+		}
+	}
+
+	/// Replaces any [`Start`](Outcome::Start) (or [`Capture`](Outcome::Capture)) which would happen
+	/// while the command is still running in a resolved outcome, as the busy policy says.
+	///
+	/// A `Start` after a [`Stop`](Outcome::Stop) or [`Wait`](Outcome::Wait) is left as is. With
+	/// [`Queue`](OnBusyUpdate::Queue), the `Start` and everything after it is split off, to be
+	/// applied once the command has ended: this returns what to do now, and that.
+	pub(super) fn when_busy(self, on_busy: &OnBusyUpdate) -> (Self, Option<Self>) {
+		match self {
+			Self::Start => match on_busy {
+				OnBusyUpdate::DoNothing => (Self::DoNothing, None),
+				OnBusyUpdate::Queue => (Self::DoNothing, Some(Self::Start)),
+				OnBusyUpdate::Restart => (Self::Restart(None), None),
+				OnBusyUpdate::Signal(sig) => (Self::Signal(*sig), None),
+			},
+			Self::Capture(callback) => match on_busy {
+				OnBusyUpdate::DoNothing => (Self::DoNothing, None),
+				OnBusyUpdate::Queue => (Self::DoNothing, Some(Self::Capture(callback))),
+				OnBusyUpdate::Restart => (Self::both(Self::Stop, Self::Capture(callback)), None),
+				OnBusyUpdate::Signal(sig) => (Self::Signal(*sig), None),
+			},
+			Self::Both(one, two) => {
+				let frees = one.frees_process();
+				match one.when_busy(on_busy) {
+					(now, Some(queued)) => (now, Some(Self::both(queued, *two))),
+					(now, None) if frees => (Self::both(now, *two), None),
+					(now, None) => {
+						let (two_now, queued) = two.when_busy(on_busy);
+						(Self::both(now, two_now), queued)
+					}
+				}
+			}
+			other => (other, None),
+		}
+	}
+
+	/// Whether the command is no longer running after this resolved outcome.
+	fn frees_process(&self) -> bool {
+		match self {
+			Self::Stop | Self::Wait => true,
+			Self::Both(one, two) => one.frees_process() || two.frees_process(),
+			_ => false,
+		}
+	}
+
+	/// Replaces any [`Start`](Outcome::Start), [`Capture`](Outcome::Capture), or
+	/// [`Restart`](Outcome::Restart) in a resolved outcome with `DoNothing`.
+	pub(super) fn without_start(self) -> Self {
+		match self {
+			Self::Start | Self::Capture(_) | Self::Restart(_) => Self::DoNothing,
+			Self::Both(one, two) => Self::both(one.without_start(), two.without_start()),
+			Self::Delay(after, then) => Self::delay(after, then.without_start()),
+			other => other,
+		}
+	}
+
+	/// Splits a resolved outcome into what to do now, and what to do after a delay, if anything.
+	///
+	/// Only the first [`Delay`](Outcome::Delay) is split off; any within it is left for later.
+	pub(super) fn split_delay(self) -> (Self, Option<(Duration, Self)>) {
+		match self {
+			Self::Delay(after, then) => (Self::DoNothing, Some((after, *then))),
+			Self::Both(one, two) => match one.split_delay() {
+				(now, Some((after, later))) => (now, Some((after, Self::both(later, *two)))),
+				(now, None) => {
+					let (two_now, later) = two.split_delay();
+					(Self::both(now, two_now), later)
+				}
+			},
+			other => (other, None),
+		}
+	}
 }
 
 #[cfg(test)]
 mod test {
 	use super::*;
 
+	#[test]
+	fn exits_anywhere_in_both() {
+		assert!(Outcome::both(Outcome::Clear, Outcome::Exit).exits());
+		assert!(!Outcome::both(Outcome::Clear, Outcome::Start).exits());
+		assert!(!Outcome::if_running(Outcome::Exit, Outcome::Start).exits());
+	}
+
+	#[test]
+	fn when_busy_replaces_start() {
+		assert_eq!(
+			Outcome::Start.when_busy(&OnBusyUpdate::Queue),
+			(Outcome::DoNothing, Some(Outcome::Start))
+		);
+		assert_eq!(
+			Outcome::Start.when_busy(&OnBusyUpdate::DoNothing),
+			(Outcome::DoNothing, None)
+		);
+		assert_eq!(
+			Outcome::Start.when_busy(&OnBusyUpdate::Restart),
+			(Outcome::Restart(None), None)
+		);
+		assert_eq!(
+			Outcome::both(Outcome::Clear, Outcome::Start)
+				.when_busy(&OnBusyUpdate::Signal(SubSignal::User1)),
+			(
+				Outcome::both(Outcome::Clear, Outcome::Signal(SubSignal::User1)),
+				None
+			)
+		);
+	}
+
+	#[test]
+	fn when_busy_queues_the_rest() {
+		assert_eq!(
+			Outcome::both(
+				Outcome::Clear,
+				Outcome::both(Outcome::Start, Outcome::Signal(SubSignal::User1))
+			)
+			.when_busy(&OnBusyUpdate::Queue),
+			(
+				Outcome::both(Outcome::Clear, Outcome::DoNothing),
+				Some(Outcome::both(
+					Outcome::Start,
+					Outcome::Signal(SubSignal::User1)
+				))
+			)
+		);
+	}
+
+	#[test]
+	fn when_busy_leaves_start_after_stop() {
+		let outcome = Outcome::both(Outcome::Stop, Outcome::Start);
+		assert_eq!(
+			outcome.clone().when_busy(&OnBusyUpdate::Queue),
+			(outcome, None)
+		);
+		assert_eq!(
+			Outcome::Restart(None).when_busy(&OnBusyUpdate::DoNothing),
+			(Outcome::Restart(None), None)
+		);
+	}
+
 	#[test]
 	fn simple_if_running() {
 		assert_eq!(
@@ -108,6 +503,57 @@ mod test {
 		assert_eq!(Outcome::Clear.resolve(false), Outcome::Clear);
 	}
 
+	#[test]
+	fn without_start_strips_nested() {
+		assert_eq!(
+			Outcome::both(Outcome::Clear, Outcome::both(Outcome::Stop, Outcome::Start))
+				.without_start(),
+			Outcome::both(
+				Outcome::Clear,
+				Outcome::both(Outcome::Stop, Outcome::DoNothing)
+			)
+		);
+	}
+
+	#[test]
+	fn capture_follows_busy_policy() {
+		let capture = Outcome::capture(|_| Box::pin(async { Ok(()) }));
+		assert_eq!(
+			capture.clone().when_busy(&OnBusyUpdate::Queue),
+			(Outcome::DoNothing, Some(capture.clone()))
+		);
+		assert_eq!(
+			capture.clone().when_busy(&OnBusyUpdate::Restart),
+			(Outcome::both(Outcome::Stop, capture.clone()), None)
+		);
+		assert_eq!(capture.without_start(), Outcome::DoNothing);
+	}
+
+	#[test]
+	fn split_delay_puts_off_the_rest() {
+		let after = Duration::from_secs(2);
+		assert_eq!(
+			Outcome::both(
+				Outcome::Clear,
+				Outcome::both(Outcome::delay(after, Outcome::Stop), Outcome::Start)
+			)
+			.split_delay(),
+			(
+				Outcome::both(Outcome::Clear, Outcome::DoNothing),
+				Some((after, Outcome::both(Outcome::Stop, Outcome::Start)))
+			)
+		);
+		assert_eq!(Outcome::Start.split_delay(), (Outcome::Start, None));
+	}
+
+	#[test]
+	fn callbacks_equal_their_clones() {
+		let one = Outcome::callback(|_| Box::pin(async { Ok(()) }));
+		let two = Outcome::callback(|_| Box::pin(async { Ok(()) }));
+		assert_eq!(one.clone(), one);
+		assert_ne!(one, two);
+	}
+
 	#[test]
 	fn nested_if_runnings() {
 		assert_eq!(
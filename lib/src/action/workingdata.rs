@@ -1,19 +1,36 @@
 use std::{
+	collections::HashMap,
 	fmt,
-	sync::{Arc, Weak},
+	path::PathBuf,
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Arc, PoisonError, Weak,
+	},
 	time::Duration,
 };
 
 use atomic_take::AtomicTake;
+use futures::future::BoxFuture;
 use once_cell::sync::OnceCell;
 use tokio::{
 	process::Command,
 	sync::{Mutex, OwnedMutexGuard},
 };
+use tracing::level_filters::LevelFilter;
 
-use crate::{command::Shell, event::Event, filter::Filterer, handler::Handler};
+use crate::{
+	command::{ResourceLimits, Shell},
+	error::RuntimeError,
+	event::{Event, ProcessEnd},
+	filter::{
+		expr::{ExprFilterer, Filter},
+		Filterer,
+	},
+	handler::{rte, Handler},
+	signal::{process::SubSignal, source::MainSignal},
+};
 
-use super::Outcome;
+use super::{OnBusyUpdate, Outcome};
 
 /// The configuration of the [action][crate::action] worker.
 ///
@@ -28,6 +45,112 @@ pub struct WorkingData {
 	/// action is called with all the collected events in the cycle.
 	pub throttle: Duration,
 
+	/// Run actions at most once per this duration, on the leading edge.
+	///
+	/// When set, this replaces the [`throttle`](WorkingData#structfield.throttle): the first event
+	/// is actioned immediately, then events arriving within the window are collected, and actioned
+	/// together once the window (counted from the start of the previous action) has elapsed. If no
+	/// events arrive in a window, nothing is run at its edge, and the next event is again actioned
+	/// immediately.
+	///
+	/// This suits chatty sources where latency matters more than batching. Defaults to `None`.
+	pub rate_limit: Option<Duration>,
+
+	/// How long the action waits for events to settle, whatever their source.
+	///
+	/// When set, an action is only triggered once no event has arrived for this long, on top of
+	/// the [`throttle`](WorkingData#structfield.throttle) (or the
+	/// [`rate_limit`](WorkingData#structfield.rate_limit)): each new event resets the window. This
+	/// coalesces bursts of events from all sources, like [`send_event()`](crate::Watchexec::send_event())
+	/// and signals, into one action, whereas the
+	/// [fs worker's debounce](crate::fs::WorkingData#structfield.debounce) only sees filesystem
+	/// events. The two are independent: the fs debounce can be kept short and this one longer.
+	///
+	/// Events rejected by the filterer don't reset the window. Defaults to `None`.
+	pub debounce: Option<Duration>,
+
+	/// Whether to trigger an action as soon as the worker starts.
+	///
+	/// When enabled, the action worker behaves as if it received an empty event (which by-passes
+	/// filters) on startup: after the throttle, the action handler is called with it (and any other
+	/// events which arrived meanwhile). This is only read when the worker starts; changing it in a
+	/// reconfigure does nothing. Defaults to `false`.
+	pub run_on_start: bool,
+
+	/// What to do when an action would start the command while it's still running.
+	///
+	/// See [`OnBusyUpdate`] for the options. Defaults to [`OnBusyUpdate::Queue`].
+	pub on_busy: OnBusyUpdate,
+
+	/// Outcomes to apply directly when a signal is received.
+	///
+	/// When a [signal event](crate::event::Tag::Signal) arrives for a signal in this map, its
+	/// outcome is applied straight away (resolved against the current state of the command, as
+	/// for an action), and the event doesn't go to the filterer nor the action handler. For
+	/// example, mapping [`MainSignal::User1`] to [`Outcome::Restart`] restarts the command
+	/// whenever Watchexec receives `SIGUSR1`. What happens to other signals is set by
+	/// [`unmapped_signals`](WorkingData#structfield.unmapped_signals). Defaults to empty.
+	pub signal_map: HashMap<MainSignal, Outcome>,
+
+	/// What to do with signals which aren't in the [`signal_map`](WorkingData#structfield.signal_map).
+	///
+	/// Defaults to [`UnmappedSignals::Handler`], which treats them as any other event; with that
+	/// and an empty map, signals are handled as they always were.
+	pub unmapped_signals: UnmappedSignals,
+
+	/// Whether to drop duplicate filesystem events within an action's batch.
+	///
+	/// A single save often produces several events for the same path and kind (e.g. a write
+	/// reported as a few successive modifications). When enabled, an event is dropped if every
+	/// pair of one of its paths and one of its [kinds](crate::event::Tag::FileEventKind) is already
+	/// in an earlier event of the batch. The first occurrence is kept, in order. Events without
+	/// paths are never dropped. Defaults to `true`; disable it to see every raw event.
+	pub dedupe: bool,
+
+	/// Whether to check that the paths of events still exist before calling the action handler.
+	///
+	/// When enabled, once the throttle is over, the path of each [`Tag::Path`] is looked up (without
+	/// following symlinks): paths which no longer exist are removed, such as files created and then
+	/// deleted within the window, and the others have their `file_type` set to what it is now.
+	/// Events left without any path are dropped, and if that's all of them, no action happens.
+	///
+	/// This costs a `stat` call per path, and drops events for deletions (as their paths are gone),
+	/// so it's best suited to handlers which only care about what's there. Defaults to `false`.
+	///
+	/// [`Tag::Path`]: crate::event::Tag::Path
+	pub drop_vanished: bool,
+
+	/// Whether to go through the whole pipeline without applying outcomes.
+	///
+	/// When enabled, the action handler is called and its outcome resolved as usual (including
+	/// outcomes from the [`signal_map`](WorkingData#structfield.signal_map)), but instead of being
+	/// applied the outcome is logged, and sent to the
+	/// [event stream](crate::Watchexec::event_stream()) as an event with a
+	/// [`Tag::WouldHaveRun`](crate::event::Tag::WouldHaveRun). No command is started or signaled,
+	/// and no callback is called. An outcome with an [`Exit`](Outcome::Exit) still stops the
+	/// runtime, so it can be quit as usual. Defaults to `false`.
+	pub dry_run: bool,
+
+	/// How many times the command may be restarted within the
+	/// [`restart_window`](WorkingData#structfield.restart_window) before it's considered to be in
+	/// a crash loop.
+	///
+	/// Every start of the command by an action counts, bar the first in the window. Once there are
+	/// more than this, further starts are delayed, by one second and then twice as long each time,
+	/// and a [`Tag::RestartBackoff`](crate::event::Tag::RestartBackoff) event is sent to the
+	/// [event stream](crate::Watchexec::event_stream()) for each. When the delay would be longer
+	/// than the window, the worker gives up: the start is dropped (the rest of the outcome is still
+	/// applied) and a [`RuntimeError::CrashLoop`] is reported. A whole window without any start
+	/// resets all this.
+	///
+	/// Defaults to `None`, which disables crash loop detection.
+	pub max_restarts: Option<usize>,
+
+	/// The window over which restarts are counted for [`max_restarts`](WorkingData#structfield.max_restarts).
+	///
+	/// Defaults to one minute.
+	pub restart_window: Duration,
+
 	/// The main handler to define: what to do when an action is triggered.
 	///
 	/// This handler is called with the [`Action`] environment, which has a certain way of returning
@@ -65,6 +188,16 @@ pub struct WorkingData {
 	/// issue a [`RuntimeError`][crate::error::RuntimeError] to the error channel.
 	pub post_spawn_handler: Arc<AtomicTake<Box<dyn Handler<PostSpawn> + Send>>>,
 
+	/// A handler triggered when a command has exited.
+	///
+	/// This handler is called with the [`PostExit`] environment, which provides details on the
+	/// command and how it ended, as soon as the action worker receives its completion event (and
+	/// before that event goes through the filterer and the action handler).
+	///
+	/// Returning an error from the handler will issue a
+	/// [`RuntimeError`][crate::error::RuntimeError] to the error channel.
+	pub post_exit_handler: Arc<AtomicTake<Box<dyn Handler<PostExit> + Send>>>,
+
 	/// Command to execute.
 	///
 	/// When `shell` is [`Shell::None`], this is expected to be in “execvp(3)” format: first
@@ -73,15 +206,172 @@ pub struct WorkingData {
 	/// doing your own joining and/or escaping there.
 	pub command: Vec<String>,
 
+	/// More commands to run alongside the [`command`](WorkingData#structfield.command).
+	///
+	/// These are in the same format as the command, and are started with it, concurrently, on
+	/// [`Outcome::Start`] (each going through the pre-spawn and post-spawn handlers). They're then
+	/// handled as one: interventions like [`Outcome::Stop`] or [`Outcome::Signal`] apply to all of
+	/// them, the set is running as long as any of them is, and a single completion event is issued
+	/// once they've all ended. That event has a [`Tag::ProcessCompletion`](crate::event::Tag::ProcessCompletion)
+	/// and a [`Tag::Process`](crate::event::Tag::Process) for each, in the order they ended, and
+	/// the `stopped-by` metadata of each.
+	///
+	/// The main command is the one whose ID is published and given to the post-exit handler. If
+	/// it's empty, or vetoed by the pre-spawn handler, none of these are started either.
+	///
+	/// Defaults to none.
+	pub parallel_commands: Vec<Vec<String>>,
+
 	/// Whether to use process groups (on Unix) or job control (on Windows) to run the command.
 	///
-	/// This makes use of [command_group] under the hood.
+	/// This makes use of [command_group] under the hood. On Unix, the command is started in a new
+	/// session (with `setsid`), and signals are sent to its whole process group. On Windows, it's
+	/// assigned to a Job Object with `JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE`, so killing it kills
+	/// every process in the job. Either way, stopping the command also stops the processes it
+	/// started, e.g. those run by a shell, instead of leaving them behind holding files or ports.
+	///
+	/// Defaults to `true`. Turn this off if the command needs to stay in Watchexec's session, e.g.
+	/// to read from the terminal.
 	///
 	/// If you want to known whether a spawned command was run in a process group, you should use
 	/// the value in [`PostSpawn`] instead of reading this one, as it may have changed in the
 	/// meantime.
 	pub grouped: bool,
 
+	/// The signal to send to the command to ask it to stop.
+	///
+	/// This is used for [`Outcome::Stop`] and [`Outcome::Restart(None)`](Outcome::Restart): the
+	/// command is sent this signal, given [`stop_timeout`](WorkingData#structfield.stop_timeout) to
+	/// exit, and forcefully stopped if it's still running after that. The completion event of the
+	/// command records which of these stages ended it, in its `stopped-by` metadata.
+	///
+	/// On Windows, only [`SubSignal::ForceStop`] and [`SubSignal::Terminate`] are supported for
+	/// this, and both terminate the process immediately.
+	///
+	/// Defaults to [`SubSignal::ForceStop`], which stops the command at once.
+	pub stop_signal: SubSignal,
+
+	/// How long to wait for the command to exit after sending the
+	/// [`stop_signal`](WorkingData#structfield.stop_signal), before forcefully stopping it.
+	///
+	/// Defaults to 10 seconds. This is not used when the stop signal is [`SubSignal::ForceStop`].
+	pub stop_timeout: Duration,
+
+	/// How long the command may run for before it's stopped.
+	///
+	/// When the command has been running for this long, a
+	/// [`Tag::ProcessTimeout`](crate::event::Tag::ProcessTimeout) event is issued, and the command
+	/// is stopped as for [`Outcome::Stop`], using the
+	/// [`stop_signal`](WorkingData#structfield.stop_signal) and
+	/// [`stop_timeout`](WorkingData#structfield.stop_timeout). Its completion event then also has a
+	/// [`Tag::ProcessTimeout`](crate::event::Tag::ProcessTimeout), to tell it apart from a command
+	/// which exited by itself. The timer is cancelled if the command exits first. Defaults to
+	/// `None`, for no timeout.
+	pub command_timeout: Option<Duration>,
+
+	/// Limits on the resources the command may use.
+	///
+	/// See [`ResourceLimits`] for the limits available. These are applied on Unix only, and do
+	/// nothing on Windows. Defaults to no limits.
+	pub rlimits: ResourceLimits,
+
+	/// Whether to run the command with its stdout and stderr attached to a pseudo-terminal.
+	///
+	/// Many programs only use colours or other interactive output when they're writing to a
+	/// terminal. When this is enabled, a new PTY is allocated for each command, and what the
+	/// command writes to it is copied to Watchexec's stdout. The PTY has the window size of the
+	/// terminal Watchexec is running in, if any, and follows it when it's resized. Stdin is not
+	/// affected. The PTY is closed once the command (and any descendant still holding it) exits.
+	///
+	/// This is currently only supported on Linux and Android; elsewhere, a warning is emitted and
+	/// the command inherits Watchexec's stdio as usual. Defaults to `false`.
+	pub pty: bool,
+
+	/// Whether to restart the command when its program changes on disk.
+	///
+	/// When enabled, the program is resolved (as with `PATH` lookup) and added to the pathset as a
+	/// [`WatchedPath::file()`](crate::fs::WatchedPath::file()), so it's seen even when replaced by
+	/// a rename, as installers often do. When an action includes an event for the program while
+	/// the command is running, and the outcome from the handler wouldn't already start, stop, or
+	/// restart it, a [`Restart`](Outcome::Restart) is added to the outcome.
+	///
+	/// This only works with [`Shell::None`], as there's no single program to watch when a shell
+	/// runs the command. The program is resolved when the configuration is applied; changing `PATH`
+	/// in the meantime isn't taken into account. Defaults to `false`.
+	pub watch_command_binary: bool,
+
+	/// Whether to capture the output of the command as events.
+	///
+	/// When enabled, the command's stdout and stderr are piped, split into lines, and each line is
+	/// sent as an event with a [`Tag::Output`](crate::event::Tag::Output), instead of being
+	/// written to Watchexec's own stdout and stderr. A last line without a terminator is sent when
+	/// the stream closes, i.e. in general when the command exits; this may come after the completion
+	/// event of the command. Output events go through filtering and to the action handler like any
+	/// other, so the handler should take care not to start the command in response to them: see
+	/// [`Event::is_output()`].
+	///
+	/// Reading the output is paused while the event channel is full, which in turn blocks the
+	/// command as it writes. The pre-spawn handler can still redirect the streams elsewhere, which
+	/// disables their capture. This has no effect when [`pty`](WorkingData#structfield.pty) is
+	/// enabled. Defaults to `false`.
+	pub capture_output: bool,
+
+	/// Whether a carriage return on its own also ends a line of captured output.
+	///
+	/// Progress bars and spinners often redraw a line with a carriage return: when enabled, each
+	/// redraw becomes a line. Otherwise, carriage returns are kept in the line, except for one just
+	/// before a newline, which is always removed. Defaults to `false`.
+	pub output_cr_lines: bool,
+
+	/// How much of each of stdout and stderr to keep, in bytes, for [`Outcome::Capture`].
+	///
+	/// Output past this is read but dropped, and the capture is marked as
+	/// [`truncated`](super::CapturedOutput#structfield.truncated), so a chatty command can't make
+	/// Watchexec run out of memory. Defaults to 1 MiB.
+	pub capture_limit: usize,
+
+	/// Whether to keep the command's stdin open, for [`Outcome::Stdin`].
+	///
+	/// When enabled, the command's stdin is a pipe held by Watchexec, instead of being inherited
+	/// from Watchexec's own stdin, so programs which take commands on stdin (REPLs, test runners in
+	/// watch mode) can be driven from the action handler. The pipe is closed when the command ends.
+	/// The pre-spawn handler can still redirect stdin elsewhere, which disables this. Defaults to
+	/// `false`.
+	pub keep_stdin: bool,
+
+	/// Whether to set environment variables describing the triggering events on the command.
+	///
+	/// When enabled, the paths in the events which triggered the action are summarised with
+	/// [`summarise_events_to_env()`][crate::paths::summarise_events_to_env()], and each category
+	/// is set as `{env_prefix}{CATEGORY}_PATH`, e.g. `WATCHEXEC_WRITTEN_PATH`. Paths are joined as
+	/// [`OsString`][std::ffi::OsString]s, so non-UTF-8 paths are passed through unchanged.
+	///
+	/// This is done before the pre-spawn handler runs, so it can override or remove them. Defaults
+	/// to `false`.
+	pub environment: bool,
+
+	/// The prefix of the environment variables set when `environment` is enabled.
+	///
+	/// Defaults to `WATCHEXEC_`.
+	pub env_prefix: String,
+
+	/// Whether to write the triggering events to a file for the command.
+	///
+	/// When enabled, the events which triggered the action are serialized as a JSON array to a new
+	/// file in the temporary directory, whose path is set on the command as
+	/// `{env_prefix}EVENTS_FILE`, e.g. `WATCHEXEC_EVENTS_FILE`. The file is removed once the command
+	/// has ended, however it ends. This needs the `serde` feature. Defaults to `false`.
+	pub events_to_file: bool,
+
+	/// The working directory to run the command in.
+	///
+	/// Relative paths are resolved against Watchexec's own current directory, at spawn time. If
+	/// the directory doesn't exist (or isn't a directory), the command is not spawned and a
+	/// [`RuntimeError::CommandWorkdir`](crate::error::RuntimeError::CommandWorkdir) is issued. This
+	/// can be overridden for a single action with [`Action::workdir()`]. Defaults to `None`, which
+	/// runs the command in Watchexec's current directory.
+	pub workdir: Option<PathBuf>,
+
 	/// The shell to use to run the command.
 	///
 	/// See the [`Shell`] enum documentation for more details.
@@ -91,32 +381,439 @@ pub struct WorkingData {
 	///
 	/// The default is a no-op, which will always pass every event.
 	pub filterer: Arc<dyn Filterer>,
+
+	/// [Filter expressions](Filter) events must pass, after the filterer.
+	///
+	/// These are compiled into an [`ExprFilterer`](crate::filter::expr::ExprFilterer) once, when
+	/// the action worker first sees them (so after each reconfigure which changes them), not on
+	/// every event. Events which by-pass the filterer (like empty events) also by-pass these.
+	///
+	/// Invalid globs are reported when [starting](crate::Watchexec::new()) Watchexec; if they're
+	/// only given on reconfigure, an error is issued for every event instead, which then fails.
+	/// Use [`Filter::from_str()`](std::str::FromStr::from_str) or
+	/// [`ExprFilterer::new()`](crate::filter::expr::ExprFilterer::new()) to check them beforehand.
+	///
+	/// Defaults to none, which passes every event.
+	pub filters: Vec<Filter>,
+
+	/// An additional predicate events must pass, after the filterer.
+	///
+	/// Events are only acted upon if both the [`filterer`](WorkingData#structfield.filterer) and
+	/// this return `true`. Events which by-pass the filterer (like empty events) also by-pass this.
+	///
+	/// This runs on the hot path, synchronously, for every event: it should be cheap and must not
+	/// block. The default always returns `true`.
+	pub predicate: Arc<dyn Fn(&Event) -> bool + Send + Sync>,
+
+	/// An additional predicate events must pass, after the [`predicate`](#structfield.predicate),
+	/// which can do async work.
+	///
+	/// This is for filtering which needs I/O, like asking a service or looking up a database. It's
+	/// given a clone of each event which the other filters passed, and the event is acted upon if
+	/// it resolves to `true`. Events which by-pass the filterer also by-pass this.
+	///
+	/// The action worker awaits it for every event, and receives no other events meanwhile: its
+	/// latency adds to that of every event, and a slow predicate will back events up all the way
+	/// to the watchers. If it doesn't resolve within the
+	/// [`async_predicate_timeout`](#structfield.async_predicate_timeout), the event is passed
+	/// (with a warning). Defaults to `None`, which costs nothing.
+	pub async_predicate: Option<Arc<dyn Fn(Event) -> BoxFuture<'static, bool> + Send + Sync>>,
+
+	/// How long to wait for the [`async_predicate`](#structfield.async_predicate) of an event
+	/// before passing it.
+	///
+	/// Defaults to 1 second.
+	pub async_predicate_timeout: Duration,
+
+	/// A function to transform events before they're filtered.
+	///
+	/// This is given every event the action worker receives, and returns the event to act on
+	/// instead, which may be changed in any way (e.g. to map a build output path back to its
+	/// source), or `None` to drop it. Dropped events are counted as filtered.
+	///
+	/// As for ordering: events are sent to [observers](crate::Watchexec::event_stream()) and (for
+	/// process completions) to the post-exit handler before this, as received. The mapped event is
+	/// then what the [`signal_map`](WorkingData#structfield.signal_map), the
+	/// [`filterer`](WorkingData#structfield.filterer), the
+	/// [`predicate`](WorkingData#structfield.predicate), and the action handler see. The path globs
+	/// of the [filesystem worker](crate::fs::WorkingData#structfield.ignore_globs) run before
+	/// events even reach the action worker, so on the paths as the watcher reports them.
+	///
+	/// This runs on the hot path, synchronously, for every event: it should be cheap and must not
+	/// block. The default returns the event unchanged.
+	pub event_mapper: Arc<dyn Fn(Event) -> Option<Event> + Send + Sync>,
+
+	/// Set by [`Watchexec`](crate::Watchexec) from the [`InitConfig`](crate::config::InitConfig),
+	/// for [`Outcome::LogLevel`].
+	pub(crate) log_level: LogLevelHandler,
+}
+
+/// The [log level handler](crate::config::InitConfig#structfield.log_level_handler), shared
+/// between the runtime and the action worker.
+#[derive(Clone, Default)]
+pub(crate) struct LogLevelHandler(
+	Arc<std::sync::Mutex<Option<Box<dyn Handler<LevelFilter> + Send>>>>,
+);
+
+impl LogLevelHandler {
+	pub fn new(handler: Option<Box<dyn Handler<LevelFilter> + Send>>) -> Self {
+		Self(Arc::new(std::sync::Mutex::new(handler)))
+	}
+
+	/// Calls the handler with the new level.
+	pub fn set(&self, level: LevelFilter) -> Result<(), RuntimeError> {
+		let mut handler = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+		match handler.as_mut() {
+			Some(handler) => handler.handle(level).map_err(|e| rte("log level", e)),
+			None => Err(RuntimeError::NoLogLevelHandler),
+		}
+	}
 }
 
 impl fmt::Debug for WorkingData {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
 		f.debug_struct("WorkingData")
 			.field("throttle", &self.throttle)
+			.field("rate_limit", &self.rate_limit)
+			.field("debounce", &self.debounce)
+			.field("run_on_start", &self.run_on_start)
+			.field("on_busy", &self.on_busy)
+			.field("signal_map", &self.signal_map)
+			.field("unmapped_signals", &self.unmapped_signals)
+			.field("dedupe", &self.dedupe)
+			.field("drop_vanished", &self.drop_vanished)
+			.field("dry_run", &self.dry_run)
+			.field("max_restarts", &self.max_restarts)
+			.field("restart_window", &self.restart_window)
 			.field("shell", &self.shell)
 			.field("command", &self.command)
+			.field("parallel_commands", &self.parallel_commands)
 			.field("grouped", &self.grouped)
+			.field("stop_signal", &self.stop_signal)
+			.field("stop_timeout", &self.stop_timeout)
+			.field("command_timeout", &self.command_timeout)
+			.field("rlimits", &self.rlimits)
+			.field("pty", &self.pty)
+			.field("watch_command_binary", &self.watch_command_binary)
+			.field("capture_output", &self.capture_output)
+			.field("output_cr_lines", &self.output_cr_lines)
+			.field("capture_limit", &self.capture_limit)
+			.field("keep_stdin", &self.keep_stdin)
+			.field("environment", &self.environment)
+			.field("env_prefix", &self.env_prefix)
+			.field("events_to_file", &self.events_to_file)
+			.field("workdir", &self.workdir)
 			.field("filterer", &self.filterer)
+			.field("filters", &self.filters)
+			.field("async_predicate_timeout", &self.async_predicate_timeout)
 			.finish_non_exhaustive()
 	}
 }
 
+/// What the action worker does with signals which aren't in the
+/// [`signal_map`](WorkingData#structfield.signal_map).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum UnmappedSignals {
+	/// Treat them as any other event: filter them, and call the action handler with them.
+	Handler,
+
+	/// Send them on to the command (if it's running), as with [`Outcome::Signal`].
+	Forward,
+
+	/// Drop them.
+	Ignore,
+}
+
+impl Default for UnmappedSignals {
+	fn default() -> Self {
+		Self::Handler
+	}
+}
+
+impl WorkingData {
+	/// Whether this is the same configuration as `other`.
+	///
+	/// Handlers, the filterer, the predicates, and the event mapper can't be compared by value, so
+	/// they are compared by identity: a clone is the same as its original, but setting a handler again (even to the
+	/// same function) makes a different configuration.
+	pub(crate) fn same_as(&self, other: &Self) -> bool {
+		fn same_arc<T: ?Sized>(a: &Arc<T>, b: &Arc<T>) -> bool {
+			Arc::as_ptr(a).cast::<()>() == Arc::as_ptr(b).cast::<()>()
+		}
+
+		self.diff(other).is_empty()
+			&& same_arc(&self.action_handler, &other.action_handler)
+			&& same_arc(&self.pre_spawn_handler, &other.pre_spawn_handler)
+			&& same_arc(&self.post_spawn_handler, &other.post_spawn_handler)
+			&& same_arc(&self.post_exit_handler, &other.post_exit_handler)
+			&& same_arc(&self.filterer, &other.filterer)
+			&& same_arc(&self.predicate, &other.predicate)
+			&& match (&self.async_predicate, &other.async_predicate) {
+				(Some(a), Some(b)) => same_arc(a, b),
+				(a, b) => a.is_none() && b.is_none(),
+			} && same_arc(&self.event_mapper, &other.event_mapper)
+	}
+
+	/// The fields which differ from `other`, with their values in this configuration.
+	///
+	/// This leaves out the handlers, the filterer, the predicates, and the event mapper, which can't
+	/// be compared by value (see [`same_as()`](WorkingData::same_as())).
+	pub(crate) fn diff(&self, other: &Self) -> Vec<(&'static str, String)> {
+		// destructured so new fields can't be forgotten here
+		let Self {
+			throttle,
+			rate_limit,
+			debounce,
+			run_on_start,
+			on_busy,
+			signal_map,
+			unmapped_signals,
+			dedupe,
+			drop_vanished,
+			dry_run,
+			max_restarts,
+			restart_window,
+			action_handler: _,
+			pre_spawn_handler: _,
+			post_spawn_handler: _,
+			post_exit_handler: _,
+			command,
+			parallel_commands,
+			grouped,
+			stop_signal,
+			stop_timeout,
+			command_timeout,
+			rlimits,
+			pty,
+			watch_command_binary,
+			capture_output,
+			output_cr_lines,
+			capture_limit,
+			keep_stdin,
+			environment,
+			env_prefix,
+			events_to_file,
+			workdir,
+			shell,
+			filterer: _,
+			filters,
+			predicate: _,
+			log_level: _,
+			async_predicate: _,
+			async_predicate_timeout,
+			event_mapper: _,
+		} = self;
+
+		let mut diff = Vec::new();
+		let mut field = |name: &'static str, same: bool, value: &dyn fmt::Debug| {
+			if !same {
+				diff.push((name, format!("{:?}", value)));
+			}
+		};
+		field("throttle", *throttle == other.throttle, throttle);
+		field("rate_limit", *rate_limit == other.rate_limit, rate_limit);
+		field("debounce", *debounce == other.debounce, debounce);
+		field(
+			"run_on_start",
+			*run_on_start == other.run_on_start,
+			run_on_start,
+		);
+		field("on_busy", *on_busy == other.on_busy, on_busy);
+		field("signal_map", *signal_map == other.signal_map, signal_map);
+		field(
+			"unmapped_signals",
+			*unmapped_signals == other.unmapped_signals,
+			unmapped_signals,
+		);
+		field("dedupe", *dedupe == other.dedupe, dedupe);
+		field(
+			"drop_vanished",
+			*drop_vanished == other.drop_vanished,
+			drop_vanished,
+		);
+		field("dry_run", *dry_run == other.dry_run, dry_run);
+		field(
+			"max_restarts",
+			*max_restarts == other.max_restarts,
+			max_restarts,
+		);
+		field(
+			"restart_window",
+			*restart_window == other.restart_window,
+			restart_window,
+		);
+		field("command", *command == other.command, command);
+		field(
+			"parallel_commands",
+			*parallel_commands == other.parallel_commands,
+			parallel_commands,
+		);
+		field("grouped", *grouped == other.grouped, grouped);
+		field(
+			"stop_signal",
+			*stop_signal == other.stop_signal,
+			stop_signal,
+		);
+		field(
+			"stop_timeout",
+			*stop_timeout == other.stop_timeout,
+			stop_timeout,
+		);
+		field(
+			"command_timeout",
+			*command_timeout == other.command_timeout,
+			command_timeout,
+		);
+		field("rlimits", *rlimits == other.rlimits, rlimits);
+		field("pty", *pty == other.pty, pty);
+		field(
+			"watch_command_binary",
+			*watch_command_binary == other.watch_command_binary,
+			watch_command_binary,
+		);
+		field(
+			"capture_output",
+			*capture_output == other.capture_output,
+			capture_output,
+		);
+		field(
+			"output_cr_lines",
+			*output_cr_lines == other.output_cr_lines,
+			output_cr_lines,
+		);
+		field(
+			"capture_limit",
+			*capture_limit == other.capture_limit,
+			capture_limit,
+		);
+		field("keep_stdin", *keep_stdin == other.keep_stdin, keep_stdin);
+		field(
+			"environment",
+			*environment == other.environment,
+			environment,
+		);
+		field("env_prefix", *env_prefix == other.env_prefix, env_prefix);
+		field(
+			"events_to_file",
+			*events_to_file == other.events_to_file,
+			events_to_file,
+		);
+		field("workdir", *workdir == other.workdir, workdir);
+		field("shell", *shell == other.shell, shell);
+		field("filters", *filters == other.filters, filters);
+		field(
+			"async_predicate_timeout",
+			*async_predicate_timeout == other.async_predicate_timeout,
+			async_predicate_timeout,
+		);
+		diff
+	}
+
+	/// Describes mistakes in this configuration which would only show up once a command is run.
+	///
+	/// An empty command is fine (the action handler may never start one), but one which is there
+	/// and has nothing to run isn't.
+	pub(crate) fn problems(&self) -> Vec<String> {
+		let mut problems = Vec::new();
+
+		#[cfg(not(feature = "serde"))]
+		if self.events_to_file {
+			problems.push("events_to_file needs the serde feature".to_string());
+		}
+
+		if let Err(err) = ExprFilterer::new(self.filters.clone()) {
+			problems.push(format!("filters: {}", err));
+		}
+
+		if self.command.is_empty() {
+			return problems;
+		}
+
+		if let Shell::Unix(program) = &self.shell {
+			if program.trim().is_empty() {
+				problems.push(
+					"shell program is empty: use e.g. Shell::Unix(\"sh\"), or Shell::None to run the command directly".to_string(),
+				);
+				return problems;
+			}
+		}
+
+		problems.extend(self.command_problem(&self.command));
+		for command in &self.parallel_commands {
+			if command.is_empty() {
+				problems.push("parallel command is empty: give it something to run".to_string());
+			} else {
+				problems.extend(
+					self.command_problem(command)
+						.map(|problem| format!("parallel {}", problem)),
+				);
+			}
+		}
+
+		problems
+	}
+
+	/// Describes what's wrong with a command which is there but has nothing to run, if anything.
+	fn command_problem(&self, command: &[String]) -> Option<String> {
+		match &self.shell {
+			Shell::None if command[0].is_empty() => Some(
+				"command program is empty: with Shell::None, the first element of the command is the program to run".to_string(),
+			),
+			Shell::None => None,
+			_ if command.iter().all(|part| part.trim().is_empty()) => Some(
+				"command is blank: give the shell something to run, or leave the command empty".to_string(),
+			),
+			_ => None,
+		}
+	}
+}
+
 impl Default for WorkingData {
 	fn default() -> Self {
 		Self {
 			// set to 50ms here, but will remain 100ms on cli until 2022
 			throttle: Duration::from_millis(50),
+			rate_limit: None,
+			debounce: None,
+			run_on_start: false,
+			on_busy: OnBusyUpdate::default(),
+			signal_map: HashMap::new(),
+			unmapped_signals: UnmappedSignals::default(),
+			dedupe: true,
+			drop_vanished: false,
+			dry_run: false,
+			max_restarts: None,
+			restart_window: Duration::from_secs(60),
 			action_handler: Arc::new(AtomicTake::new(Box::new(()) as _)),
 			pre_spawn_handler: Arc::new(AtomicTake::new(Box::new(()) as _)),
 			post_spawn_handler: Arc::new(AtomicTake::new(Box::new(()) as _)),
+			post_exit_handler: Arc::new(AtomicTake::new(Box::new(()) as _)),
 			command: Vec::new(),
+			parallel_commands: Vec::new(),
 			shell: Shell::default(),
 			grouped: true,
+			stop_signal: SubSignal::ForceStop,
+			stop_timeout: Duration::from_secs(10),
+			command_timeout: None,
+			rlimits: ResourceLimits::default(),
+			pty: false,
+			watch_command_binary: false,
+			capture_output: false,
+			output_cr_lines: false,
+			capture_limit: 1024 * 1024,
+			keep_stdin: false,
+			environment: false,
+			env_prefix: "WATCHEXEC_".into(),
+			events_to_file: false,
+			workdir: None,
 			filterer: Arc::new(()),
+			filters: Vec::new(),
+			predicate: Arc::new(|_| true),
+			async_predicate: None,
+			async_predicate_timeout: Duration::from_secs(1),
+			event_mapper: Arc::new(Some),
+			log_level: LogLevelHandler::default(),
 		}
 	}
 }
@@ -132,7 +829,8 @@ impl Default for WorkingData {
 pub struct Action {
 	/// The collected events which triggered the action.
 	pub events: Arc<Vec<Event>>,
-	pub(super) outcome: Arc<OnceCell<Outcome>>,
+	pub(crate) outcome: Arc<OnceCell<Outcome>>,
+	pub(crate) workdir: Arc<OnceCell<PathBuf>>,
 }
 
 impl Action {
@@ -143,6 +841,15 @@ impl Action {
 		}
 	}
 
+	/// Set the working directory of the command, for this action only.
+	///
+	/// This overrides the [`workdir`](WorkingData#structfield.workdir) of the working data when
+	/// the outcome of this action starts a command, and must be called before
+	/// [`outcome()`](Action::outcome()). Only the first call has an effect.
+	pub fn workdir(&self, dir: impl Into<PathBuf>) {
+		self.workdir.set(dir.into()).ok();
+	}
+
 	/// Set the action's outcome.
 	///
 	/// This takes `self` and `Action` is not `Clone`, so it's only possible to call it once.
@@ -161,7 +868,8 @@ impl Action {
 /// you should not do.
 ///
 /// The [`PreSpawn::command()`] method is the only way to mutate the command, and the mutex guard it
-/// returns _must_ be dropped before the handler returns.
+/// returns _must_ be dropped before the handler returns. The [`PreSpawn::veto()`] method cancels
+/// the spawn.
 #[derive(Debug)]
 #[non_exhaustive]
 pub struct PreSpawn {
@@ -174,6 +882,7 @@ pub struct PreSpawn {
 	pub events: Arc<Vec<Event>>,
 
 	command_w: Weak<Mutex<Command>>,
+	vetoed: Arc<AtomicBool>,
 }
 
 impl PreSpawn {
@@ -181,18 +890,30 @@ impl PreSpawn {
 		command: Command,
 		cmd: Vec<String>,
 		events: Arc<Vec<Event>>,
-	) -> (Self, Arc<Mutex<Command>>) {
+	) -> (Self, Arc<Mutex<Command>>, Arc<AtomicBool>) {
 		let arc = Arc::new(Mutex::new(command));
+		let vetoed = Arc::new(AtomicBool::new(false));
 		(
 			Self {
 				command: cmd,
 				events,
 				command_w: Arc::downgrade(&arc),
+				vetoed: vetoed.clone(),
 			},
 			arc.clone(),
+			vetoed,
 		)
 	}
 
+	/// Cancel the spawn.
+	///
+	/// Once the handler returns, the command is dropped instead of being spawned, and the rest of
+	/// the outcome is applied as if it had been (so e.g. a [`Wait`](Outcome::Wait) after it does
+	/// nothing). The post-spawn and post-exit handlers aren't called.
+	pub fn veto(&self) {
+		self.vetoed.store(true, Ordering::SeqCst);
+	}
+
 	/// Get write access to the command that will be spawned.
 	///
 	/// Keeping the lock alive beyond the end of the handler may cause the command to be cancelled,
@@ -229,3 +950,28 @@ pub struct PostSpawn {
 	/// Whether the command was run in a process group.
 	pub grouped: bool,
 }
+
+/// The environment given to the post-exit handler.
+///
+/// Like [`PostSpawn`], this is Clone, as there's nothing left to do to the command.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct PostExit {
+	/// The final command the process was spawned with.
+	pub command: Vec<String>,
+
+	/// The collected events which triggered the action the command issues from.
+	pub events: Arc<Vec<Event>>,
+
+	/// The process ID or the process group ID.
+	pub id: u32,
+
+	/// Whether the command was run in a process group.
+	pub grouped: bool,
+
+	/// How the command ended, if known.
+	pub status: Option<ProcessEnd>,
+
+	/// How long the command ran for.
+	pub duration: Duration,
+}
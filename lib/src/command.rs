@@ -1,5 +1,8 @@
 //! Command construction, configuration, and tracking.
 
+#[doc(inline)]
+pub use limits::ResourceLimits;
+
 #[doc(inline)]
 pub use process::Process;
 
@@ -9,6 +12,19 @@ pub use shell::Shell;
 #[doc(inline)]
 pub use supervisor::Supervisor;
 
+pub(crate) use events_file::EventsFile;
+pub(crate) use output::Capture;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) use pty::Pty;
+pub(crate) use set::{merge_completions, SupervisorSet};
+pub(crate) use supervisor::Timeout;
+
+mod events_file;
+mod limits;
+mod output;
 mod process;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod pty;
+mod set;
 mod shell;
 mod supervisor;
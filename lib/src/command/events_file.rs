@@ -0,0 +1,74 @@
+//! Files describing the events which triggered a command.
+
+use std::path::{Path, PathBuf};
+
+use tracing::trace;
+
+use crate::{error::RuntimeError, event::Event};
+
+/// A temporary file holding the events which triggered a command, as JSON.
+///
+/// The file is removed when this is dropped. The supervisor holds it for as long as it watches
+/// the process, so it's cleaned up however the process ends, including if it's killed or the
+/// supervisor is aborted.
+#[derive(Debug)]
+pub(crate) struct EventsFile {
+	path: PathBuf,
+}
+
+impl EventsFile {
+	/// Writes the events to a new file in the temporary directory.
+	#[cfg(feature = "serde")]
+	pub async fn write(events: &[Event]) -> Result<Self, RuntimeError> {
+		use std::sync::atomic::{AtomicU64, Ordering};
+
+		static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+		let path = std::env::temp_dir().join(format!(
+			"watchexec-events-{}-{}.json",
+			std::process::id(),
+			COUNTER.fetch_add(1, Ordering::Relaxed)
+		));
+
+		let json = serde_json::to_vec(events).map_err(|err| RuntimeError::IoError {
+			about: "serializing events file",
+			err: err.into(),
+		})?;
+		tokio::fs::write(&path, json)
+			.await
+			.map_err(|err| RuntimeError::IoError {
+				about: "writing events file",
+				err,
+			})?;
+
+		trace!(?path, events=%events.len(), "events file written");
+		Ok(Self { path })
+	}
+
+	/// Serializing events needs the `serde` feature, so without it this errors.
+	#[cfg(not(feature = "serde"))]
+	pub async fn write(_events: &[Event]) -> Result<Self, RuntimeError> {
+		Err(RuntimeError::IoError {
+			about: "writing events file",
+			err: std::io::Error::new(
+				std::io::ErrorKind::Unsupported,
+				"events files need the serde feature",
+			),
+		})
+	}
+
+	/// The path to the file.
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+}
+
+impl Drop for EventsFile {
+	fn drop(&mut self) {
+		if let Err(err) = std::fs::remove_file(&self.path) {
+			tracing::debug!(path=?self.path, %err, "could not remove events file");
+		} else {
+			trace!(path=?self.path, "events file removed");
+		}
+	}
+}
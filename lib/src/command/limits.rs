@@ -0,0 +1,116 @@
+//! Resource limits for commands.
+//!
+//! Setting resource limits in the child between fork and exec needs `unsafe` (a `pre_exec` hook),
+//! which this crate doesn't use. Instead, when limits are set, the command is run through `sh`,
+//! which applies them with its `ulimit` builtin and then `exec`s the command, so the process is
+//! the same once it's started.
+
+use tokio::process::Command;
+
+/// Limits on the resources a command may use.
+///
+/// These apply to the command process, and are inherited by its children (each process gets its
+/// own allowance, the limits aren't shared). They're only supported on Unix, and do nothing on
+/// Windows. They're applied by running the command through `sh`, which sets them with its `ulimit`
+/// builtin and then `exec`s the command.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ResourceLimits {
+	/// Maximum CPU time, in seconds.
+	///
+	/// When the command has used this much CPU time, it's sent `SIGXCPU` (which terminates it by
+	/// default), and if it's still going one second later, it's killed.
+	pub cpu_seconds: Option<u64>,
+
+	/// Maximum size of the address space, in bytes.
+	///
+	/// This is rounded down to a whole number of KiB. Allocations which would go over the limit
+	/// fail.
+	pub address_space: Option<u64>,
+}
+
+impl ResourceLimits {
+	/// Sets the maximum CPU time, in seconds.
+	pub fn cpu_seconds(mut self, seconds: u64) -> Self {
+		self.cpu_seconds = Some(seconds);
+		self
+	}
+
+	/// Sets the maximum size of the address space, in bytes.
+	pub fn address_space(mut self, bytes: u64) -> Self {
+		self.address_space = Some(bytes);
+		self
+	}
+
+	/// Whether no limit is set.
+	pub fn is_empty(&self) -> bool {
+		self.cpu_seconds.is_none() && self.address_space.is_none()
+	}
+
+	/// The `sh` script which applies the limits, then runs the command from its arguments.
+	///
+	/// The soft CPU limit is lowered before the hard one, as the hard limit can't go below it.
+	fn script(&self) -> String {
+		let mut script = String::new();
+		if let Some(secs) = self.cpu_seconds {
+			script.push_str(&format!(
+				"ulimit -S -t {} && ulimit -H -t {} && ",
+				secs,
+				secs.saturating_add(1)
+			));
+		}
+		if let Some(bytes) = self.address_space {
+			script.push_str(&format!("ulimit -v {} && ", bytes / 1024));
+		}
+		script.push_str("exec \"$@\"");
+		script
+	}
+
+	/// Wraps the command so it runs with the limits.
+	///
+	/// This must be called before anything other than the program and arguments is set on the
+	/// command, as these are all that's carried over.
+	#[cfg(unix)]
+	pub(crate) fn wrap(&self, command: Command) -> Command {
+		if self.is_empty() {
+			return command;
+		}
+
+		let std = command.as_std();
+		let mut wrapped = Command::new("sh");
+		wrapped
+			.arg("-c")
+			.arg(self.script())
+			.arg("watchexec-limits")
+			.arg(std.get_program())
+			.args(std.get_args());
+		tracing::trace!(limits=?self, "running command through sh to apply resource limits");
+		wrapped
+	}
+
+	/// Resource limits aren't supported on this platform, so this does nothing.
+	#[cfg(not(unix))]
+	pub(crate) fn wrap(&self, command: Command) -> Command {
+		if !self.is_empty() {
+			tracing::debug!(limits=?self, "resource limits are not supported on this platform");
+		}
+		command
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn script_applies_each_limit() {
+		assert_eq!(ResourceLimits::default().script(), "exec \"$@\"");
+		assert_eq!(
+			ResourceLimits::default()
+				.cpu_seconds(2)
+				.address_space(64 * 1024 * 1024 + 10)
+				.script(),
+			"ulimit -S -t 2 && ulimit -H -t 3 && ulimit -v 65536 && exec \"$@\""
+		);
+	}
+}
@@ -0,0 +1,326 @@
+//! Capture of the output of commands, as events or for [`Outcome::Capture`].
+//!
+//! Each captured stream is read by a task, which splits it into lines and sends each line as an
+//! event with a [`Tag::Output`], or collects it up to a limit. The task ends when the stream is
+//! closed, which is generally when the command (and any descendant still holding it) exits,
+//! flushing any final partial line.
+//!
+//! [`Outcome::Capture`]: crate::action::Outcome::Capture
+
+use std::process::ExitStatus;
+
+use tokio::{
+	io::{AsyncRead, AsyncReadExt},
+	join, spawn,
+	sync::mpsc::Sender,
+	task::JoinHandle,
+};
+use tracing::{debug, trace};
+
+use crate::{
+	action::{CapturedOutput, OutputCallback},
+	error::RuntimeError,
+	event::{Event, OutputStream, Source, Tag},
+};
+
+use super::Process;
+
+/// Lines longer than this are split, so a command which never writes a newline doesn't make us
+/// buffer its output indefinitely.
+const MAX_LINE: usize = 16 * 1024;
+
+/// How to capture output.
+#[derive(Clone, Debug)]
+pub(crate) enum Capture {
+	/// Send each line as an event.
+	Lines {
+		/// Whether a carriage return alone also ends a line.
+		cr_lines: bool,
+	},
+
+	/// Collect the output, and give it to the callback once the process has ended.
+	Collect {
+		/// How many bytes of each stream to keep.
+		limit: usize,
+
+		/// What to give the output to.
+		callback: OutputCallback,
+	},
+}
+
+impl Capture {
+	/// Starts capturing the streams of the process which were piped.
+	///
+	/// When collecting, this returns the [`Collection`] to finish once the process has ended.
+	pub fn start(
+		self,
+		process: &mut Process,
+		id: u32,
+		events: Sender<Event>,
+	) -> Option<Collection> {
+		let (stdout, stderr) = process.take_output();
+		match self {
+			Self::Lines { cr_lines } => {
+				if let Some(stdout) = stdout {
+					spawn(read_lines(
+						stdout,
+						OutputStream::Stdout,
+						id,
+						cr_lines,
+						events.clone(),
+					));
+				}
+				if let Some(stderr) = stderr {
+					spawn(read_lines(
+						stderr,
+						OutputStream::Stderr,
+						id,
+						cr_lines,
+						events,
+					));
+				}
+				None
+			}
+			Self::Collect { limit, callback } => Some(Collection {
+				stdout: stdout.map(|stdout| spawn(collect(stdout, limit))),
+				stderr: stderr.map(|stderr| spawn(collect(stderr, limit))),
+				callback,
+			}),
+		}
+	}
+}
+
+/// Output being collected for an [`Outcome::Capture`](crate::action::Outcome::Capture).
+#[derive(Debug)]
+pub(crate) struct Collection {
+	stdout: Option<JoinHandle<(Vec<u8>, bool)>>,
+	stderr: Option<JoinHandle<(Vec<u8>, bool)>>,
+	callback: OutputCallback,
+}
+
+impl Collection {
+	/// Waits for the streams to close, then calls back with the output and the `status`.
+	pub async fn finish(self, status: Option<ExitStatus>) -> Result<(), RuntimeError> {
+		async fn stream(
+			reader: Option<JoinHandle<(Vec<u8>, bool)>>,
+		) -> Result<(Vec<u8>, bool), RuntimeError> {
+			match reader {
+				Some(reader) => reader
+					.await
+					.map_err(|err| RuntimeError::InternalSupervisor(err.to_string())),
+				None => Ok((Vec::new(), false)),
+			}
+		}
+
+		let (stdout, stderr) = join!(stream(self.stdout), stream(self.stderr));
+		let ((stdout, out_cut), (stderr, err_cut)) = (stdout?, stderr?);
+		trace!(stdout=%stdout.len(), stderr=%stderr.len(), "calling back with captured output");
+		(self.callback.0)(CapturedOutput {
+			status: status.map(Into::into),
+			stdout,
+			stderr,
+			truncated: out_cut || err_cut,
+		})
+		.await
+	}
+}
+
+/// Reads the stream to its end, keeping up to `limit` bytes, and whether there was more.
+async fn collect(mut reader: impl AsyncRead + Unpin, limit: usize) -> (Vec<u8>, bool) {
+	let mut kept = Vec::new();
+	let mut truncated = false;
+	let mut buf = [0; 4096];
+	loop {
+		let n = match reader.read(&mut buf).await {
+			Ok(0) => break,
+			Ok(n) => n,
+			Err(err) => {
+				debug!(%err, "error reading command output, stopping capture");
+				break;
+			}
+		};
+
+		let room = limit.saturating_sub(kept.len());
+		if n > room {
+			truncated = true;
+		}
+		kept.extend_from_slice(&buf[..n.min(room)]);
+	}
+
+	(kept, truncated)
+}
+
+async fn read_lines(
+	mut reader: impl AsyncRead + Unpin,
+	stream: OutputStream,
+	id: u32,
+	cr_lines: bool,
+	events: Sender<Event>,
+) {
+	let send = |line| {
+		let events = events.clone();
+		async move {
+			let event = Event {
+				tags: vec![
+					Tag::Source(Source::Internal),
+					Tag::Process(id),
+					Tag::Output { stream, line },
+				],
+				metadata: Default::default(),
+			};
+			events.send(event).await.is_ok()
+		}
+	};
+
+	let mut splitter = LineSplitter::new(cr_lines);
+	let mut buf = [0; 4096];
+	loop {
+		let n = match reader.read(&mut buf).await {
+			Ok(0) => break,
+			Ok(n) => n,
+			Err(err) => {
+				debug!(%err, ?stream, "error reading command output, stopping capture");
+				break;
+			}
+		};
+
+		for line in splitter.push(&buf[..n]) {
+			if !send(line).await {
+				trace!(?stream, "event channel closed, stopping capture");
+				return;
+			}
+		}
+	}
+
+	if let Some(line) = splitter.finish() {
+		send(line).await;
+	}
+	trace!(?stream, "command output closed");
+}
+
+/// Splits a byte stream into lines.
+///
+/// Lines end with `\n` (a preceding `\r` is removed), and with `cr_lines` also with a lone `\r`,
+/// as used for progress bars. In the latter case, empty segments between carriage returns are not
+/// emitted. Invalid UTF-8 is replaced.
+#[derive(Debug)]
+struct LineSplitter {
+	buf: Vec<u8>,
+	cr_lines: bool,
+	after_cr: bool,
+}
+
+impl LineSplitter {
+	fn new(cr_lines: bool) -> Self {
+		Self {
+			buf: Vec::new(),
+			cr_lines,
+			after_cr: false,
+		}
+	}
+
+	fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+		let mut lines = Vec::new();
+		for &byte in chunk {
+			let after_cr = std::mem::take(&mut self.after_cr);
+			match byte {
+				b'\n' if after_cr && self.buf.is_empty() => {}
+				b'\n' => {
+					if self.buf.last() == Some(&b'\r') {
+						self.buf.pop();
+					}
+					lines.push(self.take());
+				}
+				b'\r' if self.cr_lines => {
+					if !self.buf.is_empty() {
+						lines.push(self.take());
+					}
+					self.after_cr = true;
+				}
+				_ => {
+					self.buf.push(byte);
+					if self.buf.len() >= MAX_LINE {
+						lines.push(self.take());
+					}
+				}
+			}
+		}
+
+		lines
+	}
+
+	fn finish(&mut self) -> Option<String> {
+		if self.buf.is_empty() {
+			None
+		} else {
+			Some(self.take())
+		}
+	}
+
+	fn take(&mut self) -> String {
+		let line = String::from_utf8_lossy(&self.buf).into_owned();
+		self.buf.clear();
+		line
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	fn split(cr_lines: bool, chunks: &[&[u8]]) -> Vec<String> {
+		let mut splitter = LineSplitter::new(cr_lines);
+		let mut lines: Vec<String> = chunks.iter().flat_map(|c| splitter.push(c)).collect();
+		lines.extend(splitter.finish());
+		lines
+	}
+
+	#[test]
+	fn splits_across_chunks() {
+		assert_eq!(
+			split(false, &[b"one\ntw", b"o\n\nthr", b"ee"]),
+			vec!["one", "two", "", "three"]
+		);
+	}
+
+	#[test]
+	fn strips_crlf() {
+		assert_eq!(split(false, &[b"one\r\ntwo\r", b"\n"]), vec!["one", "two"]);
+	}
+
+	#[test]
+	fn carriage_returns_kept_by_default() {
+		assert_eq!(split(false, &[b"10%\r50%\r100%\n"]), vec!["10%\r50%\r100%"]);
+	}
+
+	#[test]
+	fn carriage_returns_as_lines() {
+		assert_eq!(
+			split(true, &[b"\r10%\r50%\r", b"\n100%\r\ndone\n"]),
+			vec!["10%", "50%", "100%", "done"]
+		);
+	}
+
+	#[test]
+	fn long_lines_are_split() {
+		let long = vec![b'a'; MAX_LINE + 1];
+		let lines = split(false, &[&long]);
+		assert_eq!(lines.len(), 2);
+		assert_eq!(lines[0].len(), MAX_LINE);
+		assert_eq!(lines[1], "a");
+	}
+
+	#[tokio::test]
+	async fn collect_stops_keeping_at_limit() {
+		assert_eq!(
+			collect(&b"hello world"[..], 5).await,
+			(b"hello".to_vec(), true)
+		);
+		assert_eq!(collect(&b"hello"[..], 5).await, (b"hello".to_vec(), false));
+	}
+
+	#[test]
+	fn invalid_utf8_is_replaced() {
+		assert_eq!(split(false, &[b"caf\xe9\n"]), vec!["caf\u{fffd}"]);
+	}
+}
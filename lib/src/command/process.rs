@@ -1,7 +1,7 @@
 use std::process::ExitStatus;
 
 use command_group::AsyncGroupChild;
-use tokio::process::Child;
+use tokio::process::{Child, ChildStderr, ChildStdin, ChildStdout};
 use tracing::{debug, trace};
 
 use crate::error::RuntimeError;
@@ -30,6 +30,25 @@ impl Default for Process {
 }
 
 impl Process {
+	/// Takes the piped stdout and stderr of the process, if any.
+	pub(crate) fn take_output(&mut self) -> (Option<ChildStdout>, Option<ChildStderr>) {
+		let child = match self {
+			Self::None | Self::Done(_) => return (None, None),
+			Self::Grouped(c) => c.inner(),
+			Self::Ungrouped(c) => c,
+		};
+		(child.stdout.take(), child.stderr.take())
+	}
+
+	/// Takes the piped stdin of the process, if any.
+	pub(crate) fn take_stdin(&mut self) -> Option<ChildStdin> {
+		match self {
+			Self::None | Self::Done(_) => None,
+			Self::Grouped(c) => c.inner().stdin.take(),
+			Self::Ungrouped(c) => c.stdin.take(),
+		}
+	}
+
 	/// Sends a Unix signal to the process.
 	///
 	/// Does nothing if the process is not running.
@@ -117,17 +136,31 @@ impl Process {
 	/// status.
 	///
 	/// Returns immediately with the cached exit status if the `Process` is `Done`, and with `None`
-	/// if the `Process` is `None`.
+	/// if the `Process` is `None`. A grouped process whose exit status was lost (because an earlier,
+	/// abandoned wait reaped it) also gives `None`, and becomes `None`.
 	pub async fn wait(&mut self) -> Result<Option<ExitStatus>, RuntimeError> {
 		match self {
 			Self::None => Ok(None),
 			Self::Done(status) => Ok(Some(*status)),
 			Self::Grouped(c) => {
 				trace!("waiting on process group");
-				let status = c.wait().await.map_err(|err| RuntimeError::IoError {
-					about: "waiting on process group",
-					err,
-				})?;
+				let status = match c.wait().await {
+					Ok(status) => status,
+					// the wait runs on a blocking thread, so a wait which was given up on (e.g. to
+					// send a signal) can still reap the process, and then its exit status is lost
+					#[cfg(unix)]
+					Err(err) if err.raw_os_error() == Some(libc::ECHILD) => {
+						debug!("process group was already reaped, exit status is unknown");
+						*self = Self::None;
+						return Ok(None);
+					}
+					Err(err) => {
+						return Err(RuntimeError::IoError {
+							about: "waiting on process group",
+							err,
+						})
+					}
+				};
 				trace!(?status, "converting to ::Done");
 				*self = Self::Done(status);
 				Ok(Some(status))
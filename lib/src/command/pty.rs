@@ -0,0 +1,166 @@
+//! Pseudo-terminal allocation for commands.
+//!
+//! The command's stdout and stderr are attached to the slave side of a new PTY, so it believes it's
+//! running interactively (and e.g. keeps its colours). The master side is read by a blocking task
+//! which copies to Watchexec's own stdout. When every holder of the slave side has closed it (in
+//! general, when the command and its descendants have exited), reads on the master fail, and the
+//! task ends and closes the master.
+//!
+//! There's no safe way to set a PTY's window size with our dependencies, so this is done with
+//! `stty` run on the slave side, once at creation and then whenever SIGWINCH is received.
+
+use std::{
+	fs::{File, OpenOptions},
+	io::{stdout, Read, Write},
+	os::unix::fs::OpenOptionsExt,
+	path::{Path, PathBuf},
+	process::Stdio,
+};
+
+use nix::{
+	fcntl::OFlag,
+	pty::{grantpt, posix_openpt, ptsname_r, unlockpt, PtyMaster},
+};
+use terminal_size::{terminal_size, Height, Width};
+use tokio::{
+	process::Command,
+	select,
+	signal::unix::{signal, SignalKind},
+	spawn,
+	task::spawn_blocking,
+};
+use tracing::{debug, trace, warn};
+
+use crate::error::RuntimeError;
+
+/// A PTY whose slave side has been given to a command, waiting for the command to be spawned.
+#[derive(Debug)]
+pub(crate) struct Pty {
+	master: PtyMaster,
+	slave: PathBuf,
+}
+
+impl Pty {
+	/// Allocates a PTY and sets it as the stdout and stderr of the command.
+	pub async fn attach(command: &mut Command) -> Result<Self, RuntimeError> {
+		let master = open_master().map_err(|err| RuntimeError::IoError {
+			about: "allocating pty",
+			err,
+		})?;
+		let slave = PathBuf::from(ptsname_r(&master).map_err(|err| RuntimeError::IoError {
+			about: "obtaining pty slave name",
+			err: err.into(),
+		})?);
+		trace!(?slave, "pty allocated");
+
+		let out = open_slave(&slave)?;
+		let err = out.try_clone().map_err(|err| RuntimeError::IoError {
+			about: "duplicating pty slave",
+			err,
+		})?;
+		command.stdout(Stdio::from(out)).stderr(Stdio::from(err));
+
+		resize(&slave).await;
+		Ok(Self { master, slave })
+	}
+
+	/// Starts copying the command's output, and propagating window size changes.
+	///
+	/// This must be called once the command has been spawned, and the [`Command`] (which holds
+	/// the slave side open) dropped, or the copy will never finish.
+	pub fn forward(self) {
+		let Self { mut master, slave } = self;
+		let copy = spawn_blocking(move || {
+			let mut buf = [0; 4096];
+			loop {
+				match master.read(&mut buf) {
+					Ok(0) => break,
+					Ok(n) => {
+						let mut out = stdout();
+						if out.write_all(&buf[..n]).and_then(|_| out.flush()).is_err() {
+							break;
+						}
+					}
+					// EIO is what we get on Linux once the slave side is closed
+					Err(err) => {
+						trace!(%err, "pty read ended");
+						break;
+					}
+				}
+			}
+			debug!("pty closed");
+		});
+
+		spawn(async move {
+			let mut winch = match signal(SignalKind::window_change()) {
+				Ok(winch) => winch,
+				Err(err) => {
+					warn!(%err, "cannot listen for window size changes, pty will not be resized");
+					copy.await.ok();
+					return;
+				}
+			};
+
+			let mut copy = copy;
+			loop {
+				select! {
+					_ = &mut copy => break,
+					Some(_) = winch.recv() => {
+						trace!("window size changed, resizing pty");
+						resize(&slave).await;
+					}
+				}
+			}
+		});
+	}
+}
+
+fn open_master() -> std::io::Result<PtyMaster> {
+	let master = posix_openpt(OFlag::O_RDWR | OFlag::O_NOCTTY)?;
+	grantpt(&master)?;
+	unlockpt(&master)?;
+	Ok(master)
+}
+
+fn open_slave(path: &Path) -> Result<File, RuntimeError> {
+	OpenOptions::new()
+		.read(true)
+		.write(true)
+		.custom_flags(libc::O_NOCTTY)
+		.open(path)
+		.map_err(|err| RuntimeError::IoError {
+			about: "opening pty slave",
+			err,
+		})
+}
+
+/// Copies the size of our terminal (if we're in one) to the PTY.
+async fn resize(slave: &Path) {
+	let (Width(cols), Height(rows)) = match terminal_size() {
+		Some(size) => size,
+		None => return,
+	};
+
+	let tty = match open_slave(slave) {
+		Ok(tty) => tty,
+		Err(err) => {
+			debug!(%err, "cannot open pty slave, pty not resized");
+			return;
+		}
+	};
+
+	let status = Command::new("stty")
+		.arg("rows")
+		.arg(rows.to_string())
+		.arg("cols")
+		.arg(cols.to_string())
+		.stdin(tty)
+		.status()
+		.await;
+
+	match status {
+		Ok(status) if status.success() => trace!(%rows, %cols, "pty resized"),
+		Ok(status) => debug!(%status, "stty failed, pty not resized"),
+		Err(err) => debug!(%err, "cannot run stty, pty not resized"),
+	}
+}
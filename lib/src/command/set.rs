@@ -0,0 +1,134 @@
+//! Tracking of the commands started together by an action.
+//!
+//! The [`command`](crate::action::WorkingData#structfield.command) and its
+//! [`parallel_commands`](crate::action::WorkingData#structfield.parallel_commands) each have their
+//! own [`Supervisor`], and are handled as one by a [`SupervisorSet`]. When there's more than one,
+//! their events go through [`merge_completions()`], which waits for all of them to end and then
+//! issues a single completion event for the set.
+
+use std::collections::HashMap;
+
+use futures::future::join_all;
+use tokio::sync::mpsc::{Receiver, Sender};
+use tracing::{debug, error, trace};
+
+use crate::{
+	error::RuntimeError,
+	event::{Event, Source, Tag},
+	signal::process::SubSignal,
+};
+
+use super::Supervisor;
+
+/// The supervisors of the commands started by an action, handled as one.
+///
+/// The first is the main command: its ID is the one of the set.
+#[derive(Debug)]
+pub(crate) struct SupervisorSet {
+	main: Supervisor,
+	others: Vec<Supervisor>,
+}
+
+impl SupervisorSet {
+	/// A set of the main command and the others.
+	pub fn new(main: Supervisor, others: Vec<Supervisor>) -> Self {
+		Self { main, others }
+	}
+
+	fn all(&self) -> impl Iterator<Item = &Supervisor> {
+		std::iter::once(&self.main).chain(&self.others)
+	}
+
+	/// The ID of the main command.
+	pub fn id(&self) -> u32 {
+		self.main.id()
+	}
+
+	/// Issues a signal to every command.
+	pub async fn signal(&self, signal: SubSignal) {
+		join_all(self.all().map(|sup| sup.signal(signal))).await;
+	}
+
+	/// Stops every command.
+	pub async fn kill(&self) {
+		join_all(self.all().map(Supervisor::kill)).await;
+	}
+
+	/// Writes to the stdin of every command which keeps it.
+	///
+	/// Returns `false` if none does.
+	pub fn write_stdin(&self, bytes: Vec<u8>) -> bool {
+		let mut kept = false;
+		for sup in self.all() {
+			kept |= sup.write_stdin(bytes.clone());
+		}
+		kept
+	}
+
+	/// Returns true if any command is still running.
+	pub fn is_running(&self) -> bool {
+		self.all().any(Supervisor::is_running)
+	}
+
+	/// Returns only when every command's supervisor completes.
+	///
+	/// This is cancel-safe, like [`Supervisor::wait()`].
+	pub async fn wait(&mut self) -> Result<(), RuntimeError> {
+		self.main.wait().await?;
+		for sup in &mut self.others {
+			sup.wait().await?;
+		}
+		Ok(())
+	}
+}
+
+/// Forwards the events of a set of `expected` commands, merging their completions into one.
+///
+/// Other events (like output lines) are forwarded as they come. The merged completion event is
+/// sent once `expected` completions have been received, or if the commands' events stop before
+/// then, with what has been received.
+pub(crate) async fn merge_completions(
+	mut from: Receiver<Event>,
+	to: Sender<Event>,
+	mut expected: usize,
+) {
+	let mut merged: Option<Event> = None;
+	while expected > 0 {
+		match from.recv().await {
+			None => break,
+			Some(event) if event.is_completion() => {
+				trace!(?event, %expected, "got completion of a command of the set");
+				expected -= 1;
+				let into = merged.get_or_insert_with(|| Event {
+					tags: vec![Tag::Source(Source::Internal)],
+					metadata: HashMap::new(),
+				});
+				into.tags.extend(
+					event
+						.tags
+						.into_iter()
+						.filter(|tag| !matches!(tag, Tag::Source(Source::Internal))),
+				);
+				for (key, values) in event.metadata {
+					into.metadata.entry(key).or_default().extend(values);
+				}
+			}
+			Some(event) => forward(&to, event).await,
+		}
+	}
+
+	if let Some(event) = merged {
+		debug!(?event, "all commands of the set have completed");
+		forward(&to, event).await;
+	}
+
+	while let Some(event) = from.recv().await {
+		forward(&to, event).await;
+	}
+}
+
+async fn forward(to: &Sender<Event>, event: Event) {
+	if let Err(err) = to.send(event).await {
+		error!(%err, "while forwarding event of a command set");
+	}
+}
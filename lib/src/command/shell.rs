@@ -1,3 +1,8 @@
+use std::{
+	env,
+	path::{Path, PathBuf},
+};
+
 use tokio::process::Command;
 use tracing::trace;
 
@@ -7,11 +12,15 @@ use tracing::trace;
 /// `Cmd` is only available in Windows, while `Powershell` is also available on unices (provided the
 /// end-user has it installed, of course).
 ///
-/// See [`Config.cmd`] for the semantics of `None` vs the other options.
+/// See [`command`](crate::action::WorkingData#structfield.command) on the action working data for
+/// the semantics of `None` vs the other options.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Shell {
 	/// Use no shell, and execute the command directly.
 	///
+	/// The command is taken as a pre-split argv vector: the first element is the program, and the
+	/// rest are its arguments, passed as-is without any quoting, splitting, or expansion.
+	///
 	/// This is the default, however as consumer of this library you are encouraged to set your own
 	/// default as makes sense in your application / for your platform.
 	None,
@@ -19,7 +28,8 @@ pub enum Shell {
 	/// Use the given string as a unix shell invocation.
 	///
 	/// This means two things:
-	/// - the program is invoked with `-c` followed by the command, and
+	/// - the program is invoked with `-c` followed by the command, which is all the command parts
+	///   joined with a single space (so `["echo $HOME && ls"]` works as written), and
 	/// - the string will be split on space, and the resulting vec used as execvp(3) arguments:
 	///   first is the shell program, rest are additional arguments (which come before the `-c`
 	///   mentioned above). This is a very simplistic approach deliberately: it will not support
@@ -104,6 +114,50 @@ impl Shell {
 			}
 		}
 	}
+
+	/// Find the (canonicalised) path to the program a command would run.
+	///
+	/// This is only possible with [`Shell::None`]: with a shell, the command can run anything. The
+	/// program is looked up in `PATH` (trying the `PATHEXT` extensions on Windows) unless it
+	/// contains a path separator. Returns `None` if the program can't be found.
+	pub(crate) fn program_path(&self, cmd: &[String]) -> Option<PathBuf> {
+		let program = match (self, cmd.first()) {
+			(Shell::None, Some(program)) => Path::new(program),
+			_ => return None,
+		};
+
+		let found = if program.components().count() > 1 {
+			Some(program.to_owned()).filter(|p| p.is_file())
+		} else {
+			env::var_os("PATH")
+				.and_then(|paths| env::split_paths(&paths).find_map(|dir| find_in(&dir, program)))
+		};
+
+		found.and_then(|path| dunce::canonicalize(path).ok())
+	}
+}
+
+#[cfg(not(windows))]
+fn find_in(dir: &Path, program: &Path) -> Option<PathBuf> {
+	Some(dir.join(program)).filter(|p| p.is_file())
+}
+
+#[cfg(windows)]
+fn find_in(dir: &Path, program: &Path) -> Option<PathBuf> {
+	let path = dir.join(program);
+	if path.is_file() {
+		return Some(path);
+	}
+
+	if path.extension().is_some() {
+		return None;
+	}
+
+	let exts = env::var("PATHEXT").unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".into());
+	exts.split(';')
+		.filter(|ext| !ext.is_empty())
+		.map(|ext| path.with_extension(ext.trim_start_matches('.')))
+		.find(|p| p.is_file())
 }
 
 #[cfg(test)]
@@ -111,6 +165,21 @@ mod test {
 	use super::Shell;
 	use command_group::AsyncCommandGroup;
 
+	#[test]
+	#[cfg(unix)]
+	fn program_path_only_without_shell() {
+		let cmd = vec!["sh".to_string(), "-c".to_string(), "true".to_string()];
+		assert!(Shell::None
+			.program_path(&cmd)
+			.map(|p| p.is_absolute())
+			.unwrap_or(false));
+		assert_eq!(Shell::Unix("sh".into()).program_path(&cmd), None);
+		assert_eq!(
+			Shell::None.program_path(&["does-not-exist-for-watchexec-tests".into()]),
+			None
+		);
+	}
+
 	#[tokio::test]
 	#[cfg(unix)]
 	async fn unix_shell_default() -> Result<(), std::io::Error> {
@@ -133,6 +202,33 @@ mod test {
 		Ok(())
 	}
 
+	#[tokio::test]
+	#[cfg(unix)]
+	async fn unix_shell_interprets_command() -> Result<(), std::io::Error> {
+		assert!(Shell::Unix("sh".into())
+			.to_command(&["test -n \"$HOME\" && true".into()])
+			.group_status()
+			.await?
+			.success());
+		Ok(())
+	}
+
+	#[tokio::test]
+	#[cfg(unix)]
+	async fn unix_shell_none_does_not_interpret() -> Result<(), std::io::Error> {
+		assert!(Shell::None
+			.to_command(&["test".into(), "$HOME".into(), "=".into(), "$HOME".into()])
+			.group_status()
+			.await?
+			.success());
+		assert!(!Shell::None
+			.to_command(&["test".into(), "$HOME".into(), "=".into(), "/".into()])
+			.group_status()
+			.await?
+			.success());
+		Ok(())
+	}
+
 	#[tokio::test]
 	#[cfg(unix)]
 	async fn unix_shell_alternate() -> Result<(), std::io::Error> {
@@ -1,17 +1,22 @@
-use std::sync::{
-	atomic::{AtomicBool, Ordering},
-	Arc,
+use std::{
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Arc,
+	},
+	time::{Duration, Instant},
 };
 
 use command_group::AsyncCommandGroup;
 use tokio::{
-	process::Command,
+	io::AsyncWriteExt,
+	process::{ChildStdin, Command},
 	select, spawn,
 	sync::{
 		mpsc::{self, Sender},
-		oneshot,
+		oneshot, watch,
 	},
 	task::JoinHandle,
+	time::{sleep_until, Instant as TokioInstant},
 };
 use tracing::{debug, error, trace};
 
@@ -21,7 +26,7 @@ use crate::{
 	signal::process::SubSignal,
 };
 
-use super::Process;
+use super::{Capture, EventsFile, Process};
 
 #[derive(Clone, Copy, Debug)]
 enum Intervention {
@@ -29,11 +34,44 @@ enum Intervention {
 	Signal(SubSignal),
 }
 
+impl Intervention {
+	/// The intervention to make to stop the process with this signal.
+	///
+	/// See [`Supervisor::signal()`] for the Windows specifics.
+	fn stop(signal: SubSignal) -> Self {
+		match signal {
+			SubSignal::ForceStop => Self::Kill,
+			SubSignal::Terminate if cfg!(windows) => Self::Kill,
+			sig => Self::Signal(sig),
+		}
+	}
+}
+
+/// How long a supervised process may run for, and how to stop it once that's elapsed.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Timeout {
+	/// How long the process may run for.
+	pub after: Duration,
+
+	/// The signal to send to ask the process to stop.
+	pub signal: SubSignal,
+
+	/// How long to wait after the signal before forcefully stopping the process.
+	pub grace: Duration,
+}
+
 /// A task which supervises a process.
 ///
 /// This spawns a process from a [`Command`] and waits for it to complete while handling
 /// interventions to it: orders to terminate it, or to send a signal to it. It also immediately
-/// issues a [`Tag::ProcessCompletion`] event when the process completes.
+/// issues a [`Tag::ProcessCompletion`] event when the process completes, which includes how long
+/// the process ran for, alongside a [`Tag::Process`] with the ID of the process (or group).
+///
+/// If an intervention was made before the process ended, the last one is recorded in the
+/// `stopped-by` metadata of the completion event, as `kill` or `signal:` followed by the signal
+/// (e.g. `signal:Terminate`). This indicates what most likely ended the process. If the process
+/// was stopped as it ran past its timeout, the completion event also has a
+/// [`Tag::ProcessTimeout`].
 #[derive(Debug)]
 pub struct Supervisor {
 	id: u32,
@@ -47,6 +85,7 @@ pub struct Supervisor {
 	// 2. This way it's typed-enforced that I send only once
 	waiter: Option<oneshot::Receiver<()>>,
 	ongoing: Arc<AtomicBool>,
+	stdin: Option<mpsc::UnboundedSender<Vec<u8>>>,
 }
 
 impl Supervisor {
@@ -56,8 +95,35 @@ impl Supervisor {
 		events: Sender<Event>,
 		command: &mut Command,
 		grouped: bool,
+	) -> Result<Self, RuntimeError> {
+		Self::spawn_reporting(errors, events, command, grouped, None, None, None, None)
+	}
+
+	/// Same as [`spawn()`](Supervisor::spawn()), also publishing the process ID to `pid`,
+	/// capturing the output of the process as `capture` says, and holding on to the `events_file`
+	/// until the process ends.
+	///
+	/// Collected output is handed over once the process has ended, before the completion event.
+	///
+	/// If the command's stdin is piped, it's kept for [`write_stdin()`](Supervisor::write_stdin()).
+	/// If a `timeout` is given, the process is stopped once it has run for that long, after issuing
+	/// a [`Tag::ProcessTimeout`] event.
+	///
+	/// The ID is set as soon as the process is spawned, and reset to `None` when it ends, unless
+	/// another ID has been published since.
+	#[allow(clippy::too_many_arguments)]
+	pub(crate) fn spawn_reporting(
+		errors: Sender<RuntimeError>,
+		events: Sender<Event>,
+		command: &mut Command,
+		grouped: bool,
+		pid: Option<Arc<watch::Sender<Option<u32>>>>,
+		capture: Option<Capture>,
+		events_file: Option<EventsFile>,
+		timeout: Option<Timeout>,
 	) -> Result<Self, RuntimeError> {
 		debug!(%grouped, ?command, "spawning command");
+		let started = Instant::now();
 		let (process, id) = if grouped {
 			let proc = command.group_spawn().map_err(|err| RuntimeError::IoError {
 				about: "spawning process group",
@@ -76,14 +142,37 @@ impl Supervisor {
 			(Process::Ungrouped(proc), id)
 		};
 
+		if let Some(pid) = &pid {
+			pid.send_replace(Some(id));
+		}
+
+		let mut process = process;
+		let collection =
+			capture.and_then(|capture| capture.start(&mut process, id, events.clone()));
+		let stdin = process
+			.take_stdin()
+			.map(|stdin| forward_stdin(stdin, errors.clone()));
+
 		let ongoing = Arc::new(AtomicBool::new(true));
 		let (notify, waiter) = oneshot::channel();
 		let (int_s, int_r) = mpsc::channel(8);
 
 		let going = ongoing.clone();
 		let handle = spawn(async move {
+			// removed when this task ends, however it does
+			let _events_file = events_file;
 			let mut process = process;
 			let mut int = int_r;
+			let mut last_intervention = None;
+			let mut timed_out = false;
+
+			// when to next escalate, and how, if the process runs past its timeout
+			let mut overrun = timeout.map(|t| {
+				(
+					TokioInstant::from_std(started + t.after),
+					Intervention::stop(t.signal),
+				)
+			});
 
 			debug!(?process, "starting task to watch on process");
 
@@ -97,6 +186,7 @@ impl Supervisor {
 								errors.send(err).await.ok();
 								trace!("marking process as done");
 								going.store(false, Ordering::SeqCst);
+								clear_pid(pid.as_deref(), id);
 								trace!("closing supervisor task early");
 								notify.send(()).ok();
 								return;
@@ -104,37 +194,33 @@ impl Supervisor {
 						}
 					},
 					Some(int) = int.recv() => {
-						match int {
-							Intervention::Kill => {
-								if let Err(err) = process.kill().await {
-									error!(%err, "while killing process");
-									errors.send(err).await.ok();
-									trace!("continuing to watch command");
-								}
-							}
-							#[cfg(unix)]
-							Intervention::Signal(sig) => {
-								if let Some(sig) = sig.to_nix() {
-									if let Err(err) = process.signal(sig) {
-										error!(%err, "while sending signal to process");
-										errors.send(err).await.ok();
-										trace!("continuing to watch command");
-									}
-								} else {
-									let err = RuntimeError::UnsupportedSignal(sig);
-									error!(%err, "while sending signal to process");
-									errors.send(err).await.ok();
-									trace!("continuing to watch command");
+						last_intervention = Some(int);
+						intervene(&mut process, int, &errors).await;
+					}
+					_ = sleep_until(overrun.map_or_else(TokioInstant::now, |(at, _)| at)), if overrun.is_some() => {
+						if let (Some((_, int)), Some(timeout)) = (overrun.take(), timeout) {
+							if !timed_out {
+								timed_out = true;
+								debug!(after=?timeout.after, "process ran past its timeout, stopping it");
+								let event = Event {
+									tags: vec![
+										Tag::Source(Source::Internal),
+										Tag::ProcessTimeout(timeout.after),
+										Tag::Process(id),
+									],
+									metadata: Default::default(),
+								};
+								if let Err(err) = events.send(event).await {
+									error!(%err, "while sending process timeout event");
 								}
 							}
-							#[cfg(windows)]
-							Intervention::Signal(sig) => {
-								// https://github.com/watchexec/watchexec/issues/219
-								let err = RuntimeError::UnsupportedSignal(sig);
-								error!(%err, "while sending signal to process");
-								errors.send(err).await.ok();
-								trace!("continuing to watch command");
+
+							// if asking nicely doesn't do it within the grace period, force it
+							if let Intervention::Signal(_) = int {
+								overrun = Some((TokioInstant::now() + timeout.grace, Intervention::Kill));
 							}
+							last_intervention = Some(int);
+							intervene(&mut process, int, &errors).await;
 						}
 					}
 					else => break,
@@ -148,13 +234,34 @@ impl Supervisor {
 					errors.send(err).await.ok();
 				}
 				Ok(status) => {
-					let event = Event {
+					if let Some(collection) = collection {
+						if let Err(err) = collection.finish(status).await {
+							error!(%err, "while handing over captured output");
+							errors.send(err).await.ok();
+						}
+					}
+
+					let mut event = Event {
 						tags: vec![
 							Tag::Source(Source::Internal),
-							Tag::ProcessCompletion(status.map(|s| s.into())),
+							Tag::ProcessCompletion(status.map(|s| s.into()), started.elapsed()),
+							Tag::Process(id),
 						],
 						metadata: Default::default(),
 					};
+					if timed_out {
+						if let Some(timeout) = timeout {
+							event.tags.push(Tag::ProcessTimeout(timeout.after));
+						}
+					}
+
+					if let Some(int) = last_intervention {
+						let by = match int {
+							Intervention::Kill => "kill".to_owned(),
+							Intervention::Signal(sig) => format!("signal:{:?}", sig),
+						};
+						event.metadata.insert("stopped-by".into(), vec![by]);
+					}
 
 					debug!(?event, "creating synthetic process completion event");
 					if let Err(err) = events.send(event).await {
@@ -172,6 +279,7 @@ impl Supervisor {
 
 			trace!("marking process as done");
 			going.store(false, Ordering::SeqCst);
+			clear_pid(pid.as_deref(), id);
 			trace!("closing supervisor task");
 			notify.send(()).ok();
 		});
@@ -181,6 +289,7 @@ impl Supervisor {
 			waiter: Some(waiter),
 			ongoing,
 			intervene: int_s,
+			stdin,
 			handle, // TODO: is there anything useful to do with this? do we need to keep it?
 		})
 	}
@@ -196,16 +305,23 @@ impl Supervisor {
 
 	/// Issues a signal to the process.
 	///
-	/// On Windows, this currently only supports [`SubSignal::ForceStop`].
+	/// With grouped processes on Unix, the signal is delivered to the whole process group.
+	///
+	/// On Windows, this currently only supports [`SubSignal::ForceStop`] and
+	/// [`SubSignal::Terminate`], which both terminate the process. Other signals are reported to the
+	/// error channel as [`RuntimeError::UnsupportedSignal`].
 	///
 	/// While this is async, it returns once the signal intervention has been sent internally, not
 	/// when the signal has been delivered.
 	pub async fn signal(&self, signal: SubSignal) {
 		if cfg!(windows) {
-			if let SubSignal::ForceStop = signal {
+			if let SubSignal::ForceStop | SubSignal::Terminate = signal {
 				self.intervene.send(Intervention::Kill).await.ok();
+			} else {
+				// https://github.com/watchexec/watchexec/issues/219
+				trace!(?signal, "sending unsupported signal intervention");
+				self.intervene.send(Intervention::Signal(signal)).await.ok();
 			}
-		// else: https://github.com/watchexec/watchexec/issues/219
 		} else {
 			trace!(?signal, "sending signal intervention");
 			self.intervene.send(Intervention::Signal(signal)).await.ok();
@@ -213,6 +329,18 @@ impl Supervisor {
 		// only errors on channel closed, and that only happens if the process is dead
 	}
 
+	/// Writes to the process's stdin, if it's kept.
+	///
+	/// This returns `false` if the stdin of the process isn't piped, or has been closed. Otherwise,
+	/// the bytes are written in the background, in order; errors doing so are sent to the error
+	/// channel, and close the stdin.
+	pub fn write_stdin(&self, bytes: Vec<u8>) -> bool {
+		match &self.stdin {
+			Some(stdin) => stdin.send(bytes).is_ok(),
+			None => false,
+		}
+	}
+
 	/// Stops the process.
 	///
 	/// While this is async, it returns once the signal intervention has been sent internally, not
@@ -237,17 +365,20 @@ impl Supervisor {
 	///
 	/// This is almost always equivalent to waiting for the _process_ to complete, but may not be
 	/// 100% in sync.
+	///
+	/// This is cancel-safe: it can be used with a timeout and called again later.
 	pub async fn wait(&mut self) -> Result<(), RuntimeError> {
 		if !self.ongoing.load(Ordering::SeqCst) {
 			trace!("supervisor already completed");
 			return Ok(());
 		}
 
-		if let Some(waiter) = self.waiter.take() {
+		// the waiter is only cleared once it has resolved, so that this is cancel-safe
+		if let Some(waiter) = self.waiter.as_mut() {
 			debug!("waiting on supervisor completion");
-			waiter
-				.await
-				.map_err(|err| RuntimeError::InternalSupervisor(err.to_string()))?;
+			let res = waiter.await;
+			self.waiter = None;
+			res.map_err(|err| RuntimeError::InternalSupervisor(err.to_string()))?;
 			debug!("supervisor completed");
 
 			if self.ongoing.swap(false, Ordering::SeqCst) {
@@ -269,3 +400,78 @@ impl Supervisor {
 		Ok(())
 	}
 }
+
+/// Makes an intervention to the process, reporting errors doing so.
+async fn intervene(process: &mut Process, int: Intervention, errors: &Sender<RuntimeError>) {
+	match int {
+		Intervention::Kill => {
+			if let Err(err) = process.kill().await {
+				error!(%err, "while killing process");
+				errors.send(err).await.ok();
+				trace!("continuing to watch command");
+			}
+		}
+		#[cfg(unix)]
+		Intervention::Signal(sig) => {
+			if let Some(sig) = sig.to_nix() {
+				if let Err(err) = process.signal(sig) {
+					error!(%err, "while sending signal to process");
+					errors.send(err).await.ok();
+					trace!("continuing to watch command");
+				}
+			} else {
+				let err = RuntimeError::UnsupportedSignal(sig);
+				error!(%err, "while sending signal to process");
+				errors.send(err).await.ok();
+				trace!("continuing to watch command");
+			}
+		}
+		#[cfg(windows)]
+		Intervention::Signal(sig) => {
+			// https://github.com/watchexec/watchexec/issues/219
+			let err = RuntimeError::UnsupportedSignal(sig);
+			error!(%err, "while sending signal to process");
+			errors.send(err).await.ok();
+			trace!("continuing to watch command");
+		}
+	}
+}
+
+/// Writes the bytes received on the returned channel to the stdin, until that fails.
+fn forward_stdin(
+	mut stdin: ChildStdin,
+	errors: Sender<RuntimeError>,
+) -> mpsc::UnboundedSender<Vec<u8>> {
+	let (bytes_s, mut bytes_r) = mpsc::unbounded_channel::<Vec<u8>>();
+	spawn(async move {
+		while let Some(bytes) = bytes_r.recv().await {
+			trace!(len=%bytes.len(), "writing to command stdin");
+			if let Err(err) = async {
+				stdin.write_all(&bytes).await?;
+				stdin.flush().await
+			}
+			.await
+			{
+				error!(%err, "while writing to command stdin");
+				errors
+					.send(RuntimeError::IoError {
+						about: "writing to command stdin",
+						err,
+					})
+					.await
+					.ok();
+				break;
+			}
+		}
+		trace!("command stdin closed");
+	});
+	bytes_s
+}
+
+fn clear_pid(pid: Option<&watch::Sender<Option<u32>>>, id: u32) {
+	if let Some(pid) = pid {
+		if *pid.borrow() == Some(id) {
+			pid.send_replace(None);
+		}
+	}
+}
@@ -1,16 +1,27 @@
 //! Configuration and builders for [`crate::Watchexec`].
 
-use std::{fmt, path::Path, sync::Arc, time::Duration};
+use std::{
+	collections::{HashMap, HashSet},
+	fmt,
+	path::{Path, PathBuf},
+	sync::Arc,
+	time::Duration,
+};
 
 use atomic_take::AtomicTake;
+use futures::future::BoxFuture;
+use tokio::sync::mpsc;
+use tracing::level_filters::LevelFilter;
 
 use crate::{
-	action::{Action, PostSpawn, PreSpawn},
-	command::Shell,
+	action::{Action, OnBusyUpdate, Outcome, PostExit, PostSpawn, PreSpawn, UnmappedSignals},
+	command::{ResourceLimits, Shell},
 	error::RuntimeError,
-	filter::Filterer,
-	fs::Watcher,
-	handler::Handler,
+	event::Event,
+	filter::{expr::Filter, Filterer},
+	fs::{Backend, FsBackend, KindCategory, OutsideRoot, WatchedPath, Watcher, WhilePaused},
+	handler::{CatchUnwindFnHandler, Handler},
+	signal::{process::SubSignal, source::MainSignal},
 };
 
 /// Runtime configuration for [`Watchexec`][crate::Watchexec].
@@ -25,7 +36,7 @@ use crate::{
 /// You should see the detailed documentation on [fs::WorkingData][crate::fs::WorkingData] and
 /// [action::WorkingData][crate::action::WorkingData] for important information and particulars
 /// about each field, especially the handlers.
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug)]
 #[non_exhaustive]
 pub struct RuntimeConfig {
 	/// Working data for the filesystem event source.
@@ -38,6 +49,46 @@ pub struct RuntimeConfig {
 	/// This is the task responsible for scheduling the actions in response to events, applying the
 	/// filtering, etc.
 	pub action: crate::action::WorkingData,
+
+	/// Whether to run the filesystem event source.
+	///
+	/// This is only read when constructing the instance: changing it in a reconfigure does not
+	/// start or stop the filesystem watcher. Defaults to `true`.
+	pub enable_fs: bool,
+
+	/// Whether to run the signal event source.
+	///
+	/// When disabled, watchexec does not listen for signals at all, which leaves e.g. SIGINT to its
+	/// default behaviour or to the embedding application. This is only read when constructing the
+	/// instance: changing it in a reconfigure does not start or stop listening for signals.
+	/// Defaults to `true`.
+	pub enable_signal: bool,
+
+	/// Whether to run the keyboard event source.
+	///
+	/// See the [keyboard module][crate::keyboard] for details. This is only read when constructing
+	/// the instance: changing it in a reconfigure does not start or stop reading the keyboard.
+	/// Defaults to `false`.
+	pub enable_keyboard: bool,
+
+	/// How often the [timer][crate::timer] emits events, if at all.
+	///
+	/// Unlike the other event sources, this can be changed in a reconfigure, which resets the
+	/// timer. Defaults to `None`.
+	pub timer: Option<Duration>,
+}
+
+impl Default for RuntimeConfig {
+	fn default() -> Self {
+		Self {
+			fs: Default::default(),
+			action: Default::default(),
+			enable_fs: true,
+			enable_signal: true,
+			enable_keyboard: false,
+			timer: None,
+		}
+	}
 }
 
 impl RuntimeConfig {
@@ -51,18 +102,348 @@ impl RuntimeConfig {
 		self
 	}
 
+	/// Add a path to the pathset to be watched.
+	///
+	/// Unlike [`pathset()`](RuntimeConfig::pathset()), which replaces the pathset with paths to
+	/// watch recursively, this takes a [`WatchedPath`], so can be used to add paths to watch
+	/// non-recursively:
+	///
+	/// ```
+	/// # use watchexec::{config::RuntimeConfig, fs::WatchedPath};
+	/// let mut config = RuntimeConfig::default();
+	/// config
+	///     .pathset(["src"])
+	///     .add_path(WatchedPath::non_recursive("Cargo.toml"));
+	/// ```
+	pub fn add_path(&mut self, path: impl Into<WatchedPath>) -> &mut Self {
+		self.fs.pathset.push(path.into());
+		self
+	}
+
+	/// Toggle whether to run the filesystem event source.
+	///
+	/// This only takes effect when constructing the instance: it's ignored, with a warning, by
+	/// [`Watchexec::reconfigure()`](crate::Watchexec::reconfigure()). See the
+	/// [documentation on the field](RuntimeConfig#structfield.enable_fs) for more details.
+	pub fn enable_fs(&mut self, enable: bool) -> &mut Self {
+		self.enable_fs = enable;
+		self
+	}
+
+	/// Toggle whether to run the signal event source.
+	///
+	/// This only takes effect when constructing the instance: it's ignored, with a warning, by
+	/// [`Watchexec::reconfigure()`](crate::Watchexec::reconfigure()). See the
+	/// [documentation on the field](RuntimeConfig#structfield.enable_signal) for more details.
+	pub fn enable_signal(&mut self, enable: bool) -> &mut Self {
+		self.enable_signal = enable;
+		self
+	}
+
+	/// Toggle whether to run the keyboard event source.
+	///
+	/// This only takes effect when constructing the instance: it's ignored, with a warning, by
+	/// [`Watchexec::reconfigure()`](crate::Watchexec::reconfigure()). See the
+	/// [documentation on the field](RuntimeConfig#structfield.enable_keyboard) for more details.
+	pub fn enable_keyboard(&mut self, enable: bool) -> &mut Self {
+		self.enable_keyboard = enable;
+		self
+	}
+
+	/// Set (or unset) the interval of the timer event source.
+	///
+	/// See the [documentation on the field](RuntimeConfig#structfield.timer) for more details.
+	pub fn timer(&mut self, interval: Option<Duration>) -> &mut Self {
+		self.timer = interval;
+		self
+	}
+
 	/// Set the file watcher type to use.
 	pub fn file_watcher(&mut self, watcher: Watcher) -> &mut Self {
 		self.fs.watcher = watcher;
 		self
 	}
 
+	/// Set a custom backend for the file watcher.
+	///
+	/// This sets the watcher type to [`Watcher::Custom`] with the backend. See the
+	/// [fs backend documentation](crate::fs::FsBackend) for more details.
+	pub fn fs_backend(&mut self, backend: impl FsBackend + 'static) -> &mut Self {
+		self.fs.watcher = Watcher::Custom(Backend(Arc::new(backend)));
+		self
+	}
+
+	/// Set the filesystem event debounce window.
+	///
+	/// See the [documentation on the field](crate::fs::WorkingData#structfield.debounce) for more
+	/// details.
+	pub fn fs_debounce(&mut self, debounce: impl Into<Duration>) -> &mut Self {
+		self.fs.debounce = debounce.into();
+		self
+	}
+
+	/// Set the debounce window for a category of filesystem events.
+	///
+	/// See the [documentation on the field](crate::fs::WorkingData#structfield.debounce_kinds) for
+	/// more details.
+	pub fn fs_debounce_kind(
+		&mut self,
+		category: KindCategory,
+		debounce: impl Into<Duration>,
+	) -> &mut Self {
+		self.fs.debounce_kinds.insert(category, debounce.into());
+		self
+	}
+
+	/// Set the categories of filesystem events which by-pass the debounce.
+	///
+	/// See the [documentation on the field](crate::fs::WorkingData#structfield.immediate_kinds) for
+	/// more details.
+	pub fn fs_immediate_kinds(
+		&mut self,
+		categories: impl IntoIterator<Item = KindCategory>,
+	) -> &mut Self {
+		self.fs.immediate_kinds = categories.into_iter().collect();
+		self
+	}
+
+	/// Set the quiet period after the filesystem watcher is created.
+	///
+	/// See the [documentation on the field](crate::fs::WorkingData#structfield.settle) for more
+	/// details.
+	pub fn fs_settle(&mut self, settle: impl Into<Duration>) -> &mut Self {
+		self.fs.settle = settle.into();
+		self
+	}
+
+	/// Toggle whether the filesystem worker emits events for directories.
+	///
+	/// See the [documentation on the field](crate::fs::WorkingData#structfield.watch_directories)
+	/// for more details.
+	pub fn fs_watch_directories(&mut self, enable: bool) -> &mut Self {
+		self.fs.watch_directories = enable;
+		self
+	}
+
+	/// Toggle whether the filesystem worker follows symlinked directories.
+	///
+	/// See the [documentation on the field](crate::fs::WorkingData#structfield.follow_symlinks)
+	/// for more details.
+	pub fn fs_follow_symlinks(&mut self, enable: bool) -> &mut Self {
+		self.fs.follow_symlinks = enable;
+		self
+	}
+
+	/// Set what the filesystem worker does with events while watching is paused.
+	///
+	/// See the [documentation on the field](crate::fs::WorkingData#structfield.while_paused)
+	/// for more details.
+	pub fn fs_while_paused(&mut self, while_paused: WhilePaused) -> &mut Self {
+		self.fs.while_paused = while_paused;
+		self
+	}
+
+	/// Toggle whether to attach the backend's event to each filesystem event.
+	///
+	/// See the [documentation on the field](crate::fs::WorkingData#structfield.raw_events)
+	/// for more details.
+	pub fn fs_raw_events(&mut self, enable: bool) -> &mut Self {
+		self.fs.raw_events = enable;
+		self
+	}
+
+	/// Toggle whether to drop modify events of files whose content didn't change.
+	///
+	/// See the [documentation on the field](crate::fs::WorkingData#structfield.suppress_identical)
+	/// for more details.
+	pub fn fs_suppress_identical(&mut self, enable: bool) -> &mut Self {
+		self.fs.suppress_identical = enable;
+		self
+	}
+
+	/// Set how many times to retry watching a path which failed to be watched.
+	///
+	/// See the [documentation on the field](crate::fs::WorkingData#structfield.watch_retries)
+	/// for more details.
+	pub fn fs_watch_retries(&mut self, retries: u32) -> &mut Self {
+		self.fs.watch_retries = retries;
+		self
+	}
+
+	/// Set the directory the filesystem worker's globs are rooted at.
+	///
+	/// See the [documentation on the field](crate::fs::WorkingData#structfield.project_root) for
+	/// more details.
+	pub fn fs_project_root(&mut self, root: impl Into<PathBuf>) -> &mut Self {
+		self.fs.project_root = Some(root.into());
+		self
+	}
+
+	/// Set how the filesystem worker's globs treat paths outside the project root.
+	///
+	/// See the [documentation on the field](crate::fs::WorkingData#structfield.outside_root) for
+	/// more details.
+	pub fn fs_outside_root(&mut self, outside: OutsideRoot) -> &mut Self {
+		self.fs.outside_root = outside;
+		self
+	}
+
+	/// Set the ignore files for the filesystem worker to read.
+	///
+	/// See the [documentation on the field](crate::fs::WorkingData#structfield.ignore_files) for
+	/// more details.
+	pub fn ignore_files<I, P>(&mut self, files: I) -> &mut Self
+	where
+		I: IntoIterator<Item = P>,
+		P: Into<PathBuf>,
+	{
+		self.fs.ignore_files = files.into_iter().map(Into::into).collect();
+		self
+	}
+
+	/// Toggle whether to discover ignore files within the watched paths.
+	///
+	/// See the [documentation on the field](crate::fs::WorkingData#structfield.use_gitignore) for
+	/// more details.
+	pub fn use_gitignore(&mut self, enable: bool) -> &mut Self {
+		self.fs.use_gitignore = enable;
+		self
+	}
+
+	/// Toggle whether to use the global ignore files.
+	///
+	/// See the [documentation on the field](crate::fs::WorkingData#structfield.use_global_ignores)
+	/// for more details.
+	pub fn use_global_ignores(&mut self, enable: bool) -> &mut Self {
+		self.fs.use_global_ignores = enable;
+		self
+	}
+
+	/// Watch a git repository.
+	///
+	/// This sets the pathset to just the `root` of the repository, enables
+	/// [`use_gitignore()`](RuntimeConfig::use_gitignore()) and
+	/// [`use_global_ignores()`](RuntimeConfig::use_global_ignores()), and adds ignore globs for the
+	/// `.git` directory (which changes all the time as git works). The root isn't checked to be a
+	/// repository, nor searched for from a subdirectory; see
+	/// [`project::origins()`](crate::project::origins()) for that.
+	///
+	/// Each of these is an ordinary setting, so any of them can be changed afterwards.
+	pub fn watch_git_repo(&mut self, root: impl Into<PathBuf>) -> &mut Self {
+		self.pathset([root.into()]);
+		for glob in [".git", ".git/**"] {
+			if !self.fs.ignore_globs.iter().any(|g| g == glob) {
+				self.fs.ignore_globs.push(glob.to_string());
+			}
+		}
+		self.use_gitignore(true).use_global_ignores(true)
+	}
+
 	/// Set the action throttle.
 	pub fn action_throttle(&mut self, throttle: impl Into<Duration>) -> &mut Self {
 		self.action.throttle = throttle.into();
 		self
 	}
 
+	/// Set (or unset) the leading-edge action rate limit.
+	///
+	/// See the [documentation on the field](crate::action::WorkingData#structfield.rate_limit)
+	/// for more details.
+	pub fn action_rate_limit(&mut self, window: Option<Duration>) -> &mut Self {
+		self.action.rate_limit = window;
+		self
+	}
+
+	/// Set (or unset) the action debounce, which applies to events from every source.
+	///
+	/// See the [documentation on the field](crate::action::WorkingData#structfield.debounce)
+	/// for more details.
+	pub fn action_debounce(&mut self, debounce: Option<Duration>) -> &mut Self {
+		self.action.debounce = debounce;
+		self
+	}
+
+	/// Toggle whether to trigger an action on startup.
+	///
+	/// See the [documentation on the field](crate::action::WorkingData#structfield.run_on_start)
+	/// for more details.
+	pub fn run_on_start(&mut self, run: bool) -> &mut Self {
+		self.action.run_on_start = run;
+		self
+	}
+
+	/// Set what to do when an action would start the command while it's still running.
+	///
+	/// See the [documentation on the field](crate::action::WorkingData#structfield.on_busy) for
+	/// more details.
+	pub fn on_busy_update(&mut self, on_busy: OnBusyUpdate) -> &mut Self {
+		self.action.on_busy = on_busy;
+		self
+	}
+
+	/// Map a signal to an outcome to apply directly when it's received.
+	///
+	/// See the [documentation on the field](crate::action::WorkingData#structfield.signal_map) for
+	/// more details.
+	pub fn signal_outcome(&mut self, signal: MainSignal, outcome: Outcome) -> &mut Self {
+		self.action.signal_map.insert(signal, outcome);
+		self
+	}
+
+	/// Set what to do with signals which aren't mapped to an outcome.
+	///
+	/// See the [documentation on the field](crate::action::WorkingData#structfield.unmapped_signals)
+	/// for more details.
+	pub fn unmapped_signals(&mut self, unmapped: UnmappedSignals) -> &mut Self {
+		self.action.unmapped_signals = unmapped;
+		self
+	}
+
+	/// Toggle whether to drop duplicate filesystem events within an action's batch.
+	///
+	/// See the [documentation on the field](crate::action::WorkingData#structfield.dedupe) for
+	/// more details.
+	pub fn dedupe(&mut self, enable: bool) -> &mut Self {
+		self.action.dedupe = enable;
+		self
+	}
+
+	/// Toggle whether to drop the paths of events which no longer exist before actions.
+	///
+	/// See the [documentation on the field](crate::action::WorkingData#structfield.drop_vanished)
+	/// for more details.
+	pub fn drop_vanished(&mut self, enable: bool) -> &mut Self {
+		self.action.drop_vanished = enable;
+		self
+	}
+
+	/// Toggle dry runs, where outcomes are reported instead of applied.
+	///
+	/// See the [documentation on the field](crate::action::WorkingData#structfield.dry_run) for
+	/// more details.
+	pub fn dry_run(&mut self, enable: bool) -> &mut Self {
+		self.action.dry_run = enable;
+		self
+	}
+
+	/// Set how many restarts within the window make a crash loop.
+	///
+	/// See the [documentation on the field](crate::action::WorkingData#structfield.max_restarts)
+	/// for more details.
+	pub fn max_restarts(&mut self, max: Option<usize>) -> &mut Self {
+		self.action.max_restarts = max;
+		self
+	}
+
+	/// Set the window over which restarts are counted for crash loop detection.
+	///
+	/// See the [documentation on the field](crate::action::WorkingData#structfield.restart_window)
+	/// for more details.
+	pub fn restart_window(&mut self, window: impl Into<Duration>) -> &mut Self {
+		self.action.restart_window = window.into();
+		self
+	}
+
 	/// Set the shell to use to invoke commands.
 	pub fn command_shell(&mut self, shell: Shell) -> &mut Self {
 		self.action.shell = shell;
@@ -70,11 +451,138 @@ impl RuntimeConfig {
 	}
 
 	/// Toggle whether to use process groups or not.
+	///
+	/// See the [documentation on the field](crate::action::WorkingData#structfield.grouped) for more details.
 	pub fn command_grouped(&mut self, grouped: bool) -> &mut Self {
 		self.action.grouped = grouped;
 		self
 	}
 
+	/// Toggle whether to capture the output of the command as events.
+	///
+	/// See the [documentation on the field](crate::action::WorkingData#structfield.capture_output)
+	/// for more details.
+	pub fn command_capture_output(&mut self, capture: bool) -> &mut Self {
+		self.action.capture_output = capture;
+		self
+	}
+
+	/// Toggle whether to keep the command's stdin open for writing to.
+	///
+	/// See the [documentation on the field](crate::action::WorkingData#structfield.keep_stdin)
+	/// for more details.
+	pub fn command_keep_stdin(&mut self, keep: bool) -> &mut Self {
+		self.action.keep_stdin = keep;
+		self
+	}
+
+	/// Toggle whether a lone carriage return ends a line of captured output.
+	///
+	/// See the [documentation on the field](crate::action::WorkingData#structfield.output_cr_lines)
+	/// for more details.
+	pub fn command_output_cr_lines(&mut self, enable: bool) -> &mut Self {
+		self.action.output_cr_lines = enable;
+		self
+	}
+
+	/// Set how much of each output stream to keep for [`Outcome::Capture`].
+	///
+	/// See the [documentation on the field](crate::action::WorkingData#structfield.capture_limit)
+	/// for more details.
+	///
+	/// [`Outcome::Capture`]: crate::action::Outcome::Capture
+	pub fn command_capture_limit(&mut self, bytes: usize) -> &mut Self {
+		self.action.capture_limit = bytes;
+		self
+	}
+
+	/// Set the signal used to ask the command to stop.
+	///
+	/// See the [documentation on the field](crate::action::WorkingData#structfield.stop_signal)
+	/// for more details.
+	pub fn command_stop_signal(&mut self, signal: SubSignal) -> &mut Self {
+		self.action.stop_signal = signal;
+		self
+	}
+
+	/// Set how long to wait for the command to stop before forcefully stopping it.
+	///
+	/// See the [documentation on the field](crate::action::WorkingData#structfield.stop_timeout)
+	/// for more details.
+	pub fn command_stop_timeout(&mut self, timeout: impl Into<Duration>) -> &mut Self {
+		self.action.stop_timeout = timeout.into();
+		self
+	}
+
+	/// Set (or unset) how long the command may run for before it's stopped.
+	///
+	/// See the [documentation on the field](crate::action::WorkingData#structfield.command_timeout)
+	/// for more details.
+	pub fn command_timeout(&mut self, timeout: Option<Duration>) -> &mut Self {
+		self.action.command_timeout = timeout;
+		self
+	}
+
+	/// Set the resource limits for the command.
+	///
+	/// See the [documentation on the field](crate::action::WorkingData#structfield.rlimits) for
+	/// more details.
+	pub fn command_rlimits(&mut self, limits: ResourceLimits) -> &mut Self {
+		self.action.rlimits = limits;
+		self
+	}
+
+	/// Set (or unset) the working directory of the command.
+	///
+	/// See the [documentation on the field](crate::action::WorkingData#structfield.workdir) for
+	/// more details.
+	pub fn command_workdir(&mut self, dir: Option<PathBuf>) -> &mut Self {
+		self.action.workdir = dir;
+		self
+	}
+
+	/// Toggle whether to run the command in a pseudo-terminal.
+	///
+	/// See the [documentation on the field](crate::action::WorkingData#structfield.pty) for more
+	/// details.
+	pub fn command_pty(&mut self, enable: bool) -> &mut Self {
+		self.action.pty = enable;
+		self
+	}
+
+	/// Toggle whether to restart the command when its program changes.
+	///
+	/// See the [documentation on the field](crate::action::WorkingData#structfield.watch_command_binary)
+	/// for more details.
+	pub fn command_watch_binary(&mut self, enable: bool) -> &mut Self {
+		self.action.watch_command_binary = enable;
+		self
+	}
+
+	/// Toggle whether to set environment variables describing the events on the command.
+	///
+	/// See the [documentation on the field](crate::action::WorkingData#structfield.environment)
+	/// for more details.
+	pub fn command_environment(&mut self, environment: bool) -> &mut Self {
+		self.action.environment = environment;
+		self
+	}
+
+	/// Set the prefix of the event environment variables.
+	pub fn command_env_prefix(&mut self, prefix: impl Into<String>) -> &mut Self {
+		self.action.env_prefix = prefix.into();
+		self
+	}
+
+	/// Toggle whether to write the triggering events to a file for the command.
+	///
+	/// See the [documentation on the field](crate::action::WorkingData#structfield.events_to_file)
+	/// for more details.
+	pub fn command_events_file(&mut self, enabled: bool) -> &mut Self {
+		self.action.events_to_file = enabled;
+		self
+	}
+
 	/// Set the command to run on action.
 	pub fn command<I, S>(&mut self, command: I) -> &mut Self
 	where
@@ -85,12 +593,83 @@ impl RuntimeConfig {
 		self
 	}
 
+	/// Set more commands to run alongside the command, concurrently.
+	///
+	/// See the [documentation on the field](crate::action::WorkingData#structfield.parallel_commands)
+	/// for more details.
+	pub fn parallel_commands<I, C, S>(&mut self, commands: I) -> &mut Self
+	where
+		I: IntoIterator<Item = C>,
+		C: IntoIterator<Item = S>,
+		S: AsRef<str>,
+	{
+		self.action.parallel_commands = commands
+			.into_iter()
+			.map(|command| command.into_iter().map(|c| c.as_ref().to_owned()).collect())
+			.collect();
+		self
+	}
+
 	/// Set the filterer implementation to use.
 	pub fn filterer(&mut self, filterer: Arc<dyn Filterer>) -> &mut Self {
 		self.action.filterer = filterer;
 		self
 	}
 
+	/// Set the filter expressions events must pass in addition to the filterer.
+	///
+	/// See the [documentation on the field](crate::action::WorkingData#structfield.filters) for
+	/// more details.
+	pub fn filters(&mut self, filters: impl IntoIterator<Item = Filter>) -> &mut Self {
+		self.action.filters = filters.into_iter().collect();
+		self
+	}
+
+	/// Set the predicate events must pass in addition to the filterer.
+	///
+	/// See the [documentation on the field](crate::action::WorkingData#structfield.predicate) for
+	/// more details.
+	pub fn filter_predicate(
+		&mut self,
+		predicate: impl Fn(&Event) -> bool + Send + Sync + 'static,
+	) -> &mut Self {
+		self.action.predicate = Arc::new(predicate);
+		self
+	}
+
+	/// Set the async predicate events must pass in addition to the filterer and the predicate.
+	///
+	/// See the [documentation on the field](crate::action::WorkingData#structfield.async_predicate)
+	/// for more details, in particular about latency.
+	pub fn filter_predicate_async(
+		&mut self,
+		predicate: impl Fn(Event) -> BoxFuture<'static, bool> + Send + Sync + 'static,
+	) -> &mut Self {
+		self.action.async_predicate = Some(Arc::new(predicate));
+		self
+	}
+
+	/// Set how long to wait for the async predicate before passing an event.
+	///
+	/// See the [documentation on the field](crate::action::WorkingData#structfield.async_predicate_timeout)
+	/// for more details.
+	pub fn filter_predicate_timeout(&mut self, timeout: Duration) -> &mut Self {
+		self.action.async_predicate_timeout = timeout;
+		self
+	}
+
+	/// Set the function which transforms events before they're filtered.
+	///
+	/// See the [documentation on the field](crate::action::WorkingData#structfield.event_mapper)
+	/// for more details.
+	pub fn event_mapper(
+		&mut self,
+		mapper: impl Fn(Event) -> Option<Event> + Send + Sync + 'static,
+	) -> &mut Self {
+		self.action.event_mapper = Arc::new(mapper);
+		self
+	}
+
 	/// Set the action handler.
 	pub fn on_action(&mut self, handler: impl Handler<Action> + Send + 'static) -> &mut Self {
 		self.action.action_handler = Arc::new(AtomicTake::new(Box::new(handler) as _));
@@ -144,23 +723,953 @@ impl RuntimeConfig {
 		self.action.post_spawn_handler = Arc::new(AtomicTake::empty());
 		self
 	}
-}
 
-/// Initialisation configuration for [`Watchexec`][crate::Watchexec].
-///
-/// This is used only for constructing the instance.
-///
-/// Use [`InitConfig::default()`] to build a new one, and the inherent methods to change values.
-/// This struct is marked non-exhaustive such that new options may be added without breaking change.
-#[non_exhaustive]
-pub struct InitConfig {
-	/// Runtime error handler.
+	/// Set the post-exit handler.
+	pub fn on_post_exit(&mut self, handler: impl Handler<PostExit> + Send + 'static) -> &mut Self {
+		self.action.post_exit_handler = Arc::new(AtomicTake::new(Box::new(handler) as _));
+		self
+	}
+
+	/// Keep the post-exit handler the same.
+	///
+	/// This is especially useful when reconfiguring _within_ the action handler.
+	///
+	/// Passing this config to [`Watchexec::new()`][crate::Watchexec::new()] will cause a
+	/// [`CriticalError::MissingHandler`][crate::error::CriticalError::MissingHandler].
+	pub fn keep_post_exit(&mut self) -> &mut Self {
+		self.action.post_exit_handler = Arc::new(AtomicTake::empty());
+		self
+	}
+
+	/// Applies a [`RuntimeConfigPatch`], changing only the settings it contains.
+	///
+	/// This makes it possible to keep a canonical `RuntimeConfig` around, tweak a few settings, and
+	/// pass the result to [`Watchexec::reconfigure()`][crate::Watchexec::reconfigure()] without
+	/// having to rebuild (or risk clobbering) the rest of the configuration.
+	pub fn merge(&mut self, patch: RuntimeConfigPatch) -> &mut Self {
+		let RuntimeConfigPatch {
+			enable_fs,
+			enable_signal,
+			enable_keyboard,
+			timer,
+			pathset,
+			file_watcher,
+			fs_debounce,
+			fs_debounce_kinds,
+			fs_immediate_kinds,
+			fs_settle,
+			fs_watch_directories,
+			fs_follow_symlinks,
+			fs_while_paused,
+			fs_raw_events,
+			fs_suppress_identical,
+			fs_watch_retries,
+			fs_project_root,
+			fs_outside_root,
+			ignore_globs,
+			watch_globs,
+			ignore_files,
+			use_gitignore,
+			use_global_ignores,
+			action_throttle,
+			action_rate_limit,
+			action_debounce,
+			run_on_start,
+			on_busy_update,
+			signal_outcomes,
+			unmapped_signals,
+			dedupe,
+			drop_vanished,
+			dry_run,
+			max_restarts,
+			restart_window,
+			command_shell,
+			command_grouped,
+			command_capture_output,
+			command_keep_stdin,
+			command_output_cr_lines,
+			command_capture_limit,
+			command_stop_signal,
+			command_stop_timeout,
+			command_timeout,
+			command_rlimits,
+			command_workdir,
+			command_pty,
+			command_watch_binary,
+			command_environment,
+			command_env_prefix,
+			command_events_file,
+			command,
+			parallel_commands,
+			filterer,
+			filters,
+			filter_predicate_timeout,
+		} = patch;
+
+		if let Some(enable_fs) = enable_fs {
+			self.enable_fs = enable_fs;
+		}
+		if let Some(enable_signal) = enable_signal {
+			self.enable_signal = enable_signal;
+		}
+		if let Some(enable_keyboard) = enable_keyboard {
+			self.enable_keyboard = enable_keyboard;
+		}
+		if let Some(timer) = timer {
+			self.timer = timer;
+		}
+		if let Some(pathset) = pathset {
+			self.fs.pathset = pathset;
+		}
+		if let Some(file_watcher) = file_watcher {
+			self.fs.watcher = file_watcher;
+		}
+		if let Some(fs_debounce) = fs_debounce {
+			self.fs.debounce = fs_debounce;
+		}
+		self.fs.debounce_kinds.extend(fs_debounce_kinds);
+		if let Some(fs_immediate_kinds) = fs_immediate_kinds {
+			self.fs.immediate_kinds = fs_immediate_kinds;
+		}
+		if let Some(fs_settle) = fs_settle {
+			self.fs.settle = fs_settle;
+		}
+		if let Some(fs_watch_directories) = fs_watch_directories {
+			self.fs.watch_directories = fs_watch_directories;
+		}
+		if let Some(fs_follow_symlinks) = fs_follow_symlinks {
+			self.fs.follow_symlinks = fs_follow_symlinks;
+		}
+		if let Some(fs_while_paused) = fs_while_paused {
+			self.fs.while_paused = fs_while_paused;
+		}
+		if let Some(fs_raw_events) = fs_raw_events {
+			self.fs.raw_events = fs_raw_events;
+		}
+		if let Some(fs_suppress_identical) = fs_suppress_identical {
+			self.fs.suppress_identical = fs_suppress_identical;
+		}
+		if let Some(fs_watch_retries) = fs_watch_retries {
+			self.fs.watch_retries = fs_watch_retries;
+		}
+		if let Some(fs_project_root) = fs_project_root {
+			self.fs.project_root = fs_project_root;
+		}
+		if let Some(fs_outside_root) = fs_outside_root {
+			self.fs.outside_root = fs_outside_root;
+		}
+		if let Some(ignore_globs) = ignore_globs {
+			self.fs.ignore_globs = ignore_globs;
+		}
+		if let Some(watch_globs) = watch_globs {
+			self.fs.watch_globs = watch_globs;
+		}
+		if let Some(ignore_files) = ignore_files {
+			self.fs.ignore_files = ignore_files;
+		}
+		if let Some(use_gitignore) = use_gitignore {
+			self.fs.use_gitignore = use_gitignore;
+		}
+		if let Some(use_global_ignores) = use_global_ignores {
+			self.fs.use_global_ignores = use_global_ignores;
+		}
+		if let Some(action_throttle) = action_throttle {
+			self.action.throttle = action_throttle;
+		}
+		if let Some(action_rate_limit) = action_rate_limit {
+			self.action.rate_limit = action_rate_limit;
+		}
+		if let Some(action_debounce) = action_debounce {
+			self.action.debounce = action_debounce;
+		}
+		if let Some(run_on_start) = run_on_start {
+			self.action.run_on_start = run_on_start;
+		}
+		if let Some(on_busy_update) = on_busy_update {
+			self.action.on_busy = on_busy_update;
+		}
+		self.action.signal_map.extend(signal_outcomes);
+		if let Some(unmapped_signals) = unmapped_signals {
+			self.action.unmapped_signals = unmapped_signals;
+		}
+		if let Some(dedupe) = dedupe {
+			self.action.dedupe = dedupe;
+		}
+		if let Some(drop_vanished) = drop_vanished {
+			self.action.drop_vanished = drop_vanished;
+		}
+		if let Some(dry_run) = dry_run {
+			self.action.dry_run = dry_run;
+		}
+		if let Some(max_restarts) = max_restarts {
+			self.action.max_restarts = max_restarts;
+		}
+		if let Some(restart_window) = restart_window {
+			self.action.restart_window = restart_window;
+		}
+		if let Some(command_shell) = command_shell {
+			self.action.shell = command_shell;
+		}
+		if let Some(command_grouped) = command_grouped {
+			self.action.grouped = command_grouped;
+		}
+		if let Some(command_capture_output) = command_capture_output {
+			self.action.capture_output = command_capture_output;
+		}
+		if let Some(command_keep_stdin) = command_keep_stdin {
+			self.action.keep_stdin = command_keep_stdin;
+		}
+		if let Some(command_output_cr_lines) = command_output_cr_lines {
+			self.action.output_cr_lines = command_output_cr_lines;
+		}
+		if let Some(command_capture_limit) = command_capture_limit {
+			self.action.capture_limit = command_capture_limit;
+		}
+		if let Some(command_stop_signal) = command_stop_signal {
+			self.action.stop_signal = command_stop_signal;
+		}
+		if let Some(command_stop_timeout) = command_stop_timeout {
+			self.action.stop_timeout = command_stop_timeout;
+		}
+		if let Some(command_timeout) = command_timeout {
+			self.action.command_timeout = command_timeout;
+		}
+		if let Some(command_rlimits) = command_rlimits {
+			self.action.rlimits = command_rlimits;
+		}
+		if let Some(command_workdir) = command_workdir {
+			self.action.workdir = command_workdir;
+		}
+		if let Some(command_pty) = command_pty {
+			self.action.pty = command_pty;
+		}
+		if let Some(command_watch_binary) = command_watch_binary {
+			self.action.watch_command_binary = command_watch_binary;
+		}
+		if let Some(command_environment) = command_environment {
+			self.action.environment = command_environment;
+		}
+		if let Some(command_env_prefix) = command_env_prefix {
+			self.action.env_prefix = command_env_prefix;
+		}
+		if let Some(command_events_file) = command_events_file {
+			self.action.events_to_file = command_events_file;
+		}
+		if let Some(command) = command {
+			self.action.command = command;
+		}
+		if let Some(parallel_commands) = parallel_commands {
+			self.action.parallel_commands = parallel_commands;
+		}
+		if let Some(filterer) = filterer {
+			self.action.filterer = filterer;
+		}
+		if let Some(filters) = filters {
+			self.action.filters = filters;
+		}
+		if let Some(filter_predicate_timeout) = filter_predicate_timeout {
+			self.action.async_predicate_timeout = filter_predicate_timeout;
+		}
+
+		self
+	}
+
+	/// Describes the fields which differ from `other`, with their values in this configuration.
+	///
+	/// Handlers, the filterer, the predicate, and the event mapper can't be compared by value, so
+	/// they're left out.
+	/// This is used to log what a reconfigure sets, by diffing against the default.
+	pub(crate) fn diff(&self, other: &Self) -> ConfigDiff {
+		let Self {
+			fs,
+			action,
+			enable_fs,
+			enable_signal,
+			enable_keyboard,
+			timer,
+		} = self;
+
+		let mut fields: Vec<_> = fs
+			.diff(&other.fs)
+			.into_iter()
+			.map(|(name, value)| ("fs", name, value))
+			.chain(
+				action
+					.diff(&other.action)
+					.into_iter()
+					.map(|(name, value)| ("action", name, value)),
+			)
+			.collect();
+		for (name, value, other) in [
+			("enable_fs", enable_fs, other.enable_fs),
+			("enable_signal", enable_signal, other.enable_signal),
+			("enable_keyboard", enable_keyboard, other.enable_keyboard),
+		] {
+			if *value != other {
+				fields.push(("", name, value.to_string()));
+			}
+		}
+		if *timer != other.timer {
+			fields.push(("", "timer", format!("{:?}", timer)));
+		}
+
+		ConfigDiff(fields)
+	}
+}
+
+/// The fields which differ between two [`RuntimeConfig`]s, from [`RuntimeConfig::diff()`].
+///
+/// This displays compactly as `section.field=value` pairs separated by spaces, or as `none` if
+/// there's no difference.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct ConfigDiff(Vec<(&'static str, &'static str, String)>);
+
+impl ConfigDiff {
+	/// Whether there's no difference.
+	pub fn is_empty(&self) -> bool {
+		self.0.is_empty()
+	}
+}
+
+impl fmt::Display for ConfigDiff {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		if self.is_empty() {
+			return f.write_str("none");
+		}
+
+		for (n, (section, name, value)) in self.0.iter().enumerate() {
+			if n > 0 {
+				f.write_str(" ")?;
+			}
+			if !section.is_empty() {
+				write!(f, "{}.", section)?;
+			}
+			write!(f, "{}={}", name, value)?;
+		}
+		Ok(())
+	}
+}
+
+/// A partial [`RuntimeConfig`], for use with [`RuntimeConfig::merge()`].
+///
+/// Every field is optional: only those which are `Some` are applied. Use
+/// [`RuntimeConfigPatch::default()`] to start from an empty patch, and the convenience (chainable!)
+/// methods to fill it in; these have the same names as the ones on `RuntimeConfig`.
+///
+/// Maps, that is [`fs_debounce_kinds`](#structfield.fs_debounce_kinds) and
+/// [`signal_outcomes`](#structfield.signal_outcomes), are merged entry by entry, so a patch can add
+/// or change entries but not remove them. Settings which are `Option`s themselves, like the
+/// [`timer`](#structfield.timer), are doubly wrapped so that a patch can unset them.
+///
+/// Everything else can be set, except for what's a closure or a handler: the action, pre-spawn,
+/// post-spawn, and post-exit handlers, the filter predicate and async predicate, and the event
+/// mapper are not part of the patch; use [`RuntimeConfig::on_action()`] and friends,
+/// [`RuntimeConfig::filter_predicate()`], [`RuntimeConfig::filter_predicate_async()`], and
+/// [`RuntimeConfig::event_mapper()`] for those. Neither are [`RuntimeConfig::add_path()`] and
+/// [`RuntimeConfig::watch_git_repo()`], which add to the current settings rather than set them.
+#[derive(Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct RuntimeConfigPatch {
+	/// See [`RuntimeConfig::enable_fs`].
+	pub enable_fs: Option<bool>,
+
+	/// See [`RuntimeConfig::enable_signal`].
+	pub enable_signal: Option<bool>,
+
+	/// See [`RuntimeConfig::enable_keyboard`].
+	pub enable_keyboard: Option<bool>,
+
+	/// See [`RuntimeConfig::timer`].
+	///
+	/// Set this to `Some(None)` to unset the setting.
+	pub timer: Option<Option<Duration>>,
+
+	/// See [`crate::fs::WorkingData::pathset`].
+	pub pathset: Option<Vec<WatchedPath>>,
+
+	/// See [`crate::fs::WorkingData::watcher`].
+	pub file_watcher: Option<Watcher>,
+
+	/// See [`crate::fs::WorkingData::debounce`].
+	pub fs_debounce: Option<Duration>,
+
+	/// See [`crate::fs::WorkingData::debounce_kinds`].
+	///
+	/// Entries are added to the setting, replacing those with the same key.
+	pub fs_debounce_kinds: HashMap<KindCategory, Duration>,
+
+	/// See [`crate::fs::WorkingData::immediate_kinds`].
+	pub fs_immediate_kinds: Option<HashSet<KindCategory>>,
+
+	/// See [`crate::fs::WorkingData::settle`].
+	pub fs_settle: Option<Duration>,
+
+	/// See [`crate::fs::WorkingData::watch_directories`].
+	pub fs_watch_directories: Option<bool>,
+
+	/// See [`crate::fs::WorkingData::follow_symlinks`].
+	pub fs_follow_symlinks: Option<bool>,
+
+	/// See [`crate::fs::WorkingData::while_paused`].
+	pub fs_while_paused: Option<WhilePaused>,
+
+	/// See [`crate::fs::WorkingData::raw_events`].
+	pub fs_raw_events: Option<bool>,
+
+	/// See [`crate::fs::WorkingData::suppress_identical`].
+	pub fs_suppress_identical: Option<bool>,
+
+	/// See [`crate::fs::WorkingData::watch_retries`].
+	pub fs_watch_retries: Option<u32>,
+
+	/// See [`crate::fs::WorkingData::project_root`].
+	///
+	/// Set this to `Some(None)` to unset the setting.
+	pub fs_project_root: Option<Option<PathBuf>>,
+
+	/// See [`crate::fs::WorkingData::outside_root`].
+	pub fs_outside_root: Option<OutsideRoot>,
+
+	/// See [`crate::fs::WorkingData::ignore_globs`].
+	pub ignore_globs: Option<Vec<String>>,
+
+	/// See [`crate::fs::WorkingData::watch_globs`].
+	pub watch_globs: Option<Vec<String>>,
+
+	/// See [`crate::fs::WorkingData::ignore_files`].
+	pub ignore_files: Option<Vec<PathBuf>>,
+
+	/// See [`crate::fs::WorkingData::use_gitignore`].
+	pub use_gitignore: Option<bool>,
+
+	/// See [`crate::fs::WorkingData::use_global_ignores`].
+	pub use_global_ignores: Option<bool>,
+
+	/// See [`crate::action::WorkingData::throttle`].
+	pub action_throttle: Option<Duration>,
+
+	/// See [`crate::action::WorkingData::rate_limit`].
+	///
+	/// Set this to `Some(None)` to unset the setting.
+	pub action_rate_limit: Option<Option<Duration>>,
+
+	/// See [`crate::action::WorkingData::debounce`].
+	///
+	/// Set this to `Some(None)` to unset the setting.
+	pub action_debounce: Option<Option<Duration>>,
+
+	/// See [`crate::action::WorkingData::run_on_start`].
+	pub run_on_start: Option<bool>,
+
+	/// See [`crate::action::WorkingData::on_busy`].
+	pub on_busy_update: Option<OnBusyUpdate>,
+
+	/// See [`crate::action::WorkingData::signal_map`].
+	///
+	/// Entries are added to the setting, replacing those with the same key.
+	pub signal_outcomes: HashMap<MainSignal, Outcome>,
+
+	/// See [`crate::action::WorkingData::unmapped_signals`].
+	pub unmapped_signals: Option<UnmappedSignals>,
+
+	/// See [`crate::action::WorkingData::dedupe`].
+	pub dedupe: Option<bool>,
+
+	/// See [`crate::action::WorkingData::drop_vanished`].
+	pub drop_vanished: Option<bool>,
+
+	/// See [`crate::action::WorkingData::dry_run`].
+	pub dry_run: Option<bool>,
+
+	/// See [`crate::action::WorkingData::max_restarts`].
+	///
+	/// Set this to `Some(None)` to unset the setting.
+	pub max_restarts: Option<Option<usize>>,
+
+	/// See [`crate::action::WorkingData::restart_window`].
+	pub restart_window: Option<Duration>,
+
+	/// See [`crate::action::WorkingData::shell`].
+	pub command_shell: Option<Shell>,
+
+	/// See [`crate::action::WorkingData::grouped`].
+	pub command_grouped: Option<bool>,
+
+	/// See [`crate::action::WorkingData::capture_output`].
+	pub command_capture_output: Option<bool>,
+
+	/// See [`crate::action::WorkingData::keep_stdin`].
+	pub command_keep_stdin: Option<bool>,
+
+	/// See [`crate::action::WorkingData::output_cr_lines`].
+	pub command_output_cr_lines: Option<bool>,
+
+	/// See [`crate::action::WorkingData::capture_limit`].
+	pub command_capture_limit: Option<usize>,
+
+	/// See [`crate::action::WorkingData::stop_signal`].
+	pub command_stop_signal: Option<SubSignal>,
+
+	/// See [`crate::action::WorkingData::stop_timeout`].
+	pub command_stop_timeout: Option<Duration>,
+
+	/// See [`crate::action::WorkingData::command_timeout`].
+	///
+	/// Set this to `Some(None)` to unset the setting.
+	pub command_timeout: Option<Option<Duration>>,
+
+	/// See [`crate::action::WorkingData::rlimits`].
+	pub command_rlimits: Option<ResourceLimits>,
+
+	/// See [`crate::action::WorkingData::workdir`].
+	///
+	/// Set this to `Some(None)` to unset the setting.
+	pub command_workdir: Option<Option<PathBuf>>,
+
+	/// See [`crate::action::WorkingData::pty`].
+	pub command_pty: Option<bool>,
+
+	/// See [`crate::action::WorkingData::watch_command_binary`].
+	pub command_watch_binary: Option<bool>,
+
+	/// See [`crate::action::WorkingData::environment`].
+	pub command_environment: Option<bool>,
+
+	/// See [`crate::action::WorkingData::env_prefix`].
+	pub command_env_prefix: Option<String>,
+
+	/// See [`crate::action::WorkingData::events_to_file`].
+	pub command_events_file: Option<bool>,
+
+	/// See [`crate::action::WorkingData::command`].
+	pub command: Option<Vec<String>>,
+
+	/// See [`crate::action::WorkingData::parallel_commands`].
+	pub parallel_commands: Option<Vec<Vec<String>>>,
+
+	/// See [`crate::action::WorkingData::filterer`].
+	pub filterer: Option<Arc<dyn Filterer>>,
+
+	/// See [`crate::action::WorkingData::filters`].
+	pub filters: Option<Vec<Filter>>,
+
+	/// See [`crate::action::WorkingData::async_predicate_timeout`].
+	pub filter_predicate_timeout: Option<Duration>,
+}
+
+impl RuntimeConfigPatch {
+	/// Toggle whether to run the filesystem event source.
+	///
+	/// Like the [setting on `RuntimeConfig`](RuntimeConfig::enable_fs()), this only takes effect
+	/// when constructing the instance.
+	pub fn enable_fs(&mut self, enable: bool) -> &mut Self {
+		self.enable_fs = Some(enable);
+		self
+	}
+
+	/// Toggle whether to run the signal event source.
+	///
+	/// Like the [setting on `RuntimeConfig`](RuntimeConfig::enable_signal()), this only takes
+	/// effect when constructing the instance.
+	pub fn enable_signal(&mut self, enable: bool) -> &mut Self {
+		self.enable_signal = Some(enable);
+		self
+	}
+
+	/// Toggle whether to run the keyboard event source.
+	///
+	/// Like the [setting on `RuntimeConfig`](RuntimeConfig::enable_keyboard()), this only takes
+	/// effect when constructing the instance.
+	pub fn enable_keyboard(&mut self, enable: bool) -> &mut Self {
+		self.enable_keyboard = Some(enable);
+		self
+	}
+
+	/// Set (or unset) the interval of the timer event source.
+	pub fn timer(&mut self, interval: Option<Duration>) -> &mut Self {
+		self.timer = Some(interval);
+		self
+	}
+
+	/// Set the pathset to be watched.
+	pub fn pathset<I, P>(&mut self, pathset: I) -> &mut Self
+	where
+		I: IntoIterator<Item = P>,
+		P: AsRef<Path>,
+	{
+		self.pathset = Some(pathset.into_iter().map(|p| p.as_ref().into()).collect());
+		self
+	}
+
+	/// Set the file watcher type to use.
+	pub fn file_watcher(&mut self, watcher: Watcher) -> &mut Self {
+		self.file_watcher = Some(watcher);
+		self
+	}
+
+	/// Set a custom backend for the file watcher.
+	pub fn fs_backend(&mut self, backend: impl FsBackend + 'static) -> &mut Self {
+		self.file_watcher = Some(Watcher::Custom(Backend(Arc::new(backend))));
+		self
+	}
+
+	/// Set the filesystem event debounce window.
+	pub fn fs_debounce(&mut self, debounce: impl Into<Duration>) -> &mut Self {
+		self.fs_debounce = Some(debounce.into());
+		self
+	}
+
+	/// Set the debounce window for a category of filesystem events.
+	pub fn fs_debounce_kind(
+		&mut self,
+		category: KindCategory,
+		debounce: impl Into<Duration>,
+	) -> &mut Self {
+		self.fs_debounce_kinds.insert(category, debounce.into());
+		self
+	}
+
+	/// Set the categories of filesystem events which by-pass the debounce.
+	pub fn fs_immediate_kinds(
+		&mut self,
+		categories: impl IntoIterator<Item = KindCategory>,
+	) -> &mut Self {
+		self.fs_immediate_kinds = Some(categories.into_iter().collect());
+		self
+	}
+
+	/// Set the quiet period after the filesystem watcher is created.
+	pub fn fs_settle(&mut self, settle: impl Into<Duration>) -> &mut Self {
+		self.fs_settle = Some(settle.into());
+		self
+	}
+
+	/// Toggle whether the filesystem worker emits events for directories.
+	pub fn fs_watch_directories(&mut self, enable: bool) -> &mut Self {
+		self.fs_watch_directories = Some(enable);
+		self
+	}
+
+	/// Toggle whether the filesystem worker follows symlinked directories.
+	pub fn fs_follow_symlinks(&mut self, enable: bool) -> &mut Self {
+		self.fs_follow_symlinks = Some(enable);
+		self
+	}
+
+	/// Set what the filesystem worker does with events while watching is paused.
+	pub fn fs_while_paused(&mut self, while_paused: WhilePaused) -> &mut Self {
+		self.fs_while_paused = Some(while_paused);
+		self
+	}
+
+	/// Toggle whether to attach the backend's event to each filesystem event.
+	pub fn fs_raw_events(&mut self, enable: bool) -> &mut Self {
+		self.fs_raw_events = Some(enable);
+		self
+	}
+
+	/// Toggle whether to drop modify events of files whose content didn't change.
+	pub fn fs_suppress_identical(&mut self, enable: bool) -> &mut Self {
+		self.fs_suppress_identical = Some(enable);
+		self
+	}
+
+	/// Set how many times to retry watching a path which failed to be watched.
+	pub fn fs_watch_retries(&mut self, retries: u32) -> &mut Self {
+		self.fs_watch_retries = Some(retries);
+		self
+	}
+
+	/// Set the directory the filesystem worker's globs are rooted at.
+	pub fn fs_project_root(&mut self, root: impl Into<PathBuf>) -> &mut Self {
+		self.fs_project_root = Some(Some(root.into()));
+		self
+	}
+
+	/// Set how the filesystem worker's globs treat paths outside the project root.
+	pub fn fs_outside_root(&mut self, outside: OutsideRoot) -> &mut Self {
+		self.fs_outside_root = Some(outside);
+		self
+	}
+
+	/// Set the globs of paths for the filesystem worker to ignore.
+	pub fn ignore_globs<I, S>(&mut self, globs: I) -> &mut Self
+	where
+		I: IntoIterator<Item = S>,
+		S: AsRef<str>,
+	{
+		self.ignore_globs = Some(globs.into_iter().map(|g| g.as_ref().to_owned()).collect());
+		self
+	}
+
+	/// Set the globs of paths for the filesystem worker to keep.
+	pub fn watch_globs<I, S>(&mut self, globs: I) -> &mut Self
+	where
+		I: IntoIterator<Item = S>,
+		S: AsRef<str>,
+	{
+		self.watch_globs = Some(globs.into_iter().map(|g| g.as_ref().to_owned()).collect());
+		self
+	}
+
+	/// Set the ignore files for the filesystem worker to read.
+	pub fn ignore_files<I, P>(&mut self, files: I) -> &mut Self
+	where
+		I: IntoIterator<Item = P>,
+		P: Into<PathBuf>,
+	{
+		self.ignore_files = Some(files.into_iter().map(Into::into).collect());
+		self
+	}
+
+	/// Toggle whether to discover ignore files within the watched paths.
+	pub fn use_gitignore(&mut self, enable: bool) -> &mut Self {
+		self.use_gitignore = Some(enable);
+		self
+	}
+
+	/// Toggle whether to use the global ignore files.
+	pub fn use_global_ignores(&mut self, enable: bool) -> &mut Self {
+		self.use_global_ignores = Some(enable);
+		self
+	}
+
+	/// Set the action throttle.
+	pub fn action_throttle(&mut self, throttle: impl Into<Duration>) -> &mut Self {
+		self.action_throttle = Some(throttle.into());
+		self
+	}
+
+	/// Set (or unset) the leading-edge action rate limit.
+	pub fn action_rate_limit(&mut self, window: Option<Duration>) -> &mut Self {
+		self.action_rate_limit = Some(window);
+		self
+	}
+
+	/// Set (or unset) the action debounce, which applies to events from every source.
+	pub fn action_debounce(&mut self, debounce: Option<Duration>) -> &mut Self {
+		self.action_debounce = Some(debounce);
+		self
+	}
+
+	/// Toggle whether to trigger an action on startup.
+	pub fn run_on_start(&mut self, run: bool) -> &mut Self {
+		self.run_on_start = Some(run);
+		self
+	}
+
+	/// Set what to do when an action would start the command while it's still running.
+	pub fn on_busy_update(&mut self, on_busy: OnBusyUpdate) -> &mut Self {
+		self.on_busy_update = Some(on_busy);
+		self
+	}
+
+	/// Map a signal to an outcome to apply directly when it's received.
+	pub fn signal_outcome(&mut self, signal: MainSignal, outcome: Outcome) -> &mut Self {
+		self.signal_outcomes.insert(signal, outcome);
+		self
+	}
+
+	/// Set what to do with signals which aren't mapped to an outcome.
+	pub fn unmapped_signals(&mut self, unmapped: UnmappedSignals) -> &mut Self {
+		self.unmapped_signals = Some(unmapped);
+		self
+	}
+
+	/// Toggle whether to drop duplicate filesystem events within an action's batch.
+	pub fn dedupe(&mut self, enable: bool) -> &mut Self {
+		self.dedupe = Some(enable);
+		self
+	}
+
+	/// Toggle whether to drop the paths of events which no longer exist before actions.
+	pub fn drop_vanished(&mut self, enable: bool) -> &mut Self {
+		self.drop_vanished = Some(enable);
+		self
+	}
+
+	/// Toggle dry runs, where outcomes are reported instead of applied.
+	pub fn dry_run(&mut self, enable: bool) -> &mut Self {
+		self.dry_run = Some(enable);
+		self
+	}
+
+	/// Set how many restarts within the window make a crash loop.
+	pub fn max_restarts(&mut self, max: Option<usize>) -> &mut Self {
+		self.max_restarts = Some(max);
+		self
+	}
+
+	/// Set the window over which restarts are counted for crash loop detection.
+	pub fn restart_window(&mut self, window: impl Into<Duration>) -> &mut Self {
+		self.restart_window = Some(window.into());
+		self
+	}
+
+	/// Set the shell to use to invoke commands.
+	pub fn command_shell(&mut self, shell: Shell) -> &mut Self {
+		self.command_shell = Some(shell);
+		self
+	}
+
+	/// Toggle whether to use process groups or not.
+	///
+	/// See the [documentation on the field](crate::action::WorkingData#structfield.grouped) for more details.
+	pub fn command_grouped(&mut self, grouped: bool) -> &mut Self {
+		self.command_grouped = Some(grouped);
+		self
+	}
+
+	/// Toggle whether to capture the output of the command as events.
+	pub fn command_capture_output(&mut self, capture: bool) -> &mut Self {
+		self.command_capture_output = Some(capture);
+		self
+	}
+
+	/// Toggle whether to keep the command's stdin open for writing to.
+	pub fn command_keep_stdin(&mut self, keep: bool) -> &mut Self {
+		self.command_keep_stdin = Some(keep);
+		self
+	}
+
+	/// Toggle whether a lone carriage return ends a line of captured output.
+	pub fn command_output_cr_lines(&mut self, enable: bool) -> &mut Self {
+		self.command_output_cr_lines = Some(enable);
+		self
+	}
+
+	/// Set how much of each output stream to keep for [`Outcome::Capture`].
+	pub fn command_capture_limit(&mut self, bytes: usize) -> &mut Self {
+		self.command_capture_limit = Some(bytes);
+		self
+	}
+
+	/// Set the signal used to ask the command to stop.
+	pub fn command_stop_signal(&mut self, signal: SubSignal) -> &mut Self {
+		self.command_stop_signal = Some(signal);
+		self
+	}
+
+	/// Set how long to wait for the command to stop before forcefully stopping it.
+	pub fn command_stop_timeout(&mut self, timeout: impl Into<Duration>) -> &mut Self {
+		self.command_stop_timeout = Some(timeout.into());
+		self
+	}
+
+	/// Set (or unset) how long the command may run for before it's stopped.
+	pub fn command_timeout(&mut self, timeout: Option<Duration>) -> &mut Self {
+		self.command_timeout = Some(timeout);
+		self
+	}
+
+	/// Set the resource limits for the command.
+	pub fn command_rlimits(&mut self, limits: ResourceLimits) -> &mut Self {
+		self.command_rlimits = Some(limits);
+		self
+	}
+
+	/// Set (or unset) the working directory of the command.
+	pub fn command_workdir(&mut self, dir: Option<PathBuf>) -> &mut Self {
+		self.command_workdir = Some(dir);
+		self
+	}
+
+	/// Toggle whether to run the command in a pseudo-terminal.
+	pub fn command_pty(&mut self, enable: bool) -> &mut Self {
+		self.command_pty = Some(enable);
+		self
+	}
+
+	/// Toggle whether to restart the command when its program changes.
+	pub fn command_watch_binary(&mut self, enable: bool) -> &mut Self {
+		self.command_watch_binary = Some(enable);
+		self
+	}
+
+	/// Toggle whether to set environment variables describing the events on the command.
+	pub fn command_environment(&mut self, environment: bool) -> &mut Self {
+		self.command_environment = Some(environment);
+		self
+	}
+
+	/// Set the prefix of the event environment variables.
+	pub fn command_env_prefix(&mut self, prefix: impl Into<String>) -> &mut Self {
+		self.command_env_prefix = Some(prefix.into());
+		self
+	}
+
+	/// Toggle whether to write the triggering events to a file for the command.
+	pub fn command_events_file(&mut self, enabled: bool) -> &mut Self {
+		self.command_events_file = Some(enabled);
+		self
+	}
+
+	/// Set the command to run on action.
+	pub fn command<I, S>(&mut self, command: I) -> &mut Self
+	where
+		I: IntoIterator<Item = S>,
+		S: AsRef<str>,
+	{
+		self.command = Some(command.into_iter().map(|c| c.as_ref().to_owned()).collect());
+		self
+	}
+
+	/// Set more commands to run alongside the command, concurrently.
+	pub fn parallel_commands<I, C, S>(&mut self, commands: I) -> &mut Self
+	where
+		I: IntoIterator<Item = C>,
+		C: IntoIterator<Item = S>,
+		S: AsRef<str>,
+	{
+		self.parallel_commands = Some(
+			commands
+				.into_iter()
+				.map(|command| command.into_iter().map(|c| c.as_ref().to_owned()).collect())
+				.collect(),
+		);
+		self
+	}
+
+	/// Set the filterer implementation to use.
+	pub fn filterer(&mut self, filterer: Arc<dyn Filterer>) -> &mut Self {
+		self.filterer = Some(filterer);
+		self
+	}
+
+	/// Set the filter expressions events must pass in addition to the filterer.
+	pub fn filters(&mut self, filters: impl IntoIterator<Item = Filter>) -> &mut Self {
+		self.filters = Some(filters.into_iter().collect());
+		self
+	}
+
+	/// Set how long to wait for the async predicate before passing an event.
+	pub fn filter_predicate_timeout(&mut self, timeout: Duration) -> &mut Self {
+		self.filter_predicate_timeout = Some(timeout);
+		self
+	}
+}
+
+/// Initialisation configuration for [`Watchexec`][crate::Watchexec].
+///
+/// This is used only for constructing the instance.
+///
+/// Use [`InitConfig::default()`] to build a new one, and the inherent methods to change values, or
+/// use [`InitConfig::builder()`] for a fluent construction. This struct is marked non-exhaustive
+/// such that new options may be added without breaking change.
+#[non_exhaustive]
+pub struct InitConfig {
+	/// Runtime error handlers.
 	///
-	/// This is run on every runtime error that occurs within watchexec. By default the placeholder
-	/// `()` handler is used, which discards all errors.
+	/// These are run in sequence on every runtime error that occurs within watchexec. By default
+	/// there are none, which discards all errors. As there may be several handlers, each is given
+	/// the error in an [`Arc`].
 	///
-	/// If the handler errors, [_that_ error][crate::error::RuntimeError::Handler] is immediately
-	/// given to the handler. If this second handler call errors as well, its error is ignored.
+	/// A handler erroring does not prevent the others from running. Once all have run,
+	/// [_those_ errors][crate::error::RuntimeError::Handler] are in turn given to every handler. If
+	/// these second handler calls error as well, their errors are ignored.
+	///
+	/// A handler which fails on [`error_handler_failure_limit`](InitConfig#structfield.error_handler_failure_limit)
+	/// errors in a row is disabled.
 	///
 	/// # Examples
 	///
@@ -173,37 +1682,245 @@ pub struct InitConfig {
 	///     Ok::<(), Infallible>(())
 	/// });
 	/// ```
-	pub error_handler: Box<dyn Handler<RuntimeError> + Send>,
+	pub error_handlers: Vec<Box<dyn Handler<Arc<RuntimeError>> + Send>>,
+
+	/// How many consecutive failures disable an error handler.
+	///
+	/// A handler which is persistently broken would otherwise add several log lines to every
+	/// runtime error, forever. Once a handler has failed on this many errors in a row, it's dropped,
+	/// with a single log message saying so. Runtime errors are still logged, and given to the other
+	/// handlers. A success resets the count.
+	///
+	/// Only the handling of runtime errors is counted, not that of other handlers' failures. Zero
+	/// disables this, keeping handlers however often they fail. Defaults to 10.
+	pub error_handler_failure_limit: usize,
 
 	/// Internal: the buffer size of the channel which carries runtime errors.
 	///
 	/// The default (64) is usually fine. If you expect a much larger throughput of runtime errors,
-	/// or if your `error_handler` is slow, adjusting this value may help.
+	/// or if your `error_handlers` are slow, adjusting this value may help.
 	pub error_channel_size: usize,
 
 	/// Internal: the buffer size of the channel which carries events.
 	///
 	/// The default (1024) is usually fine. If you expect a much larger throughput of events,
 	/// adjusting this value may help.
+	///
+	/// This is where backpressure happens: events are taken off this channel by the action worker,
+	/// which doesn't read more while it's applying an action (e.g. waiting for a command to stop).
+	/// When the channel is full, sending an event blocks until there's room, unless the
+	/// [`event_overflow`](InitConfig#structfield.event_overflow) policy says otherwise.
 	pub event_channel_size: usize,
+
+	/// Warn when the event channel stays nearly full for this long.
+	///
+	/// The depth of the event channel is sampled regularly, and if it's been at 90% or more of the
+	/// [`event_channel_size`](InitConfig#structfield.event_channel_size) for this long, a
+	/// [`RuntimeError::ChannelSaturated`] is sent to the error handlers, with a suggested size.
+	/// This is sent once per stretch of saturation: the channel has to drain below the threshold
+	/// before it's sent again.
+	///
+	/// This is only a warning: the channel isn't resized automatically, so the suggested size has
+	/// to be set as the `event_channel_size` by hand, for the next time an instance is constructed.
+	///
+	/// A channel which is often full means sources are being held up (or are dropping events,
+	/// depending on the [`event_overflow`](InitConfig#structfield.event_overflow) policy), so
+	/// raising the size may help. If it's full because the action worker is waiting, e.g. for a
+	/// command to stop, a larger channel will only fill up later.
+	///
+	/// Defaults to 10 seconds. `None` disables the check.
+	pub saturation_warning: Option<Duration>,
+
+	/// What the internal event sources do when the event channel is full.
+	///
+	/// This applies to the filesystem and signal workers. Events sent with
+	/// [`Watchexec::send_event()`][crate::Watchexec::send_event()] always wait for room, and those
+	/// sent with [`Watchexec::try_send_event()`][crate::Watchexec::try_send_event()] never do.
+	///
+	/// Defaults to [`OverflowPolicy::Block`].
+	pub event_overflow: OverflowPolicy,
+
+	/// Force-quit when interrupted twice within this window.
+	///
+	/// The first [`Interrupt`](crate::signal::source::MainSignal::Interrupt) (Ctrl-C) is handled as
+	/// usual, e.g. by stopping the command gracefully. If a second one arrives within this long of
+	/// the first, the command is killed (with `SIGKILL` on Unix) and Watchexec exits straight away,
+	/// like on [`Outcome::Exit`](crate::action::Outcome::Exit), even if the command ignores
+	/// interrupts and the action worker is waiting for it to stop. The signal events are still
+	/// sent as usual. This needs the signal source to be
+	/// [enabled](RuntimeConfig::enable_signal()).
+	///
+	/// Defaults to `None`, which disables it.
+	pub force_quit_window: Option<Duration>,
+
+	/// The handler to change the log level with.
+	///
+	/// Watchexec logs with [`tracing`], but doesn't set up the subscriber, so it can't change the
+	/// level by itself. If this is given (usually calling a
+	/// [reload handle](https://docs.rs/tracing-subscriber/*/tracing_subscriber/reload/index.html)
+	/// of the subscriber), [`Watchexec::set_log_level()`](crate::Watchexec::set_log_level()) and
+	/// [`Outcome::LogLevel`] call it with the new level, so verbosity can be raised while debugging
+	/// and lowered after, without restarting. Errors from the handler are returned from
+	/// `set_log_level()`, or issued as runtime errors for the outcome.
+	///
+	/// Defaults to `None`, with which changing the level errors with
+	/// [`RuntimeError::NoLogLevelHandler`].
+	pub log_level_handler: Option<Box<dyn Handler<LevelFilter> + Send>>,
+}
+
+/// What to do with a new event when the event channel is full.
+///
+/// See [`InitConfig::event_overflow`](InitConfig#structfield.event_overflow).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum OverflowPolicy {
+	/// Wait until there's room in the channel.
+	///
+	/// No events are lost, but the event source stops processing while it waits. For the
+	/// filesystem worker, this means events from the watcher pile up (and are eventually coalesced)
+	/// instead; under a file storm, this can add a lot of latency.
+	Block,
+
+	/// Drop the new event.
+	///
+	/// Each dropped event is reported as a [`RuntimeError::EventChannelTrySend`]. The events
+	/// already in the channel are unaffected, so actions will still happen.
+	DropNewest,
+}
+
+impl Default for OverflowPolicy {
+	fn default() -> Self {
+		Self::Block
+	}
+}
+
+impl OverflowPolicy {
+	/// Sends an event according to the policy.
+	pub(crate) async fn send(
+		self,
+		events: &mpsc::Sender<Event>,
+		event: Event,
+		ctx: &'static str,
+	) -> Result<(), RuntimeError> {
+		match self {
+			Self::Block => events
+				.send(event)
+				.await
+				.map_err(|err| RuntimeError::EventChannelSend { ctx, err }),
+			Self::DropNewest => events
+				.try_send(event)
+				.map_err(|err| RuntimeError::EventChannelTrySend { ctx, err }),
+		}
+	}
 }
 
 impl Default for InitConfig {
 	fn default() -> Self {
 		Self {
-			error_handler: Box::new(()) as _,
+			error_handlers: Vec::new(),
+			error_handler_failure_limit: 10,
 			error_channel_size: 64,
 			event_channel_size: 1024,
+			saturation_warning: Some(Duration::from_secs(10)),
+			event_overflow: OverflowPolicy::default(),
+			force_quit_window: None,
+			log_level_handler: None,
 		}
 	}
 }
 
 impl InitConfig {
-	/// Set the runtime error handler.
+	/// Start building an `InitConfig` fluently.
+	///
+	/// ```
+	/// # use std::convert::Infallible;
+	/// # use watchexec::config::InitConfig;
+	/// let init = InitConfig::builder()
+	///     .event_channel_size(4096)
+	///     .on_error(|err| async move {
+	///         tracing::error!("{}", err);
+	///         Ok::<(), Infallible>(())
+	///     })
+	///     .build();
+	/// ```
+	pub fn builder() -> InitConfigBuilder {
+		InitConfigBuilder::default()
+	}
+
+	/// Set the runtime error handler, replacing any others.
+	///
+	/// See the [documentation on the field](InitConfig#structfield.error_handlers) for more details.
+	pub fn on_error(
+		&mut self,
+		handler: impl Handler<Arc<RuntimeError>> + Send + 'static,
+	) -> &mut Self {
+		self.error_handlers = vec![Box::new(handler) as _];
+		self
+	}
+
+	/// Add a runtime error handler, to be run after those already set.
+	///
+	/// ```
+	/// # use std::{convert::Infallible, io::stderr};
+	/// # use watchexec::{config::InitConfig, handler::PrintDisplay};
+	/// let mut init = InitConfig::default();
+	/// init.on_error(PrintDisplay(stderr()));
+	/// init.add_error_handler(|err| async move {
+	///     // e.g. count errors in a metrics sink
+	///     # drop(err);
+	///     Ok::<(), Infallible>(())
+	/// });
+	/// ```
+	///
+	/// See the [documentation on the field](InitConfig#structfield.error_handlers) for more details.
+	pub fn add_error_handler(
+		&mut self,
+		handler: impl Handler<Arc<RuntimeError>> + Send + 'static,
+	) -> &mut Self {
+		self.error_handlers.push(Box::new(handler) as _);
+		self
+	}
+
+	/// Set the runtime error handler to a synchronous closure, replacing any others.
+	///
+	/// This avoids the `async move` ceremony when all the handler does is e.g. print or log. Panics
+	/// within the closure are caught and handled as an error of the handler, i.e. the closure is
+	/// called again with a [`RuntimeError::HandlerPanic`] error.
+	///
+	/// ```
+	/// # use std::convert::Infallible;
+	/// # use watchexec::config::InitConfig;
+	/// let mut init = InitConfig::default();
+	/// init.on_error_sync(|err| {
+	///     eprintln!("{}", err);
+	///     Ok::<(), Infallible>(())
+	/// });
+	/// ```
+	pub fn on_error_sync<F, E>(&mut self, handler: F) -> &mut Self
+	where
+		F: Fn(Arc<RuntimeError>) -> Result<(), E> + Send + 'static,
+		E: std::error::Error + 'static,
+	{
+		self.error_handlers = vec![Box::new(CatchUnwindFnHandler(handler)) as _];
+		self
+	}
+
+	/// Set the handler to change the log level with.
+	///
+	/// See the [documentation on the field](InitConfig#structfield.log_level_handler) for more details.
+	pub fn on_log_level(
+		&mut self,
+		handler: impl Handler<LevelFilter> + Send + 'static,
+	) -> &mut Self {
+		self.log_level_handler = Some(Box::new(handler));
+		self
+	}
+
+	/// Set how many consecutive failures disable an error handler.
 	///
-	/// See the [documentation on the field](InitConfig#structfield.error_handler) for more details.
-	pub fn on_error(&mut self, handler: impl Handler<RuntimeError> + Send + 'static) -> &mut Self {
-		self.error_handler = Box::new(handler) as _;
+	/// See the [documentation on the field](InitConfig#structfield.error_handler_failure_limit) for more details.
+	pub fn error_handler_failure_limit(&mut self, limit: usize) -> &mut Self {
+		self.error_handler_failure_limit = limit;
 		self
 	}
 
@@ -222,13 +1939,289 @@ impl InitConfig {
 		self.event_channel_size = size;
 		self
 	}
+
+	/// Set how long the event channel may stay nearly full before a warning.
+	///
+	/// See the [documentation on the field](InitConfig#structfield.saturation_warning) for more details.
+	pub fn saturation_warning(&mut self, after: Option<Duration>) -> &mut Self {
+		self.saturation_warning = after;
+		self
+	}
+
+	/// Set what the event sources do when the event channel is full.
+	///
+	/// See the [documentation on the field](InitConfig#structfield.event_overflow) for more details.
+	pub fn event_overflow(&mut self, policy: OverflowPolicy) -> &mut Self {
+		self.event_overflow = policy;
+		self
+	}
+
+	/// Set the window within which a second interrupt force-quits.
+	///
+	/// See the [documentation on the field](InitConfig#structfield.force_quit_window) for more details.
+	pub fn force_quit_window(&mut self, window: Option<Duration>) -> &mut Self {
+		self.force_quit_window = window;
+		self
+	}
+}
+
+/// Builder for [`InitConfig`].
+///
+/// Obtain one with [`InitConfig::builder()`]. Options not set keep their default values.
+#[derive(Debug, Default)]
+pub struct InitConfigBuilder {
+	config: InitConfig,
+}
+
+impl InitConfigBuilder {
+	/// Set the runtime error handler, replacing any others.
+	///
+	/// See the [documentation on the field](InitConfig#structfield.error_handlers) for more details.
+	pub fn on_error(mut self, handler: impl Handler<Arc<RuntimeError>> + Send + 'static) -> Self {
+		self.config.on_error(handler);
+		self
+	}
+
+	/// Add a runtime error handler, to be run after those already set.
+	///
+	/// See [`InitConfig::add_error_handler()`] for more details.
+	pub fn add_error_handler(
+		mut self,
+		handler: impl Handler<Arc<RuntimeError>> + Send + 'static,
+	) -> Self {
+		self.config.add_error_handler(handler);
+		self
+	}
+
+	/// Set the runtime error handler to a synchronous closure.
+	///
+	/// See [`InitConfig::on_error_sync()`] for more details.
+	pub fn on_error_sync<F, E>(mut self, handler: F) -> Self
+	where
+		F: Fn(Arc<RuntimeError>) -> Result<(), E> + Send + 'static,
+		E: std::error::Error + 'static,
+	{
+		self.config.on_error_sync(handler);
+		self
+	}
+
+	/// Set the handler to change the log level with.
+	///
+	/// See the [documentation on the field](InitConfig#structfield.log_level_handler) for more details.
+	pub fn on_log_level(mut self, handler: impl Handler<LevelFilter> + Send + 'static) -> Self {
+		self.config.on_log_level(handler);
+		self
+	}
+
+	/// Set how many consecutive failures disable an error handler.
+	///
+	/// See the [documentation on the field](InitConfig#structfield.error_handler_failure_limit) for more details.
+	pub fn error_handler_failure_limit(mut self, limit: usize) -> Self {
+		self.config.error_handler_failure_limit(limit);
+		self
+	}
+
+	/// Set the buffer size of the channel which carries runtime errors.
+	///
+	/// See the [documentation on the field](InitConfig#structfield.error_channel_size) for more details.
+	pub fn error_channel_size(mut self, size: usize) -> Self {
+		self.config.error_channel_size(size);
+		self
+	}
+
+	/// Set the buffer size of the channel which carries events.
+	///
+	/// See the [documentation on the field](InitConfig#structfield.event_channel_size) for more details.
+	pub fn event_channel_size(mut self, size: usize) -> Self {
+		self.config.event_channel_size(size);
+		self
+	}
+
+	/// Set how long the event channel may stay nearly full before a warning.
+	///
+	/// See the [documentation on the field](InitConfig#structfield.saturation_warning) for more details.
+	pub fn saturation_warning(mut self, after: Option<Duration>) -> Self {
+		self.config.saturation_warning(after);
+		self
+	}
+
+	/// Set what the event sources do when the event channel is full.
+	///
+	/// See the [documentation on the field](InitConfig#structfield.event_overflow) for more details.
+	pub fn event_overflow(mut self, policy: OverflowPolicy) -> Self {
+		self.config.event_overflow(policy);
+		self
+	}
+
+	/// Set the window within which a second interrupt force-quits.
+	///
+	/// See the [documentation on the field](InitConfig#structfield.force_quit_window) for more details.
+	pub fn force_quit_window(mut self, window: Option<Duration>) -> Self {
+		self.config.force_quit_window(window);
+		self
+	}
+
+	/// Finish building the [`InitConfig`].
+	///
+	/// # Panics
+	///
+	/// Panics if either channel size is zero.
+	pub fn build(self) -> InitConfig {
+		assert!(
+			self.config.error_channel_size > 0,
+			"InitConfig: error_channel_size must be greater than zero"
+		);
+		assert!(
+			self.config.event_channel_size > 0,
+			"InitConfig: event_channel_size must be greater than zero"
+		);
+		self.config
+	}
 }
 
 impl fmt::Debug for InitConfig {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
 		f.debug_struct("InitConfig")
+			.field(
+				"error_handler_failure_limit",
+				&self.error_handler_failure_limit,
+			)
 			.field("error_channel_size", &self.error_channel_size)
 			.field("event_channel_size", &self.event_channel_size)
+			.field("saturation_warning", &self.saturation_warning)
+			.field("event_overflow", &self.event_overflow)
+			.field("force_quit_window", &self.force_quit_window)
+			.field("log_level_handler", &self.log_level_handler.is_some())
 			.finish_non_exhaustive()
 	}
 }
+
+#[cfg(test)]
+mod test {
+	use std::time::Duration;
+
+	use tokio::sync::mpsc::{self, error::TrySendError};
+
+	use super::{InitConfig, OverflowPolicy, RuntimeConfig, RuntimeConfigPatch};
+	use crate::{error::RuntimeError, event::Event, fs::KindCategory};
+
+	#[test]
+	fn diff_shows_only_changed_fields() {
+		let default = RuntimeConfig::default();
+		assert!(default.diff(&RuntimeConfig::default()).is_empty());
+		assert_eq!(default.diff(&default).to_string(), "none");
+
+		let mut config = RuntimeConfig::default();
+		config
+			.pathset(["src"])
+			.action_throttle(Duration::from_secs(1))
+			.enable_keyboard(true)
+			.on_action(|_: crate::action::Action| async { Ok::<(), RuntimeError>(()) });
+		assert_eq!(
+			config.diff(&default).to_string(),
+			r#"fs.pathset=[WatchedPath { path: "src", mode: Recursive }] action.throttle=1s enable_keyboard=true"#
+		);
+	}
+
+	#[test]
+	fn merge_applies_only_set_fields() {
+		let mut config = RuntimeConfig::default();
+		config.pathset(["src"]).command(["true"]);
+
+		let mut patch = RuntimeConfigPatch::default();
+		patch.action_throttle(Duration::from_secs(1));
+		config.merge(patch);
+
+		assert_eq!(config.fs.pathset, vec!["src".into()]);
+		assert_eq!(config.action.command, vec!["true".to_string()]);
+		assert_eq!(config.action.throttle, Duration::from_secs(1));
+	}
+
+	#[test]
+	fn merge_empty_patch_is_noop() {
+		let mut config = RuntimeConfig::default();
+		config.pathset(["src"]).command_grouped(false);
+		config.merge(RuntimeConfigPatch::default());
+
+		assert_eq!(config.fs.pathset, vec!["src".into()]);
+		assert!(!config.action.grouped);
+	}
+
+	#[test]
+	fn merge_unsets_options_and_extends_maps() {
+		let mut config = RuntimeConfig::default();
+		config
+			.command_timeout(Some(Duration::from_secs(5)))
+			.fs_debounce_kind(KindCategory::Create, Duration::from_secs(1));
+
+		let mut patch = RuntimeConfigPatch::default();
+		patch
+			.command_timeout(None)
+			.fs_debounce_kind(KindCategory::Remove, Duration::from_secs(2))
+			.dry_run(true);
+		config.merge(patch);
+
+		assert_eq!(config.action.command_timeout, None);
+		assert!(config.action.dry_run);
+		assert_eq!(
+			config.fs.debounce_kinds.get(&KindCategory::Create),
+			Some(&Duration::from_secs(1))
+		);
+		assert_eq!(
+			config.fs.debounce_kinds.get(&KindCategory::Remove),
+			Some(&Duration::from_secs(2))
+		);
+	}
+
+	#[test]
+	fn watch_git_repo_can_be_overridden() {
+		let mut config = RuntimeConfig::default();
+		config.watch_git_repo("/repo").watch_git_repo("/repo");
+		assert_eq!(config.fs.pathset, vec!["/repo".into()]);
+		assert_eq!(config.fs.ignore_globs, vec![".git", ".git/**"]);
+		assert!(config.fs.use_gitignore && config.fs.use_global_ignores);
+
+		config.use_global_ignores(false);
+		assert!(config.fs.use_gitignore && !config.fs.use_global_ignores);
+	}
+
+	#[test]
+	fn builder_sets_sizes() {
+		let init = InitConfig::builder()
+			.error_channel_size(8)
+			.event_channel_size(16)
+			.build();
+		assert_eq!(init.error_channel_size, 8);
+		assert_eq!(init.event_channel_size, 16);
+	}
+
+	#[test]
+	#[should_panic(expected = "event_channel_size must be greater than zero")]
+	fn builder_rejects_zero_event_channel() {
+		InitConfig::builder().event_channel_size(0).build();
+	}
+
+	#[tokio::test]
+	async fn drop_newest_does_not_wait() {
+		let (ev_s, mut ev_r) = mpsc::channel(1);
+		let first = Event::default();
+		OverflowPolicy::Block
+			.send(&ev_s, first.clone(), "test")
+			.await
+			.expect("channel has room");
+
+		let res = OverflowPolicy::DropNewest
+			.send(&ev_s, Event::default(), "test")
+			.await;
+		assert!(matches!(
+			res,
+			Err(RuntimeError::EventChannelTrySend {
+				err: TrySendError::Full(_),
+				..
+			})
+		));
+
+		assert_eq!(ev_r.recv().await, Some(first));
+		assert!(ev_r.try_recv().is_err());
+	}
+}
@@ -2,7 +2,7 @@ use miette::Diagnostic;
 use thiserror::Error;
 use tokio::{sync::mpsc, task::JoinError};
 
-use crate::event::Event;
+use crate::{event::Event, ShutdownReason};
 
 use super::RuntimeError;
 
@@ -12,9 +12,11 @@ use super::RuntimeError;
 #[diagnostic(url(docsrs))]
 pub enum CriticalError {
 	/// Pseudo-error used to signal a graceful exit.
-	#[error("this should never be printed (exit)")]
+	///
+	/// The main task resolves to the reason instead of this error.
+	#[error("this should never be printed (exit: {0:?})")]
 	#[diagnostic(code(watchexec::runtime::exit))]
-	Exit,
+	Exit(ShutdownReason),
 
 	/// For custom critical errors.
 	///
@@ -51,6 +53,20 @@ pub enum CriticalError {
 	#[diagnostic(code(watchexec::critical::main_task_join))]
 	MainTaskJoin(#[source] JoinError),
 
+	/// The configuration given to [`Watchexec::new()`][crate::Watchexec::new()] is invalid.
+	///
+	/// This is checked before anything is started; the message describes each problem found.
+	#[error("invalid configuration: {0}")]
+	#[diagnostic(code(watchexec::critical::config_invalid))]
+	ConfigInvalid(String),
+
+	/// The main task stopped with an error, as seen from [`Watchexec::wait()`][crate::Watchexec::wait()].
+	///
+	/// The error itself is returned by the main task handle; this holds its message.
+	#[error("main task stopped: {0}")]
+	#[diagnostic(code(watchexec::critical::main_task_stopped))]
+	MainTaskStopped(String),
+
 	/// Error received when a handler is missing on initialisation.
 	///
 	/// This is a **bug** and should be reported.
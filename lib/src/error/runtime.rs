@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, time::Duration};
 
 use miette::Diagnostic;
 use thiserror::Error;
@@ -38,7 +38,7 @@ pub enum RuntimeError {
 	},
 
 	/// Error received when creating a filesystem watcher fails.
-	#[error("{kind:?} watcher failed to instantiate: {err}")]
+	#[error("{kind:?} watcher failed to instantiate: {err}{}", hint(.cause))]
 	#[diagnostic(
 		code(watchexec::runtime::fs_watcher_error),
 		help("perhaps retry with the poll watcher")
@@ -47,25 +47,37 @@ pub enum RuntimeError {
 		/// The kind of watcher that failed to instantiate.
 		kind: Watcher,
 
+		/// The likely cause of the error.
+		cause: FsWatcherCause,
+
+		/// The OS error number, if the error came from the OS.
+		errno: Option<i32>,
+
 		/// The underlying error.
 		#[source]
 		err: notify::Error,
 	},
 
 	/// Error received when reading a filesystem event fails.
-	#[error("{kind:?} watcher received an event that we could not read: {err}")]
+	#[error("{kind:?} watcher received an event that we could not read: {err}{}", hint(.cause))]
 	#[diagnostic(code(watchexec::runtime::fs_watcher_event))]
 	FsWatcherEvent {
 		/// The kind of watcher that failed to read an event.
 		kind: Watcher,
 
+		/// The likely cause of the error.
+		cause: FsWatcherCause,
+
+		/// The OS error number, if the error came from the OS.
+		errno: Option<i32>,
+
 		/// The underlying error.
 		#[source]
 		err: notify::Error,
 	},
 
 	/// Error received when adding to the pathset for the filesystem watcher fails.
-	#[error("while adding {path:?} to the {kind:?} watcher: {err}")]
+	#[error("while adding {path:?} to the {kind:?} watcher: {err}{}", hint(.cause))]
 	#[diagnostic(code(watchexec::runtime::fs_watcher_path_add))]
 	FsWatcherPathAdd {
 		/// The path that was attempted to be added.
@@ -74,13 +86,19 @@ pub enum RuntimeError {
 		/// The kind of watcher that failed to add a path.
 		kind: Watcher,
 
+		/// The likely cause of the error.
+		cause: FsWatcherCause,
+
+		/// The OS error number, if the error came from the OS.
+		errno: Option<i32>,
+
 		/// The underlying error.
 		#[source]
 		err: notify::Error,
 	},
 
 	/// Error received when removing from the pathset for the filesystem watcher fails.
-	#[error("while removing {path:?} from the {kind:?} watcher: {err}")]
+	#[error("while removing {path:?} from the {kind:?} watcher: {err}{}", hint(.cause))]
 	#[diagnostic(code(watchexec::runtime::fs_watcher_path_remove))]
 	FsWatcherPathRemove {
 		/// The path that was attempted to be removed.
@@ -89,6 +107,12 @@ pub enum RuntimeError {
 		/// The kind of watcher that failed to remove a path.
 		kind: Watcher,
 
+		/// The likely cause of the error.
+		cause: FsWatcherCause,
+
+		/// The OS error number, if the error came from the OS.
+		errno: Option<i32>,
+
 		/// The underlying error.
 		#[source]
 		err: notify::Error,
@@ -127,6 +151,22 @@ pub enum RuntimeError {
 		err: mpsc::error::TrySendError<Event>,
 	},
 
+	/// Warning sent when the event channel has stayed nearly full for a while.
+	///
+	/// See [`InitConfig::saturation_warning`](crate::config::InitConfig#structfield.saturation_warning).
+	#[error("event channel has been nearly full ({depth} of {size}) for {duration:?}, consider raising the event_channel_size to {}", .size.saturating_mul(2))]
+	#[diagnostic(code(watchexec::runtime::channel_saturated))]
+	ChannelSaturated {
+		/// The size of the event channel.
+		size: usize,
+
+		/// How many events were in the channel when this was sent.
+		depth: usize,
+
+		/// How long the channel has been nearly full.
+		duration: Duration,
+	},
+
 	/// Error received when a [`Handler`][crate::handler::Handler] errors.
 	///
 	/// The error is completely opaque, having been flattened into a string at the error point.
@@ -142,6 +182,14 @@ pub enum RuntimeError {
 		err: String,
 	},
 
+	/// Error received when a synchronous [`Handler`][crate::handler::Handler] panics.
+	///
+	/// This is only produced by handlers which catch panics, such as the one installed by
+	/// [`InitConfig::on_error_sync()`][crate::config::InitConfig::on_error_sync()].
+	#[error("handler panicked: {0}")]
+	#[diagnostic(code(watchexec::runtime::handler_panic))]
+	HandlerPanic(String),
+
 	/// Error received when a [`Handler`][crate::handler::Handler] which has been passed a lock has kept that lock open after the handler has completed.
 	#[error("{0} handler returned while holding a lock alive")]
 	#[diagnostic(code(watchexec::runtime::handler_lock_held))]
@@ -152,6 +200,18 @@ pub enum RuntimeError {
 	#[diagnostic(code(watchexec::runtime::process))]
 	Process(#[source] std::io::Error),
 
+	/// Error received when the working directory of a command cannot be used.
+	#[error("cannot run command in {path:?}: {err}")]
+	#[diagnostic(code(watchexec::runtime::command_workdir))]
+	CommandWorkdir {
+		/// The working directory, resolved against the current directory.
+		path: PathBuf,
+
+		/// The underlying error.
+		#[source]
+		err: std::io::Error,
+	},
+
 	/// Error received when a process did not start correctly, or finished before we could even tell.
 	#[error("process was dead on arrival")]
 	#[diagnostic(code(watchexec::runtime::process_doa))]
@@ -183,6 +243,18 @@ pub enum RuntimeError {
 		// TODO: extract glob error into diagnostic
 	},
 
+	/// Error received when parsing a glob from the [fs working data](crate::fs::WorkingData) fails.
+	#[error("cannot parse fs glob '{glob}': {err}")]
+	#[diagnostic(code(watchexec::runtime::fs_glob))]
+	FsGlob {
+		/// The erroring glob.
+		glob: String,
+
+		/// The underlying error.
+		#[source]
+		err: globset::Error,
+	},
+
 	/// Error received when an [`IgnoreFile`](crate::ignore::files::IgnoreFile) cannot be read.
 	#[error("cannot read ignore '{file}': {err}")]
 	#[diagnostic(code(watchexec::runtime::ignore_file_read))]
@@ -215,8 +287,181 @@ pub enum RuntimeError {
 		err: Box<dyn std::error::Error + Send + Sync>,
 	},
 
+	/// The command kept being restarted, and backing off didn't help, so it wasn't started again.
+	///
+	/// See [`max_restarts`](crate::action::WorkingData#structfield.max_restarts).
+	#[error("command is in a crash loop, started {starts} times within {window:?}; not starting it again")]
+	#[diagnostic(code(watchexec::runtime::crash_loop))]
+	CrashLoop {
+		/// How many times the command was started within the window.
+		starts: usize,
+
+		/// The restart window.
+		window: Duration,
+	},
+
+	/// The log level can't be changed, as no handler was given for it.
+	///
+	/// See [`InitConfig::on_log_level()`](crate::config::InitConfig::on_log_level()).
+	#[error("cannot change the log level: no log level handler was set")]
+	#[diagnostic(code(watchexec::runtime::no_log_level_handler))]
+	NoLogLevelHandler,
+
 	/// A set of related [`RuntimeError`]s.
 	#[error("related: {0:?}")]
 	#[diagnostic(code(watchexec::runtime::set))]
 	Set(#[related] Vec<RuntimeError>),
 }
+
+/// The likely cause of a filesystem watcher error.
+///
+/// Some limits of the native watchers surface as cryptic errors: on Linux, running out of inotify
+/// watches is reported by the kernel as "no space left on device". This classifies the common
+/// causes so they can be shown with a hint of what to do, or acted upon.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FsWatcherCause {
+	/// The limit on watcher instances (or on open files) was reached.
+	///
+	/// On Linux, this is `fs.inotify.max_user_instances`.
+	InstanceLimit,
+
+	/// The limit on watched paths was reached.
+	///
+	/// On Linux, this is `fs.inotify.max_user_watches`.
+	WatchLimit,
+
+	/// The path does not exist.
+	PathNotFound,
+
+	/// The path may not be read.
+	PermissionDenied,
+
+	/// None of the above.
+	Other,
+}
+
+impl FsWatcherCause {
+	/// Classifies a notify error.
+	pub fn of(err: &notify::Error) -> Self {
+		match &err.kind {
+			notify::ErrorKind::MaxFilesWatch => Self::WatchLimit,
+			notify::ErrorKind::PathNotFound => Self::PathNotFound,
+			notify::ErrorKind::Io(err) => match err.kind() {
+				std::io::ErrorKind::NotFound => Self::PathNotFound,
+				std::io::ErrorKind::PermissionDenied => Self::PermissionDenied,
+				_ => err
+					.raw_os_error()
+					.and_then(Self::of_errno)
+					.unwrap_or(Self::Other),
+			},
+			_ => Self::Other,
+		}
+	}
+
+	#[cfg(target_os = "linux")]
+	fn of_errno(errno: i32) -> Option<Self> {
+		match errno {
+			libc::EMFILE => Some(Self::InstanceLimit),
+			libc::ENOSPC => Some(Self::WatchLimit),
+			_ => None,
+		}
+	}
+
+	#[cfg(not(target_os = "linux"))]
+	fn of_errno(_errno: i32) -> Option<Self> {
+		None
+	}
+
+	/// What to do about it, if there's anything to say.
+	pub fn hint(self) -> Option<&'static str> {
+		match self {
+			Self::InstanceLimit => Some(if cfg!(target_os = "linux") {
+				"too many watchers are open: raise the limit with `sysctl fs.inotify.max_user_instances`, or use the poll watcher"
+			} else {
+				"too many watchers are open: close some, or use the poll watcher"
+			}),
+			Self::WatchLimit => Some(if cfg!(target_os = "linux") {
+				"too many paths are watched: raise the limit with `sysctl fs.inotify.max_user_watches`, watch fewer paths, or use the poll watcher"
+			} else {
+				"too many paths are watched: watch fewer paths, or use the poll watcher"
+			}),
+			Self::PathNotFound => Some("the path does not exist"),
+			Self::PermissionDenied => Some("check the path may be read by this user"),
+			Self::Other => None,
+		}
+	}
+
+	/// Whether watching may succeed if tried again later.
+	///
+	/// This is the case of permission problems and other I/O errors, which can be transient (e.g.
+	/// on a network mount), and of a path not found which exists by the time this is checked. The
+	/// watcher limits won't go away by themselves.
+	pub fn may_recover(self) -> bool {
+		!matches!(self, Self::InstanceLimit | Self::WatchLimit)
+	}
+
+	/// The OS error number of a notify error, if it came from the OS.
+	pub fn errno(err: &notify::Error) -> Option<i32> {
+		match &err.kind {
+			notify::ErrorKind::Io(err) => err.raw_os_error(),
+			_ => None,
+		}
+	}
+}
+
+fn hint(cause: &FsWatcherCause) -> String {
+	cause
+		.hint()
+		.map(|hint| format!(" ({})", hint))
+		.unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn watch_limit_gets_a_hint() {
+		let err = notify::Error::new(notify::ErrorKind::MaxFilesWatch);
+		assert_eq!(FsWatcherCause::of(&err), FsWatcherCause::WatchLimit);
+		assert_eq!(FsWatcherCause::errno(&err), None);
+
+		let err = RuntimeError::FsWatcherPathAdd {
+			path: "/project".into(),
+			kind: Watcher::Native,
+			cause: FsWatcherCause::of(&err),
+			errno: None,
+			err,
+		};
+		assert!(err.to_string().contains("too many paths are watched"));
+	}
+
+	#[test]
+	fn io_errors_are_classified() {
+		let io = |err: std::io::Error| notify::Error::io(err);
+		assert_eq!(
+			FsWatcherCause::of(&io(std::io::ErrorKind::PermissionDenied.into())),
+			FsWatcherCause::PermissionDenied
+		);
+		assert_eq!(
+			FsWatcherCause::of(&io(std::io::ErrorKind::NotFound.into())),
+			FsWatcherCause::PathNotFound
+		);
+		assert_eq!(
+			FsWatcherCause::of(&io(std::io::ErrorKind::Other.into())),
+			FsWatcherCause::Other
+		);
+
+		#[cfg(target_os = "linux")]
+		{
+			let err = io(std::io::Error::from_raw_os_error(libc::ENOSPC));
+			assert_eq!(FsWatcherCause::of(&err), FsWatcherCause::WatchLimit);
+			assert_eq!(FsWatcherCause::errno(&err), Some(libc::ENOSPC));
+			assert_eq!(
+				FsWatcherCause::of(&io(std::io::Error::from_raw_os_error(libc::EMFILE))),
+				FsWatcherCause::InstanceLimit
+			);
+		}
+	}
+}
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, time::Duration};
 
 use ignore::gitignore::Gitignore;
 use miette::Diagnostic;
@@ -21,12 +21,30 @@ pub enum ReconfigError {
 	/// Error received when the action processor cannot be updated.
 	#[error("reconfig: action watch: {0}")]
 	#[diagnostic(code(watchexec::reconfig::action_watch))]
-	ActionWatch(#[from] watch::error::SendError<action::WorkingData>),
+	ActionWatch(Box<SendError<action::WorkingData>>),
 
 	/// Error received when the fs event source cannot be updated.
 	#[error("reconfig: fs watch: {0}")]
 	#[diagnostic(code(watchexec::reconfig::fs_watch))]
-	FsWatch(#[from] watch::error::SendError<fs::WorkingData>),
+	FsWatch(Box<SendError<fs::WorkingData>>),
+
+	/// Error received when the timer event source cannot be updated.
+	#[error("reconfig: timer watch: {0}")]
+	#[diagnostic(code(watchexec::reconfig::timer_watch))]
+	TimerWatch(#[from] watch::error::SendError<Option<Duration>>),
+}
+
+// the working data can be large, so it's boxed to keep the error small
+impl From<SendError<action::WorkingData>> for ReconfigError {
+	fn from(err: SendError<action::WorkingData>) -> Self {
+		Self::ActionWatch(Box::new(err))
+	}
+}
+
+impl From<SendError<fs::WorkingData>> for ReconfigError {
+	fn from(err: SendError<fs::WorkingData>) -> Self {
+		Self::FsWatch(Box::new(err))
+	}
 }
 
 /// Error when parsing a glob pattern from string.
@@ -12,11 +12,17 @@ use std::{
 	num::{NonZeroI32, NonZeroI64},
 	path::{Path, PathBuf},
 	process::ExitStatus,
+	time::Duration,
 };
 
 use filekind::FileEventKind;
+#[cfg(feature = "serde")]
+use serde_crate::{Deserialize, Serialize};
 
-use crate::signal::{process::SubSignal, source::MainSignal};
+use crate::{
+	action::Outcome,
+	signal::{process::SubSignal, source::MainSignal},
+};
 
 /// Re-export of the Notify file event types.
 pub mod filekind {
@@ -27,7 +33,20 @@ pub mod filekind {
 }
 
 /// An event, as far as watchexec cares about.
+///
+/// [`Event::default()`] has no tags nor metadata: such an empty event by-passes filtering in the
+/// action worker, which makes it useful to trigger an action manually.
+///
+/// With the `serde` feature, events (and all the types they contain) can be serialized and
+/// deserialized. In JSON, tags are objects with a `kind` and a `value`, e.g.
+/// `{"kind":"source","value":"filesystem"}`, and paths are strings when they are valid Unicode,
+/// or `{"bytes":[...]}` (on Unix) or `{"wide":[...]}` (on Windows) otherwise.
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(
+	feature = "serde",
+	derive(Serialize, Deserialize),
+	serde(crate = "serde_crate")
+)]
 pub struct Event {
 	/// Structured, classified information which can be used to filter or classify the event.
 	pub tags: Vec<Tag>,
@@ -38,11 +57,27 @@ pub struct Event {
 
 /// Something which can be used to filter or qualify an event.
 #[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(
+	feature = "serde",
+	derive(Serialize, Deserialize),
+	serde(
+		crate = "serde_crate",
+		rename_all = "kebab-case",
+		tag = "kind",
+		content = "value"
+	)
+)]
 #[non_exhaustive]
 pub enum Tag {
 	/// The event is about a path or file in the filesystem.
 	Path {
 		/// Path to the file or directory.
+		///
+		/// Paths are kept as [`PathBuf`]s from the watcher to the command, so ones which aren't
+		/// valid Unicode make it through unchanged to filterers, to the command's environment (see
+		/// [`summarise_events_to_env()`](crate::paths::summarise_events_to_env())), and through
+		/// serialisation. Only the [`Display`](std::fmt::Display) of events is lossy.
+		#[cfg_attr(feature = "serde", serde(with = "serde_path"))]
 		path: PathBuf,
 
 		/// Optional file type, if known.
@@ -50,8 +85,34 @@ pub enum Tag {
 	},
 
 	/// Kind of a filesystem event (create, remove, modify, etc).
+	#[cfg_attr(feature = "serde", serde(with = "serde_notify::kind"))]
 	FileEventKind(FileEventKind),
 
+	/// The event is about a path being renamed, with both its old and new paths.
+	///
+	/// This is alongside the event's [`Path`](Tag::Path) tags. How much rename information is
+	/// available depends on the backend:
+	///
+	/// - inotify (Linux) reports both sides of renames within the watched paths, which are paired
+	///   up by their cookie. A path renamed into or out of the watched paths only has one side,
+	///   so doesn't get this tag.
+	/// - On Windows, the two sides are reported one after the other, and paired up when they
+	///   arrive within a short window.
+	/// - FSEvents (macOS) and kqueue only report that a path was renamed, without the other side,
+	///   and the poll watcher sees renames as a removal and a creation: events from these never
+	///   have this tag.
+	///
+	/// The old path doesn't exist anymore, so it's canonicalised through its parent directory.
+	Rename {
+		/// The path before the rename.
+		#[cfg_attr(feature = "serde", serde(with = "serde_path"))]
+		from: PathBuf,
+
+		/// The path after the rename.
+		#[cfg_attr(feature = "serde", serde(with = "serde_path"))]
+		to: PathBuf,
+	},
+
 	/// The general source of the event.
 	Source(Source),
 
@@ -62,7 +123,94 @@ pub enum Tag {
 	Signal(MainSignal),
 
 	/// The event is about the subprocess ending.
-	ProcessCompletion(Option<ProcessEnd>),
+	///
+	/// This carries the end status of the process, if known, and how long it ran for. Completion
+	/// events issued by Watchexec also have a [`Process`](Tag::Process) tag with the ID of the
+	/// command.
+	ProcessCompletion(Option<ProcessEnd>, Duration),
+
+	/// The subprocess ran for longer than its timeout.
+	///
+	/// This carries the timeout. It's issued in its own event (with a [`Process`](Tag::Process)
+	/// tag) when the timeout expires, and also added to the completion event of the process, as
+	/// it was then stopped. See
+	/// [`command_timeout`](crate::action::WorkingData#structfield.command_timeout).
+	ProcessTimeout(Duration),
+
+	/// The event is about the lifecycle of the filesystem watcher itself.
+	FileSystem(FsEventKind),
+
+	/// The event is about a key being pressed, as read by the [keyboard][crate::keyboard] source.
+	Keyboard(char),
+
+	/// The event is a line of output from the command.
+	///
+	/// These are issued when [`capture_output`](crate::action::WorkingData#structfield.capture_output)
+	/// is enabled, alongside a [`Process`](Tag::Process) tag with the ID of the command.
+	Output {
+		/// Which output stream of the command the line is from.
+		stream: OutputStream,
+
+		/// The line, without its terminator.
+		///
+		/// Output which isn't valid UTF-8 is converted lossily.
+		line: String,
+	},
+
+	/// The outcome an action would have applied, had it not been a
+	/// [dry run](crate::action::WorkingData#structfield.dry_run).
+	///
+	/// Outcomes can't be serialized (they may hold callbacks), so events with this tag can't be
+	/// either: this tag is skipped by serde, and serializing it errors.
+	#[cfg_attr(feature = "serde", serde(skip))]
+	WouldHaveRun(Outcome),
+
+	/// The command is being started after this delay, as it seems to be in a crash loop.
+	///
+	/// See [`max_restarts`](crate::action::WorkingData#structfield.max_restarts).
+	RestartBackoff(Duration),
+
+	/// The path of the event as reached through a followed symlink.
+	///
+	/// Event paths are canonicalised, so [`Path`](Tag::Path) tags have the path the symlink
+	/// resolves to. When [`follow_symlinks`](crate::fs::WorkingData#structfield.follow_symlinks) is
+	/// enabled, events about paths behind a symlinked directory also get this tag, with the path
+	/// on the symlink's side, alongside the [`Path`](Tag::Path) tag.
+	SymlinkPath {
+		/// Path through the symlink.
+		#[cfg_attr(feature = "serde", serde(with = "serde_path"))]
+		path: PathBuf,
+	},
+
+	/// The event was emitted by the [timer][crate::timer], which fires every interval.
+	///
+	/// This carries the interval. Timer events also have a [`Source::Time`] tag.
+	Timer(Duration),
+
+	/// The event from the watcher backend this filesystem event was made from.
+	///
+	/// This is only attached when [`raw_events`](crate::fs::WorkingData#structfield.raw_events) is
+	/// enabled, for handlers which need more than the normalised tags. When events are coalesced
+	/// (by the debounce, or while paused), the merged event has one of these for each. The paths
+	/// are as the backend reported them, before canonicalisation and filtering, so may include
+	/// paths which were then filtered out of the [`Path`](Tag::Path) tags.
+	///
+	/// What's in there depends on the backend, through [notify](notify::Event):
+	///
+	/// - inotify (Linux) gives the most precise [kind](FileEventKind) (accesses, closes after
+	///   writing, both halves of renames…), and the inotify cookie of rename halves as the
+	///   `tracker` attribute. The inotify mask itself isn't kept by notify.
+	/// - On Windows, kinds are as precise as `ReadDirectoryChangesW` actions, and rename halves have
+	///   no tracker.
+	/// - FSEvents (macOS) kinds come from its flags, with the `info` attribute saying what kind of
+	///   item the path is, and the `flag` attribute asking for a rescan when events were dropped.
+	/// - kqueue and the poll watcher only give coarse kinds, and no attributes.
+	/// - [Custom](crate::fs::Watcher::Custom) backends give whatever they emit.
+	///
+	/// When serialized, the `source` attribute of the event is kept, but it's lost when
+	/// deserializing, as notify doesn't allow setting it.
+	#[cfg_attr(feature = "serde", serde(with = "serde_notify::event"))]
+	RawEvent(Box<notify::Event>),
 }
 
 impl Tag {
@@ -71,19 +219,83 @@ impl Tag {
 		match self {
 			Tag::Path { .. } => "Path",
 			Tag::FileEventKind(_) => "FileEventKind",
+			Tag::Rename { .. } => "Rename",
 			Tag::Source(_) => "Source",
 			Tag::Process(_) => "Process",
 			Tag::Signal(_) => "Signal",
-			Tag::ProcessCompletion(_) => "ProcessCompletion",
+			Tag::ProcessCompletion(..) => "ProcessCompletion",
+			Tag::ProcessTimeout(_) => "ProcessTimeout",
+			Tag::FileSystem(_) => "FileSystem",
+			Tag::Keyboard(_) => "Keyboard",
+			Tag::Output { .. } => "Output",
+			Tag::WouldHaveRun(_) => "WouldHaveRun",
+			Tag::RestartBackoff(_) => "RestartBackoff",
+			Tag::SymlinkPath { .. } => "SymlinkPath",
+			Tag::Timer(_) => "Timer",
+			Tag::RawEvent(_) => "RawEvent",
 		}
 	}
 }
 
+/// An output stream of a command, for [`Tag::Output`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(
+	feature = "serde",
+	derive(Serialize, Deserialize),
+	serde(crate = "serde_crate", rename_all = "kebab-case")
+)]
+pub enum OutputStream {
+	/// The standard output.
+	Stdout,
+
+	/// The standard error.
+	Stderr,
+}
+
+impl fmt::Display for OutputStream {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Stdout => write!(f, "stdout"),
+			Self::Stderr => write!(f, "stderr"),
+		}
+	}
+}
+
+/// A lifecycle event of the filesystem watcher.
+///
+/// These are emitted by the [fs][crate::fs] worker about the watcher itself, rather than about any
+/// particular file. Events carrying this tag by-pass filters.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(
+	feature = "serde",
+	derive(Serialize, Deserialize),
+	serde(crate = "serde_crate", rename_all = "kebab-case")
+)]
+#[non_exhaustive]
+pub enum FsEventKind {
+	/// The watcher was dropped and re-created.
+	///
+	/// Changes that happened between the old watcher going away and the new one being set up may
+	/// have been missed, so it may be necessary to re-scan the watched paths.
+	WatcherReinit,
+
+	/// A path which could not be watched is now watched, after some retries.
+	///
+	/// The path is in the [`Path`](Tag::Path) tag of the event. See
+	/// [`WorkingData::watch_retries`](crate::fs::WorkingData#structfield.watch_retries).
+	WatchRecovered,
+}
+
 /// The type of a file.
 ///
 /// This is a simplification of the [`std::fs::FileType`] type, which is not constructable and may
 /// differ on different platforms.
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(
+	feature = "serde",
+	derive(Serialize, Deserialize),
+	serde(crate = "serde_crate", rename_all = "kebab-case")
+)]
 pub enum FileType {
 	/// A regular file.
 	File,
@@ -138,6 +350,16 @@ impl fmt::Display for FileType {
 /// portability, we use `i64`. On all platforms, the "success" value is zero, so we special-case
 /// that as a variant and use `NonZeroI*` to niche the other values.
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(
+	feature = "serde",
+	derive(Serialize, Deserialize),
+	serde(
+		crate = "serde_crate",
+		rename_all = "kebab-case",
+		tag = "kind",
+		content = "value"
+	)
+)]
 pub enum ProcessEnd {
 	/// The process ended successfully, with exit status = 0.
 	Success,
@@ -218,6 +440,11 @@ impl From<ExitStatus> for ProcessEnd {
 ///
 /// This is set by the event source. Note that not all of these are currently used.
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(
+	feature = "serde",
+	derive(Serialize, Deserialize),
+	serde(crate = "serde_crate", rename_all = "kebab-case")
+)]
 #[non_exhaustive]
 pub enum Source {
 	/// Event comes from a file change.
@@ -256,7 +483,425 @@ impl fmt::Display for Source {
 	}
 }
 
+/// Serde representation of paths which survives non-Unicode paths.
+#[cfg(feature = "serde")]
+mod serde_path {
+	use std::path::{Path, PathBuf};
+
+	use serde_crate::{Deserialize, Deserializer, Serialize, Serializer};
+
+	#[derive(Deserialize, Serialize)]
+	#[serde(crate = "serde_crate", untagged)]
+	enum Repr {
+		Str(String),
+		#[cfg(unix)]
+		Bytes {
+			bytes: Vec<u8>,
+		},
+		#[cfg(windows)]
+		Wide {
+			wide: Vec<u16>,
+		},
+	}
+
+	pub fn serialize<S: Serializer>(path: &Path, ser: S) -> Result<S::Ok, S::Error> {
+		let repr = match path.to_str() {
+			Some(s) => Repr::Str(s.to_owned()),
+			#[cfg(unix)]
+			None => {
+				use std::os::unix::ffi::OsStrExt;
+				Repr::Bytes {
+					bytes: path.as_os_str().as_bytes().to_vec(),
+				}
+			}
+			#[cfg(windows)]
+			None => {
+				use std::os::windows::ffi::OsStrExt;
+				Repr::Wide {
+					wide: path.as_os_str().encode_wide().collect(),
+				}
+			}
+			#[cfg(not(any(unix, windows)))]
+			None => Repr::Str(path.to_string_lossy().into_owned()),
+		};
+
+		repr.serialize(ser)
+	}
+
+	pub fn deserialize<'de, D: Deserializer<'de>>(de: D) -> Result<PathBuf, D::Error> {
+		Ok(match Repr::deserialize(de)? {
+			Repr::Str(s) => s.into(),
+			#[cfg(unix)]
+			Repr::Bytes { bytes } => {
+				use std::{ffi::OsString, os::unix::ffi::OsStringExt};
+				OsString::from_vec(bytes).into()
+			}
+			#[cfg(windows)]
+			Repr::Wide { wide } => {
+				use std::{ffi::OsString, os::windows::ffi::OsStringExt};
+				OsString::from_wide(&wide).into()
+			}
+		})
+	}
+}
+
+/// Serde representation of notify's types.
+///
+/// These mirror the types in [`filekind`] and [`notify::Event`], with the same representation as
+/// notify's own `serde` feature, so that feature isn't needed (and a change in notify doesn't
+/// change ours).
+#[cfg(feature = "serde")]
+mod serde_notify {
+	use std::path::PathBuf;
+
+	use serde_crate::{Deserialize, Deserializer, Serialize, Serializer};
+
+	/// The mirrored types.
+	mod nk {
+		pub use notify::event::Flag;
+
+		pub use crate::event::filekind::*;
+	}
+
+	/// Defines a mirror of a notify enum which only has unit variants, and the conversions.
+	macro_rules! unit_mirror {
+		($(#[$attr:meta])* $name:ident { $($variant:ident),+ $(,)? }) => {
+			#[derive(Deserialize, Serialize)]
+			#[serde(crate = "serde_crate")]
+			$(#[$attr])*
+			enum $name {
+				$($variant),+
+			}
+
+			impl From<&nk::$name> for $name {
+				fn from(kind: &nk::$name) -> Self {
+					match kind {
+						$(nk::$name::$variant => Self::$variant),+
+					}
+				}
+			}
+
+			impl From<$name> for nk::$name {
+				fn from(kind: $name) -> Self {
+					match kind {
+						$($name::$variant => Self::$variant),+
+					}
+				}
+			}
+		};
+	}
+
+	#[derive(Deserialize, Serialize)]
+	#[serde(crate = "serde_crate", rename_all = "kebab-case")]
+	enum FileEventKind {
+		Any,
+		Access(AccessKind),
+		Create(CreateKind),
+		Modify(ModifyKind),
+		Remove(RemoveKind),
+		Other,
+	}
+
+	impl From<&nk::FileEventKind> for FileEventKind {
+		fn from(kind: &nk::FileEventKind) -> Self {
+			match kind {
+				nk::FileEventKind::Any => Self::Any,
+				nk::FileEventKind::Access(kind) => Self::Access(kind.into()),
+				nk::FileEventKind::Create(kind) => Self::Create(kind.into()),
+				nk::FileEventKind::Modify(kind) => Self::Modify(kind.into()),
+				nk::FileEventKind::Remove(kind) => Self::Remove(kind.into()),
+				nk::FileEventKind::Other => Self::Other,
+			}
+		}
+	}
+
+	impl From<FileEventKind> for nk::FileEventKind {
+		fn from(kind: FileEventKind) -> Self {
+			match kind {
+				FileEventKind::Any => Self::Any,
+				FileEventKind::Access(kind) => Self::Access(kind.into()),
+				FileEventKind::Create(kind) => Self::Create(kind.into()),
+				FileEventKind::Modify(kind) => Self::Modify(kind.into()),
+				FileEventKind::Remove(kind) => Self::Remove(kind.into()),
+				FileEventKind::Other => Self::Other,
+			}
+		}
+	}
+
+	#[derive(Deserialize, Serialize)]
+	#[serde(
+		crate = "serde_crate",
+		rename_all = "kebab-case",
+		tag = "kind",
+		content = "mode"
+	)]
+	enum AccessKind {
+		Any,
+		Read,
+		Open(AccessMode),
+		Close(AccessMode),
+		Other,
+	}
+
+	impl From<&nk::AccessKind> for AccessKind {
+		fn from(kind: &nk::AccessKind) -> Self {
+			match kind {
+				nk::AccessKind::Any => Self::Any,
+				nk::AccessKind::Read => Self::Read,
+				nk::AccessKind::Open(mode) => Self::Open(mode.into()),
+				nk::AccessKind::Close(mode) => Self::Close(mode.into()),
+				nk::AccessKind::Other => Self::Other,
+			}
+		}
+	}
+
+	impl From<AccessKind> for nk::AccessKind {
+		fn from(kind: AccessKind) -> Self {
+			match kind {
+				AccessKind::Any => Self::Any,
+				AccessKind::Read => Self::Read,
+				AccessKind::Open(mode) => Self::Open(mode.into()),
+				AccessKind::Close(mode) => Self::Close(mode.into()),
+				AccessKind::Other => Self::Other,
+			}
+		}
+	}
+
+	#[derive(Deserialize, Serialize)]
+	#[serde(
+		crate = "serde_crate",
+		rename_all = "kebab-case",
+		tag = "kind",
+		content = "mode"
+	)]
+	enum ModifyKind {
+		Any,
+		Data(DataChange),
+		Metadata(MetadataKind),
+		#[serde(rename = "rename")]
+		Name(RenameMode),
+		Other,
+	}
+
+	impl From<&nk::ModifyKind> for ModifyKind {
+		fn from(kind: &nk::ModifyKind) -> Self {
+			match kind {
+				nk::ModifyKind::Any => Self::Any,
+				nk::ModifyKind::Data(change) => Self::Data(change.into()),
+				nk::ModifyKind::Metadata(kind) => Self::Metadata(kind.into()),
+				nk::ModifyKind::Name(mode) => Self::Name(mode.into()),
+				nk::ModifyKind::Other => Self::Other,
+			}
+		}
+	}
+
+	impl From<ModifyKind> for nk::ModifyKind {
+		fn from(kind: ModifyKind) -> Self {
+			match kind {
+				ModifyKind::Any => Self::Any,
+				ModifyKind::Data(change) => Self::Data(change.into()),
+				ModifyKind::Metadata(kind) => Self::Metadata(kind.into()),
+				ModifyKind::Name(mode) => Self::Name(mode.into()),
+				ModifyKind::Other => Self::Other,
+			}
+		}
+	}
+
+	unit_mirror!(
+		#[serde(rename_all = "kebab-case")]
+		AccessMode {
+			Any,
+			Execute,
+			Read,
+			Write,
+			Other
+		}
+	);
+
+	unit_mirror!(
+		#[serde(rename_all = "kebab-case", tag = "kind")]
+		CreateKind {
+			Any,
+			File,
+			Folder,
+			Other
+		}
+	);
+
+	unit_mirror!(
+		#[serde(rename_all = "kebab-case")]
+		DataChange {
+			Any,
+			Size,
+			Content,
+			Other
+		}
+	);
+
+	unit_mirror!(
+		#[serde(rename_all = "kebab-case")]
+		MetadataKind {
+			Any,
+			AccessTime,
+			WriteTime,
+			Permissions,
+			Ownership,
+			Extended,
+			Other
+		}
+	);
+
+	unit_mirror!(
+		#[serde(rename_all = "kebab-case")]
+		RenameMode {
+			Any,
+			To,
+			From,
+			Both,
+			Other
+		}
+	);
+
+	unit_mirror!(
+		#[serde(rename_all = "kebab-case", tag = "kind")]
+		RemoveKind {
+			Any,
+			File,
+			Folder,
+			Other
+		}
+	);
+
+	unit_mirror!(Flag { Rescan });
+
+	#[derive(Deserialize, Serialize)]
+	#[serde(crate = "serde_crate")]
+	struct Event {
+		#[serde(rename = "type")]
+		kind: FileEventKind,
+		paths: Vec<PathBuf>,
+		#[serde(default, flatten)]
+		attrs: Attributes,
+	}
+
+	/// The attributes notify serialises: the process ID isn't.
+	#[derive(Default, Deserialize, Serialize)]
+	#[serde(crate = "serde_crate")]
+	struct Attributes {
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		tracker: Option<usize>,
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		flag: Option<Flag>,
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		info: Option<String>,
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		source: Option<String>,
+	}
+
+	pub mod kind {
+		use super::*;
+
+		pub fn serialize<S: Serializer>(
+			kind: &nk::FileEventKind,
+			ser: S,
+		) -> Result<S::Ok, S::Error> {
+			FileEventKind::from(kind).serialize(ser)
+		}
+
+		pub fn deserialize<'de, D: Deserializer<'de>>(
+			de: D,
+		) -> Result<nk::FileEventKind, D::Error> {
+			FileEventKind::deserialize(de).map(Into::into)
+		}
+	}
+
+	/// The `source` attribute can't be set on a notify event from outside notify, so it's lost
+	/// when deserializing.
+	pub mod event {
+		use super::*;
+
+		pub fn serialize<S: Serializer>(event: &notify::Event, ser: S) -> Result<S::Ok, S::Error> {
+			Event {
+				kind: (&event.kind).into(),
+				paths: event.paths.clone(),
+				attrs: Attributes {
+					tracker: event.attrs.tracker(),
+					flag: event.attrs.flag().as_ref().map(Into::into),
+					info: event.attrs.info().map(ToOwned::to_owned),
+					source: event.attrs.source().map(ToOwned::to_owned),
+				},
+			}
+			.serialize(ser)
+		}
+
+		pub fn deserialize<'de, D: Deserializer<'de>>(
+			de: D,
+		) -> Result<Box<notify::Event>, D::Error> {
+			let Event { kind, paths, attrs } = Event::deserialize(de)?;
+			let mut event = notify::Event::new(kind.into());
+			event.paths = paths;
+			if let Some(tracker) = attrs.tracker {
+				event.attrs.set_tracker(tracker);
+			}
+			if let Some(flag) = attrs.flag {
+				event.attrs.set_flag(flag.into());
+			}
+			if let Some(info) = attrs.info {
+				event.attrs.set_info(&info);
+			}
+			Ok(Box::new(event))
+		}
+	}
+}
+
 impl Event {
+	/// Creates a filesystem event about a path.
+	///
+	/// This is tagged like the events from the filesystem watcher: with a
+	/// [`Filesystem`](Source::Filesystem) source, the `kind`, and the path. Unlike the watcher,
+	/// this doesn't look at the filesystem, so the path is used as given and the file type is left
+	/// unknown. As filterers match against the paths in the pathset, which are absolute, the path
+	/// should usually be absolute too.
+	///
+	/// ```
+	/// # use watchexec::event::{Event, filekind::{CreateKind, FileEventKind}};
+	/// let event = Event::from_path("/src/main.rs", FileEventKind::Create(CreateKind::File));
+	/// assert_eq!(event.paths().next().unwrap().0, std::path::Path::new("/src/main.rs"));
+	/// ```
+	pub fn from_path(path: impl Into<PathBuf>, kind: FileEventKind) -> Self {
+		Self {
+			tags: vec![
+				Tag::Source(Source::Filesystem),
+				Tag::FileEventKind(kind),
+				Tag::Path {
+					path: path.into(),
+					file_type: None,
+				},
+			],
+			metadata: HashMap::new(),
+		}
+	}
+
+	/// Creates an event for a signal received by the main process.
+	///
+	/// This is tagged like the events from the signal source: with the signal, and a
+	/// [`Keyboard`](Source::Keyboard) source for [`Interrupt`](MainSignal::Interrupt) (as that's
+	/// usually Ctrl-C), or an [`Os`](Source::Os) one otherwise. Such an event is handled like a
+	/// real signal, e.g. by the [`signal_map`](crate::action::WorkingData#structfield.signal_map).
+	pub fn signal(sig: MainSignal) -> Self {
+		Self {
+			tags: vec![
+				Tag::Source(if sig == MainSignal::Interrupt {
+					Source::Keyboard
+				} else {
+					Source::Os
+				}),
+				Tag::Signal(sig),
+			],
+			metadata: HashMap::new(),
+		}
+	}
+
 	/// Returns true if the event has an Internal source tag.
 	pub fn is_internal(&self) -> bool {
 		self.tags
@@ -264,6 +909,13 @@ impl Event {
 			.any(|tag| matches!(tag, Tag::Source(Source::Internal)))
 	}
 
+	/// Returns true if the event has a [`FileSystem`][Tag::FileSystem] watcher lifecycle tag.
+	pub fn is_fs_lifecycle(&self) -> bool {
+		self.tags
+			.iter()
+			.any(|tag| matches!(tag, Tag::FileSystem(_)))
+	}
+
 	/// Returns true if the event has no tags.
 	pub fn is_empty(&self) -> bool {
 		self.tags.is_empty()
@@ -277,6 +929,14 @@ impl Event {
 		})
 	}
 
+	/// Return all sources in the event's tags.
+	pub fn sources(&self) -> impl Iterator<Item = Source> + '_ {
+		self.tags.iter().filter_map(|p| match p {
+			Tag::Source(s) => Some(*s),
+			_ => None,
+		})
+	}
+
 	/// Return all signals in the event's tags.
 	pub fn signals(&self) -> impl Iterator<Item = MainSignal> + '_ {
 		self.tags.iter().filter_map(|p| match p {
@@ -285,13 +945,75 @@ impl Event {
 		})
 	}
 
+	/// Return the backend events this event was made from.
+	///
+	/// These are only there with [`raw_events`](crate::fs::WorkingData#structfield.raw_events)
+	/// enabled: see [`Tag::RawEvent`].
+	pub fn raw_events(&self) -> impl Iterator<Item = &notify::Event> + '_ {
+		self.tags.iter().filter_map(|p| match p {
+			Tag::RawEvent(event) => Some(event.as_ref()),
+			_ => None,
+		})
+	}
+
+	/// Return all keys pressed in the event's tags.
+	pub fn keys(&self) -> impl Iterator<Item = char> + '_ {
+		self.tags.iter().filter_map(|p| match p {
+			Tag::Keyboard(c) => Some(*c),
+			_ => None,
+		})
+	}
+
+	/// Return all lines of command output in the event's tags.
+	pub fn output_lines(&self) -> impl Iterator<Item = (OutputStream, &str)> {
+		self.tags.iter().filter_map(|p| match p {
+			Tag::Output { stream, line } => Some((*stream, line.as_str())),
+			_ => None,
+		})
+	}
+
+	/// Returns true if the event is command output (and nothing else but its source and process).
+	pub fn is_output(&self) -> bool {
+		self.output_lines().next().is_some()
+			&& self.tags.iter().all(|tag| {
+				matches!(
+					tag,
+					Tag::Output { .. } | Tag::Process(_) | Tag::Source(Source::Internal)
+				)
+			})
+	}
+
 	/// Return all process completions in the event's tags.
 	pub fn completions(&self) -> impl Iterator<Item = Option<ProcessEnd>> + '_ {
 		self.tags.iter().filter_map(|p| match p {
-			Tag::ProcessCompletion(s) => Some(*s),
+			Tag::ProcessCompletion(s, _) => Some(*s),
 			_ => None,
 		})
 	}
+
+	/// Returns true if the event is a process completion (and nothing else but its source,
+	/// process, and timeout).
+	pub fn is_completion(&self) -> bool {
+		self.completions().next().is_some()
+			&& self.tags.iter().all(|tag| {
+				matches!(
+					tag,
+					Tag::ProcessCompletion(..)
+						| Tag::ProcessTimeout(_)
+						| Tag::Process(_) | Tag::Source(Source::Internal)
+				)
+			})
+	}
+
+	/// Returns true if the event is about a process running for longer than its timeout.
+	///
+	/// This is the case both for the event issued when the timeout expires, and for the completion
+	/// event of the process.
+	pub fn is_timeout(&self) -> bool {
+		self.tags
+			.iter()
+			.any(|tag| matches!(tag, Tag::ProcessTimeout(_)))
+	}
 }
 
 impl fmt::Display for Event {
@@ -306,11 +1028,25 @@ impl fmt::Display for Event {
 					}
 				}
 				Tag::FileEventKind(kind) => write!(f, " kind={:?}", kind)?,
+				Tag::Rename { from, to } => {
+					write!(f, " rename={}->{}", from.display(), to.display())?
+				}
 				Tag::Source(s) => write!(f, " source={:?}", s)?,
 				Tag::Process(p) => write!(f, " process={}", p)?,
 				Tag::Signal(s) => write!(f, " signal={:?}", s)?,
-				Tag::ProcessCompletion(None) => write!(f, " command-completed")?,
-				Tag::ProcessCompletion(Some(c)) => write!(f, " command-completed({:?})", c)?,
+				Tag::ProcessCompletion(None, d) => write!(f, " command-completed after={:?}", d)?,
+				Tag::ProcessCompletion(Some(c), d) => {
+					write!(f, " command-completed({:?}) after={:?}", c, d)?
+				}
+				Tag::ProcessTimeout(d) => write!(f, " command-timeout={:?}", d)?,
+				Tag::FileSystem(k) => write!(f, " fs={:?}", k)?,
+				Tag::Keyboard(c) => write!(f, " key={:?}", c)?,
+				Tag::Output { stream, line } => write!(f, " {}={:?}", stream, line)?,
+				Tag::WouldHaveRun(outcome) => write!(f, " would-have-run={:?}", outcome)?,
+				Tag::RestartBackoff(delay) => write!(f, " restart-backoff={:?}", delay)?,
+				Tag::SymlinkPath { path } => write!(f, " symlink-path={}", path.display())?,
+				Tag::Timer(period) => write!(f, " timer={:?}", period)?,
+				Tag::RawEvent(event) => write!(f, " raw={:?}", event.kind)?,
 			}
 		}
 
@@ -1,4 +1,4 @@
-//! The `Filterer` trait, two implementations, and some helper functions.
+//! The `Filterer` trait, three implementations, and some helper functions.
 
 use std::sync::Arc;
 
@@ -9,6 +9,7 @@ use crate::{
 	event::Event,
 };
 
+pub mod expr;
 pub mod globset;
 pub mod tagged;
 
@@ -0,0 +1,310 @@
+//! Simple filter expressions, in the style of watchexec's `--filter` and `--ignore` options.
+//!
+//! A [`Filter`] either selects events ([`FilterKind::Filter`]) or rejects them
+//! ([`FilterKind::Ignore`]), by path glob and/or by file event kind. A list of them is compiled
+//! into an [`ExprFilterer`], which passes an event if any applicable filter selects it (or none
+//! applies) and no applicable ignore rejects it.
+//!
+//! These are usually given to the action worker as
+//! [`action::WorkingData::filters`](crate::action::WorkingData#structfield.filters), but the
+//! compiled form is also a [`Filterer`] on its own.
+
+use std::{path::Path, str::FromStr};
+
+use globset::{GlobBuilder, GlobMatcher};
+use tracing::{trace, trace_span};
+
+use crate::{
+	error::{GlobParseError, RuntimeError},
+	event::{Event, Tag},
+	filter::Filterer,
+};
+
+/// Whether a [`Filter`] selects or rejects the events it matches.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FilterKind {
+	/// Events matching any filter pass, events matching none of them fail.
+	Filter,
+
+	/// Events matching any ignore fail, whatever the other filters say.
+	Ignore,
+}
+
+impl Default for FilterKind {
+	fn default() -> Self {
+		Self::Filter
+	}
+}
+
+/// A filter expression.
+///
+/// A filter _matches_ an event if any of its paths matches the
+/// [`path_glob`](Filter#structfield.path_glob), and any of its file event kinds matches the
+/// [`on_kind`](Filter#structfield.on_kind) glob; a filter with neither matches everything. If
+/// [`negate`](Filter#structfield.negate) is set, that is reversed.
+///
+/// A filter only _applies_ to events which have what it looks at: one with a path glob is skipped
+/// for events without paths (like signals), and one with a kind glob for events without file event
+/// kinds. So filtering on `*.rs` doesn't drop a process completion.
+///
+/// Filters can be parsed from a string of the form:
+///
+/// ```text
+/// [-][!]{path glob}[ on {kind glob}]
+/// ```
+///
+/// where `-` makes it an ignore, and `!` negates it. Either the path glob or the kind part can be
+/// left out. For example:
+///
+/// ```
+/// # use watchexec::filter::expr::{Filter, FilterKind};
+/// let filter: Filter = "-!*.rs on modify".parse().unwrap();
+/// assert_eq!(filter.kind, FilterKind::Ignore);
+/// assert!(filter.negate);
+/// assert_eq!(filter.path_glob.as_deref(), Some("*.rs"));
+/// assert_eq!(filter.on_kind.as_deref(), Some("modify"));
+/// ```
+///
+/// A leading `-` or `!` which is part of the glob can be escaped with a backslash.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Filter {
+	/// The glob to match paths against, if any.
+	///
+	/// A glob without a `/` is matched against the file name only, so `*.rs` matches Rust files
+	/// anywhere. Otherwise, it's matched against the whole path, and a relative glob can match
+	/// from any directory, so `target/**` matches within any `target` directory.
+	pub path_glob: Option<String>,
+
+	/// Whether this selects or rejects the events it matches.
+	pub kind: FilterKind,
+
+	/// Whether to reverse the match.
+	pub negate: bool,
+
+	/// The glob to match file event kinds against, if any.
+	///
+	/// This is matched, case-insensitively, against the [`FileEventKind`]'s debug format (like
+	/// `Modify(Data(Content))`), and its top-level kind alone (like `Modify`).
+	///
+	/// [`FileEventKind`]: crate::event::filekind::FileEventKind
+	pub on_kind: Option<String>,
+}
+
+impl Filter {
+	/// A filter selecting paths matching the glob.
+	pub fn select(glob: impl Into<String>) -> Self {
+		Self {
+			path_glob: Some(glob.into()),
+			..Self::default()
+		}
+	}
+
+	/// A filter rejecting paths matching the glob.
+	pub fn ignore(glob: impl Into<String>) -> Self {
+		Self {
+			path_glob: Some(glob.into()),
+			kind: FilterKind::Ignore,
+			..Self::default()
+		}
+	}
+}
+
+impl FromStr for Filter {
+	type Err = GlobParseError;
+
+	/// Parses a filter from its string form (see the [type documentation](Filter)).
+	///
+	/// This checks that the globs compile.
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let (kind, rest) = match s.strip_prefix('-') {
+			Some(rest) => (FilterKind::Ignore, rest),
+			None => (FilterKind::Filter, s),
+		};
+		let (negate, rest) = match rest.strip_prefix('!') {
+			Some(rest) => (true, rest),
+			None => (false, rest),
+		};
+		let (path, on_kind) = match rest.strip_prefix("on ") {
+			Some(on_kind) => ("", Some(on_kind)),
+			None => match rest.rsplit_once(" on ") {
+				Some((path, on_kind)) => (path, Some(on_kind)),
+				None => (rest, None),
+			},
+		};
+
+		let path_glob = match path.trim() {
+			"" => None,
+			glob => Some(match glob.strip_prefix('\\') {
+				Some(escaped) if escaped.starts_with(['-', '!']) => escaped.to_owned(),
+				_ => glob.to_owned(),
+			}),
+		};
+		let on_kind = match on_kind.map(str::trim) {
+			Some("") => return Err(GlobParseError::new(s, "missing kind after `on`")),
+			on_kind => on_kind.map(ToOwned::to_owned),
+		};
+		if path_glob.is_none() && on_kind.is_none() {
+			return Err(GlobParseError::new(s, "filter has nothing to match"));
+		}
+
+		let filter = Self {
+			path_glob,
+			kind,
+			negate,
+			on_kind,
+		};
+		CompiledFilter::new(&filter)?;
+		Ok(filter)
+	}
+}
+
+/// A [`Filter`] with its globs compiled.
+#[derive(Debug)]
+struct CompiledFilter {
+	/// The path glob, and whether it matches the file name rather than the whole path.
+	path: Option<(GlobMatcher, bool)>,
+	kind: FilterKind,
+	negate: bool,
+	on_kind: Option<GlobMatcher>,
+}
+
+impl CompiledFilter {
+	fn new(filter: &Filter) -> Result<Self, GlobParseError> {
+		// errors are reported against the glob as given, not as compiled
+		fn compile(
+			glob: &str,
+			pattern: &str,
+			case_insensitive: bool,
+		) -> Result<GlobMatcher, GlobParseError> {
+			GlobBuilder::new(pattern)
+				.literal_separator(true)
+				.case_insensitive(case_insensitive)
+				.build()
+				.map(|glob| glob.compile_matcher())
+				.map_err(|err| GlobParseError::new(glob, &err.kind().to_string()))
+		}
+
+		let path = filter
+			.path_glob
+			.as_deref()
+			.map(|glob| {
+				if !glob.contains('/') {
+					compile(glob, glob, false).map(|m| (m, true))
+				} else if glob.starts_with('/') || glob.starts_with("**") {
+					compile(glob, glob, false).map(|m| (m, false))
+				} else {
+					compile(glob, &format!("**/{}", glob), false).map(|m| (m, false))
+				}
+			})
+			.transpose()?;
+		let on_kind = filter
+			.on_kind
+			.as_deref()
+			.map(|glob| compile(glob, glob, true))
+			.transpose()?;
+
+		Ok(Self {
+			path,
+			kind: filter.kind,
+			negate: filter.negate,
+			on_kind,
+		})
+	}
+
+	/// Whether this matches the event, or `None` if it doesn't apply to it.
+	fn matches(&self, paths: &[&Path], kinds: &[String]) -> Option<bool> {
+		let path = match &self.path {
+			None => true,
+			Some(_) if paths.is_empty() => return None,
+			Some((glob, true)) => paths
+				.iter()
+				.any(|path| path.file_name().map_or(false, |name| glob.is_match(name))),
+			Some((glob, false)) => paths.iter().any(|path| glob.is_match(path)),
+		};
+		let kind = match &self.on_kind {
+			None => true,
+			Some(_) if kinds.is_empty() => return None,
+			Some(glob) => kinds.iter().any(|kind| {
+				glob.is_match(kind) || glob.is_match(kind.split('(').next().unwrap_or(kind))
+			}),
+		};
+		Some((path && kind) != self.negate)
+	}
+}
+
+/// A filterer for a list of [filter expressions](Filter), compiled.
+///
+/// An event passes if no applicable ignore matches it, and either no filter applies to it or any
+/// applicable filter matches it. That is, filters are OR-ed together, ignores are AND-ed as NOTs,
+/// and the two are AND-ed.
+#[derive(Debug)]
+pub struct ExprFilterer {
+	source: Vec<Filter>,
+	compiled: Vec<CompiledFilter>,
+}
+
+impl ExprFilterer {
+	/// Compiles a list of filters.
+	pub fn new(filters: Vec<Filter>) -> Result<Self, GlobParseError> {
+		let compiled = filters
+			.iter()
+			.map(CompiledFilter::new)
+			.collect::<Result<_, _>>()?;
+		Ok(Self {
+			source: filters,
+			compiled,
+		})
+	}
+
+	/// The filters this was compiled from.
+	pub fn filters(&self) -> &[Filter] {
+		&self.source
+	}
+
+	/// Checks an event against the filters.
+	pub fn check(&self, event: &Event) -> bool {
+		let _span = trace_span!("expr_filterer_check").entered();
+		if self.compiled.is_empty() {
+			return true;
+		}
+
+		let paths: Vec<&Path> = event.paths().map(|(path, _)| path).collect();
+		let kinds: Vec<String> = event
+			.tags
+			.iter()
+			.filter_map(|tag| match tag {
+				Tag::FileEventKind(kind) => Some(format!("{:?}", kind)),
+				_ => None,
+			})
+			.collect();
+
+		let mut applied = false;
+		let mut selected = false;
+		for (filter, compiled) in self.source.iter().zip(&self.compiled) {
+			match (compiled.kind, compiled.matches(&paths, &kinds)) {
+				(_, None) => {}
+				(FilterKind::Ignore, Some(true)) => {
+					trace!(?filter, "ignore matched (fail)");
+					return false;
+				}
+				(FilterKind::Ignore, Some(false)) => {}
+				(FilterKind::Filter, Some(matched)) => {
+					applied = true;
+					selected |= matched;
+				}
+			}
+		}
+
+		trace!(%applied, %selected, "checked filters");
+		!applied || selected
+	}
+}
+
+impl Filterer for ExprFilterer {
+	/// Filter an event.
+	///
+	/// This implementation never errors.
+	fn check_event(&self, event: &Event) -> Result<bool, RuntimeError> {
+		Ok(self.check(event))
+	}
+}
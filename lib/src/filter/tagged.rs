@@ -379,6 +379,7 @@ impl TaggedFilterer {
 					trace!("path glob match with match_tag is already handled");
 					return Ok(None);
 				} else {
+					// non-glob operators need a string: non-Unicode paths are matched lossily
 					filter.matches(resolved.to_string_lossy())
 				}
 			}
@@ -408,7 +409,7 @@ impl TaggedFilterer {
 					|| filter.matches(format!("SIG{}", text))?
 					|| filter.matches(int.to_string())?)
 			}
-			(Tag::ProcessCompletion(ope), Matcher::ProcessCompletion) => match ope {
+			(Tag::ProcessCompletion(ope, _), Matcher::ProcessCompletion) => match ope {
 				None => filter.matches("_"),
 				Some(ProcessEnd::Success) => filter.matches("success"),
 				Some(ProcessEnd::ExitError(int)) => filter.matches(format!("error({})", int)),
@@ -754,7 +755,17 @@ impl Matcher {
 			Tag::Source(_) => &[Matcher::Source],
 			Tag::Process(_) => &[Matcher::Process],
 			Tag::Signal(_) => &[Matcher::Signal],
-			Tag::ProcessCompletion(_) => &[Matcher::ProcessCompletion],
+			Tag::ProcessCompletion(..) => &[Matcher::ProcessCompletion],
+			Tag::FileSystem(_)
+			| Tag::Rename { .. }
+			| Tag::ProcessTimeout(_)
+			| Tag::Keyboard(_)
+			| Tag::Output { .. }
+			| Tag::WouldHaveRun(_)
+			| Tag::RestartBackoff(_)
+			| Tag::SymlinkPath { .. }
+			| Tag::Timer(_)
+			| Tag::RawEvent(_) => &[],
 		}
 	}
 }
@@ -1,38 +1,73 @@
 //! Event source for changes to files and directories.
 
 use std::{
-	collections::{HashMap, HashSet},
+	collections::{BTreeMap, HashMap, HashSet, VecDeque},
+	fmt,
 	fs::metadata,
 	mem::take,
 	path::{Path, PathBuf},
-	sync::{Arc, Mutex},
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Arc, Mutex,
+	},
 	time::Duration,
 };
 
+use globset::{Glob, GlobSet, GlobSetBuilder};
 use notify::Watcher as _;
-use tokio::sync::{mpsc, watch};
+use tokio::{
+	select,
+	sync::{
+		mpsc::{self, error::SendError, Permit},
+		watch,
+	},
+	time::{sleep_until, Instant},
+};
 use tracing::{debug, error, trace, warn};
 
 use crate::{
-	error::{CriticalError, RuntimeError},
-	event::{Event, Source, Tag},
+	config::OverflowPolicy,
+	error::{CriticalError, FsWatcherCause, RuntimeError},
+	event::{
+		filekind::{CreateKind, FileEventKind, ModifyKind, RemoveKind, RenameMode},
+		Event, FileType, FsEventKind, Source, Tag,
+	},
+	ignore::{self, IgnoreFile, IgnoreFilterer},
+	metrics::{self, Counters},
+	project::ProjectType,
 };
 
+#[doc(inline)]
+pub use backend::{Backend, BackendWatcher, FsBackend, RawEvents};
+
+mod backend;
+
 /// What kind of filesystem watcher to use.
 ///
-/// For now only native and poll watchers are supported. In the future there may be additional
-/// watchers available on some platforms.
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// Native and poll watchers are provided by notify. Other sources of events can be plugged in as
+/// an [`FsBackend`], used with [`Custom`](Watcher::Custom).
+#[derive(Clone, Debug, PartialEq, Eq)]
 #[non_exhaustive]
 pub enum Watcher {
 	/// The Notify-recommended watcher on the platform.
 	///
 	/// For platforms Notify supports, that's a [native implementation][notify::RecommendedWatcher],
 	/// for others it's polling with a default interval.
+	///
+	/// If the native watcher fails to instantiate (for example because the inotify instance limit
+	/// has been reached), the error is reported and the fs worker falls back to a poll watcher with
+	/// a one second interval.
 	Native,
 
 	/// Notify’s [poll watcher][notify::PollWatcher] with a custom interval.
 	Poll(Duration),
+
+	/// A custom [`FsBackend`].
+	///
+	/// Changing the backend (to one which isn't a clone of it) re-creates the watcher, like
+	/// changing the kind of watcher does. Set it with
+	/// [`RuntimeConfig::fs_backend()`](crate::config::RuntimeConfig::fs_backend()).
+	Custom(Backend),
 }
 
 impl Default for Watcher {
@@ -43,64 +78,623 @@ impl Default for Watcher {
 
 impl Watcher {
 	fn create(
-		self,
+		&self,
 		f: impl notify::EventHandler,
 	) -> Result<Box<dyn notify::Watcher + Send>, RuntimeError> {
 		match self {
 			Self::Native => notify::RecommendedWatcher::new(f).map(|w| Box::new(w) as _),
-			Self::Poll(delay) => notify::PollWatcher::with_delay(Arc::new(Mutex::new(f)), delay)
+			Self::Poll(delay) => notify::PollWatcher::with_delay(Arc::new(Mutex::new(f)), *delay)
 				.map(|w| Box::new(w) as _),
+			Self::Custom(backend) => backend::Adapter::create(backend, f).map(|w| Box::new(w) as _),
 		}
-		.map_err(|err| RuntimeError::FsWatcherCreate { kind: self, err })
+		.map_err(|err| RuntimeError::FsWatcherCreate {
+			kind: self.clone(),
+			cause: FsWatcherCause::of(&err),
+			errno: FsWatcherCause::errno(&err),
+			err,
+		})
 	}
 }
 
 /// The configuration of the [fs][self] worker.
 ///
 /// This is marked non-exhaustive so new configuration can be added without breaking.
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 #[non_exhaustive]
 pub struct WorkingData {
 	/// The set of paths to be watched.
+	///
+	/// Paths which cannot be watched (for example because they don't exist yet) are each reported
+	/// as a [`RuntimeError::FsWatcherPathAdd`] identifying the path, and the others are watched
+	/// regardless. For a path which doesn't exist, its nearest existing ancestor is watched in its
+	/// stead, and the path itself is watched once it's created (events for anything else in that
+	/// ancestor are dropped). Changes made inside the path before it's watched, e.g. right as it's
+	/// created, may be missed. Such paths are retried whenever the working data is sent again, so once they
+	/// exist, reconfiguring with a different pathset, or with the same one using
+	/// [`Watchexec::reconfigure_force()`](crate::Watchexec::reconfigure_force()), starts watching
+	/// them.
+	///
+	/// Each path is watched recursively, non-recursively, or as a single file, according to its
+	/// [`WatchedPath`] mode.
+	///
+	/// A new pathset is applied to the running watcher: only the added paths are watched, and the
+	/// removed ones unwatched (a path whose mode changed is both), so events for the other paths
+	/// keep coming throughout. That's also the case when the pathset is emptied and filled again.
+	/// The watcher is only re-created when the [`watcher`](WorkingData#structfield.watcher)
+	/// changes.
 	pub pathset: Vec<WatchedPath>,
 
 	/// The kind of watcher to be used.
 	pub watcher: Watcher,
+
+	/// How long to wait for filesystem activity to settle before emitting an event.
+	///
+	/// Events from the watcher are buffered, and a single coalesced [`Event`] containing all their
+	/// tags is emitted once no new event has arrived for this long. Each new event resets the
+	/// window, but only up to ten windows after the first event of the batch: files which are
+	/// written to continuously (like logs) still trigger, at most that often. Events still buffered
+	/// when the worker stops are flushed then. A zero duration disables the buffering. Defaults to
+	/// 50ms.
+	///
+	/// This can be overridden for some kinds of events with
+	/// [`debounce_kinds`](WorkingData#structfield.debounce_kinds) and
+	/// [`immediate_kinds`](WorkingData#structfield.immediate_kinds).
+	pub debounce: Duration,
+
+	/// Debounce windows for some [categories](KindCategory) of events, instead of the
+	/// [`debounce`](WorkingData#structfield.debounce).
+	///
+	/// Events with different windows are buffered separately: each window is only reset by events
+	/// which use it, and when it elapses only those events are coalesced and emitted. Events are
+	/// categorised by their [`FileEventKind`](crate::event::filekind::FileEventKind) tag, and
+	/// those without one use the default window.
+	pub debounce_kinds: HashMap<KindCategory, Duration>,
+
+	/// Categories of events which by-pass the debounce entirely.
+	///
+	/// These are emitted as soon as they're seen, on their own, while other events keep being
+	/// buffered. This is the same as a zero window in
+	/// [`debounce_kinds`](WorkingData#structfield.debounce_kinds), and takes precedence over it.
+	/// For example, with [`Remove`](KindCategory::Remove) and [`Rename`](KindCategory::Rename)
+	/// here, an editor which deletes and re-creates a file on save will trigger on the deletion
+	/// straight away, and not wait for the modifications to settle.
+	pub immediate_kinds: HashSet<KindCategory>,
+
+	/// How long to stay quiet for after the watcher is created.
+	///
+	/// Right after watching starts, editors and build tools may still be writing files, which
+	/// would trigger straight away. Events arriving within this period after the watcher is
+	/// created, including when it's re-created on reconfigure (e.g. to change the
+	/// [`watcher`](WorkingData#structfield.watcher) kind), are dropped. Unlike the
+	/// [`debounce`](WorkingData#structfield.debounce), this only happens once per watcher, and
+	/// nothing is emitted for the dropped events when it ends. Defaults to zero, which disables it.
+	pub settle: Duration,
+
+	/// Globs of paths to drop events for.
+	///
+	/// These are evaluated in the filesystem worker, before events reach the action worker. Globs
+	/// are matched against the full path of the event, and against the path relative to each
+	/// watched path it is under, or only against the path relative to the
+	/// [`project_root`](WorkingData#structfield.project_root) if that is set. Path tags matching
+	/// any of these globs are removed from events, and events left without any path are dropped
+	/// entirely. Invalid globs are reported as errors and otherwise ignored.
+	pub ignore_globs: Vec<String>,
+
+	/// Globs of paths to keep events for.
+	///
+	/// When non-empty, path tags which do not match any of these globs are removed from events, in
+	/// the same way as for [`ignore_globs`](WorkingData#structfield.ignore_globs). Ignore globs
+	/// take precedence.
+	pub watch_globs: Vec<String>,
+
+	/// The directory globs are rooted at.
+	///
+	/// When set, [`ignore_globs`](WorkingData#structfield.ignore_globs) and
+	/// [`watch_globs`](WorkingData#structfield.watch_globs) are matched only against paths made
+	/// relative to this directory, the way patterns in a `.gitignore` are rooted at the directory
+	/// the file is in, so `src/**/*.rs` means the same thing wherever the watched paths are. Paths
+	/// outside of it are handled according to
+	/// [`outside_root`](WorkingData#structfield.outside_root). The root is canonicalised (if it
+	/// exists) when the globs are compiled, like event paths are. Defaults to `None`.
+	pub project_root: Option<PathBuf>,
+
+	/// How globs treat paths outside the [`project_root`](WorkingData#structfield.project_root).
+	///
+	/// Does nothing if there's no project root. Defaults to [`OutsideRoot::NoMatch`].
+	pub outside_root: OutsideRoot,
+
+	/// Ignore files to drop events for.
+	///
+	/// These are read as gitignore files (including negations), each applying in the directory it
+	/// is in, and evaluated in the filesystem worker alongside the
+	/// [`ignore_globs`](WorkingData#structfield.ignore_globs). Files which cannot be read or
+	/// contain invalid patterns are reported as errors and otherwise ignored.
+	///
+	/// Ignore files are read whenever the working data is sent: edits to them take effect on the
+	/// next [`Watchexec::reconfigure_force()`](crate::Watchexec::reconfigure_force()), or the next
+	/// reconfigure which changes the filesystem configuration.
+	pub ignore_files: Vec<PathBuf>,
+
+	/// Whether to discover ignore files within the watched paths.
+	///
+	/// When enabled, each watched directory is searched (recursively) for ignore files as by
+	/// [`ignore::from_origin()`](crate::ignore::from_origin()), that is `.gitignore`, `.ignore`, and
+	/// the like, and these are used in the same way as
+	/// [`ignore_files`](WorkingData#structfield.ignore_files). Nested files only apply within
+	/// their own directory. Defaults to `false`.
+	pub use_gitignore: bool,
+
+	/// Whether to use the global ignore files.
+	///
+	/// When enabled, the ignore files found by
+	/// [`ignore::from_environment()`](crate::ignore::from_environment()), such as git's
+	/// `core.excludesFile`, are used in the same way as
+	/// [`ignore_files`](WorkingData#structfield.ignore_files), and apply within every watched
+	/// directory. Where a repository's own `.git/config` sets `core.excludesFile` and
+	/// [`use_gitignore`](WorkingData#structfield.use_gitignore) is enabled, that replaces the
+	/// global git ignore file there. Defaults to `false`.
+	pub use_global_ignores: bool,
+
+	/// Whether to emit events for directories.
+	///
+	/// Directories change when the files inside them do, so handlers which only care about files
+	/// may prefer not to see them. When disabled, path tags for directories are removed from
+	/// events, in the same way as for [`ignore_globs`](WorkingData#structfield.ignore_globs), and
+	/// events left without any path are dropped.
+	///
+	/// Whether a path is a directory is known from the stat done when the event comes in. For
+	/// paths which are gone by then (removed or renamed away), the kind of the event is used
+	/// instead, if it says; otherwise the path is kept. Paths from a [custom](Watcher::Custom)
+	/// backend are never stat-ed, so only the event kind applies. Defaults to `true`.
+	pub watch_directories: bool,
+
+	/// Whether to follow symlinked directories.
+	///
+	/// When enabled, the recursively-watched directories are searched for symlinks to directories
+	/// whenever the working data is sent, and the directories these resolve to are added to the
+	/// watched paths (recursively), unless they're already within a watched directory. Entries of
+	/// the pathset which are themselves symlinks to directories are watched through the symlink
+	/// already, so they're only searched. Events about paths
+	/// behind them, which have the resolved path in their [`Path`](Tag::Path) tag, also get a
+	/// [`SymlinkPath`](Tag::SymlinkPath) tag with the path through the symlink.
+	///
+	/// Symlinks found within a followed directory are followed in turn, up to
+	/// [`SYMLINK_DEPTH_LIMIT`] levels deep, and directories already seen are not searched
+	/// again, so cyclic symlinks don't lead to infinite recursion. Symlinks which appear later are
+	/// only picked up on the next reconfigure. Defaults to `false`.
+	pub follow_symlinks: bool,
+
+	/// What to do with events while watching is [paused](crate::Watchexec::pause()).
+	///
+	/// See [`WhilePaused`] for the options. Defaults to [`WhilePaused::Coalesce`].
+	pub while_paused: WhilePaused,
+
+	/// Whether to attach the backend's event to each filesystem event, as a
+	/// [`Tag::RawEvent`](Tag::RawEvent).
+	///
+	/// This is for handlers which need details that normalisation loses: what's available depends
+	/// on the backend, see the tag's documentation. It's off by default, as it roughly doubles the
+	/// memory taken by each filesystem event. Defaults to `false`.
+	pub raw_events: bool,
+
+	/// How many times to retry watching a path which failed to be watched.
+	///
+	/// Some failures can pass: a directory on a network mount may be briefly unavailable, or its
+	/// permissions may be in the middle of being changed. When watching a path which exists fails
+	/// with an error which [may recover](crate::error::FsWatcherCause::may_recover()), it's retried
+	/// after [`WATCH_RETRY_DELAY`], then after twice as long every time, up to
+	/// [`WATCH_RETRY_MAX_DELAY`] between attempts. A warning is logged for each failed attempt,
+	/// and when the path is finally watched, an event is emitted with the
+	/// [`WatchRecovered`](FsEventKind::WatchRecovered) tag. The error is only reported once this
+	/// many retries have failed.
+	///
+	/// Paths which don't exist are waited for instead, see [`pathset`](#structfield.pathset).
+	/// Retries are started from scratch whenever the working data is sent. Set to `0` to disable.
+	/// Defaults to `5`.
+	pub watch_retries: u32,
+
+	/// Whether to drop write events of files whose content is the same as before.
+	///
+	/// Editors often write files out again without changing them, e.g. with a format-on-save which
+	/// had nothing to do. When enabled, the content of files in create and modify events (including
+	/// renames, but not metadata changes) is hashed, and compared to the hash from the last time
+	/// the file was seen: events for which every path is a file with unchanged content are
+	/// dropped. The first event for a file since this was enabled always goes through, as there's
+	/// nothing to compare to, and so does the first after it's removed.
+	///
+	/// This reads the files, in the filesystem worker: to bound the cost, files larger than
+	/// [`IDENTICAL_SIZE_LIMIT`] are not hashed (their events always go through), and the hashes
+	/// of only the [`IDENTICAL_CACHE_SIZE`] files seen latest are remembered. Defaults to `false`.
+	pub suppress_identical: bool,
+
+	/// Bumped by [`Watchexec`](crate::Watchexec) on every change, so it can tell when the worker
+	/// has applied it.
+	pub(crate) generation: u64,
+}
+
+impl WorkingData {
+	/// Describes mistakes in this configuration which would only show up in the worker.
+	pub(crate) fn problems(&self) -> Vec<String> {
+		let ignore = self.ignore_globs.iter().map(|glob| ("ignore_globs", glob));
+		let watch = self.watch_globs.iter().map(|glob| ("watch_globs", glob));
+		ignore
+			.chain(watch)
+			.filter_map(|(field, glob)| {
+				Glob::new(glob)
+					.err()
+					.map(|err| format!("{}: {}", field, err))
+			})
+			.collect()
+	}
+
+	/// The fields which differ from `other`, with their values in this configuration.
+	///
+	/// The generation is left out, as it's only meaningful within Watchexec.
+	pub(crate) fn diff(&self, other: &Self) -> Vec<(&'static str, String)> {
+		// destructured so new fields can't be forgotten here
+		let Self {
+			pathset,
+			watcher,
+			debounce,
+			debounce_kinds,
+			immediate_kinds,
+			settle,
+			ignore_globs,
+			watch_globs,
+			project_root,
+			outside_root,
+			ignore_files,
+			use_gitignore,
+			use_global_ignores,
+			watch_directories,
+			follow_symlinks,
+			while_paused,
+			raw_events,
+			watch_retries,
+			suppress_identical,
+			generation: _,
+		} = self;
+
+		let mut diff = Vec::new();
+		let mut field = |name: &'static str, same: bool, value: &dyn fmt::Debug| {
+			if !same {
+				diff.push((name, format!("{:?}", value)));
+			}
+		};
+		field("pathset", *pathset == other.pathset, pathset);
+		field("watcher", *watcher == other.watcher, watcher);
+		field("debounce", *debounce == other.debounce, debounce);
+		field(
+			"debounce_kinds",
+			*debounce_kinds == other.debounce_kinds,
+			debounce_kinds,
+		);
+		field(
+			"immediate_kinds",
+			*immediate_kinds == other.immediate_kinds,
+			immediate_kinds,
+		);
+		field("settle", *settle == other.settle, settle);
+		field(
+			"ignore_globs",
+			*ignore_globs == other.ignore_globs,
+			ignore_globs,
+		);
+		field(
+			"watch_globs",
+			*watch_globs == other.watch_globs,
+			watch_globs,
+		);
+		field(
+			"project_root",
+			*project_root == other.project_root,
+			project_root,
+		);
+		field(
+			"outside_root",
+			*outside_root == other.outside_root,
+			outside_root,
+		);
+		field(
+			"ignore_files",
+			*ignore_files == other.ignore_files,
+			ignore_files,
+		);
+		field(
+			"use_gitignore",
+			*use_gitignore == other.use_gitignore,
+			use_gitignore,
+		);
+		field(
+			"use_global_ignores",
+			*use_global_ignores == other.use_global_ignores,
+			use_global_ignores,
+		);
+		field(
+			"watch_directories",
+			*watch_directories == other.watch_directories,
+			watch_directories,
+		);
+		field(
+			"follow_symlinks",
+			*follow_symlinks == other.follow_symlinks,
+			follow_symlinks,
+		);
+		field(
+			"while_paused",
+			*while_paused == other.while_paused,
+			while_paused,
+		);
+		field("raw_events", *raw_events == other.raw_events, raw_events);
+		field(
+			"watch_retries",
+			*watch_retries == other.watch_retries,
+			watch_retries,
+		);
+		field(
+			"suppress_identical",
+			*suppress_identical == other.suppress_identical,
+			suppress_identical,
+		);
+		diff
+	}
+}
+
+impl Default for WorkingData {
+	fn default() -> Self {
+		Self {
+			pathset: Vec::new(),
+			watcher: Watcher::default(),
+			debounce: Duration::from_millis(50),
+			debounce_kinds: HashMap::new(),
+			immediate_kinds: HashSet::new(),
+			settle: Duration::ZERO,
+			ignore_globs: Vec::new(),
+			watch_globs: Vec::new(),
+			project_root: None,
+			outside_root: OutsideRoot::default(),
+			ignore_files: Vec::new(),
+			use_gitignore: false,
+			use_global_ignores: false,
+			watch_directories: true,
+			follow_symlinks: false,
+			while_paused: WhilePaused::default(),
+			raw_events: false,
+			watch_retries: 5,
+			suppress_identical: false,
+			generation: 0,
+		}
+	}
+}
+
+/// What the filesystem worker does with events while watching is paused.
+///
+/// See [`Watchexec::pause()`](crate::Watchexec::pause()).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum WhilePaused {
+	/// Drop the events.
+	Discard,
+
+	/// Merge the events into one, and emit it on resume.
+	///
+	/// The merged event has every distinct tag of the events (so each path once), and their
+	/// metadata. It by-passes the debounce. If nothing happened while paused, nothing is emitted.
+	Coalesce,
+}
+
+impl Default for WhilePaused {
+	fn default() -> Self {
+		Self::Coalesce
+	}
+}
+
+/// How globs treat paths outside the [project root](WorkingData#structfield.project_root).
+///
+/// This applies to each glob set separately: as ignore globs take precedence, with
+/// [`Match`](OutsideRoot::Match) and any ignore globs set, paths outside the root are dropped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum OutsideRoot {
+	/// Paths outside the root match every glob.
+	///
+	/// They're dropped by [ignore globs](WorkingData#structfield.ignore_globs), and kept by
+	/// [watch globs](WorkingData#structfield.watch_globs).
+	Match,
+
+	/// Paths outside the root match no glob.
+	///
+	/// They're kept by [ignore globs](WorkingData#structfield.ignore_globs), and dropped by
+	/// [watch globs](WorkingData#structfield.watch_globs).
+	NoMatch,
+}
+
+impl Default for OutsideRoot {
+	fn default() -> Self {
+		Self::NoMatch
+	}
+}
+
+/// The broad category of a filesystem event, for per-kind debouncing.
+///
+/// This is the top level of [`FileEventKind`](crate::event::filekind::FileEventKind), except that
+/// renames (which Notify has as a kind of modification) are their own category.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum KindCategory {
+	/// Access to a file or directory, e.g. opening or closing it.
+	Access,
+
+	/// Creation of a file or directory.
+	Create,
+
+	/// Modification of the contents or metadata of a file or directory, other than renaming.
+	Modify,
+
+	/// Renaming of a file or directory.
+	Rename,
+
+	/// Removal of a file or directory.
+	Remove,
+
+	/// Events of unknown or other kinds.
+	Other,
+}
+
+impl KindCategory {
+	/// The category of an event kind.
+	pub fn of(kind: &FileEventKind) -> Self {
+		match kind {
+			FileEventKind::Access(_) => Self::Access,
+			FileEventKind::Create(_) => Self::Create,
+			FileEventKind::Modify(ModifyKind::Name(_)) => Self::Rename,
+			FileEventKind::Modify(_) => Self::Modify,
+			FileEventKind::Remove(_) => Self::Remove,
+			FileEventKind::Any | FileEventKind::Other => Self::Other,
+		}
+	}
 }
 
 /// A path to watch.
 ///
-/// This is currently only a wrapper around a [`PathBuf`], but may be augmented in the future.
-#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub struct WatchedPath(PathBuf);
+/// Paths are watched recursively by default, that is, along with all their descendants if they're
+/// directories. Use [`WatchedPath::non_recursive()`] to only watch the path itself and, for a
+/// directory, its immediate children, which avoids walking a whole tree to watch a single file.
+///
+/// Watching a file directly stops working if it's replaced rather than written to, as is often done
+/// to update files atomically (writing to a temporary file, then renaming it over). Use
+/// [`WatchedPath::file()`] to watch a single file through its parent directory instead, which
+/// handles this.
+///
+/// Two `WatchedPath`s with the same path but different modes are different: changing the mode of
+/// a path in a reconfigure re-watches it.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct WatchedPath {
+	path: PathBuf,
+	mode: Mode,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum Mode {
+	Recursive,
+	NonRecursive,
+	File,
+}
+
+impl WatchedPath {
+	/// A path to watch recursively.
+	pub fn recursive(path: impl Into<PathBuf>) -> Self {
+		Self {
+			path: path.into(),
+			mode: Mode::Recursive,
+		}
+	}
+
+	/// A path to watch non-recursively.
+	pub fn non_recursive(path: impl Into<PathBuf>) -> Self {
+		Self {
+			path: path.into(),
+			mode: Mode::NonRecursive,
+		}
+	}
+
+	/// A single file to watch, even across replacements.
+	///
+	/// The parent directory is watched non-recursively, and events for its other entries are
+	/// dropped (unless they're also covered by another path in the pathset). This also means that
+	/// the file doesn't need to exist for the watch to succeed, only its parent directory.
+	pub fn file(path: impl Into<PathBuf>) -> Self {
+		Self {
+			path: path.into(),
+			mode: Mode::File,
+		}
+	}
+
+	/// Whether this path is watched recursively.
+	pub fn is_recursive(&self) -> bool {
+		self.mode == Mode::Recursive
+	}
+
+	/// Whether this is a single file watched through its parent directory.
+	pub fn is_file(&self) -> bool {
+		self.mode == Mode::File
+	}
+
+	/// The path to give to the watcher.
+	fn target(&self) -> &Path {
+		match (self.mode, self.path.parent()) {
+			(Mode::File, Some(parent)) if parent != Path::new("") => parent,
+			(Mode::File, _) => Path::new("."),
+			_ => &self.path,
+		}
+	}
+
+	fn recursive_mode(&self) -> notify::RecursiveMode {
+		if self.mode == Mode::Recursive {
+			notify::RecursiveMode::Recursive
+		} else {
+			notify::RecursiveMode::NonRecursive
+		}
+	}
+
+	/// Whether an event for this (canonical) path is covered by this (canonicalised) entry.
+	fn covers(&self, path: &Path) -> bool {
+		match self.mode {
+			Mode::Recursive => path.starts_with(&self.path),
+			Mode::NonRecursive => path == self.path || path.parent() == Some(&self.path),
+			Mode::File => path == self.path,
+		}
+	}
+
+	fn canonicalised(&self) -> Self {
+		let path = match self.mode {
+			Mode::File => match (dunce::canonicalize(self.target()), self.path.file_name()) {
+				(Ok(parent), Some(name)) => parent.join(name),
+				_ => self.path.clone(),
+			},
+			_ => dunce::canonicalize(&self.path).unwrap_or_else(|_| self.path.clone()),
+		};
+
+		Self {
+			path,
+			mode: self.mode,
+		}
+	}
+}
+
+impl Default for WatchedPath {
+	fn default() -> Self {
+		Self::recursive(PathBuf::new())
+	}
+}
 
 impl From<PathBuf> for WatchedPath {
 	fn from(path: PathBuf) -> Self {
-		Self(path)
+		Self::recursive(path)
 	}
 }
 
 impl From<&str> for WatchedPath {
 	fn from(path: &str) -> Self {
-		Self(path.into())
+		Self::recursive(path)
 	}
 }
 
 impl From<&Path> for WatchedPath {
 	fn from(path: &Path) -> Self {
-		Self(path.into())
+		Self::recursive(path)
 	}
 }
 
 impl From<WatchedPath> for PathBuf {
 	fn from(path: WatchedPath) -> Self {
-		path.0
+		path.path
 	}
 }
 
 impl AsRef<Path> for WatchedPath {
 	fn as_ref(&self) -> &Path {
-		self.0.as_ref()
+		self.path.as_ref()
 	}
 }
 
@@ -111,7 +705,21 @@ impl AsRef<Path> for WatchedPath {
 /// This only does a bare minimum of setup; to actually start the work, you need to set a non-empty
 /// pathset on the [`WorkingData`] with the [`watch`] channel, and send a notification. Take care
 /// _not_ to drop the watch sender: this will cause the worker to stop gracefully, which may not be
-/// what was expected.
+/// what was expected. Setting `shutdown` to `true` will also stop the worker. Events still buffered
+/// by the debounce are then flushed, coalesced as usual, before it stops.
+///
+/// While `paused` is `true`, events from the watcher are not emitted, but dropped or held until it
+/// goes back to `false`, as set in the [`while_paused`](WorkingData#structfield.while_paused)
+/// option. The watcher itself keeps running.
+///
+/// The `watched` channel is updated with the set of paths which are actually being watched (sorted)
+/// every time the pathset is applied. This can differ from the configured pathset if some paths
+/// could not be watched, for example because they don't exist. The `applied` channel is then
+/// updated with the generation of the working data, which is only meaningful within Watchexec.
+///
+/// If the `events` channel is full, what happens to a new event depends on the `overflow` policy.
+/// While blocked, raw events from the watcher keep being buffered and coalesced, and new working
+/// data is still applied. Raw and coalesced events are counted in `metrics`.
 ///
 /// Note that the paths emitted by the watcher are canonicalised. No guarantee is made about the
 /// implementation or output of that canonicalisation (i.e. it might not be `std`'s).
@@ -121,83 +729,350 @@ impl AsRef<Path> for WatchedPath {
 /// Direct usage:
 ///
 /// ```no_run
+/// use std::sync::Arc;
+///
 /// use tokio::sync::{mpsc, watch};
-/// use watchexec::fs::{worker, WorkingData};
+/// use watchexec::{
+///     config::OverflowPolicy,
+///     fs::{worker, WorkingData},
+///     metrics::Counters,
+/// };
 ///
 /// #[tokio::main]
 /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
 ///     let (ev_s, _) = mpsc::channel(1024);
 ///     let (er_s, _) = mpsc::channel(64);
 ///     let (wd_s, wd_r) = watch::channel(WorkingData::default());
+///     let (_sd_s, sd_r) = watch::channel(false);
+///     let (_ps_s, ps_r) = watch::channel(false);
+///     let (wp_s, _) = watch::channel(Vec::new());
+///     let (ap_s, _) = watch::channel(0);
 ///
 ///     let mut wkd = WorkingData::default();
 ///     wkd.pathset = vec![".".into()];
 ///     wd_s.send(wkd)?;
 ///
-///     worker(wd_r, er_s, ev_s).await?;
+///     let metrics = Arc::new(Counters::default());
+///     worker(wd_r, er_s, ev_s, sd_r, ps_r, wp_s, ap_s, OverflowPolicy::Block, metrics).await?;
 ///     Ok(())
 /// }
 /// ```
+#[allow(clippy::too_many_arguments)]
 pub async fn worker(
 	mut working: watch::Receiver<WorkingData>,
 	errors: mpsc::Sender<RuntimeError>,
 	events: mpsc::Sender<Event>,
+	mut shutdown: watch::Receiver<bool>,
+	mut paused: watch::Receiver<bool>,
+	watched: watch::Sender<Vec<PathBuf>>,
+	applied: watch::Sender<u64>,
+	overflow: OverflowPolicy,
+	metrics: Arc<Counters>,
 ) -> Result<(), CriticalError> {
 	debug!("launching filesystem worker");
 
 	let mut watcher_type = Watcher::default();
-	let mut watcher = None;
+	let mut active_type = Watcher::default();
+	let mut watcher: Option<Box<dyn notify::Watcher + Send>> = None;
+	let mut had_watcher = false;
 	let mut pathset = HashSet::new();
 
-	while working.changed().await.is_ok() {
+	let (raw_s, mut raw_r) = mpsc::channel(RAW_EVENTS_BUFFER);
+	let mut globs = PathGlobs::default();
+	let mut waiting = PendingPaths::default();
+	let mut retries = Retries::default();
+	let mut identical = IdenticalWrites::default();
+	let mut debouncer = Debouncer::default();
+	let mut settle;
+	let mut watch_directories = true;
+	let mut symlinks = Symlinks::default();
+	let mut settle_until: Option<Instant> = None;
+	let mut while_paused = WhilePaused::default();
+	let mut held = HeldEvents::default();
+	let mut outbox = Outbox::new(overflow);
+	let raw_events = Arc::new(AtomicBool::new(false));
+
+	loop {
+		select! {
+			changed = working.changed() => if changed.is_err() {
+				break;
+			},
+			Ok(_) = shutdown.changed() => {
+				debug!("shutdown requested, stopping filesystem worker");
+				break;
+			}
+			Ok(_) = paused.changed() => {
+				let is_paused = *paused.borrow();
+				debug!(paused=%is_paused, "watching paused or resumed");
+				if !is_paused {
+					if let Some(event) = held.take() {
+						trace!("emitting the events held while paused");
+						flush_pending(vec![event], &mut outbox, &events, &errors, &metrics).await?;
+					}
+				}
+				continue;
+			}
+			permit = events.reserve(), if !outbox.is_empty() => {
+				outbox.send_next(permit, &errors).await?;
+				continue;
+			}
+			Some(event) = raw_r.recv(), if outbox.is_empty() => {
+				metrics::add(&metrics.fs_events, 1);
+				if waiting.is_trigger(&event) {
+					if let Some(w) = watcher.as_mut() {
+						if waiting.retry(w.as_mut(), &mut pathset) {
+							publish_watched(&pathset, &watched);
+						}
+					}
+				}
+
+				if let Some(until) = settle_until {
+					if Instant::now() < until {
+						trace!("fs event dropped while the watcher settles");
+						continue;
+					}
+					settle_until = None;
+				}
+
+				let event = match waiting.apply(event) {
+					Some(event) => event,
+					None => {
+						trace!("fs event dropped as only seen for a pending path's ancestor");
+						continue;
+					}
+				};
+				let event = match globs.apply(event) {
+					Some(event) => event,
+					None => {
+						trace!("fs event dropped by path globs");
+						continue;
+					}
+				};
+				let event = if watch_directories {
+					event
+				} else {
+					match drop_directories(event) {
+						Some(event) => event,
+						None => {
+							trace!("fs event dropped as only about directories");
+							continue;
+						}
+					}
+				};
+				let event = symlinks.tag(event);
+				let event = match identical.apply(event).await {
+					Some(event) => event,
+					None => {
+						trace!("fs event dropped as the files' content is unchanged");
+						continue;
+					}
+				};
+
+				if *paused.borrow() {
+					match while_paused {
+						WhilePaused::Discard => trace!("fs event dropped while paused"),
+						WhilePaused::Coalesce => {
+							trace!("fs event held while paused");
+							held.push(event);
+						}
+					}
+					continue;
+				}
+
+				if let Some(event) = debouncer.push(event) {
+					flush_pending(vec![event], &mut outbox, &events, &errors, &metrics).await?;
+				}
+				continue;
+			}
+			_ = sleep_until(retries.deadline().unwrap_or_else(Instant::now)), if retries.deadline().is_some() => {
+				let w = match watcher.as_mut() {
+					Some(w) => w,
+					None => {
+						retries.clear();
+						continue;
+					}
+				};
+
+				let mut recovered = Vec::new();
+				for (path, attempt) in retries.take_due(Instant::now()) {
+					match w.watch(path.target(), path.recursive_mode()) {
+						Ok(()) => {
+							debug!(?path, %attempt, "path is now watched");
+							pathset.insert(path.clone());
+							recovered.push(path);
+						}
+						Err(err) => match retries.reschedule(path.clone(), attempt) {
+							Some(delay) => {
+								warn!(?path, %attempt, %err, ?delay, "still cannot watch path, retrying later");
+							}
+							None => {
+								warn!(?path, %attempt, %err, "cannot watch path, giving up");
+								for e in notify_multi_path_errors(&active_type, path, err, false) {
+									errors.send(e).await?;
+								}
+							}
+						},
+					}
+				}
+
+				if !recovered.is_empty() {
+					publish_watched(&pathset, &watched);
+					for path in recovered {
+						let ev = Event {
+							tags: vec![
+								Tag::Source(Source::Internal),
+								Tag::FileSystem(FsEventKind::WatchRecovered),
+								Tag::Path {
+									path: path.into(),
+									file_type: None,
+								},
+							],
+							metadata: Default::default(),
+						};
+						outbox.send(ev, &events, &errors).await?;
+					}
+				}
+				continue;
+			}
+			_ = sleep_until(debouncer.deadline().unwrap_or_else(Instant::now)), if debouncer.deadline().is_some() => {
+				for pending in debouncer.take_due(Instant::now()) {
+					trace!(pending=%pending.len(), "debounce window elapsed, flushing fs events");
+					flush_pending(pending, &mut outbox, &events, &errors, &metrics).await?;
+				}
+				continue;
+			}
+		}
+
 		// In separate scope so we drop the working read lock as early as we can
-		let (new_watcher, to_watch, to_drop) = {
+		let (new_watcher, to_watch, to_drop, glob_errors, ignores, generation) = {
 			let data = working.borrow();
 			trace!(?data, "filesystem worker got a working data change");
+			let generation = data.generation;
+
+			debouncer.configure(&data);
+			settle = data.settle;
+			watch_directories = data.watch_directories;
+			while_paused = data.while_paused;
+			raw_events.store(data.raw_events, Ordering::Relaxed);
+			retries.max = data.watch_retries;
+			identical.configure(data.suppress_identical);
+			let (new_globs, glob_errors) = PathGlobs::new(&data);
+			globs = new_globs;
+
+			let wanted = if data.follow_symlinks {
+				let (followed, extra) = Symlinks::resolve(&data.pathset);
+				symlinks = followed;
+				let mut wanted = data.pathset.clone();
+				wanted.extend(extra);
+				wanted
+			} else {
+				symlinks = Symlinks::default();
+				data.pathset.clone()
+			};
 
-			if data.pathset.is_empty() {
-				trace!("no more watched paths, dropping watcher");
+			let ignores =
+				(!data.ignore_files.is_empty() || data.use_gitignore || data.use_global_ignores)
+					.then(|| {
+						(
+							data.pathset.clone(),
+							data.ignore_files.clone(),
+							data.use_gitignore,
+							data.use_global_ignores,
+						)
+					});
+
+			let same_watcher = watcher.is_some() && watcher_type == data.watcher;
+			if !same_watcher && data.pathset.is_empty() {
+				trace!("no watched paths with this watcher, dropping it");
 				watcher.take();
 				pathset.drain();
-				continue;
-			}
-
-			if watcher.is_none() || watcher_type != data.watcher {
+				(
+					None,
+					Vec::new(),
+					Vec::new(),
+					glob_errors,
+					ignores,
+					generation,
+				)
+			} else if !same_watcher {
 				pathset.drain();
 
-				(Some(data.watcher), data.pathset.clone(), Vec::new())
+				(
+					Some(data.watcher.clone()),
+					wanted,
+					Vec::new(),
+					glob_errors,
+					ignores,
+					generation,
+				)
 			} else {
-				let mut to_watch = Vec::with_capacity(data.pathset.len());
+				// only the difference is applied, so the watcher keeps running for the other paths
+				let mut to_watch = Vec::with_capacity(wanted.len());
 				let mut to_drop = Vec::with_capacity(pathset.len());
-				for path in data.pathset.iter() {
+				for path in wanted.iter() {
 					if !pathset.contains(path) {
 						to_watch.push(path.clone());
 					}
 				}
 
 				for path in pathset.iter() {
-					if !data.pathset.contains(path) {
+					if !wanted.contains(path) {
 						to_drop.push(path.clone());
 					}
 				}
 
-				(None, to_watch, to_drop)
+				(None, to_watch, to_drop, glob_errors, ignores, generation)
 			}
 		};
 
+		for err in glob_errors {
+			errors.send(err).await?;
+		}
+
+		// pending paths are re-evaluated from the new pathset
+		waiting.clear(watcher.as_mut().map(|w| w.as_mut() as _));
+		retries.clear();
+
+		if let Some((paths, files, discover, global)) = ignores {
+			let (ignores, ignore_errors) = load_ignores(&paths, &files, discover, global).await;
+			globs.ignore_files = ignores;
+			for err in ignore_errors {
+				errors.send(err).await?;
+			}
+		}
+
+		let mut reinit = false;
 		if let Some(kind) = new_watcher {
 			debug!(?kind, "creating new watcher");
-			let n_errors = errors.clone();
-			let n_events = events.clone();
-			match kind.create(move |nev: Result<notify::Event, notify::Error>| {
-				trace!(event = ?nev, "receiving possible event from watcher");
-				if let Err(e) = process_event(nev, kind, n_events.clone()) {
-					n_errors.try_send(e).ok();
+			let created = match create_watcher(
+				kind.clone(),
+				raw_events.clone(),
+				errors.clone(),
+				raw_s.clone(),
+			) {
+				Err(err) if kind == Watcher::Native => {
+					let fallback = Watcher::Poll(FALLBACK_POLL_INTERVAL);
+					warn!(%err, ?fallback, "native watcher failed to instantiate, falling back to polling");
+					errors.send(err).await?;
+					create_watcher(
+						fallback.clone(),
+						raw_events.clone(),
+						errors.clone(),
+						raw_s.clone(),
+					)
+					.map(|w| (w, fallback))
 				}
-			}) {
-				Ok(w) => {
+				res => res.map(|w| (w, kind.clone())),
+			};
+
+			match created {
+				Ok((w, active)) => {
 					watcher = Some(w);
 					watcher_type = kind;
+					active_type = active;
+					reinit = had_watcher;
+					had_watcher = true;
+					settle_until = (!settle.is_zero()).then(|| Instant::now() + settle);
 				}
 				Err(e) => {
 					errors.send(e).await?;
@@ -210,9 +1085,9 @@ pub async fn worker(
 
 			for path in to_drop {
 				trace!(?path, "removing path from the watcher");
-				if let Err(err) = w.unwatch(path.as_ref()) {
+				if let Err(err) = w.unwatch(path.target()) {
 					error!(?err, "notify unwatch() error");
-					for e in notify_multi_path_errors(watcher_type, path, err, true) {
+					for e in notify_multi_path_errors(&active_type, path, err, true) {
 						errors.send(e).await?;
 					}
 				} else {
@@ -222,9 +1097,17 @@ pub async fn worker(
 
 			for path in to_watch {
 				trace!(?path, "adding path to the watcher");
-				if let Err(err) = w.watch(path.as_ref(), notify::RecursiveMode::Recursive) {
+				if let Err(err) = w.watch(path.target(), path.recursive_mode()) {
 					error!(?err, "notify watch() error");
-					for e in notify_multi_path_errors(watcher_type, path, err, false) {
+					if !path.target().exists() {
+						waiting.add(w.as_mut(), path.clone(), &pathset);
+					} else if FsWatcherCause::of(&err).may_recover() {
+						if let Some(delay) = retries.add(path.clone()) {
+							warn!(?path, %err, ?delay, "cannot watch path, retrying later");
+							continue;
+						}
+					}
+					for e in notify_multi_path_errors(&active_type, path, err, false) {
 						errors.send(e).await?;
 					}
 				// TODO: unwatch and re-watch manually while ignoring all the erroring paths
@@ -234,68 +1117,1300 @@ pub async fn worker(
 				}
 			}
 		}
+
+		publish_watched(&pathset, &watched);
+		applied.send_replace(generation);
+
+		if reinit {
+			debug!("watcher was re-created, emitting reinit event");
+			let ev = Event {
+				tags: vec![
+					Tag::Source(Source::Internal),
+					Tag::FileSystem(FsEventKind::WatcherReinit),
+				],
+				metadata: Default::default(),
+			};
+			outbox.send(ev, &events, &errors).await?;
+		}
+	}
+
+	let pending = debouncer.take_all().into_iter().map(|pending| {
+		metrics::add(&metrics.fs_events_coalesced, pending.len() as u64 - 1);
+		coalesce(pending)
+	});
+	for event in outbox.take().into_iter().chain(pending) {
+		trace!(?event, "flushing pending fs event before stopping");
+		if let Err(err) = overflow.send(&events, event, "fs watcher").await {
+			debug!(%err, "could not flush pending fs events");
+			break;
+		}
 	}
 
 	debug!("ending file watcher");
 	Ok(())
 }
 
-fn notify_multi_path_errors(
-	kind: Watcher,
-	path: WatchedPath,
-	mut err: notify::Error,
-	rm: bool,
-) -> Vec<RuntimeError> {
-	let mut paths = take(&mut err.paths);
-	if paths.is_empty() {
-		paths.push(path.into());
-	}
-
-	let generic = err.to_string();
-	let mut err = Some(err);
+/// Updates the `watched` channel with the live pathset.
+fn publish_watched(pathset: &HashSet<WatchedPath>, watched: &watch::Sender<Vec<PathBuf>>) {
+	let mut live: Vec<PathBuf> = pathset.iter().cloned().map(PathBuf::from).collect();
+	live.sort();
+	trace!(?live, "updating set of watched paths");
+	watched.send_replace(live);
+}
 
-	let mut errs = Vec::with_capacity(paths.len());
-	for path in paths {
-		let e = err
-			.take()
-			.unwrap_or_else(|| notify::Error::generic(&generic))
-			.add_path(path.clone());
+/// Paths of the pathset which could not be watched because they don't exist (yet).
+///
+/// For each, the nearest existing ancestor is watched non-recursively in its stead. When an event
+/// is seen for a path leading to a pending path (which is how the creation of each intermediate
+/// directory shows up), watching the pending paths is retried: they're either promoted to the live
+/// pathset, or their ancestor is moved closer to them.
+#[derive(Debug, Default)]
+struct PendingPaths {
+	/// The pending paths, with the canonicalised path (as far as it exists) and the ancestor.
+	paths: Vec<(WatchedPath, WatchedPath, PathBuf)>,
 
-		errs.push(if rm {
-			RuntimeError::FsWatcherPathRemove { path, kind, err: e }
-		} else {
-			RuntimeError::FsWatcherPathAdd { path, kind, err: e }
-		});
-	}
+	/// The ancestors watched for pending paths, which aren't otherwise watched.
+	helpers: HashSet<PathBuf>,
 
-	errs
+	/// The canonicalised live pathset, to tell which events from ancestors are wanted.
+	covered: Vec<WatchedPath>,
 }
 
-fn process_event(
-	nev: Result<notify::Event, notify::Error>,
-	kind: Watcher,
-	n_events: mpsc::Sender<Event>,
-) -> Result<(), RuntimeError> {
-	let nev = nev.map_err(|err| RuntimeError::FsWatcherEvent { kind, err })?;
+impl PendingPaths {
+	/// Starts waiting for a path, if it has an existing ancestor.
+	fn add(&mut self, w: &mut dyn notify::Watcher, path: WatchedPath, live: &HashSet<WatchedPath>) {
+		let (ancestor, canonical) = match Self::nearest_ancestor(&path) {
+			Some(found) => found,
+			None => {
+				debug!(?path, "no existing ancestor to watch for pending path");
+				return;
+			}
+		};
 
-	let mut tags = Vec::with_capacity(4);
-	tags.push(Tag::Source(Source::Filesystem));
-	tags.push(Tag::FileEventKind(nev.kind));
+		let live_target = live.iter().any(|p| p.target() == ancestor);
+		if !live_target && !self.helpers.contains(&ancestor) {
+			if let Err(err) = w.watch(&ancestor, notify::RecursiveMode::NonRecursive) {
+				debug!(?path, ?ancestor, %err, "cannot watch ancestor of pending path");
+				return;
+			}
+			self.helpers.insert(ancestor.clone());
+		}
 
-	for path in nev.paths {
-		// possibly pull file_type from whatever notify (or the native driver) returns?
-		tags.push(Tag::Path {
-			file_type: metadata(&path).ok().map(|m| m.file_type().into()),
-			path: dunce::canonicalize(&path).unwrap_or_else(|err| {
-				warn!(?err, ?path, "failed to canonicalise event path");
-				path
-			}),
-		});
+		debug!(?path, ?ancestor, "waiting for path to be created");
+		self.paths.push((path, canonical, ancestor));
+		self.covered = live.iter().map(WatchedPath::canonicalised).collect();
 	}
 
-	if let Some(pid) = nev.attrs.process_id() {
-		tags.push(Tag::Process(pid));
-	}
+	fn nearest_ancestor(path: &WatchedPath) -> Option<(PathBuf, WatchedPath)> {
+		for ancestor in path.target().ancestors().skip(1) {
+			let dir = if ancestor.as_os_str().is_empty() {
+				Path::new(".")
+			} else {
+				ancestor
+			};
+
+			if dir.is_dir() {
+				let rest = path.path.strip_prefix(ancestor).ok()?;
+				let canonical = dunce::canonicalize(dir).ok()?.join(rest);
+				return Some((
+					dir.to_owned(),
+					WatchedPath {
+						path: canonical,
+						mode: path.mode,
+					},
+				));
+			}
+		}
+
+		None
+	}
+
+	/// Whether the event is about a path leading to a pending path.
+	fn is_trigger(&self, event: &Event) -> bool {
+		!self.paths.is_empty()
+			&& event.paths().any(|(path, _)| {
+				self.paths
+					.iter()
+					.any(|(_, canonical, _)| canonical.path.starts_with(path))
+			})
+	}
+
+	/// Retries watching the pending paths, and returns whether any was added to the live pathset.
+	fn retry(&mut self, w: &mut dyn notify::Watcher, live: &mut HashSet<WatchedPath>) -> bool {
+		let mut promoted = false;
+		for (path, _, _) in take(&mut self.paths) {
+			if path.target().exists() {
+				match w.watch(path.target(), path.recursive_mode()) {
+					Ok(()) => {
+						debug!(?path, "pending path was created, now watching it");
+						live.insert(path);
+						promoted = true;
+						continue;
+					}
+					Err(err) => trace!(?path, %err, "cannot watch pending path yet"),
+				}
+			}
+
+			self.add(w, path, live);
+		}
+
+		let paths = &self.paths;
+		self.helpers.retain(|helper| {
+			let used = paths.iter().any(|(_, _, ancestor)| ancestor == helper);
+			if !used {
+				trace!(?helper, "no longer watching ancestor");
+				w.unwatch(helper).ok();
+			}
+			used
+		});
+		self.covered = live.iter().map(WatchedPath::canonicalised).collect();
+
+		promoted
+	}
+
+	/// Stops waiting for all paths.
+	fn clear(&mut self, w: Option<&mut dyn notify::Watcher>) {
+		if let Some(w) = w {
+			for helper in &self.helpers {
+				w.unwatch(helper).ok();
+			}
+		}
+		*self = Self::default();
+	}
+
+	/// Strips paths seen only because an ancestor is watched, or returns `None` if none are left.
+	fn apply(&self, mut event: Event) -> Option<Event> {
+		if self.helpers.is_empty() {
+			return Some(event);
+		}
+
+		let mut had_paths = false;
+		let mut kept_paths = false;
+		event.tags.retain(|tag| match tag {
+			Tag::Path { path, .. } => {
+				had_paths = true;
+				let wanted = self.covered.iter().any(|p| p.covers(path))
+					|| self.paths.iter().any(|(_, c, _)| c.covers(path));
+				kept_paths |= wanted;
+				wanted
+			}
+			_ => true,
+		});
+
+		if had_paths && !kept_paths {
+			None
+		} else {
+			Some(event)
+		}
+	}
+}
+
+/// How long to wait before the first retry of a path which failed to be watched.
+///
+/// See [`WorkingData::watch_retries`](WorkingData#structfield.watch_retries).
+pub const WATCH_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// The longest to wait between retries of a path which failed to be watched.
+///
+/// See [`WorkingData::watch_retries`](WorkingData#structfield.watch_retries).
+pub const WATCH_RETRY_MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// Paths of the pathset which could not be watched but may be later, to be retried with a backoff.
+///
+/// See [`WorkingData::watch_retries`](WorkingData#structfield.watch_retries).
+#[derive(Debug, Default)]
+struct Retries {
+	/// The paths, with the number of the next attempt and when to make it.
+	paths: Vec<(WatchedPath, u32, Instant)>,
+
+	/// How many attempts to make for each path.
+	max: u32,
+}
+
+impl Retries {
+	/// Schedules the first retry of a path, and returns when it is, unless retries are disabled.
+	fn add(&mut self, path: WatchedPath) -> Option<Duration> {
+		self.schedule(path, 1)
+	}
+
+	/// Schedules the next retry of a path after a failed attempt, and returns when it is, unless
+	/// that was the last.
+	fn reschedule(&mut self, path: WatchedPath, attempt: u32) -> Option<Duration> {
+		self.schedule(path, attempt.saturating_add(1))
+	}
+
+	fn schedule(&mut self, path: WatchedPath, attempt: u32) -> Option<Duration> {
+		if attempt > self.max {
+			return None;
+		}
+
+		let delay = Self::delay(attempt);
+		self.paths.push((path, attempt, Instant::now() + delay));
+		Some(delay)
+	}
+
+	/// The wait before an attempt, doubling every time from the first.
+	fn delay(attempt: u32) -> Duration {
+		let factor = 1_u32.checked_shl(attempt - 1).unwrap_or(u32::MAX);
+		WATCH_RETRY_DELAY
+			.saturating_mul(factor)
+			.min(WATCH_RETRY_MAX_DELAY)
+	}
+
+	/// When the next attempt is due, if there's one.
+	fn deadline(&self) -> Option<Instant> {
+		self.paths.iter().map(|(_, _, at)| *at).min()
+	}
+
+	/// Takes the paths whose attempt is due, with the number of the attempt.
+	fn take_due(&mut self, now: Instant) -> Vec<(WatchedPath, u32)> {
+		let (due, later): (Vec<_>, Vec<_>) = take(&mut self.paths)
+			.into_iter()
+			.partition(|(_, _, at)| *at <= now);
+		self.paths = later;
+		due.into_iter()
+			.map(|(path, attempt, _)| (path, attempt))
+			.collect()
+	}
+
+	/// Stops retrying all paths.
+	fn clear(&mut self) {
+		self.paths.clear();
+	}
+}
+
+/// Files larger than this aren't hashed to check if they changed.
+///
+/// See [`WorkingData::suppress_identical`](WorkingData#structfield.suppress_identical).
+pub const IDENTICAL_SIZE_LIMIT: u64 = 16 * 1024 * 1024;
+
+/// How many files' hashes are remembered to check if they changed.
+///
+/// See [`WorkingData::suppress_identical`](WorkingData#structfield.suppress_identical).
+pub const IDENTICAL_CACHE_SIZE: usize = 4096;
+
+/// The hashes of the content of the files seen latest, to drop events which didn't change them.
+///
+/// See [`WorkingData::suppress_identical`](WorkingData#structfield.suppress_identical).
+#[derive(Debug, Default)]
+struct IdenticalWrites {
+	enabled: bool,
+
+	/// The hash of each file, and when it was last seen.
+	hashes: HashMap<PathBuf, (u64, u64)>,
+
+	/// The files by when they were last seen, to evict the least recently seen.
+	seen: BTreeMap<u64, PathBuf>,
+
+	/// Counts up on every file seen.
+	clock: u64,
+}
+
+impl IdenticalWrites {
+	fn configure(&mut self, enabled: bool) {
+		if !enabled {
+			self.hashes.clear();
+			self.seen.clear();
+		}
+		self.enabled = enabled;
+	}
+
+	/// Forgets a file.
+	fn forget(&mut self, path: &Path) {
+		if let Some((_, seen)) = self.hashes.remove(path) {
+			self.seen.remove(&seen);
+		}
+	}
+
+	/// Returns `None` if every file in a write event has the same content as last seen.
+	async fn apply(&mut self, event: Event) -> Option<Event> {
+		if !self.enabled {
+			return Some(event);
+		}
+
+		let mut removed = false;
+		let mut written = false;
+		for tag in &event.tags {
+			match tag {
+				Tag::FileEventKind(FileEventKind::Remove(_)) => removed = true,
+				Tag::FileEventKind(FileEventKind::Modify(ModifyKind::Metadata(_))) => {}
+				Tag::FileEventKind(FileEventKind::Create(_))
+				| Tag::FileEventKind(FileEventKind::Modify(_)) => written = true,
+				_ => {}
+			}
+		}
+
+		let paths: Vec<PathBuf> = event.paths().map(|(path, _)| path.to_owned()).collect();
+		if removed || !written {
+			// whatever comes next at these paths is not the same file
+			for path in &paths {
+				self.forget(path);
+			}
+			return Some(event);
+		}
+
+		let mut unchanged = !paths.is_empty();
+		for path in paths {
+			let hash = match hash_file(&path).await {
+				Some(hash) => hash,
+				None => {
+					self.forget(&path);
+					unchanged = false;
+					continue;
+				}
+			};
+
+			self.clock += 1;
+			self.seen.insert(self.clock, path.clone());
+			let previous = self.hashes.insert(path, (hash, self.clock));
+			if let Some((_, seen)) = previous {
+				self.seen.remove(&seen);
+			}
+			unchanged &= previous.map_or(false, |(previous, _)| previous == hash);
+		}
+
+		while self.hashes.len() > IDENTICAL_CACHE_SIZE {
+			let oldest = match self.seen.keys().next() {
+				Some(oldest) => *oldest,
+				None => break,
+			};
+			if let Some(path) = self.seen.remove(&oldest) {
+				self.hashes.remove(&path);
+			}
+		}
+
+		if unchanged {
+			None
+		} else {
+			Some(event)
+		}
+	}
+}
+
+/// Hashes the content of a file, if it's a file and not too large.
+///
+/// The file is read in chunks, so it's never held in memory whole.
+async fn hash_file(path: &Path) -> Option<u64> {
+	use std::hash::Hasher;
+	use tokio::io::AsyncReadExt;
+
+	let meta = tokio::fs::metadata(path).await.ok()?;
+	if !meta.is_file() || meta.len() > IDENTICAL_SIZE_LIMIT {
+		return None;
+	}
+
+	let mut file = tokio::fs::File::open(path).await.ok()?;
+	let mut hasher = std::collections::hash_map::DefaultHasher::new();
+	let mut buf = vec![0; HASH_CHUNK_SIZE];
+	let mut read = 0_u64;
+	loop {
+		let n = file.read(&mut buf).await.ok()?;
+		if n == 0 {
+			break;
+		}
+
+		read += n as u64;
+		if read > IDENTICAL_SIZE_LIMIT {
+			// it grew while we were reading it
+			return None;
+		}
+		hasher.write(&buf[..n]);
+	}
+
+	Some(hasher.finish())
+}
+
+/// How much of a file is read at a time to hash it.
+const HASH_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Compiled [`ignore_globs`](WorkingData#structfield.ignore_globs) and
+/// [`watch_globs`](WorkingData#structfield.watch_globs), plus the filtering needed for
+/// [file paths](WatchedPath::file()).
+///
+/// The [`ignore_files`](WorkingData#structfield.ignore_files) are loaded separately, as that's
+/// async, with [`load_ignores()`].
+#[derive(Debug, Default)]
+struct PathGlobs {
+	roots: Vec<PathBuf>,
+	project_root: Option<PathBuf>,
+	outside_root: OutsideRoot,
+	ignore: Option<GlobSet>,
+	watch: Option<GlobSet>,
+	files: Vec<WatchedPath>,
+	others: Vec<WatchedPath>,
+	ignore_files: Vec<(PathBuf, IgnoreFilterer)>,
+}
+
+impl PathGlobs {
+	fn new(data: &WorkingData) -> (Self, Vec<RuntimeError>) {
+		let mut errors = Vec::new();
+		let ignore = Self::compile(&data.ignore_globs, &mut errors);
+		let watch = Self::compile(&data.watch_globs, &mut errors);
+
+		let project_root = data
+			.project_root
+			.as_ref()
+			.map(|root| dunce::canonicalize(root).unwrap_or_else(|_| root.clone()));
+
+		let roots = if project_root.is_none() && (ignore.is_some() || watch.is_some()) {
+			data.pathset
+				.iter()
+				.map(|p| dunce::canonicalize(p).unwrap_or_else(|_| p.as_ref().to_owned()))
+				.collect()
+		} else {
+			Vec::new()
+		};
+
+		let (files, others) = if data.pathset.iter().any(WatchedPath::is_file) {
+			data.pathset
+				.iter()
+				.map(WatchedPath::canonicalised)
+				.partition(WatchedPath::is_file)
+		} else {
+			(Vec::new(), Vec::new())
+		};
+
+		(
+			Self {
+				roots,
+				project_root,
+				outside_root: data.outside_root,
+				ignore,
+				watch,
+				files,
+				others,
+				ignore_files: Vec::new(),
+			},
+			errors,
+		)
+	}
+
+	fn compile(globs: &[String], errors: &mut Vec<RuntimeError>) -> Option<GlobSet> {
+		if globs.is_empty() {
+			return None;
+		}
+
+		let mut builder = GlobSetBuilder::new();
+		for glob in globs {
+			match Glob::new(glob) {
+				Ok(g) => {
+					builder.add(g);
+				}
+				Err(err) => errors.push(RuntimeError::FsGlob {
+					glob: glob.clone(),
+					err,
+				}),
+			}
+		}
+
+		match builder.build() {
+			Ok(set) => Some(set),
+			Err(err) => {
+				errors.push(RuntimeError::FsGlob {
+					glob: globs.join(", "),
+					err,
+				});
+				None
+			}
+		}
+	}
+
+	fn is_match(&self, set: &GlobSet, path: &Path) -> bool {
+		if let Some(root) = &self.project_root {
+			return match path.strip_prefix(root) {
+				Ok(rel) => set.is_match(rel),
+				Err(_) => self.outside_root == OutsideRoot::Match,
+			};
+		}
+
+		set.is_match(path)
+			|| self
+				.roots
+				.iter()
+				.filter_map(|root| path.strip_prefix(root).ok())
+				.any(|rel| set.is_match(rel))
+	}
+
+	fn allows(&self, path: &Path, file_type: Option<&FileType>) -> bool {
+		if !self.files.is_empty()
+			&& self.files.iter().any(|f| path.parent() == f.path.parent())
+			&& !self
+				.files
+				.iter()
+				.chain(self.others.iter())
+				.any(|p| p.covers(path))
+		{
+			return false;
+		}
+
+		if let Some(ignore) = &self.ignore {
+			if self.is_match(ignore, path) {
+				return false;
+			}
+		}
+
+		let is_dir = matches!(file_type, Some(FileType::Dir));
+		if self
+			.ignore_files
+			.iter()
+			.filter(|(origin, _)| path.starts_with(origin))
+			.any(|(_, filterer)| !filterer.check_path(path, is_dir))
+		{
+			return false;
+		}
+
+		if let Some(watch) = &self.watch {
+			return self.is_match(watch, path);
+		}
+
+		true
+	}
+
+	/// Strips disallowed paths from the event, or returns `None` if no paths are left.
+	fn apply(&self, mut event: Event) -> Option<Event> {
+		if self.ignore.is_none()
+			&& self.watch.is_none()
+			&& self.files.is_empty()
+			&& self.ignore_files.is_empty()
+		{
+			return Some(event);
+		}
+
+		let mut had_paths = false;
+		let mut kept_paths = false;
+		event.tags.retain(|tag| match tag {
+			Tag::Path { path, file_type } => {
+				had_paths = true;
+				let allowed = self.allows(path, file_type.as_ref());
+				kept_paths |= allowed;
+				allowed
+			}
+			_ => true,
+		});
+
+		if had_paths && !kept_paths {
+			None
+		} else {
+			Some(event)
+		}
+	}
+}
+
+/// How many levels of symlinks are followed within followed symlinks.
+///
+/// See [`follow_symlinks`](WorkingData#structfield.follow_symlinks).
+pub const SYMLINK_DEPTH_LIMIT: usize = 8;
+
+/// Symlinked directories followed for
+/// [`follow_symlinks`](WorkingData#structfield.follow_symlinks).
+#[derive(Debug, Default)]
+struct Symlinks {
+	/// Each symlink (canonical but for its last component) and where it resolves to, by
+	/// decreasing length of the resolved path, so nested ones are found first.
+	links: Vec<(PathBuf, PathBuf)>,
+}
+
+impl Symlinks {
+	/// Finds the symlinked directories to follow from a pathset, and the paths to watch for them.
+	fn resolve(pathset: &[WatchedPath]) -> (Self, Vec<WatchedPath>) {
+		let mut links = Vec::new();
+		let mut extra = Vec::new();
+		let mut seen = HashSet::new();
+		let mut roots = Vec::new();
+		let mut search = Vec::new();
+
+		for path in pathset.iter().filter(|p| !p.is_file()) {
+			let target = match dunce::canonicalize(&path.path) {
+				Ok(target) if target.is_dir() => target,
+				_ => continue,
+			};
+
+			if let Some(link) = canonical_link(&path.path) {
+				links.push((link, target.clone()));
+			}
+
+			seen.insert(target.clone());
+			if path.is_recursive() {
+				roots.push(target.clone());
+				search.push((target, 0));
+			}
+		}
+
+		while let Some((dir, depth)) = search.pop() {
+			let entries = match std::fs::read_dir(&dir) {
+				Ok(entries) => entries,
+				Err(err) => {
+					trace!(?dir, %err, "cannot search directory for symlinks");
+					continue;
+				}
+			};
+
+			for entry in entries.flatten() {
+				let path = entry.path();
+				match entry.file_type() {
+					Ok(ft) if ft.is_dir() => search.push((path, depth)),
+					Ok(ft) if ft.is_symlink() => {
+						let target = match dunce::canonicalize(&path) {
+							Ok(target) if target.is_dir() => target,
+							_ => continue,
+						};
+
+						if roots.iter().any(|root| target.starts_with(root)) {
+							trace!(
+								?path,
+								?target,
+								"symlink within watched paths, not following"
+							);
+						} else if depth >= SYMLINK_DEPTH_LIMIT {
+							warn!(?path, ?target, "symlinks nested too deep, not following");
+						} else if seen.insert(target.clone()) {
+							trace!(?path, ?target, "following symlinked directory");
+							links.push((path, target.clone()));
+							extra.push(WatchedPath::recursive(&target));
+							roots.push(target.clone());
+							search.push((target, depth + 1));
+						}
+					}
+					_ => {}
+				}
+			}
+		}
+
+		links.sort_by_key(|(_, target)| std::cmp::Reverse(target.as_os_str().len()));
+		(Self { links }, extra)
+	}
+
+	/// Adds a [`SymlinkPath`](Tag::SymlinkPath) tag for each path behind a followed symlink.
+	fn tag(&self, mut event: Event) -> Event {
+		if self.links.is_empty() {
+			return event;
+		}
+
+		let through: Vec<PathBuf> = event
+			.paths()
+			.filter_map(|(path, _)| {
+				self.links.iter().find_map(|(link, target)| {
+					path.strip_prefix(target).ok().map(|rel| link.join(rel))
+				})
+			})
+			.collect();
+		event
+			.tags
+			.extend(through.into_iter().map(|path| Tag::SymlinkPath { path }));
+		event
+	}
+}
+
+/// If the path is a symlink, canonicalises it but for its last component.
+fn canonical_link(path: &Path) -> Option<PathBuf> {
+	let is_symlink = std::fs::symlink_metadata(path)
+		.map(|m| m.file_type().is_symlink())
+		.unwrap_or(false);
+	if !is_symlink {
+		return None;
+	}
+
+	let parent = match path.parent() {
+		Some(parent) if parent != Path::new("") => parent,
+		_ => Path::new("."),
+	};
+	dunce::canonicalize(parent)
+		.ok()
+		.zip(path.file_name())
+		.map(|(parent, name)| parent.join(name))
+}
+
+/// Strips directory paths from the event, or returns `None` if no paths are left.
+///
+/// See [`watch_directories`](WorkingData#structfield.watch_directories).
+fn drop_directories(mut event: Event) -> Option<Event> {
+	let kind_is_dir = event.tags.iter().any(|tag| {
+		matches!(
+			tag,
+			Tag::FileEventKind(
+				FileEventKind::Create(CreateKind::Folder)
+					| FileEventKind::Remove(RemoveKind::Folder)
+			)
+		)
+	});
+
+	let mut had_paths = false;
+	let mut kept_paths = false;
+	event.tags.retain(|tag| match tag {
+		Tag::Path { file_type, .. } => {
+			had_paths = true;
+			let is_dir = match file_type {
+				Some(file_type) => *file_type == FileType::Dir,
+				None => kind_is_dir,
+			};
+			kept_paths |= !is_dir;
+			!is_dir
+		}
+		_ => true,
+	});
+
+	if had_paths && !kept_paths {
+		None
+	} else {
+		Some(event)
+	}
+}
+
+/// Reads and compiles the ignore files for a pathset.
+///
+/// Ignore files are grouped by the watched directory they're in, and compiled into one filterer
+/// per such directory. Ignore files outside of any watched directory get a filterer of their own,
+/// rooted at their parent. Global ignore files are added to every filterer. Errors are collected,
+/// and the files they concern are skipped.
+async fn load_ignores(
+	pathset: &[WatchedPath],
+	files: &[PathBuf],
+	discover: bool,
+	global: bool,
+) -> (Vec<(PathBuf, IgnoreFilterer)>, Vec<RuntimeError>) {
+	let mut errors = Vec::new();
+
+	let globals = if global {
+		let (found, errs) = ignore::from_environment().await;
+		trace!(found=%found.len(), "found global ignore files");
+		errors.extend(errs.into_iter().map(|err| RuntimeError::IoError {
+			about: "finding global ignore files",
+			err,
+		}));
+		found
+	} else {
+		Vec::new()
+	};
+
+	let mut origins: Vec<PathBuf> = pathset
+		.iter()
+		.filter_map(|p| dunce::canonicalize(p.target()).ok())
+		.filter(|p| p.is_dir())
+		.collect();
+	origins.sort();
+	origins.dedup();
+
+	let mut grouped: Vec<(PathBuf, Vec<IgnoreFile>)> =
+		origins.into_iter().map(|o| (o, Vec::new())).collect();
+
+	for file in files {
+		let path = dunce::canonicalize(file).unwrap_or_else(|_| file.clone());
+		let applies_in = path.parent().map(Path::to_owned);
+		let ignore_file = IgnoreFile {
+			path,
+			applies_in: applies_in.clone(),
+			applies_to: None,
+		};
+
+		let parent = applies_in.unwrap_or_default();
+		match grouped.iter_mut().find(|(o, _)| parent.starts_with(o)) {
+			Some((_, list)) => list.push(ignore_file),
+			None => grouped.push((parent, vec![ignore_file])),
+		}
+	}
+
+	let mut filterers = Vec::with_capacity(grouped.len());
+	for (origin, mut list) in grouped {
+		if discover {
+			let (found, errs) = ignore::from_origin(&origin).await;
+			trace!(?origin, found=%found.len(), "discovered ignore files");
+			list.extend(found);
+			errors.extend(errs.into_iter().map(|err| RuntimeError::IoError {
+				about: "discovering ignore files",
+				err,
+			}));
+		}
+
+		// a project's own core.excludesFile is the only discovered file not applying in a
+		// directory, and replaces the global git one
+		let git = Some(ProjectType::Git);
+		let local_git_excludes = list
+			.iter()
+			.any(|file| file.applies_in.is_none() && file.applies_to == git);
+		list.extend(
+			globals
+				.iter()
+				.filter(|file| !(local_git_excludes && file.applies_to == git))
+				.cloned(),
+		);
+
+		if list.is_empty() {
+			continue;
+		}
+
+		let mut filterer = IgnoreFilterer::empty(&origin);
+		for file in &list {
+			if let Err(err) = filterer.add_file(file).await {
+				errors.push(err);
+			}
+		}
+		filterer.finish();
+		filterers.push((origin, filterer));
+	}
+
+	(filterers, errors)
+}
+
+/// The poll interval used when the native watcher cannot be instantiated.
+const FALLBACK_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+fn create_watcher(
+	kind: Watcher,
+	raw_events: Arc<AtomicBool>,
+	errors: mpsc::Sender<RuntimeError>,
+	events: mpsc::Sender<Event>,
+) -> Result<Box<dyn notify::Watcher + Send>, RuntimeError> {
+	let mut renames = Renames::default();
+	kind.clone()
+		.create(move |nev: Result<notify::Event, notify::Error>| {
+			trace!(event = ?nev, "receiving possible event from watcher");
+			let raw = raw_events.load(Ordering::Relaxed);
+			if let Err(e) = process_event(nev, &kind, &mut renames, raw, events.clone()) {
+				errors.try_send(e).ok();
+			}
+		})
+}
+
+/// Events merged into one as they arrive, keeping every distinct tag and metadata value in order.
+///
+/// This holds events while paused, and coalesces the batches of the debounce. Tags are indexed by
+/// their hash where they have one, so merging stays cheap for the many events of e.g. a large
+/// checkout.
+#[derive(Debug, Default)]
+struct HeldEvents {
+	merged: Option<Event>,
+	hashed: HashMap<u64, Vec<usize>>,
+	unhashed: Vec<usize>,
+	values: HashMap<String, HashSet<String>>,
+}
+
+impl HeldEvents {
+	fn push(&mut self, event: Event) {
+		let merged = self.merged.get_or_insert_with(Event::default);
+		for tag in event.tags {
+			let same = match tag_hash(&tag) {
+				Some(hash) => self.hashed.entry(hash).or_default(),
+				None => &mut self.unhashed,
+			};
+			if !same.iter().any(|i| merged.tags[*i] == tag) {
+				same.push(merged.tags.len());
+				merged.tags.push(tag);
+			}
+		}
+
+		for (key, values) in event.metadata {
+			let seen = self.values.entry(key.clone()).or_default();
+			let entry = merged.metadata.entry(key).or_default();
+			for value in values {
+				if seen.insert(value.clone()) {
+					entry.push(value);
+				}
+			}
+		}
+	}
+
+	fn take(&mut self) -> Option<Event> {
+		self.hashed.clear();
+		self.unhashed.clear();
+		self.values.clear();
+		self.merged.take()
+	}
+}
+
+/// Hashes the tags which there can be many of in a batch of filesystem events.
+fn tag_hash(tag: &Tag) -> Option<u64> {
+	use std::hash::{Hash, Hasher};
+
+	let mut hasher = std::collections::hash_map::DefaultHasher::new();
+	std::mem::discriminant(tag).hash(&mut hasher);
+	match tag {
+		Tag::Path { path, file_type } => {
+			path.hash(&mut hasher);
+			file_type
+				.as_ref()
+				.map(std::mem::discriminant)
+				.hash(&mut hasher);
+		}
+		Tag::SymlinkPath { path } => path.hash(&mut hasher),
+		Tag::Rename { from, to } => (from, to).hash(&mut hasher),
+		Tag::FileEventKind(kind) => kind.hash(&mut hasher),
+		Tag::RawEvent(event) => event.hash(&mut hasher),
+		_ => return None,
+	}
+
+	Some(hasher.finish())
+}
+
+/// How long after the first half of a rename its second half may arrive to be paired with it.
+const RENAME_WINDOW: Duration = Duration::from_millis(100);
+
+/// How many paired-up renames are remembered, to not tag them again from a later "both" event.
+const RENAMES_REMEMBERED: usize = 16;
+
+/// Pairs up the two halves of renames, for backends which report them as separate events.
+///
+/// Halves are paired up if they arrive one after the other within the [`RENAME_WINDOW`], and have
+/// the same tracker (their inotify cookie on Linux, nothing on Windows).
+#[derive(Debug, Default)]
+struct Renames {
+	from: Option<(Option<usize>, PathBuf, Instant)>,
+	paired: VecDeque<usize>,
+}
+
+impl Renames {
+	/// Returns the `(from, to)` paths of the rename the event completes or describes, if any.
+	fn track(&mut self, event: &notify::Event) -> Option<(PathBuf, PathBuf)> {
+		let mode = match &event.kind {
+			FileEventKind::Modify(ModifyKind::Name(mode)) => mode,
+			_ => return None,
+		};
+
+		let tracker = event.attrs.tracker();
+		match (mode, &event.paths[..]) {
+			(RenameMode::Both, [from, to]) => {
+				if tracker.map_or(false, |t| self.paired.contains(&t)) {
+					trace!(?tracker, "rename was already paired up from its halves");
+					return None;
+				}
+				Some((from.clone(), to.clone()))
+			}
+			(RenameMode::From, [from]) => {
+				self.from = Some((tracker, from.clone(), Instant::now()));
+				None
+			}
+			(RenameMode::To, [to]) => {
+				let (from_tracker, from, at) = self.from.take()?;
+				if from_tracker != tracker || at.elapsed() > RENAME_WINDOW {
+					trace!(?from, ?to, "rename halves don't match, not pairing them");
+					return None;
+				}
+
+				if let Some(t) = tracker {
+					if self.paired.len() == RENAMES_REMEMBERED {
+						self.paired.pop_front();
+					}
+					self.paired.push_back(t);
+				}
+				Some((from, to.clone()))
+			}
+			_ => None,
+		}
+	}
+}
+
+/// Canonicalises a path which may not exist anymore, through its parent directory.
+fn canonicalise_vanished(path: PathBuf) -> PathBuf {
+	match (path.parent(), path.file_name()) {
+		(Some(parent), Some(name)) => dunce::canonicalize(parent)
+			.map(|parent| parent.join(name))
+			.unwrap_or(path),
+		_ => path,
+	}
+}
+
+/// How many raw events may be queued between the watcher and the debouncer.
+const RAW_EVENTS_BUFFER: usize = 4096;
+
+/// How many debounce windows after its first event a batch is flushed at the latest.
+///
+/// Without this, a file written more often than the window (like a log) would never be flushed.
+const DEBOUNCE_MAX_WINDOWS: u32 = 10;
+
+/// Buffers events until their debounce window elapses.
+///
+/// Events are sorted into buckets by window, each with its own deadline, so a short window isn't
+/// held up by events with a longer one (and doesn't flush them early either).
+#[derive(Debug, Default)]
+struct Debouncer {
+	default: Duration,
+	kinds: HashMap<KindCategory, Duration>,
+	immediate: HashSet<KindCategory>,
+	buckets: Vec<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+	window: Duration,
+	events: Vec<Event>,
+	deadline: Instant,
+	latest: Instant,
+}
+
+impl Debouncer {
+	fn configure(&mut self, data: &WorkingData) {
+		self.default = data.debounce;
+		self.kinds = data.debounce_kinds.clone();
+		self.immediate = data.immediate_kinds.clone();
+	}
+
+	fn window(&self, event: &Event) -> Duration {
+		let category = event.tags.iter().find_map(|tag| match tag {
+			Tag::FileEventKind(kind) => Some(KindCategory::of(kind)),
+			_ => None,
+		});
+
+		match category {
+			Some(cat) if self.immediate.contains(&cat) => Duration::ZERO,
+			Some(cat) => self.kinds.get(&cat).copied().unwrap_or(self.default),
+			None => self.default,
+		}
+	}
+
+	/// Buffers an event, or returns it if it's to be emitted right away.
+	fn push(&mut self, event: Event) -> Option<Event> {
+		let window = self.window(&event);
+		if window.is_zero() {
+			trace!("fs event by-passes the debounce");
+			return Some(event);
+		}
+
+		let now = Instant::now();
+		if let Some(bucket) = self.buckets.iter_mut().find(|b| b.window == window) {
+			bucket.events.push(event);
+			bucket.deadline = (now + window).min(bucket.latest);
+			trace!(?window, pending=%bucket.events.len(), "buffering fs event, resetting debounce window");
+		} else {
+			trace!(?window, "buffering fs event, starting debounce window");
+			self.buckets.push(Bucket {
+				window,
+				events: vec![event],
+				deadline: now + window,
+				latest: now + window * DEBOUNCE_MAX_WINDOWS,
+			});
+		}
+
+		None
+	}
+
+	/// The earliest deadline, if any events are buffered.
+	fn deadline(&self) -> Option<Instant> {
+		self.buckets.iter().map(|b| b.deadline).min()
+	}
+
+	/// Takes the events whose window has elapsed, in batches to coalesce.
+	fn take_due(&mut self, now: Instant) -> Vec<Vec<Event>> {
+		let (due, rest) = take(&mut self.buckets)
+			.into_iter()
+			.partition::<Vec<_>, _>(|b| b.deadline <= now);
+		self.buckets = rest;
+		due.into_iter().map(|b| b.events).collect()
+	}
+
+	/// Takes all buffered events, in batches to coalesce.
+	fn take_all(&mut self) -> Vec<Vec<Event>> {
+		take(&mut self.buckets)
+			.into_iter()
+			.map(|b| b.events)
+			.collect()
+	}
+}
+
+/// Coalesces a batch of events into one, and emits it.
+async fn flush_pending(
+	pending: Vec<Event>,
+	outbox: &mut Outbox,
+	events: &mpsc::Sender<Event>,
+	errors: &mpsc::Sender<RuntimeError>,
+	metrics: &Counters,
+) -> Result<(), CriticalError> {
+	if pending.is_empty() {
+		return Ok(());
+	}
+
+	metrics::add(&metrics.fs_events_coalesced, pending.len() as u64 - 1);
+	let event = coalesce(pending);
+	trace!(?event, "emitting coalesced fs event");
+	outbox.send(event, events, errors).await?;
+
+	Ok(())
+}
+
+/// Events on their way to the event channel.
+///
+/// With [`OverflowPolicy::Block`], events wait here for room in the channel, which the worker waits
+/// for in its main loop. That way it keeps applying new working data while the channel is full, so
+/// a reconfiguration waiting on it (like a [swap](crate::Watchexec::reconfigure_atomic()), which
+/// holds up the action worker) can't deadlock. With other policies, events are sent right away.
+#[derive(Debug)]
+struct Outbox {
+	overflow: OverflowPolicy,
+	queue: VecDeque<Event>,
+}
+
+impl Outbox {
+	fn new(overflow: OverflowPolicy) -> Self {
+		Self {
+			overflow,
+			queue: VecDeque::new(),
+		}
+	}
+
+	fn is_empty(&self) -> bool {
+		self.queue.is_empty()
+	}
+
+	/// Takes the events still waiting for room in the channel.
+	fn take(&mut self) -> VecDeque<Event> {
+		take(&mut self.queue)
+	}
+
+	async fn send(
+		&mut self,
+		event: Event,
+		events: &mpsc::Sender<Event>,
+		errors: &mpsc::Sender<RuntimeError>,
+	) -> Result<(), CriticalError> {
+		if self.overflow == OverflowPolicy::Block {
+			self.queue.push_back(event);
+		} else if let Err(err) = self.overflow.send(events, event, "fs watcher").await {
+			errors.send(err).await?;
+		}
+
+		Ok(())
+	}
+
+	/// Sends the first waiting event, now that there's room for it.
+	async fn send_next(
+		&mut self,
+		permit: Result<Permit<'_, Event>, SendError<()>>,
+		errors: &mpsc::Sender<RuntimeError>,
+	) -> Result<(), CriticalError> {
+		let event = match self.queue.pop_front() {
+			Some(event) => event,
+			None => return Ok(()),
+		};
+
+		match permit {
+			Ok(permit) => permit.send(event),
+			Err(_) => {
+				errors
+					.send(RuntimeError::EventChannelSend {
+						ctx: "fs watcher",
+						err: SendError(event),
+					})
+					.await?;
+			}
+		}
+
+		Ok(())
+	}
+}
+
+/// Merges several events into one, keeping every distinct tag and metadata value in order.
+fn coalesce(mut events: Vec<Event>) -> Event {
+	if events.len() == 1 {
+		return events.remove(0);
+	}
+
+	let mut merged = HeldEvents::default();
+	for event in events {
+		merged.push(event);
+	}
+
+	merged.take().unwrap_or_default()
+}
+
+fn notify_multi_path_errors(
+	kind: &Watcher,
+	path: WatchedPath,
+	mut err: notify::Error,
+	rm: bool,
+) -> Vec<RuntimeError> {
+	let mut paths = take(&mut err.paths);
+	if paths.is_empty() {
+		paths.push(path.into());
+	}
+
+	let generic = err.to_string();
+	let mut err = Some(err);
+
+	let mut errs = Vec::with_capacity(paths.len());
+	for path in paths {
+		let e = err
+			.take()
+			.unwrap_or_else(|| notify::Error::generic(&generic))
+			.add_path(path.clone());
+
+		let cause = FsWatcherCause::of(&e);
+		let errno = FsWatcherCause::errno(&e);
+		errs.push(if rm {
+			RuntimeError::FsWatcherPathRemove {
+				path,
+				kind: kind.clone(),
+				cause,
+				errno,
+				err: e,
+			}
+		} else {
+			RuntimeError::FsWatcherPathAdd {
+				path,
+				kind: kind.clone(),
+				cause,
+				errno,
+				err: e,
+			}
+		});
+	}
+
+	errs
+}
+
+fn process_event(
+	nev: Result<notify::Event, notify::Error>,
+	kind: &Watcher,
+	renames: &mut Renames,
+	raw: bool,
+	n_events: mpsc::Sender<Event>,
+) -> Result<(), RuntimeError> {
+	let nev = nev.map_err(|err| RuntimeError::FsWatcherEvent {
+		kind: kind.clone(),
+		cause: FsWatcherCause::of(&err),
+		errno: FsWatcherCause::errno(&err),
+		err,
+	})?;
+
+	let rename = renames.track(&nev);
+	let raw = raw.then(|| Tag::RawEvent(Box::new(nev.clone())));
+
+	let mut tags = Vec::with_capacity(4);
+	tags.push(Tag::Source(Source::Filesystem));
+	tags.push(Tag::FileEventKind(nev.kind));
+
+	for path in nev.paths {
+		if matches!(kind, Watcher::Custom(_)) {
+			tags.push(Tag::Path {
+				path,
+				file_type: None,
+			});
+			continue;
+		}
+
+		// possibly pull file_type from whatever notify (or the native driver) returns?
+		tags.push(Tag::Path {
+			file_type: metadata(&path).ok().map(|m| m.file_type().into()),
+			path: dunce::canonicalize(&path).unwrap_or_else(|err| {
+				warn!(?err, ?path, "failed to canonicalise event path");
+				path
+			}),
+		});
+	}
+
+	if let Some((from, to)) = rename {
+		tags.push(if matches!(kind, Watcher::Custom(_)) {
+			Tag::Rename { from, to }
+		} else {
+			Tag::Rename {
+				from: canonicalise_vanished(from),
+				to: dunce::canonicalize(&to).unwrap_or(to),
+			}
+		});
+	}
+
+	if let Some(pid) = nev.attrs.process_id() {
+		tags.push(Tag::Process(pid));
+	}
+
+	tags.extend(raw);
 
 	let mut metadata = HashMap::new();
 
@@ -319,3 +2434,714 @@ fn process_event(
 
 	Ok(())
 }
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	fn fs_event(path: &str, meta: &str) -> Event {
+		Event {
+			tags: vec![
+				Tag::Source(Source::Filesystem),
+				Tag::Path {
+					path: path.into(),
+					file_type: None,
+				},
+			],
+			metadata: HashMap::from([("notify-backend".to_string(), vec![meta.to_string()])]),
+		}
+	}
+
+	fn kind_event(path: &str, kind: FileEventKind) -> Event {
+		let mut event = fs_event(path, "test");
+		event.tags.push(Tag::FileEventKind(kind));
+		event
+	}
+
+	#[test]
+	fn kind_categories() {
+		use crate::event::filekind::{CreateKind, DataChange, RemoveKind, RenameMode};
+		assert_eq!(
+			KindCategory::of(&FileEventKind::Create(CreateKind::File)),
+			KindCategory::Create
+		);
+		assert_eq!(
+			KindCategory::of(&FileEventKind::Modify(ModifyKind::Data(
+				DataChange::Content
+			))),
+			KindCategory::Modify
+		);
+		assert_eq!(
+			KindCategory::of(&FileEventKind::Modify(ModifyKind::Name(RenameMode::Both))),
+			KindCategory::Rename
+		);
+		assert_eq!(
+			KindCategory::of(&FileEventKind::Remove(RemoveKind::Any)),
+			KindCategory::Remove
+		);
+		assert_eq!(KindCategory::of(&FileEventKind::Any), KindCategory::Other);
+	}
+
+	#[test]
+	fn retries_back_off_up_to_the_cap() {
+		let mut retries = Retries {
+			max: 10,
+			..Default::default()
+		};
+		assert_eq!(retries.add("a".into()), Some(WATCH_RETRY_DELAY));
+		assert_eq!(
+			retries.reschedule("a".into(), 1),
+			Some(WATCH_RETRY_DELAY * 2)
+		);
+		assert_eq!(
+			retries.reschedule("a".into(), 9),
+			Some(WATCH_RETRY_MAX_DELAY)
+		);
+		assert_eq!(retries.reschedule("a".into(), 10), None);
+
+		retries.max = 0;
+		assert_eq!(retries.add("b".into()), None);
+	}
+
+	#[test]
+	fn debouncer_by_kind() {
+		use crate::event::filekind::{CreateKind, DataChange, RemoveKind};
+		let data = WorkingData {
+			debounce: Duration::from_millis(100),
+			debounce_kinds: HashMap::from([(KindCategory::Create, Duration::from_secs(10))]),
+			immediate_kinds: HashSet::from([KindCategory::Remove]),
+			..Default::default()
+		};
+		let mut debouncer = Debouncer::default();
+		debouncer.configure(&data);
+
+		let modify = FileEventKind::Modify(ModifyKind::Data(DataChange::Content));
+		assert!(debouncer.push(kind_event("/a", modify)).is_none());
+		assert!(debouncer.push(fs_event("/b", "untagged")).is_none());
+		assert!(debouncer
+			.push(kind_event("/c", FileEventKind::Create(CreateKind::File)))
+			.is_none());
+		let removal = kind_event("/d", FileEventKind::Remove(RemoveKind::File));
+		assert_eq!(debouncer.push(removal.clone()), Some(removal));
+
+		let now = Instant::now();
+		let due = debouncer.take_due(now + Duration::from_secs(1));
+		assert_eq!(due.len(), 1);
+		assert_eq!(due[0].len(), 2);
+		assert!(
+			debouncer.deadline().expect("create still buffered") > now + Duration::from_secs(1)
+		);
+
+		let rest = debouncer.take_all();
+		assert_eq!(rest.len(), 1);
+		assert_eq!(rest[0].len(), 1);
+		assert!(debouncer.deadline().is_none());
+	}
+
+	#[test]
+	fn pending_path_nearest_ancestor() {
+		let base =
+			std::env::temp_dir().join(format!("watchexec-unit-pending-{}", std::process::id()));
+		std::fs::create_dir_all(&base).expect("create base");
+		let canon = dunce::canonicalize(&base).expect("canonicalize base");
+
+		let (ancestor, canonical) =
+			PendingPaths::nearest_ancestor(&WatchedPath::from(base.join("a").join("b")))
+				.expect("base exists");
+		assert_eq!(ancestor, base);
+		assert_eq!(canonical.path, canon.join("a").join("b"));
+
+		let (ancestor, canonical) =
+			PendingPaths::nearest_ancestor(&WatchedPath::file(base.join("a").join("f.txt")))
+				.expect("base exists");
+		assert_eq!(ancestor, base);
+		assert_eq!(canonical.path, canon.join("a").join("f.txt"));
+
+		std::fs::remove_dir_all(&base).ok();
+	}
+
+	fn globs(ignore: &[&str], watch: &[&str]) -> PathGlobs {
+		let data = WorkingData {
+			pathset: vec!["/project".into()],
+			ignore_globs: ignore.iter().map(|s| s.to_string()).collect(),
+			watch_globs: watch.iter().map(|s| s.to_string()).collect(),
+			..Default::default()
+		};
+		let (globs, errors) = PathGlobs::new(&data);
+		assert!(errors.is_empty());
+		globs
+	}
+
+	#[test]
+	fn globs_ignore_drops_event() {
+		let globs = globs(&["target/**"], &[]);
+		assert_eq!(
+			globs.apply(fs_event("/project/target/debug/foo", "poll")),
+			None
+		);
+		assert!(globs
+			.apply(fs_event("/project/src/main.rs", "poll"))
+			.is_some());
+	}
+
+	#[test]
+	fn globs_watch_requires_match() {
+		let globs = globs(&[], &["*.rs"]);
+		assert!(globs
+			.apply(fs_event("/project/src/main.rs", "poll"))
+			.is_some());
+		assert_eq!(globs.apply(fs_event("/project/README.md", "poll")), None);
+	}
+
+	#[test]
+	fn globs_ignore_wins_over_watch() {
+		let globs = globs(&["**/generated.rs"], &["*.rs"]);
+		assert_eq!(
+			globs.apply(fs_event("/project/src/generated.rs", "poll")),
+			None
+		);
+	}
+
+	#[test]
+	fn globs_rooted_at_project_root() {
+		let rooted = |ignore: &[&str], watch: &[&str], outside_root| {
+			let data = WorkingData {
+				pathset: vec!["/project/src".into(), "/elsewhere".into()],
+				ignore_globs: ignore.iter().map(|s| s.to_string()).collect(),
+				watch_globs: watch.iter().map(|s| s.to_string()).collect(),
+				project_root: Some("/project".into()),
+				outside_root,
+				..Default::default()
+			};
+			let (globs, errors) = PathGlobs::new(&data);
+			assert!(errors.is_empty());
+			globs
+		};
+
+		let globs = rooted(&[], &["src/**/*.rs"], OutsideRoot::NoMatch);
+		assert!(globs
+			.apply(fs_event("/project/src/deep/lib.rs", "poll"))
+			.is_some());
+		// not matched relative to the watched path
+		assert_eq!(
+			globs.apply(fs_event("/project/src/src/a.txt", "poll")),
+			None
+		);
+		assert_eq!(globs.apply(fs_event("/elsewhere/src/a.rs", "poll")), None);
+
+		let globs = rooted(&[], &["src/**/*.rs"], OutsideRoot::Match);
+		assert!(globs
+			.apply(fs_event("/elsewhere/src/a.txt", "poll"))
+			.is_some());
+
+		let globs = rooted(&["**/*.tmp"], &[], OutsideRoot::NoMatch);
+		assert_eq!(globs.apply(fs_event("/project/src/a.tmp", "poll")), None);
+		assert!(globs.apply(fs_event("/elsewhere/a.tmp", "poll")).is_some());
+
+		let globs = rooted(&["**/*.tmp"], &[], OutsideRoot::Match);
+		assert_eq!(globs.apply(fs_event("/elsewhere/a.txt", "poll")), None);
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn symlinks_followed_once_with_both_paths() {
+		use std::os::unix::fs::symlink;
+
+		let base =
+			std::env::temp_dir().join(format!("watchexec-fs-symlinks-{}", std::process::id()));
+		std::fs::create_dir_all(base.join("proj/inner")).expect("create test dir");
+		std::fs::create_dir_all(base.join("real")).expect("create test dir");
+		let base = dunce::canonicalize(base).expect("canonicalise test dir");
+		let (proj, real) = (base.join("proj"), base.join("real"));
+		symlink(&real, proj.join("link")).expect("create symlink");
+		symlink(&proj, real.join("back")).expect("create symlink");
+		symlink(&real, real.join("itself")).expect("create symlink");
+		symlink(&proj, proj.join("inner/up")).expect("create symlink");
+
+		let (symlinks, extra) = Symlinks::resolve(&[WatchedPath::recursive(&proj)]);
+		assert_eq!(extra, vec![WatchedPath::recursive(&real)]);
+		assert_eq!(symlinks.links, vec![(proj.join("link"), real.clone())]);
+
+		let event = symlinks.tag(path_event(real.join("a.rs")));
+		assert_eq!(
+			event.tags.last(),
+			Some(&Tag::SymlinkPath {
+				path: proj.join("link/a.rs")
+			})
+		);
+		assert_eq!(event.paths().count(), 1);
+
+		let event = symlinks.tag(path_event(proj.join("b.rs")));
+		assert!(!event
+			.tags
+			.iter()
+			.any(|tag| matches!(tag, Tag::SymlinkPath { .. })));
+
+		std::fs::remove_dir_all(base).ok();
+	}
+
+	#[test]
+	fn globs_leave_pathless_events() {
+		let globs = globs(&["*"], &[]);
+		let event = Event {
+			tags: vec![Tag::Source(Source::Filesystem)],
+			metadata: HashMap::new(),
+		};
+		assert_eq!(globs.apply(event.clone()), Some(event));
+	}
+
+	#[test]
+	fn directories_dropped_by_type_or_kind() {
+		let typed = |path: &str, file_type| Tag::Path {
+			path: path.into(),
+			file_type,
+		};
+
+		let dir_only = Event {
+			tags: vec![typed("/project/src", Some(FileType::Dir))],
+			metadata: HashMap::new(),
+		};
+		assert_eq!(drop_directories(dir_only), None);
+
+		let mixed = Event {
+			tags: vec![
+				typed("/project/src", Some(FileType::Dir)),
+				typed("/project/src/main.rs", Some(FileType::File)),
+			],
+			metadata: HashMap::new(),
+		};
+		assert_eq!(
+			drop_directories(mixed).map(|event| event.paths().count()),
+			Some(1),
+			"file path was not kept"
+		);
+
+		let removed_dir = kind_event("/project/gone", FileEventKind::Remove(RemoveKind::Folder));
+		assert_eq!(drop_directories(removed_dir), None);
+
+		let renamed = kind_event(
+			"/project/moved",
+			FileEventKind::Modify(ModifyKind::Name(notify::event::RenameMode::From)),
+		);
+		assert!(drop_directories(renamed).is_some());
+
+		let pathless = Event {
+			tags: vec![Tag::Source(Source::Filesystem)],
+			metadata: HashMap::new(),
+		};
+		assert_eq!(drop_directories(pathless.clone()), Some(pathless));
+	}
+
+	#[test]
+	fn file_paths_drop_siblings() {
+		let data = WorkingData {
+			pathset: vec![
+				WatchedPath::file("/nonexistent/bin/tool"),
+				WatchedPath::recursive("/project"),
+			],
+			..Default::default()
+		};
+		let (globs, _) = PathGlobs::new(&data);
+		assert!(globs
+			.apply(fs_event("/nonexistent/bin/tool", "poll"))
+			.is_some());
+		assert_eq!(
+			globs.apply(fs_event("/nonexistent/bin/other", "poll")),
+			None
+		);
+		assert!(globs
+			.apply(fs_event("/project/src/main.rs", "poll"))
+			.is_some());
+	}
+
+	#[test]
+	fn file_paths_keep_siblings_covered_elsewhere() {
+		let data = WorkingData {
+			pathset: vec![
+				WatchedPath::file("/nonexistent/bin/tool"),
+				WatchedPath::non_recursive("/nonexistent/bin"),
+			],
+			..Default::default()
+		};
+		let (globs, _) = PathGlobs::new(&data);
+		assert!(globs
+			.apply(fs_event("/nonexistent/bin/other", "poll"))
+			.is_some());
+	}
+
+	fn ignore_files_dir(name: &str) -> PathBuf {
+		let base = std::env::temp_dir().join(format!(
+			"watchexec-fs-ignore-{}-{}",
+			name,
+			std::process::id()
+		));
+		std::fs::create_dir_all(base.join("sub")).expect("create test dir");
+		let base = dunce::canonicalize(base).expect("canonicalise test dir");
+		std::fs::write(base.join(".gitignore"), "*.log\n!keep.log\n").expect("write ignore file");
+		std::fs::write(base.join("sub/.gitignore"), "*.tmp\n").expect("write ignore file");
+		std::fs::write(base.join("extra-ignores"), "target/\n").expect("write ignore file");
+		base
+	}
+
+	fn path_event(path: PathBuf) -> Event {
+		Event {
+			tags: vec![
+				Tag::Source(Source::Filesystem),
+				Tag::Path {
+					path,
+					file_type: Some(FileType::File),
+				},
+			],
+			metadata: HashMap::new(),
+		}
+	}
+
+	#[tokio::test]
+	async fn identical_writes_are_dropped() {
+		use crate::event::filekind::{DataChange, RemoveKind};
+
+		let path =
+			std::env::temp_dir().join(format!("watchexec-test-identical-{}", std::process::id()));
+		let path = path.to_str().expect("temp dir is utf-8");
+		let written = || {
+			kind_event(
+				path,
+				FileEventKind::Modify(ModifyKind::Data(DataChange::Any)),
+			)
+		};
+		std::fs::write(path, "one").expect("write test file");
+
+		let mut identical = IdenticalWrites::default();
+		assert!(identical.apply(written()).await.is_some());
+		identical.configure(true);
+		assert!(identical.apply(written()).await.is_some());
+		assert_eq!(identical.apply(written()).await, None);
+
+		std::fs::write(path, "two").expect("write test file");
+		assert!(identical.apply(written()).await.is_some());
+		assert_eq!(identical.apply(written()).await, None);
+
+		assert!(identical
+			.apply(kind_event(path, FileEventKind::Remove(RemoveKind::File)))
+			.await
+			.is_some());
+		assert!(identical.apply(written()).await.is_some());
+
+		std::fs::remove_file(path).ok();
+	}
+
+	#[tokio::test]
+	async fn identical_cache_stays_bounded() {
+		use crate::event::filekind::DataChange;
+
+		let base = std::env::temp_dir().join(format!(
+			"watchexec-unit-identical-cache-{}",
+			std::process::id()
+		));
+		std::fs::create_dir_all(&base).expect("create test dir");
+
+		let mut identical = IdenticalWrites::default();
+		identical.configure(true);
+		for n in 0..(IDENTICAL_CACHE_SIZE as u64 - 1) {
+			let path = PathBuf::from(format!("/fake/{}", n));
+			identical.hashes.insert(path.clone(), (0, n));
+			identical.seen.insert(n, path);
+		}
+		identical.clock = IDENTICAL_CACHE_SIZE as u64;
+
+		let mut event = Event::default();
+		for name in ["a", "b", "c"] {
+			let path = base.join(name);
+			std::fs::write(&path, name).expect("write test file");
+			event.tags.push(Tag::Path {
+				path,
+				file_type: None,
+			});
+		}
+		event
+			.tags
+			.push(Tag::FileEventKind(FileEventKind::Modify(ModifyKind::Data(
+				DataChange::Any,
+			))));
+		assert!(identical.apply(event).await.is_some());
+
+		assert_eq!(identical.hashes.len(), IDENTICAL_CACHE_SIZE);
+		assert_eq!(identical.seen.len(), IDENTICAL_CACHE_SIZE);
+		assert!(!identical.hashes.contains_key(Path::new("/fake/0")));
+		assert!(!identical.hashes.contains_key(Path::new("/fake/1")));
+		assert!(identical.hashes.contains_key(Path::new("/fake/2")));
+		assert!(identical.hashes.contains_key(&base.join("c")));
+
+		std::fs::remove_dir_all(&base).ok();
+	}
+
+	#[tokio::test]
+	async fn ignore_files_discovered_with_negations_and_nesting() {
+		let base = ignore_files_dir("discover");
+		let (ignores, errors) =
+			load_ignores(&[WatchedPath::recursive(&base)], &[], true, false).await;
+		assert!(errors.is_empty(), "{:?}", errors);
+
+		let globs = PathGlobs {
+			ignore_files: ignores,
+			..Default::default()
+		};
+		assert_eq!(globs.apply(path_event(base.join("debug.log"))), None);
+		assert!(globs.apply(path_event(base.join("keep.log"))).is_some());
+		assert_eq!(globs.apply(path_event(base.join("sub/scratch.tmp"))), None);
+		assert!(globs.apply(path_event(base.join("scratch.tmp"))).is_some());
+		assert!(globs.apply(path_event(base.join("src/main.rs"))).is_some());
+
+		std::fs::remove_dir_all(base).ok();
+	}
+
+	#[tokio::test]
+	async fn ignore_files_listed_apply_in_their_directory() {
+		let base = ignore_files_dir("listed");
+		let (ignores, errors) = load_ignores(
+			&[WatchedPath::recursive(&base)],
+			&[base.join("extra-ignores")],
+			false,
+			false,
+		)
+		.await;
+		assert!(errors.is_empty(), "{:?}", errors);
+
+		let globs = PathGlobs {
+			ignore_files: ignores,
+			..Default::default()
+		};
+		assert_eq!(
+			globs.apply(path_event(base.join("target/debug/watchexec"))),
+			None
+		);
+		assert!(globs.apply(path_event(base.join("debug.log"))).is_some());
+
+		std::fs::remove_dir_all(base).ok();
+	}
+
+	#[tokio::test]
+	async fn global_ignore_files_apply_in_watched_directories() {
+		let base = ignore_files_dir("global");
+		let other = base.join("sub");
+		std::env::set_var("WATCHEXEC_IGNORE_FILES", base.join("extra-ignores"));
+		let (ignores, _) = load_ignores(
+			&[
+				WatchedPath::recursive(&base),
+				WatchedPath::recursive(&other),
+			],
+			&[],
+			false,
+			true,
+		)
+		.await;
+		std::env::remove_var("WATCHEXEC_IGNORE_FILES");
+		assert_eq!(ignores.len(), 2);
+
+		let globs = PathGlobs {
+			ignore_files: ignores,
+			..Default::default()
+		};
+		assert_eq!(globs.apply(path_event(base.join("target/foo"))), None);
+		assert_eq!(globs.apply(path_event(other.join("target/foo"))), None);
+		assert!(globs.apply(path_event(base.join("debug.log"))).is_some());
+
+		std::fs::remove_dir_all(base).ok();
+	}
+
+	#[tokio::test]
+	async fn ignore_files_report_unreadable() {
+		let (ignores, errors) = load_ignores(
+			&[],
+			&["/nonexistent/watchexec/.ignore".into()],
+			false,
+			false,
+		)
+		.await;
+		assert_eq!(errors.len(), 1);
+		assert_eq!(ignores.len(), 1);
+	}
+
+	#[test]
+	fn globs_report_invalid() {
+		let data = WorkingData {
+			ignore_globs: vec!["a[".into()],
+			..Default::default()
+		};
+		let (_, errors) = PathGlobs::new(&data);
+		assert_eq!(errors.len(), 1);
+	}
+
+	#[tokio::test]
+	async fn debouncer_flushes_continuous_writes() {
+		tokio::time::pause();
+		let data = WorkingData {
+			debounce: Duration::from_millis(50),
+			..Default::default()
+		};
+		let mut debouncer = Debouncer::default();
+		debouncer.configure(&data);
+
+		// a write every 30ms never leaves a quiet 50ms window, but is flushed every 500ms
+		let mut flushed = Vec::new();
+		for _ in 0..40 {
+			assert!(debouncer.push(fs_event("/log", "inotify")).is_none());
+			tokio::time::advance(Duration::from_millis(30)).await;
+			for batch in debouncer.take_due(Instant::now()) {
+				flushed.push(batch.len());
+			}
+		}
+
+		assert_eq!(flushed.len(), 2);
+		assert!(flushed.iter().all(|len| *len <= 17));
+	}
+
+	#[test]
+	fn coalesce_single_is_unchanged() {
+		let event = fs_event("/a", "inotify");
+		assert_eq!(coalesce(vec![event.clone()]), event);
+	}
+
+	#[test]
+	fn coalesce_merges_distinct_tags() {
+		let merged = coalesce(vec![
+			fs_event("/a", "inotify"),
+			fs_event("/b", "inotify"),
+			fs_event("/a", "poll"),
+		]);
+
+		assert_eq!(
+			merged.tags,
+			vec![
+				Tag::Source(Source::Filesystem),
+				Tag::Path {
+					path: "/a".into(),
+					file_type: None,
+				},
+				Tag::Path {
+					path: "/b".into(),
+					file_type: None,
+				},
+			]
+		);
+		assert_eq!(
+			merged.metadata.get("notify-backend"),
+			Some(&vec!["inotify".to_string(), "poll".to_string()])
+		);
+	}
+
+	#[test]
+	fn coalesce_unions_paths_and_kinds() {
+		use crate::event::filekind::{CreateKind, DataChange};
+
+		let created = FileEventKind::Create(CreateKind::File);
+		let written = FileEventKind::Modify(ModifyKind::Data(DataChange::Content));
+		let merged = coalesce(vec![
+			kind_event("/a", created.clone()),
+			kind_event("/b", written.clone()),
+			kind_event("/b", written.clone()),
+		]);
+
+		assert_eq!(merged.paths().count(), 2);
+		let kinds: Vec<_> = merged
+			.tags
+			.iter()
+			.filter_map(|tag| match tag {
+				Tag::FileEventKind(kind) => Some(kind.clone()),
+				_ => None,
+			})
+			.collect();
+		assert_eq!(kinds, vec![created, written]);
+	}
+
+	#[tokio::test]
+	async fn worker_flushes_debounce_on_shutdown() {
+		let base =
+			std::env::temp_dir().join(format!("watchexec-unit-flush-{}", std::process::id()));
+		std::fs::create_dir_all(&base).expect("create test dir");
+		let base = dunce::canonicalize(base).expect("canonicalise test dir");
+
+		let (ev_s, mut ev_r) = mpsc::channel(16);
+		let (er_s, _er_r) = mpsc::channel(16);
+		let (wd_s, wd_r) = watch::channel(WorkingData::default());
+		let (sd_s, sd_r) = watch::channel(false);
+		let (_ps_s, ps_r) = watch::channel(false);
+		let (wp_s, mut wp_r) = watch::channel(Vec::new());
+		let (ap_s, _ap_r) = watch::channel(0);
+		let metrics = Arc::new(Counters::default());
+		let handle = tokio::spawn(worker(
+			wd_r,
+			er_s,
+			ev_s,
+			sd_r,
+			ps_r,
+			wp_s,
+			ap_s,
+			OverflowPolicy::Block,
+			metrics.clone(),
+		));
+
+		wd_s.send_replace(WorkingData {
+			pathset: vec![base.clone().into()],
+			debounce: Duration::from_secs(30),
+			..Default::default()
+		});
+		wp_r.changed().await.expect("watched paths published");
+		let file = base.join("file");
+		std::fs::write(&file, "data").expect("write test file");
+		tokio::time::timeout(Duration::from_secs(5), async {
+			while metrics.snapshot().fs_events == 0 {
+				tokio::time::sleep(Duration::from_millis(10)).await;
+			}
+		})
+		.await
+		.expect("raw event not received");
+		assert!(
+			ev_r.try_recv().is_err(),
+			"event emitted before the debounce"
+		);
+
+		sd_s.send_replace(true);
+		handle
+			.await
+			.expect("worker panicked")
+			.expect("worker errored");
+		let event = ev_r.try_recv().expect("pending event was not flushed");
+		assert!(event.paths().any(|(path, _)| path == file));
+
+		std::fs::remove_dir_all(&base).ok();
+	}
+
+	#[test]
+	fn renames_pair_halves() {
+		fn half(mode: RenameMode, path: &str, tracker: Option<usize>) -> notify::Event {
+			let event = notify::Event::new(FileEventKind::Modify(ModifyKind::Name(mode)))
+				.add_path(path.into());
+			match tracker {
+				Some(t) => event.set_tracker(t),
+				None => event,
+			}
+		}
+		let pair = Some(("/a".into(), "/b".into()));
+
+		// inotify: halves with a cookie, then the same rename as a "both" event
+		let mut renames = Renames::default();
+		assert_eq!(renames.track(&half(RenameMode::From, "/a", Some(1))), None);
+		assert_eq!(renames.track(&half(RenameMode::To, "/b", Some(1))), pair);
+		let both = half(RenameMode::Both, "/a", Some(1)).add_path("/b".into());
+		assert_eq!(renames.track(&both), None);
+
+		// a "both" event on its own
+		let both = half(RenameMode::Both, "/a", Some(2)).add_path("/b".into());
+		assert_eq!(renames.track(&both), pair);
+
+		// halves with different cookies are different renames
+		assert_eq!(renames.track(&half(RenameMode::From, "/a", Some(3))), None);
+		assert_eq!(renames.track(&half(RenameMode::To, "/b", Some(4))), None);
+
+		// windows: halves one after the other, without trackers
+		assert_eq!(renames.track(&half(RenameMode::From, "/a", None)), None);
+		assert_eq!(renames.track(&half(RenameMode::To, "/b", None)), pair);
+		assert_eq!(renames.track(&half(RenameMode::To, "/b", None)), None);
+	}
+}
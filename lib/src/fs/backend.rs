@@ -0,0 +1,102 @@
+//! Pluggable sources of raw filesystem events.
+//!
+//! By default the [fs worker](super::worker) uses a [notify] watcher, as selected by
+//! [`Watcher`](super::Watcher). An [`FsBackend`] can be used instead, to get events from elsewhere,
+//! for example from a remote host over a connection. The backend is told which paths to watch
+//! through a [`BackendWatcher`], and provides the events it sees as a stream of [`notify::Event`]s,
+//! which go through the same processing (globs, ignore files, debounce) as those of a notify
+//! watcher.
+
+use std::{fmt, path::Path, sync::Arc};
+
+use futures::{stream::BoxStream, StreamExt};
+use tokio::task::JoinHandle;
+
+/// The stream of raw events from an [`FsBackend`].
+pub type RawEvents = BoxStream<'static, Result<notify::Event, notify::Error>>;
+
+/// A source of filesystem events, to use instead of a notify watcher.
+///
+/// Set it with [`RuntimeConfig::fs_backend()`](crate::config::RuntimeConfig::fs_backend()).
+pub trait FsBackend: fmt::Debug + Send + Sync {
+	/// Creates a new watcher, and the stream of events it sees.
+	///
+	/// This is called whenever the fs worker needs a new watcher: when it starts watching, and
+	/// again if it's reconfigured with a different backend or watcher kind. The previous watcher
+	/// is dropped, and its stream is no longer read.
+	///
+	/// Paths in the events are used as they are: unlike with notify watchers, they're not
+	/// canonicalised, and their file type isn't looked up.
+	fn create(&self) -> Result<(Box<dyn BackendWatcher>, RawEvents), notify::Error>;
+}
+
+/// The handle to tell an [`FsBackend`] what to watch.
+pub trait BackendWatcher: Send {
+	/// Starts watching a path, recursively or not.
+	fn watch(&mut self, path: &Path, recursive: bool) -> Result<(), notify::Error>;
+
+	/// Stops watching a path.
+	fn unwatch(&mut self, path: &Path) -> Result<(), notify::Error>;
+}
+
+/// An [`FsBackend`], as used by [`Watcher::Custom`](super::Watcher::Custom).
+///
+/// This only compares equal to its clones.
+#[derive(Clone, Debug)]
+pub struct Backend(pub Arc<dyn FsBackend>);
+
+impl PartialEq for Backend {
+	fn eq(&self, other: &Self) -> bool {
+		Arc::as_ptr(&self.0).cast::<()>() == Arc::as_ptr(&other.0).cast::<()>()
+	}
+}
+
+impl Eq for Backend {}
+
+/// A backend watcher, and the task feeding its events to the worker, as a notify watcher.
+///
+/// The task is stopped when this is dropped.
+pub(super) struct Adapter {
+	pub watcher: Box<dyn BackendWatcher>,
+	pub task: JoinHandle<()>,
+}
+
+impl Adapter {
+	/// Creates a watcher from the backend, and spawns a task handling its events.
+	pub fn create(
+		backend: &Backend,
+		mut handler: impl notify::EventHandler,
+	) -> Result<Self, notify::Error> {
+		let (watcher, mut events) = backend.0.create()?;
+		let task = tokio::spawn(async move {
+			while let Some(event) = events.next().await {
+				handler.handle_event(event);
+			}
+			tracing::debug!("fs backend event stream ended");
+		});
+		Ok(Self { watcher, task })
+	}
+}
+
+impl Drop for Adapter {
+	fn drop(&mut self) {
+		self.task.abort();
+	}
+}
+
+impl notify::Watcher for Adapter {
+	fn new<F: notify::EventHandler>(_event_handler: F) -> notify::Result<Self> {
+		Err(notify::Error::generic(
+			"custom fs backends are created through FsBackend::create",
+		))
+	}
+
+	fn watch(&mut self, path: &Path, recursive_mode: notify::RecursiveMode) -> notify::Result<()> {
+		self.watcher
+			.watch(path, recursive_mode == notify::RecursiveMode::Recursive)
+	}
+
+	fn unwatch(&mut self, path: &Path) -> notify::Result<()> {
+		self.watcher.unwatch(path)
+	}
+}
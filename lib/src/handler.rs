@@ -7,6 +7,8 @@
 //!   debugging and testing, as they don't allow any other output customisation),
 //! - for `()`, as placeholder.
 //!
+//! Handlers can be combined into new ones with the [`combinators`] provided by [`HandlerExt`].
+//!
 //! The implementation for [`FnMut`] only supports fns that return a [`Future`]. Unfortunately
 //! it's not possible to provide an implementation for fns that don't return a `Future` as well,
 //! so to call sync code you must either provide an async handler, or use the [`SyncFnHandler`]
@@ -84,22 +86,43 @@
 //! on_data(PrintDisplay(stderr()));
 //! ```
 
-use std::{error::Error, future::Future, io::Write, marker::PhantomData};
+use std::{
+	any::Any,
+	error::Error,
+	future::Future,
+	io::Write,
+	marker::PhantomData,
+	panic::{catch_unwind, AssertUnwindSafe},
+};
 
 use tokio::{runtime::Handle, task::block_in_place};
 
 use crate::error::RuntimeError;
 
+#[doc(inline)]
+pub use combinators::HandlerExt;
+
+pub mod combinators;
+
 /// A callable that can be used to hook into watchexec.
 pub trait Handler<T> {
 	/// Call the handler with the given data.
 	fn handle(&mut self, _data: T) -> Result<(), Box<dyn Error>>;
 }
 
+/// Wraps the error of a handler into a [`RuntimeError::Handler`], leaving a caught panic as the
+/// [`RuntimeError::HandlerPanic`] it already is.
 pub(crate) fn rte(ctx: &'static str, err: Box<dyn Error>) -> RuntimeError {
-	RuntimeError::Handler {
-		ctx,
-		err: err.to_string(),
+	match err.downcast::<RuntimeError>() {
+		Ok(err) if matches!(*err, RuntimeError::HandlerPanic(_)) => *err,
+		Ok(err) => RuntimeError::Handler {
+			ctx,
+			err: err.to_string(),
+		},
+		Err(err) => RuntimeError::Handler {
+			ctx,
+			err: err.to_string(),
+		},
 	}
 }
 
@@ -155,6 +178,37 @@ where
 	}
 }
 
+/// Wrapper for [`Handler`]s that are non-future [`Fn`]s, which catches panics.
+///
+/// A panic in the inner function is returned from the handler as a
+/// [`RuntimeError::HandlerPanic`], instead of unwinding through watchexec.
+pub(crate) struct CatchUnwindFnHandler<F>(pub F);
+
+impl<T, E, F> Handler<T> for CatchUnwindFnHandler<F>
+where
+	E: Error + 'static,
+	F: Fn(T) -> Result<(), E> + Send + 'static,
+{
+	fn handle(&mut self, data: T) -> Result<(), Box<dyn Error>> {
+		match catch_unwind(AssertUnwindSafe(|| (self.0)(data))) {
+			Ok(res) => res.map_err(|e| Box::new(e) as _),
+			Err(payload) => {
+				Err(Box::new(RuntimeError::HandlerPanic(panic_message(&*payload))) as _)
+			}
+		}
+	}
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+	if let Some(s) = payload.downcast_ref::<&str>() {
+		(*s).to_owned()
+	} else if let Some(s) = payload.downcast_ref::<String>() {
+		s.clone()
+	} else {
+		"(non-string panic payload)".to_owned()
+	}
+}
+
 impl<F, U, T, E> Handler<T> for F
 where
 	E: Error + 'static,
@@ -177,6 +231,10 @@ impl<T> Handler<T> for () {
 	}
 }
 
+/// Sends the value down the channel.
+///
+/// This never blocks, as the channel is unbounded. If the receiver is gone, the value is dropped
+/// and the handler errors.
 impl<T> Handler<T> for std::sync::mpsc::Sender<T>
 where
 	T: Send + 'static,
@@ -186,12 +244,31 @@ where
 	}
 }
 
+/// Sends the value down the channel, with [`try_send`](tokio::sync::mpsc::Sender::try_send).
+///
+/// This never blocks, so it's safe to use from any handler (some are called from within the
+/// runtime, where [`blocking_send`](tokio::sync::mpsc::Sender::blocking_send) would panic). If
+/// the channel is full or the receiver is gone, the value is dropped and the handler errors; for
+/// an error handler, this means the other error handlers are called with a
+/// [`RuntimeError::Handler`](crate::error::RuntimeError::Handler) about it.
+///
+/// ```
+/// use tokio::sync::mpsc;
+/// # use watchexec::config::InitConfig;
+/// let (errors, mut errors_r) = mpsc::channel(64);
+/// let mut init = InitConfig::default();
+/// init.on_error(errors);
+/// ```
 impl<T> Handler<T> for tokio::sync::mpsc::Sender<T>
 where
-	T: std::fmt::Debug + 'static,
+	T: Send + 'static,
 {
 	fn handle(&mut self, data: T) -> Result<(), Box<dyn Error>> {
-		self.try_send(data).map_err(|e| Box::new(e) as _)
+		use tokio::sync::mpsc::error::TrySendError;
+		self.try_send(data).map_err(|e| match e {
+			TrySendError::Full(_) => "channel full".into(),
+			TrySendError::Closed(_) => "channel closed".into(),
+		})
 	}
 }
 
@@ -220,3 +297,70 @@ where
 		writeln!(self.0, "{}", data).map_err(|e| Box::new(e) as _)
 	}
 }
+
+#[cfg(test)]
+mod test {
+	use std::io::{Error, ErrorKind};
+
+	use super::{rte, CatchUnwindFnHandler, Handler};
+	use crate::error::RuntimeError;
+
+	#[test]
+	fn catch_unwind_passes_through() {
+		let mut h = CatchUnwindFnHandler(|n: u8| {
+			if n == 0 {
+				Ok(())
+			} else {
+				Err(Error::new(ErrorKind::Other, "nonzero"))
+			}
+		});
+
+		assert!(h.handle(0).is_ok());
+		assert_eq!(
+			h.handle(1).err().map(|e| e.to_string()),
+			Some("nonzero".into())
+		);
+	}
+
+	#[test]
+	fn tokio_sender_reports_full_and_closed() {
+		let (mut s, r) = tokio::sync::mpsc::channel(1);
+		assert!(s.handle(1).is_ok());
+		assert_eq!(
+			s.handle(2).err().map(|e| e.to_string()),
+			Some("channel full".into())
+		);
+		drop(r);
+		assert_eq!(
+			s.handle(3).err().map(|e| e.to_string()),
+			Some("channel closed".into())
+		);
+	}
+
+	#[test]
+	fn catch_unwind_catches_panic() {
+		let mut h = CatchUnwindFnHandler(|_: ()| -> Result<(), Error> { panic!("oh no") });
+		assert_eq!(
+			h.handle(()).err().map(|e| e.to_string()),
+			Some("handler panicked: oh no".into())
+		);
+	}
+
+	#[test]
+	fn rte_keeps_caught_panics() {
+		let mut h = CatchUnwindFnHandler(|_: ()| -> Result<(), Error> { panic!("oh no") });
+		assert!(matches!(
+			h.handle(()).map_err(|e| rte("error hook", e)),
+			Err(RuntimeError::HandlerPanic(msg)) if msg == "oh no"
+		));
+
+		let mut h = CatchUnwindFnHandler(|_: ()| Err(Error::new(ErrorKind::Other, "nope")));
+		assert!(matches!(
+			h.handle(()).map_err(|e| rte("error hook", e)),
+			Err(RuntimeError::Handler {
+				ctx: "error hook",
+				..
+			})
+		));
+	}
+}
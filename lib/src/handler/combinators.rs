@@ -0,0 +1,188 @@
+//! Combinators to build [`Handler`]s from others.
+//!
+//! These are provided on every handler by the [`HandlerExt`] trait:
+//!
+//! ```
+//! use std::io::stderr;
+//! use watchexec::handler::{Handler, HandlerExt, PrintDisplay, SyncFnHandler};
+//! # fn on_data<T: Handler<String>>(_: T) {}
+//! let (metrics, _r) = std::sync::mpsc::channel();
+//! let (log, _r) = std::sync::mpsc::channel();
+//!
+//! // log, and count
+//! on_data(log.tee(metrics));
+//!
+//! // write to a file, or to stderr if that fails
+//! let to_file = SyncFnHandler::from(|line: String| std::fs::write("log.txt", line));
+//! on_data(to_file.fallback(PrintDisplay(stderr())));
+//! ```
+//!
+//! The first handler fixes the type of the data. Where it can take several (like
+//! [`PrintDisplay`](super::PrintDisplay)), it may need to be given:
+//! `HandlerExt::<String>::tee(PrintDisplay(stdout()), metrics)`.
+//!
+//! As most handlers are given ownership of the data, those which call more than one handler need
+//! it to be [`Clone`].
+
+use std::error::Error;
+
+use super::Handler;
+
+/// Methods to combine [`Handler`]s, available on every handler.
+///
+/// See the [module documentation](self).
+pub trait HandlerExt<T>: Handler<T> + Sized {
+	/// Calls this handler, then the `next` one if this one succeeded.
+	fn and_then<H: Handler<T>>(self, next: H) -> AndThen<Self, H>
+	where
+		T: Clone,
+	{
+		AndThen(self, next)
+	}
+
+	/// Calls this handler, transforming its errors with `f`.
+	fn map_err<F>(self, f: F) -> MapErr<Self, F>
+	where
+		F: FnMut(Box<dyn Error>) -> Box<dyn Error>,
+	{
+		MapErr(self, f)
+	}
+
+	/// Calls this handler, then the `default` one only if this one errored.
+	///
+	/// If the default errors too, that's the error returned.
+	fn fallback<H: Handler<T>>(self, default: H) -> Fallback<Self, H>
+	where
+		T: Clone,
+	{
+		Fallback(self, default)
+	}
+
+	/// Calls this handler and the `other` one, whether or not this one errors.
+	///
+	/// If either errors, the error of the first one to do so is returned.
+	fn tee<H: Handler<T>>(self, other: H) -> Tee<Self, H>
+	where
+		T: Clone,
+	{
+		Tee(self, other)
+	}
+}
+
+impl<T, H: Handler<T>> HandlerExt<T> for H {}
+
+/// Handler returned by [`HandlerExt::and_then()`].
+#[derive(Clone, Debug)]
+pub struct AndThen<A, B>(A, B);
+
+impl<T: Clone, A: Handler<T>, B: Handler<T>> Handler<T> for AndThen<A, B> {
+	fn handle(&mut self, data: T) -> Result<(), Box<dyn Error>> {
+		self.0.handle(data.clone())?;
+		self.1.handle(data)
+	}
+}
+
+/// Handler returned by [`HandlerExt::map_err()`].
+#[derive(Clone, Debug)]
+pub struct MapErr<H, F>(H, F);
+
+impl<T, H, F> Handler<T> for MapErr<H, F>
+where
+	H: Handler<T>,
+	F: FnMut(Box<dyn Error>) -> Box<dyn Error>,
+{
+	fn handle(&mut self, data: T) -> Result<(), Box<dyn Error>> {
+		self.0.handle(data).map_err(&mut self.1)
+	}
+}
+
+/// Handler returned by [`HandlerExt::fallback()`].
+#[derive(Clone, Debug)]
+pub struct Fallback<A, B>(A, B);
+
+impl<T: Clone, A: Handler<T>, B: Handler<T>> Handler<T> for Fallback<A, B> {
+	fn handle(&mut self, data: T) -> Result<(), Box<dyn Error>> {
+		self.0.handle(data.clone()).or_else(|_| self.1.handle(data))
+	}
+}
+
+/// Handler returned by [`HandlerExt::tee()`].
+#[derive(Clone, Debug)]
+pub struct Tee<A, B>(A, B);
+
+impl<T: Clone, A: Handler<T>, B: Handler<T>> Handler<T> for Tee<A, B> {
+	fn handle(&mut self, data: T) -> Result<(), Box<dyn Error>> {
+		let first = self.0.handle(data.clone());
+		let second = self.1.handle(data);
+		first.and(second)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use std::{
+		io::{Error, ErrorKind},
+		sync::mpsc,
+	};
+
+	use super::{super::SyncFnHandler, Handler, HandlerExt};
+
+	fn failing() -> SyncFnHandler<u8, Error, impl FnMut(u8) -> Result<(), Error> + Send> {
+		SyncFnHandler::from(|n: u8| Err(Error::new(ErrorKind::Other, format!("failed {}", n))))
+	}
+
+	#[test]
+	fn and_then_stops_at_error() {
+		let (s, r) = mpsc::channel();
+		let mut h = s.clone().and_then(s);
+		assert!(h.handle(1).is_ok());
+		assert_eq!(r.try_iter().collect::<Vec<_>>(), vec![1, 1]);
+
+		let (s, r) = mpsc::channel();
+		let mut h = failing().and_then(s);
+		assert!(h.handle(2).is_err());
+		assert!(r.try_recv().is_err());
+	}
+
+	#[test]
+	fn map_err_transforms_errors() {
+		let mut h = failing().map_err(|err| format!("wrapped: {}", err).into());
+		assert_eq!(
+			h.handle(3).err().map(|e| e.to_string()),
+			Some("wrapped: failed 3".into())
+		);
+	}
+
+	#[test]
+	fn fallback_only_runs_on_error() {
+		let (s, r) = mpsc::channel();
+		let (d, dr) = mpsc::channel();
+		let mut h = s.fallback(d.clone());
+		assert!(h.handle(4).is_ok());
+		assert_eq!(r.try_recv().ok(), Some(4));
+		assert!(dr.try_recv().is_err());
+
+		let mut h = failing().fallback(d);
+		assert!(h.handle(5).is_ok());
+		assert_eq!(dr.try_recv().ok(), Some(5));
+	}
+
+	#[test]
+	fn tee_runs_both_and_keeps_first_error() {
+		let (s, r) = mpsc::channel();
+		let mut h = failing().tee(s);
+		assert_eq!(
+			h.handle(6).err().map(|e| e.to_string()),
+			Some("failed 6".into())
+		);
+		assert_eq!(r.try_recv().ok(), Some(6));
+
+		let mut h = failing()
+			.map_err(|_| "first".into())
+			.tee(failing().map_err(|_| "second".into()));
+		assert_eq!(
+			h.handle(7).err().map(|e| e.to_string()),
+			Some("first".into())
+		);
+	}
+}
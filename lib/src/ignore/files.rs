@@ -6,7 +6,7 @@ use std::{
 };
 
 use tokio::fs::{metadata, read_dir};
-use tracing::{trace, trace_span};
+use tracing::{trace, trace_span, Instrument};
 
 use crate::{paths::PATH_SEPARATOR, project::ProjectType};
 
@@ -62,10 +62,13 @@ pub async fn from_origin(path: impl AsRef<Path>) -> (Vec<IgnoreFile>, Vec<Error>
 	match find_file(base.join(".git/config")).await {
 		Err(err) => errors.push(err),
 		Ok(None) => {}
-		Ok(Some(path)) => match git2::Config::open(&path) {
-			Err(err) => errors.push(Error::new(ErrorKind::Other, err)),
-			Ok(config) => {
-				if let Ok(excludes) = config.get_path("core.excludesFile") {
+		Ok(Some(path)) => {
+			// the config isn't Send, so it must be dropped before awaiting
+			let excludes =
+				git2::Config::open(&path).map(|config| config.get_path("core.excludesFile").ok());
+			match excludes {
+				Err(err) => errors.push(Error::new(ErrorKind::Other, err)),
+				Ok(Some(excludes)) => {
 					discover_file(
 						&mut files,
 						&mut errors,
@@ -75,8 +78,9 @@ pub async fn from_origin(path: impl AsRef<Path>) -> (Vec<IgnoreFile>, Vec<Error>
 					)
 					.await;
 				}
+				Ok(None) => {}
 			}
-		},
+		}
 	}
 
 	discover_file(
@@ -194,10 +198,13 @@ pub async fn from_environment() -> (Vec<IgnoreFile>, Vec<Error>) {
 	}
 
 	let mut found_git_global = false;
-	match git2::Config::open_default() {
+	// the config isn't Send, so it must be dropped before awaiting
+	let excludes =
+		git2::Config::open_default().map(|config| config.get_path("core.excludesFile").ok());
+	match excludes {
 		Err(err) => errors.push(Error::new(ErrorKind::Other, err)),
-		Ok(config) => {
-			if let Ok(excludes) = config.get_path("core.excludesFile") {
+		Ok(excludes) => {
+			if let Some(excludes) = excludes {
 				if discover_file(
 					&mut files,
 					&mut errors,
@@ -291,8 +298,10 @@ pub(crate) async fn discover_file(
 	applies_to: Option<ProjectType>,
 	path: PathBuf,
 ) -> bool {
-	let _span = trace_span!("discover_file", ?path, ?applies_in, ?applies_to).entered();
-	match find_file(path).await {
+	let span = trace_span!("discover_file", ?path, ?applies_in, ?applies_to);
+	let found = find_file(path).await;
+	let _span = span.entered();
+	match found {
 		Err(err) => {
 			trace!(?err, "found an error");
 			errors.push(err);
@@ -374,70 +383,75 @@ impl DirTourist {
 
 	pub async fn next(&mut self) -> Visit {
 		if let Some(path) = self.to_visit.pop() {
-			let _span = trace_span!("visit_path", ?path).entered();
-			if self.must_skip(&path) {
-				trace!("in skip list");
+			let span = trace_span!("visit_path", ?path);
+			self.visit(path).instrument(span).await
+		} else {
+			Visit::Done
+		}
+	}
+
+	async fn visit(&mut self, path: PathBuf) -> Visit {
+		if self.must_skip(&path) {
+			trace!("in skip list");
+			return Visit::Skip;
+		}
+
+		if !self.filter.check_dir(&path) {
+			trace!("path is ignored, adding to skip list");
+			self.skip(path);
+			return Visit::Skip;
+		}
+
+		let mut dir = match read_dir(&path).await {
+			Ok(dir) => dir,
+			Err(err) => {
+				trace!("failed to read dir: {}", err);
+				self.errors.push(err);
 				return Visit::Skip;
 			}
+		};
 
-			if !self.filter.check_dir(&path) {
-				trace!("path is ignored, adding to skip list");
-				self.skip(path);
+		while let Some(entry) = match dir.next_entry().await {
+			Ok(entry) => entry,
+			Err(err) => {
+				trace!("failed to read dir entries: {}", err);
+				self.errors.push(err);
 				return Visit::Skip;
 			}
+		} {
+			let path = entry.path();
+			let file_type = entry.file_type().await;
+			let _span = trace_span!("dir_entry", ?path).entered();
 
-			let mut dir = match read_dir(&path).await {
-				Ok(dir) => dir,
-				Err(err) => {
-					trace!("failed to read dir: {}", err);
-					self.errors.push(err);
-					return Visit::Skip;
-				}
-			};
-
-			while let Some(entry) = match dir.next_entry().await {
-				Ok(entry) => entry,
-				Err(err) => {
-					trace!("failed to read dir entries: {}", err);
-					self.errors.push(err);
-					return Visit::Skip;
-				}
-			} {
-				let path = entry.path();
-				let _span = trace_span!("dir_entry", ?path).entered();
-
-				if self.must_skip(&path) {
-					trace!("in skip list");
-					continue;
-				}
+			if self.must_skip(&path) {
+				trace!("in skip list");
+				continue;
+			}
 
-				match entry.file_type().await {
-					Ok(ft) => {
-						if ft.is_dir() {
-							if !self.filter.check_dir(&path) {
-								trace!("path is ignored, adding to skip list");
-								self.skip(path);
-								continue;
-							}
-
-							trace!("found a dir, adding to list");
-							self.to_visit.push(path);
-						} else {
-							trace!("not a dir");
+			match file_type {
+				Ok(ft) => {
+					if ft.is_dir() {
+						if !self.filter.check_dir(&path) {
+							trace!("path is ignored, adding to skip list");
+							self.skip(path);
+							continue;
 						}
+
+						trace!("found a dir, adding to list");
+						self.to_visit.push(path);
+					} else {
+						trace!("not a dir");
 					}
-					Err(err) => {
-						trace!("failed to read filetype, adding to skip list: {}", err);
-						self.errors.push(err);
-						self.skip(path);
-					}
+				}
+				Err(err) => {
+					trace!("failed to read filetype, adding to skip list: {}", err);
+					self.errors.push(err);
+					self.skip(path);
 				}
 			}
-
-			Visit::Find(path)
-		} else {
-			Visit::Done
 		}
+
+		Visit::Find(path)
 	}
 
 	pub fn skip(&mut self, path: PathBuf) {
@@ -224,15 +224,22 @@ impl IgnoreFilterer {
 	/// Note that this is a slightly different implementation than the [`Filterer`] trait, as the
 	/// latter handles events with multiple associated paths.
 	pub fn check_dir(&self, path: &Path) -> bool {
-		let _span = trace_span!("check_dir", ?path).entered();
+		self.check_path(path, true)
+	}
+
+	/// Check a particular path against the ignore set.
+	///
+	/// Returns `false` if the path should be ignored.
+	pub(crate) fn check_path(&self, path: &Path, is_dir: bool) -> bool {
+		let _span = trace_span!("check_path", ?path, ?is_dir).entered();
 
 		trace!("checking against compiled ignore files");
 		match if path.strip_prefix(&self.origin).is_ok() {
 			trace!("checking against path or parents");
-			self.compiled.matched_path_or_any_parents(path, true)
+			self.compiled.matched_path_or_any_parents(path, is_dir)
 		} else {
 			trace!("checking against path only");
-			self.compiled.matched(path, true)
+			self.compiled.matched(path, is_dir)
 		} {
 			Match::None => {
 				trace!("no match (pass)");
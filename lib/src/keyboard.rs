@@ -0,0 +1,259 @@
+//! Event source for key presses on the terminal.
+//!
+//! When enabled with [`RuntimeConfig::enable_keyboard()`][crate::config::RuntimeConfig::enable_keyboard()],
+//! this reads standard input one key at a time and emits events tagged with
+//! [`Source::Keyboard`] and [`Tag::Keyboard`], which handlers can then map to outcomes (e.g. `r` to
+//! restart the command, `q` to quit).
+//!
+//! To do so, the terminal is switched out of line-buffered mode and input echo is disabled. Control
+//! characters keep generating signals, such that Ctrl-C still reaches the
+//! [signal source][crate::signal::source]. The terminal settings are restored as soon as the worker
+//! stops, including when it is aborted, or when the reading thread panics.
+//!
+//! If standard input is not a terminal, the worker does nothing and never reads from it, leaving it
+//! for the command (or the embedding application) to use. Conversely, when it is a terminal, key
+//! presses will be consumed by watchexec rather than reaching the command.
+//!
+//! This is currently only supported on Unix; elsewhere, the worker does nothing.
+
+use tokio::sync::{mpsc, watch};
+use tracing::debug;
+
+use crate::{
+	error::{CriticalError, RuntimeError},
+	event::{Event, Source, Tag},
+};
+
+/// Launch the keyboard event worker.
+///
+/// While you _can_ run several, you **must** only have one, as they compete for input.
+///
+/// The worker stops when `shutdown` is set to `true`.
+pub async fn worker(
+	errors: mpsc::Sender<RuntimeError>,
+	events: mpsc::Sender<Event>,
+	shutdown: watch::Receiver<bool>,
+) -> Result<(), CriticalError> {
+	imp_worker(errors, events, shutdown).await
+}
+
+#[cfg(unix)]
+async fn imp_worker(
+	errors: mpsc::Sender<RuntimeError>,
+	events: mpsc::Sender<Event>,
+	mut shutdown: watch::Receiver<bool>,
+) -> Result<(), CriticalError> {
+	use std::sync::{
+		atomic::{AtomicBool, Ordering},
+		Arc,
+	};
+
+	use nix::unistd::isatty;
+	use tokio::task::spawn_blocking;
+
+	const STDIN: i32 = 0;
+
+	if !isatty(STDIN).unwrap_or(false) {
+		debug!("stdin is not a terminal, keyboard worker idling");
+		shutdown.changed().await.ok();
+		return Ok(());
+	}
+
+	// Held by this future rather than the reading thread, so the terminal is restored as soon as
+	// it completes or is dropped (e.g. aborted), not when the thread next checks whether to stop.
+	let _term = match unix::TermGuard::new(STDIN) {
+		Ok(guard) => guard,
+		Err(err) => {
+			errors
+				.send(RuntimeError::IoError {
+					about: "setting up terminal for keyboard input",
+					err: err.into(),
+				})
+				.await?;
+			return Ok(());
+		}
+	};
+
+	// Set by the guard when this future completes or is dropped (e.g. aborted), to stop the thread.
+	// It's dropped before the terminal guard, so the thread stops reading keys first.
+	struct Stop(Arc<AtomicBool>);
+	impl Drop for Stop {
+		fn drop(&mut self) {
+			self.0.store(true, Ordering::SeqCst);
+		}
+	}
+
+	let stop = Stop(Arc::new(AtomicBool::new(false)));
+	let stopping = stop.0.clone();
+
+	debug!("launching keyboard worker");
+	let mut reader = spawn_blocking(move || unix::read_keys(STDIN, &stopping, &errors, &events));
+
+	tokio::select! {
+		res = &mut reader => {
+			return res.map_err(CriticalError::MainTaskJoin)?;
+		}
+		Ok(_) = shutdown.changed() => {
+			debug!("shutdown requested, stopping keyboard worker");
+		}
+	}
+
+	drop(stop);
+	reader.await.map_err(CriticalError::MainTaskJoin)?
+}
+
+#[cfg(unix)]
+mod unix {
+	use std::{
+		str::from_utf8,
+		sync::atomic::{AtomicBool, Ordering},
+	};
+
+	use nix::{
+		poll::{poll, PollFd, PollFlags},
+		sys::termios::{
+			tcgetattr, tcsetattr, LocalFlags, SetArg, SpecialCharacterIndices, Termios,
+		},
+		unistd::read,
+	};
+	use tokio::sync::mpsc;
+	use tracing::{debug, trace, warn};
+
+	use super::key_event;
+	use crate::{
+		error::{CriticalError, RuntimeError},
+		event::Event,
+	};
+
+	/// How often to check whether to stop while waiting for input, in milliseconds.
+	const POLL_INTERVAL: i32 = 100;
+
+	/// Restores the original terminal settings when dropped, including while unwinding.
+	pub(super) struct TermGuard {
+		fd: i32,
+		original: Termios,
+	}
+
+	impl TermGuard {
+		pub(super) fn new(fd: i32) -> nix::Result<Self> {
+			let original = tcgetattr(fd)?;
+			let mut keys = original.clone();
+			keys.local_flags
+				.remove(LocalFlags::ICANON | LocalFlags::ECHO);
+			keys.control_chars[SpecialCharacterIndices::VMIN as usize] = 1;
+			keys.control_chars[SpecialCharacterIndices::VTIME as usize] = 0;
+			tcsetattr(fd, SetArg::TCSANOW, &keys)?;
+			Ok(Self { fd, original })
+		}
+	}
+
+	impl Drop for TermGuard {
+		fn drop(&mut self) {
+			trace!("restoring terminal settings");
+			if let Err(err) = tcsetattr(self.fd, SetArg::TCSANOW, &self.original) {
+				warn!(%err, "could not restore terminal settings");
+			}
+		}
+	}
+
+	pub(super) fn read_keys(
+		fd: i32,
+		stop: &AtomicBool,
+		errors: &mpsc::Sender<RuntimeError>,
+		events: &mpsc::Sender<Event>,
+	) -> Result<(), CriticalError> {
+		// bytes of a character which has not been fully read yet
+		let mut pending = Vec::with_capacity(4);
+		let mut buf = [0_u8; 64];
+		while !stop.load(Ordering::SeqCst) {
+			let mut fds = [PollFd::new(fd, PollFlags::POLLIN)];
+			match poll(&mut fds, POLL_INTERVAL) {
+				Ok(0) | Err(nix::Error::EINTR) => continue,
+				Ok(_) => {}
+				Err(err) => {
+					errors.blocking_send(RuntimeError::IoError {
+						about: "polling for keyboard input",
+						err: err.into(),
+					})?;
+					break;
+				}
+			}
+
+			let n = match read(fd, &mut buf) {
+				Ok(0) => {
+					debug!("end of keyboard input, stopping");
+					break;
+				}
+				Ok(n) => n,
+				Err(nix::Error::EINTR | nix::Error::EAGAIN) => continue,
+				Err(err) => {
+					errors.blocking_send(RuntimeError::IoError {
+						about: "reading keyboard input",
+						err: err.into(),
+					})?;
+					break;
+				}
+			};
+
+			pending.extend_from_slice(&buf[..n]);
+			let valid = match from_utf8(&pending) {
+				Ok(s) => s.len(),
+				Err(err) if err.error_len().is_none() => err.valid_up_to(),
+				Err(err) => {
+					trace!(%err, "skipping invalid keyboard input");
+					pending.clear();
+					continue;
+				}
+			};
+
+			let keys: Vec<char> = from_utf8(&pending[..valid])
+				.unwrap_or_default()
+				.chars()
+				.collect();
+			pending.drain(..valid);
+
+			for key in keys {
+				trace!(?key, "key pressed");
+				if let Err(err) = events.blocking_send(key_event(key)) {
+					errors.blocking_send(RuntimeError::EventChannelSend {
+						ctx: "keyboard",
+						err,
+					})?;
+				}
+			}
+		}
+
+		Ok(())
+	}
+}
+
+#[cfg(not(unix))]
+async fn imp_worker(
+	_errors: mpsc::Sender<RuntimeError>,
+	_events: mpsc::Sender<Event>,
+	mut shutdown: watch::Receiver<bool>,
+) -> Result<(), CriticalError> {
+	debug!("keyboard input is not supported on this platform, keyboard worker idling");
+	shutdown.changed().await.ok();
+	Ok(())
+}
+
+#[cfg_attr(not(unix), allow(dead_code))]
+fn key_event(key: char) -> Event {
+	Event {
+		tags: vec![Tag::Source(Source::Keyboard), Tag::Keyboard(key)],
+		metadata: Default::default(),
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn key_event_is_tagged() {
+		let event = key_event('r');
+		assert_eq!(event.sources().collect::<Vec<_>>(), vec![Source::Keyboard]);
+		assert_eq!(event.keys().collect::<Vec<_>>(), vec!['r']);
+	}
+}
@@ -105,9 +105,18 @@ pub mod event;
 pub mod filter;
 pub mod fs;
 pub mod ignore;
+pub mod keyboard;
+pub mod metrics;
 pub mod paths;
 pub mod project;
 pub mod signal;
+pub mod timer;
+
+#[cfg(feature = "serde")]
+pub mod replay;
+
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 
 // the core experience
 pub mod config;
@@ -115,4 +124,4 @@ pub mod handler;
 mod watchexec;
 
 #[doc(inline)]
-pub use crate::watchexec::Watchexec;
+pub use crate::watchexec::{Lifecycle, ReconfigReport, ShutdownReason, Status, Watchexec};
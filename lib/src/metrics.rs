@@ -0,0 +1,78 @@
+//! Counters of what the workers have been doing, to help tune timings.
+//!
+//! The [fs][crate::fs] and [action][crate::action] workers update a shared [`Counters`] as they
+//! go. A [`Metrics`] snapshot of these can be obtained at any time, e.g. with
+//! [`Watchexec::metrics()`][crate::Watchexec::metrics()]. Comparing snapshots taken before and
+//! after some activity gives an idea of how much the debounce and throttle are collapsing events.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// A snapshot of the [`Counters`].
+///
+/// All counts are totals since the counters were created (usually when the [`Watchexec`] instance
+/// was), and wrap around on overflow.
+///
+/// [`Watchexec`]: crate::Watchexec
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Metrics {
+	/// Raw events received from the filesystem watcher.
+	pub fs_events: u64,
+
+	/// Raw filesystem events which were merged into others by the
+	/// [debounce](crate::fs::WorkingData#structfield.debounce).
+	///
+	/// The filesystem worker emits `fs_events - fs_events_coalesced` events, minus those dropped
+	/// by the path globs.
+	pub fs_events_coalesced: u64,
+
+	/// Events received by the action worker, from any source.
+	pub events_received: u64,
+
+	/// Events rejected by the filterer or the filter predicate, dropped by the
+	/// [event mapper](crate::action::WorkingData#structfield.event_mapper), or dropped because all
+	/// their paths [vanished](crate::action::WorkingData#structfield.drop_vanished).
+	pub events_filtered: u64,
+
+	/// Actions fired, i.e. calls to the action handler.
+	pub actions: u64,
+
+	/// Actions whose outcome was not applied, because the handler errored, or suppressed in part
+	/// by the completion loop guard.
+	pub actions_suppressed: u64,
+}
+
+/// The live counters behind [`Metrics`].
+///
+/// This is shared between the workers, and only updated by them.
+#[derive(Debug, Default)]
+pub struct Counters {
+	pub(crate) fs_events: AtomicU64,
+	pub(crate) fs_events_coalesced: AtomicU64,
+	pub(crate) events_received: AtomicU64,
+	pub(crate) events_filtered: AtomicU64,
+	pub(crate) actions: AtomicU64,
+	pub(crate) actions_suppressed: AtomicU64,
+}
+
+impl Counters {
+	/// Takes a snapshot of the counters.
+	///
+	/// Each counter is read separately, so the snapshot may not be exactly consistent if the
+	/// workers are active at the time.
+	pub fn snapshot(&self) -> Metrics {
+		Metrics {
+			fs_events: self.fs_events.load(Ordering::Relaxed),
+			fs_events_coalesced: self.fs_events_coalesced.load(Ordering::Relaxed),
+			events_received: self.events_received.load(Ordering::Relaxed),
+			events_filtered: self.events_filtered.load(Ordering::Relaxed),
+			actions: self.actions.load(Ordering::Relaxed),
+			actions_suppressed: self.actions_suppressed.load(Ordering::Relaxed),
+		}
+	}
+}
+
+/// Adds to a counter.
+pub(crate) fn add(counter: &AtomicU64, n: u64) {
+	counter.fetch_add(n, Ordering::Relaxed);
+}
@@ -0,0 +1,186 @@
+//! Recording the events received by a runtime to a file, and replaying them into another.
+//!
+//! This is for reproducing intermittent bugs: record the events while the problem happens, then
+//! replay them into a fresh [`Watchexec`] as many times as needed, without having to make the same
+//! changes to the same files. It needs the `serde` feature.
+//!
+//! ```no_run
+//! use watchexec::{
+//!     config::{InitConfig, RuntimeConfig},
+//!     replay::{record, replay, Pace},
+//!     Watchexec,
+//! };
+//!
+//! # async fn recording() -> Result<(), Box<dyn std::error::Error>> {
+//! // while reproducing the problem
+//! let wx = Watchexec::new(InitConfig::default(), RuntimeConfig::default())?;
+//! tokio::spawn(record(wx.event_stream(), "events.jsonl"));
+//! wx.main().await??;
+//!
+//! // later, with the same configuration
+//! let wx = Watchexec::new(InitConfig::default(), RuntimeConfig::default())?;
+//! let main = wx.main();
+//! replay(&wx, "events.jsonl", Pace::Original).await?;
+//! # Ok(()) }
+//! ```
+//!
+//! The log is in [JSON Lines](https://jsonlines.org) format: each line is an object with the
+//! `event`, and its `offset` from the start of the recording (as serde serializes a
+//! [`Duration`]). Events which can't be serialized (like
+//! [`WouldHaveRun`](crate::event::Tag::WouldHaveRun) events) are left out.
+//!
+//! The [event stream](Watchexec::event_stream()) has events from every source, including the
+//! completions of the commands which were run: replaying these into a runtime which runs the
+//! command too will see both. It also skips events if the recorder falls behind; most of the time
+//! writing a line is much faster than events come in, but a burst of filesystem events larger than
+//! the [`event_channel_size`](crate::config::InitConfig#structfield.event_channel_size) can be cut.
+
+use std::{io, path::Path, time::Duration};
+
+use futures::{Stream, StreamExt};
+use serde_crate::{Deserialize, Serialize};
+use tokio::{
+	fs::File,
+	io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+	time::{sleep_until, Instant},
+};
+use tracing::{debug, trace};
+
+use crate::{error::CriticalError, event::Event, Watchexec};
+
+/// How fast to [replay] events.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Pace {
+	/// With the same time between events as when they were recorded.
+	Original,
+
+	/// As fast as the runtime takes them.
+	///
+	/// Note that this changes how events are batched into actions by the
+	/// [throttle](crate::action::WorkingData#structfield.throttle).
+	Immediate,
+}
+
+/// A line of the log.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "serde_crate")]
+struct Entry {
+	offset: Duration,
+	event: Event,
+}
+
+/// Writes the events from the stream to a log file, until the stream ends.
+///
+/// The file is created, or truncated if it exists. Each event is written out as it's received, so
+/// the log is complete up to the last event even if the process doesn't stop cleanly. Returns the
+/// number of events written.
+///
+/// See the [module documentation](self) for the format.
+pub async fn record(
+	events: impl Stream<Item = Event>,
+	path: impl AsRef<Path>,
+) -> Result<usize, CriticalError> {
+	let path = path.as_ref();
+	debug!(?path, "recording events");
+	let mut file = File::create(path)
+		.await
+		.map_err(|err| CriticalError::IoError {
+			about: "creating replay log",
+			err,
+		})?;
+
+	let start = Instant::now();
+	let mut written = 0;
+	futures::pin_mut!(events);
+	while let Some(event) = events.next().await {
+		let entry = Entry {
+			offset: start.elapsed(),
+			event,
+		};
+		let mut line = match serde_json::to_vec(&entry) {
+			Ok(line) => line,
+			Err(err) => {
+				trace!(%err, event=?entry.event, "event can't be serialized, not recording it");
+				continue;
+			}
+		};
+		line.push(b'\n');
+
+		file.write_all(&line)
+			.await
+			.map_err(|err| CriticalError::IoError {
+				about: "writing replay log",
+				err,
+			})?;
+		written += 1;
+	}
+
+	file.flush().await.map_err(|err| CriticalError::IoError {
+		about: "writing replay log",
+		err,
+	})?;
+	debug!(?path, %written, "event stream ended, recording done");
+	Ok(written)
+}
+
+/// Reads the events from a log file, and sends them to the runtime.
+///
+/// Events are sent with [`Watchexec::send_event()`], at the given [`Pace`]: with
+/// [`Original`](Pace::Original), the offsets of the events are counted from when this is called.
+/// Returns the number of events sent, once the last has been.
+///
+/// The whole file is checked before any event is sent, so a log which doesn't parse doesn't get
+/// replayed partway. See the [module documentation](self) for the format.
+pub async fn replay(
+	wx: &Watchexec,
+	path: impl AsRef<Path>,
+	pace: Pace,
+) -> Result<usize, CriticalError> {
+	let path = path.as_ref();
+	let file = File::open(path)
+		.await
+		.map_err(|err| CriticalError::IoError {
+			about: "opening replay log",
+			err,
+		})?;
+
+	let mut entries = Vec::new();
+	let mut lines = BufReader::new(file).lines();
+	let mut number = 0;
+	while let Some(line) = lines
+		.next_line()
+		.await
+		.map_err(|err| CriticalError::IoError {
+			about: "reading replay log",
+			err,
+		})? {
+		number += 1;
+		if line.trim().is_empty() {
+			continue;
+		}
+
+		let entry: Entry = serde_json::from_str(&line).map_err(|err| CriticalError::IoError {
+			about: "parsing replay log",
+			err: io::Error::new(
+				io::ErrorKind::InvalidData,
+				format!("line {}: {}", number, err),
+			),
+		})?;
+		entries.push(entry);
+	}
+
+	debug!(?path, events=%entries.len(), ?pace, "replaying events");
+	let start = Instant::now();
+	let sent = entries.len();
+	for Entry { offset, event } in entries {
+		if pace == Pace::Original {
+			sleep_until(start + offset).await;
+		}
+
+		trace!(?event, "replaying event");
+		wx.send_event(event).await?;
+	}
+
+	Ok(sent)
+}
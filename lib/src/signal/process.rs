@@ -4,6 +4,8 @@ use std::str::FromStr;
 
 #[cfg(unix)]
 use command_group::Signal as NixSignal;
+#[cfg(feature = "serde")]
+use serde_crate::{Deserialize, Serialize};
 
 use crate::error::SignalParseError;
 
@@ -16,6 +18,11 @@ use super::source::MainSignal;
 /// On Unix, there are several "first-class" signals which have their own variants, and a generic
 /// [`Custom`][SubSignal::Custom] variant which can be used to send arbitrary signals.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(
+	feature = "serde",
+	derive(Serialize, Deserialize),
+	serde(crate = "serde_crate", rename_all = "kebab-case")
+)]
 pub enum SubSignal {
 	/// Indicate that the terminal is disconnected.
 	///
@@ -51,8 +58,9 @@ pub enum SubSignal {
 
 	/// Indicate that the process should stop.
 	///
-	/// On Unix, this is `SIGTERM`. On Windows, this is ignored for now but may be supported in the
-	/// future (see [#219](https://github.com/watchexec/watchexec/issues/219)).
+	/// On Unix, this is `SIGTERM`. On Windows, there is no equivalent request, so this is
+	/// `TerminateProcess`, like [`ForceStop`](SubSignal::ForceStop). This may change in the future
+	/// (see [#219](https://github.com/watchexec/watchexec/issues/219)).
 	///
 	/// On Unix, this signal generally indicates an action taken by the system, so it may be handled
 	/// differently than an interruption.
@@ -1,11 +1,20 @@
 //! Event source for signals / notifications sent to the main process.
 
-use tokio::{select, sync::mpsc};
-use tracing::{debug, trace};
+#[cfg(feature = "serde")]
+use serde_crate::{Deserialize, Serialize};
+use std::{sync::Arc, time::Duration};
+
+use tokio::{
+	select,
+	sync::{mpsc, watch, Notify},
+	time::Instant,
+};
+use tracing::{debug, trace, warn};
 
 use crate::{
+	config::OverflowPolicy,
 	error::{CriticalError, RuntimeError},
-	event::{Event, Source, Tag},
+	event::Event,
 };
 
 /// A notification sent to the main (watchexec) process.
@@ -14,7 +23,12 @@ use crate::{
 /// will be produced: they are respectively `Ctrl-C` (SIGINT) and `Ctrl-Break` (SIGBREAK).
 /// `Ctrl-Close` (the equivalent of `SIGHUP` on Unix, without the semantics of configuration reload)
 /// is not supported, and on console close the process will be terminated by the OS.
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+#[cfg_attr(
+	feature = "serde",
+	derive(Serialize, Deserialize),
+	serde(crate = "serde_crate", rename_all = "kebab-case")
+)]
 pub enum MainSignal {
 	/// Received when the terminal is disconnected.
 	///
@@ -23,6 +37,14 @@ pub enum MainSignal {
 	/// This signal is available because it is a common signal used to reload configuration files,
 	/// and it is reasonable that either watchexec could make use of it, or that it should be passed
 	/// on to a sub process.
+	///
+	/// It is captured by the signal source (so it does not reach the command nor terminate
+	/// watchexec) and emitted as a [`Tag::Signal`] event. To reload configuration on hangup, check
+	/// for it in the action handler and call [`Watchexec::reconfigure()`][crate::Watchexec::reconfigure()]
+	/// with the new configuration (see the `reload` example); to pass it on to the command instead,
+	/// use [`Outcome::Signal`][crate::action::Outcome::Signal]. Either can also be set up without
+	/// going through the action handler with the
+	/// [`signal_map`][crate::action::WorkingData#structfield.signal_map].
 	Hangup,
 
 	/// Received to indicate that the process should stop.
@@ -70,34 +92,80 @@ pub enum MainSignal {
 ///
 /// While you _can_ run several, you **must** only have one. This may be enforced later.
 ///
+/// The worker stops when `shutdown` is set to `true`. If the `events` channel is full, what happens
+/// to a new signal event depends on the `overflow` policy.
+///
+/// If an [`Interrupt`](MainSignal::Interrupt) is received within `force_quit_window` of the
+/// previous one, `quit` is notified as well as the event being sent, so the action worker can
+/// force-quit. See
+/// [`InitConfig::force_quit_window`](crate::config::InitConfig#structfield.force_quit_window).
+///
 /// # Examples
 ///
 /// Direct usage:
 ///
 /// ```no_run
-/// use tokio::sync::mpsc;
-/// use watchexec::signal::source::worker;
+/// use std::sync::Arc;
+/// use tokio::sync::{mpsc, watch, Notify};
+/// use watchexec::{config::OverflowPolicy, signal::source::worker};
 ///
 /// #[tokio::main]
 /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
 ///     let (ev_s, _) = mpsc::channel(1024);
 ///     let (er_s, _) = mpsc::channel(64);
+///     let (_sd_s, sd_r) = watch::channel(false);
+///     let quit = Arc::new(Notify::new());
 ///
-///     worker(er_s, ev_s).await?;
+///     worker(er_s, ev_s, sd_r, OverflowPolicy::Block, None, quit).await?;
 ///     Ok(())
 /// }
 /// ```
 pub async fn worker(
 	errors: mpsc::Sender<RuntimeError>,
 	events: mpsc::Sender<Event>,
+	shutdown: watch::Receiver<bool>,
+	overflow: OverflowPolicy,
+	force_quit_window: Option<Duration>,
+	quit: Arc<Notify>,
 ) -> Result<(), CriticalError> {
-	imp_worker(errors, events).await
+	let double_tap = DoubleTap {
+		window: force_quit_window,
+		last: None,
+		quit,
+	};
+	imp_worker(errors, events, shutdown, overflow, double_tap).await
+}
+
+/// Detects a second interrupt shortly after a first one.
+struct DoubleTap {
+	window: Option<Duration>,
+	last: Option<Instant>,
+	quit: Arc<Notify>,
+}
+
+impl DoubleTap {
+	fn check(&mut self, sig: MainSignal) {
+		let window = match self.window {
+			Some(window) if sig == MainSignal::Interrupt => window,
+			_ => return,
+		};
+
+		let now = Instant::now();
+		if self.last.map_or(false, |last| now - last <= window) {
+			warn!(?window, "interrupted twice, force-quitting");
+			self.quit.notify_one();
+		}
+		self.last = Some(now);
+	}
 }
 
 #[cfg(unix)]
 async fn imp_worker(
 	errors: mpsc::Sender<RuntimeError>,
 	events: mpsc::Sender<Event>,
+	mut shutdown: watch::Receiver<bool>,
+	overflow: OverflowPolicy,
+	mut double_tap: DoubleTap,
 ) -> Result<(), CriticalError> {
 	use tokio::signal::unix::{signal, SignalKind};
 
@@ -127,10 +195,15 @@ async fn imp_worker(
 			_ = s_terminate.recv() => MainSignal::Terminate,
 			_ = s_user1.recv() => MainSignal::User1,
 			_ = s_user2.recv() => MainSignal::User2,
+			Ok(_) = shutdown.changed() => {
+				debug!("shutdown requested, stopping signal worker");
+				return Ok(());
+			}
 		);
 
 		debug!(?sig, "received unix signal");
-		send_event(errors.clone(), events.clone(), sig).await?;
+		double_tap.check(sig);
+		send_event(errors.clone(), events.clone(), overflow, sig).await?;
 	}
 }
 
@@ -138,6 +211,9 @@ async fn imp_worker(
 async fn imp_worker(
 	errors: mpsc::Sender<RuntimeError>,
 	events: mpsc::Sender<Event>,
+	mut shutdown: watch::Receiver<bool>,
+	overflow: OverflowPolicy,
+	mut double_tap: DoubleTap,
 ) -> Result<(), CriticalError> {
 	use tokio::signal::windows::{ctrl_break, ctrl_c};
 
@@ -159,10 +235,15 @@ async fn imp_worker(
 		let sig = select!(
 			_ = sigint.recv() => MainSignal::Interrupt,
 			_ = sigbreak.recv() => MainSignal::Terminate,
+			Ok(_) = shutdown.changed() => {
+				debug!("shutdown requested, stopping signal worker");
+				return Ok(());
+			}
 		);
 
 		debug!(?sig, "received windows process notification");
-		send_event(errors.clone(), events.clone(), sig).await?;
+		double_tap.check(sig);
+		send_event(errors.clone(), events.clone(), overflow, sig).await?;
 	}
 }
 
@@ -170,30 +251,13 @@ async fn imp_worker(
 async fn send_event(
 	errors: mpsc::Sender<RuntimeError>,
 	events: mpsc::Sender<Event>,
+	overflow: OverflowPolicy,
 	sig: MainSignal,
 ) -> Result<(), CriticalError> {
-	let tags = vec![
-		Tag::Source(if sig == MainSignal::Interrupt {
-			Source::Keyboard
-		} else {
-			Source::Os
-		}),
-		Tag::Signal(sig),
-	];
-
-	let event = Event {
-		tags,
-		metadata: Default::default(),
-	};
-
+	let event = Event::signal(sig);
 	trace!(?event, "processed signal into event");
-	if let Err(err) = events.send(event).await {
-		errors
-			.send(RuntimeError::EventChannelSend {
-				ctx: "signals",
-				err,
-			})
-			.await?;
+	if let Err(err) = overflow.send(&events, event, "signals").await {
+		errors.send(err).await?;
 	}
 
 	Ok(())
@@ -0,0 +1,294 @@
+//! Test harness for driving a [`Watchexec`] instance deterministically.
+//!
+//! This is available with the `testing` feature. [`TestWatchexec`] runs a `Watchexec` without the
+//! filesystem, signal, or keyboard event sources, lets you feed it events directly, and records
+//! every action (the events it was called with and the outcome your handler chose), so tests can
+//! await actions instead of sleeping for an arbitrary amount of time.
+//!
+//! The feature also enables tokio's `test-util`, so tests can run with paused time: timers such as
+//! the action throttle then resolve as soon as the runtime is otherwise idle, or can be moved
+//! forward explicitly with [`TestWatchexec::advance()`]. Paused time requires the current-thread
+//! runtime, which is the default for `#[tokio::test]`. However, async closure handlers need the
+//! multi-threaded runtime (see the [handler module][crate::handler]), so with paused time, use
+//! synchronous handlers via [`SyncFnHandler`][crate::handler::SyncFnHandler] instead.
+//!
+//! Outcomes are applied as usual, so a configured command would really be run: leave the command
+//! empty to only record outcomes.
+//!
+//! # Examples
+//!
+//! ```
+//! # #[cfg(feature = "testing")]
+//! # mod example {
+//! # use std::convert::Infallible;
+//! use watchexec::{
+//!     action::{Action, Outcome},
+//!     config::RuntimeConfig,
+//!     event::Event,
+//!     handler::SyncFnHandler,
+//!     testing::TestWatchexec,
+//! };
+//!
+//! #[tokio::test(start_paused = true)]
+//! async fn starts_on_any_event() {
+//!     let mut runtime = RuntimeConfig::default();
+//!     runtime.on_action(SyncFnHandler::from(|action: Action| {
+//!         action.outcome(Outcome::Start);
+//!         Ok::<(), Infallible>(())
+//!     }));
+//!
+//!     let wx = TestWatchexec::new(runtime).unwrap();
+//!     wx.feed_event(Event::default()).await.unwrap();
+//!
+//!     let action = wx.await_action().await.unwrap();
+//!     assert_eq!(action.outcome, Some(Outcome::Start));
+//!     wx.shutdown().await.unwrap();
+//! }
+//! # }
+//! ```
+
+use std::{
+	error::Error,
+	sync::{Arc, Mutex},
+	time::Duration,
+};
+
+use tokio::{
+	sync::{mpsc, Mutex as AsyncMutex},
+	task::JoinHandle,
+};
+
+use crate::{
+	action::{Action, Outcome},
+	config::{InitConfig, RuntimeConfig},
+	error::{CriticalError, ReconfigError},
+	event::Event,
+	handler::Handler,
+	ReconfigReport, ShutdownReason, Watchexec,
+};
+
+/// An action recorded by [`TestWatchexec`].
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct RecordedAction {
+	/// The events the action handler was called with.
+	pub events: Arc<Vec<Event>>,
+
+	/// The outcome the handler set, if any.
+	///
+	/// This is the outcome as set, before it is resolved against the state of the command.
+	pub outcome: Option<Outcome>,
+
+	/// Whether the action handler returned an error.
+	pub errored: bool,
+}
+
+/// A [`Watchexec`] instance wired up for tests.
+///
+/// See the [module documentation](self) for details.
+#[derive(Debug)]
+pub struct TestWatchexec {
+	wx: Arc<Watchexec>,
+	main: Option<JoinHandle<Result<ShutdownReason, CriticalError>>>,
+	recorded: Arc<Mutex<Vec<RecordedAction>>>,
+	actions_s: mpsc::UnboundedSender<RecordedAction>,
+	actions_r: AsyncMutex<mpsc::UnboundedReceiver<RecordedAction>>,
+}
+
+impl TestWatchexec {
+	/// Creates and starts a new instance with the given runtime configuration.
+	///
+	/// The filesystem, signal, and keyboard event sources are disabled regardless of the
+	/// configuration. Runtime errors are discarded unless an error handler is set with
+	/// [`with_init()`](TestWatchexec::with_init()).
+	pub fn new(runtime: RuntimeConfig) -> Result<Self, CriticalError> {
+		Self::with_init(InitConfig::default(), runtime)
+	}
+
+	/// Same as [`new()`](TestWatchexec::new()), with a custom [`InitConfig`].
+	pub fn with_init(init: InitConfig, mut runtime: RuntimeConfig) -> Result<Self, CriticalError> {
+		runtime
+			.enable_fs(false)
+			.enable_signal(false)
+			.enable_keyboard(false);
+
+		let recorded = Arc::new(Mutex::new(Vec::new()));
+		let (actions_s, actions_r) = mpsc::unbounded_channel();
+		wrap_action_handler(&mut runtime, recorded.clone(), actions_s.clone());
+
+		let wx = Watchexec::new(init, runtime)?;
+		let main = Some(wx.main());
+
+		Ok(Self {
+			wx,
+			main,
+			recorded,
+			actions_s,
+			actions_r: AsyncMutex::new(actions_r),
+		})
+	}
+
+	/// Applies a new [`RuntimeConfig`], keeping the recording of actions in place.
+	pub fn reconfigure(&self, mut runtime: RuntimeConfig) -> Result<ReconfigReport, ReconfigError> {
+		wrap_action_handler(&mut runtime, self.recorded.clone(), self.actions_s.clone());
+		self.wx.reconfigure(runtime)
+	}
+
+	/// Inputs an event, as if it came from an event source.
+	pub async fn feed_event(&self, event: Event) -> Result<(), CriticalError> {
+		self.wx.send_event(event).await
+	}
+
+	/// Waits for the next action which hasn't been awaited yet.
+	///
+	/// Returns `None` if the instance has stopped and all actions have been awaited.
+	pub async fn await_action(&self) -> Option<RecordedAction> {
+		let mut actions = self.actions_r.lock().await;
+		tokio::select! {
+			action = actions.recv() => action,
+			_ = self.wx.wait_stopped() => actions.try_recv().ok(),
+		}
+	}
+
+	/// Returns every action recorded so far, whether it has been awaited or not.
+	pub fn actions(&self) -> Vec<RecordedAction> {
+		self.recorded.lock().map(|r| r.clone()).unwrap_or_default()
+	}
+
+	/// Moves paused time forward, then lets the runtime process what became ready.
+	///
+	/// See [`tokio::time::advance()`]; this panics if time is not paused.
+	pub async fn advance(&self, duration: Duration) {
+		tokio::time::advance(duration).await;
+		tokio::task::yield_now().await;
+	}
+
+	/// The underlying [`Watchexec`] instance.
+	pub fn inner(&self) -> &Arc<Watchexec> {
+		&self.wx
+	}
+
+	/// Shuts down the instance and returns the result of its main task.
+	pub async fn shutdown(mut self) -> Result<ShutdownReason, CriticalError> {
+		self.wx.shutdown(None).await?;
+		match self.main.take() {
+			Some(main) => main.await.map_err(CriticalError::MainTaskJoin)?,
+			None => Ok(ShutdownReason::Requested),
+		}
+	}
+}
+
+fn wrap_action_handler(
+	runtime: &mut RuntimeConfig,
+	recorded: Arc<Mutex<Vec<RecordedAction>>>,
+	actions: mpsc::UnboundedSender<RecordedAction>,
+) {
+	if let Some(inner) = runtime.action.action_handler.take() {
+		runtime.on_action(Recorder {
+			inner,
+			recorded,
+			actions,
+		});
+	}
+}
+
+struct Recorder {
+	inner: Box<dyn Handler<Action> + Send>,
+	recorded: Arc<Mutex<Vec<RecordedAction>>>,
+	actions: mpsc::UnboundedSender<RecordedAction>,
+}
+
+impl Handler<Action> for Recorder {
+	fn handle(&mut self, action: Action) -> Result<(), Box<dyn Error>> {
+		let events = action.events.clone();
+		let outcome = action.outcome.clone();
+		let res = self.inner.handle(action);
+
+		let record = RecordedAction {
+			events,
+			outcome: outcome.get().cloned(),
+			errored: res.is_err(),
+		};
+		if let Ok(mut recorded) = self.recorded.lock() {
+			recorded.push(record.clone());
+		}
+		self.actions.send(record).ok();
+
+		res
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use std::convert::Infallible;
+
+	use super::*;
+	use crate::{
+		event::{Source, Tag},
+		handler::SyncFnHandler,
+	};
+
+	fn internal_event() -> Event {
+		Event {
+			tags: vec![Tag::Source(Source::Internal)],
+			metadata: Default::default(),
+		}
+	}
+
+	fn outcome_per_event(runtime: &mut RuntimeConfig) {
+		runtime.on_action(SyncFnHandler::from(|action: Action| {
+			let n = action.events.len();
+			action.outcome(if n > 1 {
+				Outcome::Wait
+			} else {
+				Outcome::DoNothing
+			});
+			Ok::<(), Infallible>(())
+		}));
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn records_actions_and_outcomes() -> Result<(), Box<dyn Error>> {
+		let mut runtime = RuntimeConfig::default();
+		outcome_per_event(&mut runtime);
+		runtime.action_throttle(Duration::from_secs(60));
+
+		let wx = TestWatchexec::new(runtime)?;
+		wx.feed_event(internal_event()).await?;
+		wx.feed_event(internal_event()).await?;
+
+		// the minute-long throttle elapses instantly in paused time
+		let action = wx.await_action().await.expect("no action recorded");
+		assert_eq!(action.events.len(), 2);
+		assert_eq!(action.outcome, Some(Outcome::Wait));
+		assert!(!action.errored);
+		assert_eq!(wx.actions().len(), 1);
+
+		wx.shutdown().await?;
+		Ok(())
+	}
+
+	#[tokio::test(flavor = "multi_thread")]
+	async fn records_across_reconfigures() -> Result<(), Box<dyn Error>> {
+		let mut runtime = RuntimeConfig::default();
+		runtime.on_action(|action: Action| async move {
+			action.outcome(Outcome::DoNothing);
+			Ok::<(), Infallible>(())
+		});
+
+		let wx = TestWatchexec::new(runtime.clone())?;
+		wx.feed_event(internal_event()).await?;
+		let action = wx.await_action().await.expect("no action recorded");
+		assert_eq!(action.outcome, Some(Outcome::DoNothing));
+
+		outcome_per_event(&mut runtime);
+		wx.reconfigure(runtime).map_err(Box::new)?;
+		wx.feed_event(internal_event()).await?;
+		wx.feed_event(internal_event()).await?;
+		let action = wx.await_action().await.expect("no action recorded");
+		assert_eq!(action.outcome, Some(Outcome::Wait));
+
+		wx.shutdown().await?;
+		Ok(())
+	}
+}
@@ -0,0 +1,108 @@
+//! Event source for periodic events.
+//!
+//! When an interval is set with [`RuntimeConfig::timer()`][crate::config::RuntimeConfig::timer()],
+//! this emits an event tagged with [`Source::Time`] and [`Tag::Timer`] every interval, whether or
+//! not anything else happened. These go through the action worker like any other event, so can be
+//! used to run the command periodically (e.g. a health check), on top of running it on changes.
+//!
+//! The first event is emitted one interval after the timer is started. If the action worker is
+//! slow to take events, ticks are not bunched up to catch up: the next one is a full interval
+//! after the late one. Reconfiguring the interval resets the timer, even to the same value.
+
+use std::{future::pending, time::Duration};
+
+use tokio::{
+	select,
+	sync::{mpsc, watch},
+	time::{interval_at, Instant, Interval, MissedTickBehavior},
+};
+use tracing::{debug, trace};
+
+use crate::{
+	error::CriticalError,
+	event::{Event, Source, Tag},
+};
+
+/// Launch the timer event worker.
+///
+/// The interval is read from `config`, and the timer is reset every time it changes. When it's
+/// `None`, the worker idles until it's set.
+///
+/// The worker stops when `shutdown` is set to `true`.
+pub async fn worker(
+	mut config: watch::Receiver<Option<Duration>>,
+	events: mpsc::Sender<Event>,
+	mut shutdown: watch::Receiver<bool>,
+) -> Result<(), CriticalError> {
+	debug!("launching timer worker");
+
+	let mut configurable = true;
+	loop {
+		let period = *config.borrow_and_update();
+		let mut ticks = period.map(|period| {
+			trace!(?period, "starting timer");
+			let mut ticks = interval_at(Instant::now() + period, period);
+			ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+			(period, ticks)
+		});
+
+		loop {
+			select! {
+				Ok(_) = shutdown.changed() => {
+					debug!("shutdown requested, stopping timer worker");
+					return Ok(());
+				}
+				changed = config.changed(), if configurable => {
+					if changed.is_ok() {
+						debug!("timer reconfigured, resetting");
+						break;
+					}
+
+					trace!("timer config sender dropped, keeping the timer as is");
+					configurable = false;
+				}
+				period = tick(&mut ticks) => {
+					trace!(?period, "timer fired");
+					events.send(timer_event(period)).await?;
+				}
+			}
+		}
+	}
+}
+
+/// Waits for the next tick of the timer, if there's one, and returns its period.
+async fn tick(ticks: &mut Option<(Duration, Interval)>) -> Duration {
+	match ticks {
+		Some((period, ticks)) => {
+			ticks.tick().await;
+			*period
+		}
+		None => pending().await,
+	}
+}
+
+/// The event emitted when the timer fires.
+fn timer_event(period: Duration) -> Event {
+	Event {
+		tags: vec![Tag::Source(Source::Time), Tag::Timer(period)],
+		metadata: Default::default(),
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn timer_event_is_tagged() {
+		let event = timer_event(Duration::from_secs(30));
+		assert_eq!(event.sources().collect::<Vec<_>>(), vec![Source::Time]);
+		assert_eq!(
+			event.tags,
+			vec![
+				Tag::Source(Source::Time),
+				Tag::Timer(Duration::from_secs(30))
+			]
+		);
+	}
+}
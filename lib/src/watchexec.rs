@@ -1,27 +1,48 @@
 use std::{
 	fmt,
-	mem::{replace, take},
-	sync::Arc,
+	future::Future,
+	mem::take,
+	path::PathBuf,
+	pin::Pin,
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Arc,
+	},
+	task::{Context, Poll},
+	time::{Duration, Instant},
 };
 
 use atomic_take::AtomicTake;
-use futures::FutureExt;
+use futures::{
+	future::{pending, ready, BoxFuture, Shared},
+	FutureExt, Stream, StreamExt,
+};
 use tokio::{
-	spawn,
-	sync::{mpsc, watch, Notify},
+	runtime::Handle,
+	select, spawn,
+	sync::{
+		broadcast,
+		mpsc::{self, error::TrySendError},
+		watch, Mutex, Notify,
+	},
 	task::{JoinError, JoinHandle},
+	time::{interval, MissedTickBehavior},
 	try_join,
 };
-use tracing::{debug, error, trace};
+use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
+use tracing::{debug, error, level_filters::LevelFilter, trace, warn};
 
 use crate::{
-	action,
+	action::{self, Outcome},
 	config::{InitConfig, RuntimeConfig},
 	error::{CriticalError, ReconfigError, RuntimeError},
 	event::Event,
-	fs,
+	fs::{self, WatchedPath},
 	handler::{rte, Handler},
-	signal,
+	keyboard,
+	metrics::{Counters, Metrics},
+	signal::{self, source::MainSignal},
+	timer,
 };
 
 /// The main watchexec runtime.
@@ -30,15 +51,130 @@ use crate::{
 ///
 /// It creates the correct channels, spawns every available event sources, the action worker, the
 /// error hook, and provides an interface to change the runtime configuration during the runtime,
-/// inject synthetic events, and wait for graceful shutdown.
+/// inject synthetic events, and shut down gracefully.
 pub struct Watchexec {
-	handle: Arc<AtomicTake<JoinHandle<Result<(), CriticalError>>>>,
+	handle: Arc<AtomicTake<JoinHandle<Result<ShutdownReason, CriticalError>>>>,
 	start_lock: Arc<Notify>,
 
 	action_watch: watch::Sender<action::WorkingData>,
 	fs_watch: watch::Sender<fs::WorkingData>,
+	timer_watch: watch::Sender<Option<Duration>>,
+	fs_watched: watch::Receiver<Vec<PathBuf>>,
+	fs_applied: Option<watch::Receiver<u64>>,
+	child_pid: watch::Receiver<Option<u32>>,
+	last_outcome: watch::Receiver<Option<Outcome>>,
+	observers: broadcast::Sender<Event>,
+	lifecycle: broadcast::Sender<Lifecycle>,
+	metrics: Arc<Counters>,
 
 	event_input: mpsc::Sender<Event>,
+	event_channel_size: usize,
+	sources: [(&'static str, bool); 3],
+	batch_lock: Mutex<()>,
+	swap: Arc<Mutex<()>>,
+	log_level: action::LogLevelHandler,
+
+	shutdown: watch::Sender<bool>,
+	pause: watch::Sender<bool>,
+	force_shutdown: Arc<Notify>,
+	status: watch::Receiver<Status>,
+}
+
+/// The lifecycle status of a [`Watchexec`] instance, as returned by [`Watchexec::status()`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Status {
+	/// [`Watchexec::main()`] has not been called yet.
+	NotStarted,
+
+	/// The main task is running.
+	Running,
+
+	/// A shutdown was requested, and the workers are winding down.
+	ShuttingDown,
+
+	/// The main task has finished.
+	///
+	/// If it finished with an error, this holds its message; the error itself is returned by the
+	/// main task handle.
+	Stopped(Result<(), String>),
+}
+
+/// Why the main task stopped, as it resolves to when it does so gracefully.
+///
+/// This lets applications tell e.g. which exit code to use, or what to log.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ShutdownReason {
+	/// A shutdown was requested.
+	///
+	/// This is either [`Watchexec::shutdown()`], or an [`Outcome::Exit`] for an action which
+	/// didn't have any signal events.
+	Requested,
+
+	/// Exiting in response to a signal.
+	///
+	/// This is an [`Outcome::Exit`] for an action (or
+	/// [signal map](crate::action::WorkingData#structfield.signal_map) entry) whose events include
+	/// this signal (the first one, if there are several), or a
+	/// [force-quit](crate::config::InitConfig#structfield.force_quit_window).
+	Signal(MainSignal),
+
+	/// A handler errored with [`RuntimeError::Exit`].
+	HandlerError,
+}
+
+/// Something that happened to a [`Watchexec`] instance itself, as sent to
+/// [`Watchexec::lifecycle()`].
+///
+/// These are separate from [`Event`]s: they aren't filtered and don't trigger actions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Lifecycle {
+	/// The main task has started.
+	MainStarted,
+
+	/// The main task is stopping.
+	///
+	/// This is sent once, either when a shutdown is requested, or when the workers stop by
+	/// themselves (e.g. on an [`Outcome::Exit`]), before the main task resolves.
+	MainStopping,
+
+	/// The runtime was reconfigured, with the report of what was updated.
+	///
+	/// This is sent for every call to [`Watchexec::reconfigure()`] and its variants (including
+	/// [`Watchexec::set_command()`]), even if nothing changed. It's sent as the configuration is
+	/// handed to the workers, so before anything resulting from it (like a
+	/// [`WatcherRebuilt`](Lifecycle::WatcherRebuilt)).
+	Reconfigured(ReconfigReport),
+
+	/// The filesystem watcher was re-created, e.g. as the watcher type changed.
+	///
+	/// This is sent when the action worker receives the
+	/// [`WatcherReinit`](crate::event::FsEventKind::WatcherReinit) event for it, not for the first
+	/// watcher.
+	WatcherRebuilt,
+}
+
+/// How many lifecycle events each [`Watchexec::lifecycle()`] receiver can fall behind by.
+const LIFECYCLE_CHANNEL_SIZE: usize = 16;
+
+/// Which parts of the runtime were updated by a [`Watchexec::reconfigure()`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ReconfigReport {
+	/// Whether the [action working data](crate::action::WorkingData) was updated.
+	///
+	/// As handlers, the filterer, the filter predicate, and the event mapper can't be compared,
+	/// setting any of these (even to the same value as before) counts as a change, while passing
+	/// a clone of the previous configuration doesn't.
+	pub action: bool,
+
+	/// Whether the [filesystem working data](crate::fs::WorkingData) was updated.
+	pub fs: bool,
+
+	/// Whether the [timer](crate::config::RuntimeConfig#structfield.timer) interval was updated.
+	pub timer: bool,
 }
 
 impl fmt::Debug for Watchexec {
@@ -52,17 +188,67 @@ impl Watchexec {
 	///
 	/// Returns an [`Arc`] for convenience; use [`try_unwrap`][Arc::try_unwrap()] to get the value
 	/// directly if needed.
-	pub fn new(
+	///
+	/// The configuration is checked first, and mistakes which would otherwise only show up later
+	/// as worker errors (zero-sized channels, a command with nothing to run, malformed globs) are
+	/// returned as [`CriticalError::ConfigInvalid`] before anything is started.
+	///
+	/// The tasks are spawned onto the current tokio runtime; see
+	/// [`new_with_handle()`](Watchexec::new_with_handle()) to use another.
+	///
+	/// # Panics
+	///
+	/// Panics if called outside of a tokio runtime.
+	pub fn new(init: InitConfig, runtime: RuntimeConfig) -> Result<Arc<Self>, CriticalError> {
+		Self::new_with_handle(init, runtime, Handle::current())
+	}
+
+	/// Instantiates a new `Watchexec` runtime from configuration, running on the given tokio runtime.
+	///
+	/// This is [`new()`](Watchexec::new()), but the main task is spawned onto `handle` rather than
+	/// the current runtime, and so are all the workers and the commands' supervisors, as they're
+	/// spawned from within it. This can be called from outside of any runtime, and the methods of
+	/// the returned `Watchexec` can be used from any (or, for the non-async ones, none).
+	///
+	/// As [handlers](crate::handler) block in place, the runtime must be multi-threaded.
+	pub fn new_with_handle(
 		mut init: InitConfig,
 		mut runtime: RuntimeConfig,
+		handle: Handle,
 	) -> Result<Arc<Self>, CriticalError> {
-		debug!(?init, ?runtime, pid=%std::process::id(), "initialising");
+		validate(&init, &runtime)?;
+		watch_command_binary(&mut runtime);
+		debug!(?init, runtime=%runtime.diff(&RuntimeConfig::default()), pid=%std::process::id(), "initialising");
 
 		let (ev_s, ev_r) = mpsc::channel(init.event_channel_size);
+		let log_level = action::LogLevelHandler::new(init.log_level_handler.take());
+		runtime.action.log_level = log_level.clone();
 		let (ac_s, ac_r) = watch::channel(take(&mut runtime.action));
 		let (fs_s, fs_r) = watch::channel(fs::WorkingData::default());
+		let (tm_s, tm_r) = watch::channel(runtime.timer);
+		let (fw_s, fw_r) = watch::channel(Vec::new());
+		let (fa_s, fa_r) = watch::channel(0);
+		let (cp_s, cp_r) = watch::channel(None);
+		let (lo_s, lo_r) = watch::channel(None);
+		let (ob_s, _) = broadcast::channel(init.event_channel_size);
+		let (lc_s, _) = broadcast::channel(LIFECYCLE_CHANNEL_SIZE);
+		let metrics = Arc::new(Counters::default());
+
+		let (sd_s, sd_r) = watch::channel(false);
+		let (ps_s, ps_r) = watch::channel(false);
+		let (st_s, st_r) = watch::channel(Status::NotStarted);
 
 		let event_input = ev_s.clone();
+		let event_channel_size = init.event_channel_size;
+		let enable_fs = runtime.enable_fs;
+		let enable_signal = runtime.enable_signal;
+		let enable_keyboard = runtime.enable_keyboard;
+		let overflow = init.event_overflow;
+		let force_quit_window = init.force_quit_window;
+		let saturation_warning = init.saturation_warning;
+		let quit = Arc::new(Notify::new());
+		let swap = Arc::new(Mutex::new(()));
+		let action_swap = swap.clone();
 
 		// TODO: figure out how to do this (aka start the fs work) after the main task start lock
 		trace!("sending initial config to fs worker");
@@ -72,44 +258,185 @@ impl Watchexec {
 		trace!("creating main task");
 		let notify = Arc::new(Notify::new());
 		let start_lock = notify.clone();
-		let handle = spawn(async move {
+		let force = Arc::new(Notify::new());
+		let force_shutdown = force.clone();
+		let observers = ob_s.clone();
+		let lifecycle = lc_s.clone();
+		let counters = metrics.clone();
+		let handle = handle.spawn(async move {
 			trace!("waiting for start lock");
-			notify.notified().await;
+			let mut early_shutdown = sd_r.clone();
+			select! {
+				_ = notify.notified() => {}
+				Ok(_) = early_shutdown.changed() => {
+					debug!("shutdown requested before start, not starting");
+					st_s.send(Status::Stopped(Ok(()))).ok();
+					return Ok(ShutdownReason::Requested);
+				}
+			}
 			debug!("starting main task");
+			st_s.send(Status::Running).ok();
+			lifecycle.send(Lifecycle::MainStarted).ok();
+			let stopping = AtomicBool::new(false);
+			let stopping = || {
+				if !stopping.swap(true, Ordering::Relaxed) {
+					lifecycle.send(Lifecycle::MainStopping).ok();
+				}
+			};
 
 			let (er_s, er_r) = mpsc::channel(init.error_channel_size);
 
-			let eh = replace(&mut init.error_handler, Box::new(()) as _);
+			let eh = take(&mut init.error_handlers);
 
 			macro_rules! subtask {
 				($name:ident, $task:expr) => {{
 					debug!(subtask=%stringify!($name), "spawning subtask");
-					spawn($task).then(|jr| async { flatten(jr) })
+					spawn($task)
 				}};
 			}
 
-			let action = subtask!(
+			let rebuilds = subtask!(
+				rebuilds,
+				watch_rebuilds(observers.subscribe(), lifecycle.clone())
+			);
+			let saturation = saturation_warning.map(|window| {
+				subtask!(
+					saturation,
+					watch_saturation(ev_s.clone(), event_channel_size, window, er_s.clone())
+				)
+			});
+			let mut action = subtask!(
 				action,
-				action::worker(ac_r, er_s.clone(), ev_s.clone(), ev_r)
+				action::worker(
+					ac_r,
+					er_s.clone(),
+					ev_s.clone(),
+					ev_r,
+					sd_r.clone(),
+					cp_s,
+					lo_s,
+					observers,
+					counters.clone(),
+					quit.clone(),
+					action_swap
+				)
+			);
+			let mut fs = if enable_fs {
+				subtask!(
+					fs,
+					fs::worker(
+						fs_r,
+						er_s.clone(),
+						ev_s.clone(),
+						sd_r.clone(),
+						ps_r,
+						fw_s,
+						fa_s,
+						overflow,
+						counters
+					)
+				)
+			} else {
+				// keep the receiver so reconfigures don't fail
+				debug!(subtask=%"fs", "subtask disabled");
+				spawn(idle(fs_r, sd_r.clone()))
+			};
+			let mut signal = if enable_signal {
+				subtask!(
+					signal,
+					signal::source::worker(
+						er_s.clone(),
+						ev_s.clone(),
+						sd_r.clone(),
+						overflow,
+						force_quit_window,
+						quit
+					)
+				)
+			} else {
+				debug!(subtask=%"signal", "subtask disabled");
+				spawn(idle((), sd_r.clone()))
+			};
+			let mut keyboard = if enable_keyboard {
+				subtask!(
+					keyboard,
+					keyboard::worker(er_s.clone(), ev_s.clone(), sd_r.clone())
+				)
+			} else {
+				debug!(subtask=%"keyboard", "subtask disabled");
+				spawn(idle((), sd_r.clone()))
+			};
+
+			let mut timer = subtask!(timer, timer::worker(tm_r, ev_s.clone(), sd_r.clone()));
+
+			let mut error_hook = subtask!(
+				error_hook,
+				error_hook(er_r, eh, init.error_handler_failure_limit)
 			);
-			let fs = subtask!(fs, fs::worker(fs_r, er_s.clone(), ev_s.clone()));
-			let signal = subtask!(signal, signal::source::worker(er_s.clone(), ev_s.clone()));
 
-			let error_hook = subtask!(error_hook, error_hook(er_r, eh));
+			let workers = async {
+				try_join!(
+					(&mut action).map(flatten),
+					(&mut fs).map(flatten),
+					(&mut signal).map(flatten),
+					(&mut keyboard).map(flatten),
+					(&mut timer).map(flatten),
+				)?;
 
-			try_join!(action, error_hook, fs, signal)
-				.map(drop)
+				// the error hook only stops on an exit request, so once all the workers have wound
+				// down we send it one, after any errors they may have left in the channel
+				trace!("workers have wound down, stopping error hook");
+				er_s.send(RuntimeError::Exit).await?;
+				Ok(())
+			};
+
+			let mut shutting_down = sd_r.clone();
+			let res = select! {
+				res = async { try_join!(workers, (&mut error_hook).map(flatten)) } => res.map(|_| ShutdownReason::Requested),
+				_ = force.notified() => {
+					debug!("graceful shutdown timed out, forcing");
+					Ok(ShutdownReason::Requested)
+				}
+				_ = async {
+					shutting_down.changed().await.ok();
+					st_s.send(Status::ShuttingDown).ok();
+					stopping();
+					pending::<()>().await
+				} => unreachable!(),
+			};
+
+			stopping();
+			trace!("aborting any remaining subtasks");
+			rebuilds.abort();
+			if let Some(saturation) = saturation {
+				saturation.abort();
+			}
+			for task in [action, fs, signal, keyboard, timer, error_hook] {
+				task.abort();
+			}
+
+			let res = res
 				.or_else(|e| {
-					if matches!(e, CriticalError::Exit) {
-						trace!("got graceful exit request via critical error, erasing the error");
-						Ok(())
+					if let CriticalError::Exit(reason) = e {
+						trace!(
+							?reason,
+							"got graceful exit request via critical error, erasing the error"
+						);
+						Ok(reason)
 					} else {
 						Err(e)
 					}
 				})
-				.map(|_| {
-					debug!("main task graceful exit");
-				})
+				.map(|reason| {
+					debug!(?reason, "main task graceful exit");
+					reason
+				});
+
+			st_s.send(Status::Stopped(
+				res.as_ref().map(|_| ()).map_err(|e| e.to_string()),
+			))
+			.ok();
+			res
 		});
 
 		trace!("done with setup");
@@ -119,37 +446,517 @@ impl Watchexec {
 
 			action_watch: ac_s,
 			fs_watch: fs_s,
+			timer_watch: tm_s,
+			fs_watched: fw_r,
+			fs_applied: enable_fs.then(|| fa_r),
+			child_pid: cp_r,
+			last_outcome: lo_r,
+			observers: ob_s,
+			lifecycle: lc_s,
+			metrics,
 
 			event_input,
+			event_channel_size,
+			sources: [
+				("fs", enable_fs),
+				("signal", enable_signal),
+				("keyboard", enable_keyboard),
+			],
+			batch_lock: Mutex::new(()),
+			swap,
+			log_level,
+
+			shutdown: sd_s,
+			pause: ps_s,
+			force_shutdown,
+			status: st_r,
 		}))
 	}
 
 	/// Applies a new [`RuntimeConfig`] to the runtime.
-	pub fn reconfigure(&self, config: RuntimeConfig) -> Result<(), ReconfigError> {
-		debug!(?config, "reconfiguring");
+	///
+	/// The configuration is compared to the one last applied, and only the workers whose
+	/// configuration differs are updated; the returned [`ReconfigReport`] says which. This avoids
+	/// e.g. the filesystem worker re-applying its pathset when only an action setting changed.
+	/// Handlers and the filterer are compared by identity (see [`ReconfigReport::action`]).
+	///
+	/// Use [`reconfigure_force()`](Watchexec::reconfigure_force()) to update every worker
+	/// regardless.
+	///
+	/// Event sources are only enabled or disabled when constructing the instance: if
+	/// [`enable_fs`](RuntimeConfig#structfield.enable_fs) and co differ from that, a warning is
+	/// logged, and they're otherwise ignored.
+	pub fn reconfigure(&self, mut config: RuntimeConfig) -> Result<ReconfigReport, ReconfigError> {
+		self.warn_sources_changed(&config);
+		watch_command_binary(&mut config);
+		let generation = self.fs_watch.borrow().generation;
+		config.fs.generation = generation;
+		config.action.log_level = self.log_level.clone();
+
+		let report = ReconfigReport {
+			action: !self.action_watch.borrow().same_as(&config.action),
+			fs: *self.fs_watch.borrow() != config.fs,
+			timer: *self.timer_watch.borrow() != config.timer,
+		};
+		debug!(changed=%config.diff(&RuntimeConfig::default()), ?report, "reconfiguring");
+		self.lifecycle.send(Lifecycle::Reconfigured(report)).ok();
+
+		if report.action {
+			self.action_watch.send(config.action)?;
+		}
+		if report.fs {
+			config.fs.generation = generation + 1;
+			self.fs_watch.send(config.fs)?;
+		}
+		if report.timer {
+			self.timer_watch.send(config.timer)?;
+		}
+		Ok(report)
+	}
+
+	/// Applies a new [`RuntimeConfig`] to the runtime, and waits for it to take effect.
+	///
+	/// This is [`reconfigure()`](Watchexec::reconfigure()), but if the filesystem configuration
+	/// changed, this only returns once the filesystem worker has applied it: the new pathset is
+	/// then being watched (as far as possible, see [`watched_paths()`](Watchexec::watched_paths())),
+	/// so files created afterwards are seen. The action worker reads its configuration as it needs
+	/// it, so there's nothing to wait for there.
+	///
+	/// If the main task hasn't been started yet, this waits for it to start. If the filesystem
+	/// event source is disabled, or stops, this returns without waiting.
+	pub async fn reconfigure_async(
+		&self,
+		config: RuntimeConfig,
+	) -> Result<ReconfigReport, ReconfigError> {
+		let report = self.reconfigure(config)?;
+		if report.fs {
+			self.fs_applied().await;
+		}
+		Ok(report)
+	}
+
+	/// Applies a new [`RuntimeConfig`] to the runtime as one transaction.
+	///
+	/// This is [`reconfigure_async()`](Watchexec::reconfigure_async()), but with actions held off
+	/// for the duration: it first waits for the action being applied, if any, to complete, then
+	/// changes the configuration of every worker, waits for the filesystem worker to apply its own,
+	/// and only then lets actions run again. Events still come in meanwhile, and are held up to a
+	/// point, past which the
+	/// [overflow policy](crate::config::InitConfig#structfield.event_overflow) applies as usual.
+	/// Events which were filtered with the previous configuration are filtered again with the new
+	/// one.
+	/// This way, no action is ever run with a mix of old and new configuration, which matters when
+	/// reloading a config file which changes everything at once.
+	///
+	/// It returns once the new configuration is fully live. Note that waiting for the action being
+	/// applied can take a while, e.g. when it waits for a running command with
+	/// [`Outcome::Wait`](crate::action::Outcome::Wait). Several of these are applied one after the
+	/// other.
+	pub async fn reconfigure_atomic(
+		&self,
+		config: RuntimeConfig,
+	) -> Result<ReconfigReport, ReconfigError> {
+		let _swap = self.swap.lock().await;
+		debug!("holding actions to swap the configuration");
+		let report = self.reconfigure(config)?;
+		if report.fs {
+			self.fs_applied().await;
+		}
+		debug!("configuration swapped, releasing actions");
+		Ok(report)
+	}
+
+	/// Applies a new [`RuntimeConfig`] to the runtime, updating every worker.
+	///
+	/// Unlike [`reconfigure()`](Watchexec::reconfigure()), this sends the configuration to every
+	/// worker even if it hasn't changed. This can be used to have the filesystem worker retry
+	/// watching paths which could not be watched before, or re-read ignore files.
+	pub fn reconfigure_force(
+		&self,
+		mut config: RuntimeConfig,
+	) -> Result<ReconfigReport, ReconfigError> {
+		self.warn_sources_changed(&config);
+		watch_command_binary(&mut config);
+		config.fs.generation = self.fs_watch.borrow().generation + 1;
+		config.action.log_level = self.log_level.clone();
+		debug!(changed=%config.diff(&RuntimeConfig::default()), "reconfiguring (forced)");
+		let report = ReconfigReport {
+			action: true,
+			fs: true,
+			timer: true,
+		};
+		self.lifecycle.send(Lifecycle::Reconfigured(report)).ok();
 		self.action_watch.send(config.action)?;
 		self.fs_watch.send(config.fs)?;
+		self.timer_watch.send(config.timer)?;
+		Ok(report)
+	}
+
+	/// Warns about event sources which a new configuration would enable or disable, as that's not
+	/// possible once constructed.
+	fn warn_sources_changed(&self, config: &RuntimeConfig) {
+		let wanted = [
+			config.enable_fs,
+			config.enable_signal,
+			config.enable_keyboard,
+		];
+		for (&(source, enabled), wanted) in self.sources.iter().zip(wanted) {
+			if enabled != wanted {
+				warn!(
+					%source,
+					%enabled,
+					"event sources can only be enabled or disabled at construction, ignoring"
+				);
+			}
+		}
+	}
+
+	/// Returns the command the action worker runs, as last configured.
+	///
+	/// See [`action::WorkingData::command`](crate::action::WorkingData#structfield.command).
+	pub fn command(&self) -> Vec<String> {
+		self.action_watch.borrow().command.clone()
+	}
+
+	/// Changes the command to run, leaving the rest of the configuration as it is.
+	///
+	/// This only updates the action worker, and takes effect the next time it starts the command:
+	/// a command which is already running is left alone, and the filesystem worker isn't touched.
+	/// In particular, if [`watch_command_binary`](crate::action::WorkingData#structfield.watch_command_binary)
+	/// is enabled, the program of the new command isn't added to the pathset; use
+	/// [`reconfigure()`](Watchexec::reconfigure()) for that.
+	///
+	/// A later `reconfigure()` replaces the command with the one in its [`RuntimeConfig`], so
+	/// that should be kept in sync if both are used.
+	pub fn set_command<I, S>(&self, command: I) -> Result<(), ReconfigError>
+	where
+		I: IntoIterator<Item = S>,
+		S: AsRef<str>,
+	{
+		let mut action = self.action_watch.borrow().clone();
+		action.command = command.into_iter().map(|c| c.as_ref().to_owned()).collect();
+		debug!(command=?action.command, "setting command");
+		self.lifecycle
+			.send(Lifecycle::Reconfigured(ReconfigReport {
+				action: true,
+				fs: false,
+				timer: false,
+			}))
+			.ok();
+		self.action_watch.send(action)?;
 		Ok(())
 	}
 
+	/// Waits for the filesystem worker to apply the latest working data sent to it.
+	async fn fs_applied(&self) {
+		let mut applied = match self.fs_applied.clone() {
+			Some(applied) => applied,
+			None => return,
+		};
+
+		let target = self.fs_watch.borrow().generation;
+		while *applied.borrow() < target {
+			trace!(%target, "waiting for the fs worker to apply the working data");
+			if applied.changed().await.is_err() {
+				debug!("fs worker is gone, not waiting for it");
+				break;
+			}
+		}
+	}
+
+	/// Returns the paths currently being watched by the filesystem worker.
+	///
+	/// This reflects what the watcher has actually been told to watch, and is updated by the worker
+	/// after it applies a new pathset, so it may lag briefly behind a reconfigure. Paths which could
+	/// not be watched (for example because they don't exist) are not included, which makes it
+	/// useful to detect differences between the configured pathset and reality. The list is sorted.
+	///
+	/// This is always empty if the filesystem event source is disabled.
+	pub fn watched_paths(&self) -> Vec<PathBuf> {
+		self.fs_watched.borrow().clone()
+	}
+
+	/// Returns the ID of the command currently running, if any.
+	///
+	/// For grouped commands, this is the process group ID (which is also the ID of the group
+	/// leader). It's set as soon as the command is spawned, and reset to `None` when it ends.
+	///
+	/// Take care when using this to manipulate the process: it may have ended (and the ID been
+	/// reused) by the time you act on it.
+	pub fn current_child_pid(&self) -> Option<u32> {
+		*self.child_pid.borrow()
+	}
+
+	/// Returns a watch channel of the outcome of the latest action.
+	///
+	/// This is `None` until the first action is resolved, then updated by the action worker each
+	/// time it resolves an action (after the outcome has been resolved against the current process
+	/// state, but before it is applied). Actions whose handler errored don't produce an outcome.
+	///
+	/// This is purely for observation: it carries a copy of the outcome, so holding or reading the
+	/// receiver has no effect on what the worker does. Use [`watch::Receiver::changed()`] to wait
+	/// for the next one.
+	pub fn last_outcome(&self) -> watch::Receiver<Option<Outcome>> {
+		self.last_outcome.clone()
+	}
+
+	/// Returns a stream of the events received by the runtime.
+	///
+	/// Events are copied to the stream as the action worker receives them, before filtering, so
+	/// this includes events from every source: filesystem, signals, process completions, and those
+	/// input with [`send_event()`](Watchexec::send_event()). The stream only gets events received
+	/// after it was created, and ends when the runtime stops.
+	///
+	/// Streams are independent, and don't slow down the runtime: each buffers up to
+	/// [`event_channel_size`](crate::config::InitConfig#structfield.event_channel_size) events, and
+	/// if it falls further behind, the oldest are skipped. Dropping a stream is always fine.
+	pub fn event_stream(&self) -> impl Stream<Item = Event> + Send + 'static {
+		let mut status = self.status.clone();
+		let stopped = async move {
+			while !matches!(*status.borrow(), Status::Stopped(_)) {
+				if status.changed().await.is_err() {
+					break;
+				}
+			}
+		};
+
+		BroadcastStream::new(self.observers.subscribe())
+			.filter_map(|event| {
+				ready(match event {
+					Ok(event) => Some(event),
+					Err(BroadcastStreamRecvError::Lagged(missed)) => {
+						debug!(%missed, "event stream lagged, skipping events");
+						None
+					}
+				})
+			})
+			.take_until(stopped)
+	}
+
+	/// Pauses watching.
+	///
+	/// While paused, the filesystem watcher keeps running, but its events are either dropped or
+	/// held until [`resume()`](Watchexec::resume()), as set in
+	/// [`while_paused`](crate::fs::WorkingData#structfield.while_paused). This is useful around
+	/// operations known to churn through files, like checking out a branch, and is cheaper than
+	/// reconfiguring to an empty pathset and back, which unwatches and re-watches every path.
+	///
+	/// Only filesystem events are paused: signals, process completions, and events sent with
+	/// [`send_event()`](Watchexec::send_event()) still go through. Pausing when already paused
+	/// does nothing.
+	pub fn pause(&self) {
+		debug!("pausing watching");
+		self.pause.send_replace(true);
+	}
+
+	/// Resumes watching after a [`pause()`](Watchexec::pause()).
+	///
+	/// With [`WhilePaused::Coalesce`](crate::fs::WhilePaused::Coalesce), the events held while
+	/// paused are then emitted as one. Resuming when not paused does nothing.
+	pub fn resume(&self) {
+		debug!("resuming watching");
+		self.pause.send_replace(false);
+	}
+
+	/// Whether watching is [paused](Watchexec::pause()).
+	pub fn is_paused(&self) -> bool {
+		*self.pause.borrow()
+	}
+
+	/// Changes the log level, through the
+	/// [log level handler](crate::config::InitConfig#structfield.log_level_handler).
+	///
+	/// This takes effect straight away, without restarting anything, and stays until it's changed
+	/// again. Errors with [`RuntimeError::NoLogLevelHandler`] if no handler was given.
+	pub fn set_log_level(&self, level: LevelFilter) -> Result<(), RuntimeError> {
+		debug!(%level, "changing log level");
+		self.log_level.set(level)
+	}
+
+	/// Returns a receiver of the [`Lifecycle`] events of this instance.
+	///
+	/// The receiver only gets events sent after it was created. Each buffers up to 16 events, and
+	/// if it falls further behind, the oldest are skipped (see [`broadcast::Receiver::recv()`]).
+	pub fn lifecycle(&self) -> broadcast::Receiver<Lifecycle> {
+		self.lifecycle.subscribe()
+	}
+
+	/// Returns a snapshot of the counters of events and actions.
+	///
+	/// See the [metrics module][crate::metrics] for what is counted.
+	pub fn metrics(&self) -> Metrics {
+		self.metrics.snapshot()
+	}
+
+	/// Returns how many events are waiting in the event channel.
+	///
+	/// This is the number of events sent but not yet taken by the action worker, out of the
+	/// [`event_channel_size`](InitConfig#structfield.event_channel_size). Watching it while the
+	/// runtime is busy shows whether the channel is sized right: if it's often near the size,
+	/// sources are being held up (or dropping events, depending on the
+	/// [`event_overflow`](InitConfig#structfield.event_overflow) policy).
+	///
+	/// This is approximate: events are sent and received concurrently, so the depth may have
+	/// changed by the time it's returned. Slots reserved by senders count as taken.
+	pub fn event_queue_depth(&self) -> usize {
+		self.event_channel_size
+			.saturating_sub(self.event_input.capacity())
+	}
+
+	/// Returns the current lifecycle status of the runtime.
+	///
+	/// This is updated by the main task at each transition, so can be polled for liveness (for
+	/// example in health checks) without awaiting the main task handle. If the main task panics or
+	/// is aborted, the status is left at what it was last set to.
+	pub fn status(&self) -> Status {
+		self.status.borrow().clone()
+	}
+
 	/// Inputs an [`Event`] directly.
 	///
 	/// This can be useful for testing, for custom event sources, or for one-off action triggers
 	/// (for example, on start).
 	///
-	/// Hint: use [`Event::default()`] to send an empty event (which won't be filtered).
+	/// Hint: use [`Event::default()`] to send an empty event (which won't be filtered), and
+	/// [`Event::from_path()`] or [`Event::signal()`] for events like those of the built-in sources.
+	///
+	/// This waits for room in the event channel if it's full, which happens when events come in
+	/// faster than the action worker takes them (it doesn't while it's applying an action). Use
+	/// [`try_send_event()`](Watchexec::try_send_event()) to not wait.
 	pub async fn send_event(&self, event: Event) -> Result<(), CriticalError> {
 		self.event_input.send(event).await?;
 		Ok(())
 	}
 
+	/// Inputs a batch of [`Event`]s, to be acted on together.
+	///
+	/// The events are sent in order, and the action worker waits for the whole batch before
+	/// starting an action, so they all land in the same action (along with whatever else came in
+	/// meanwhile), instead of being split by the throttle. This waits for room in the event channel
+	/// like [`send_event()`](Watchexec::send_event()).
+	///
+	/// Concurrent calls to this are queued, so batches are never interleaved with one another.
+	/// Events input in other ways (including by `send_event()` and the filesystem worker) while a
+	/// batch is being sent can be interleaved with it, and then end up in the same action. If
+	/// this is cancelled partway through, the action worker stops waiting for the rest of the batch
+	/// after [`BATCH_STALL`](crate::action::BATCH_STALL).
+	pub async fn inject_events(&self, events: Vec<Event>) -> Result<(), CriticalError> {
+		let _batch = self.batch_lock.lock().await;
+		let last = events.len().saturating_sub(1);
+		for (n, mut event) in events.into_iter().enumerate() {
+			if n < last {
+				event
+					.metadata
+					.insert(action::BATCH_MORE.to_owned(), Vec::new());
+			}
+			self.event_input.send(event).await?;
+		}
+		Ok(())
+	}
+
+	/// Inputs an [`Event`] directly, without waiting.
+	///
+	/// If the event channel is full, this returns [`TrySendError::Full`] with the event, which can
+	/// be retried later or dropped. This can be called from both async and sync code.
+	pub fn try_send_event(&self, event: Event) -> Result<(), TrySendError<Event>> {
+		self.event_input.try_send(event)
+	}
+
+	/// Inputs an [`Event`] directly, from synchronous code.
+	///
+	/// This is the same as [`send_event()`](Watchexec::send_event()), but blocks the current thread
+	/// instead, for use from non-async threads such as FFI callbacks. As it waits for room in the
+	/// event channel, it must not be called from a thread the action worker is waiting on (e.g.
+	/// from within a synchronous handler), as that would deadlock when the channel is full: use
+	/// [`try_send_event()`](Watchexec::try_send_event()) there.
+	///
+	/// # Panics
+	///
+	/// Panics if called within an asynchronous execution context. Use `send_event()` there.
+	pub fn send_event_blocking(&self, event: Event) -> Result<(), CriticalError> {
+		self.event_input.blocking_send(event)?;
+		Ok(())
+	}
+
+	/// Shuts down watchexec gracefully.
+	///
+	/// This signals every worker to wind down, lets the action worker finish whatever action it is
+	/// in the middle of applying, and returns once the main task has completed. If `timeout` is
+	/// given and the workers have not finished within it, the remaining subtasks are aborted.
+	///
+	/// The main task handle obtained from [`main()`](Watchexec::main()) resolves successfully after
+	/// a shutdown, including if it was requested before `main()` was called.
+	///
+	/// Calling this more than once is a no-op.
+	pub async fn shutdown(&self, timeout: Option<Duration>) -> Result<(), CriticalError> {
+		if self.shutdown.send_replace(true) {
+			debug!("shutdown already requested, ignoring");
+			return Ok(());
+		}
+
+		debug!(?timeout, "shutting down");
+		if let Some(t) = timeout {
+			if tokio::time::timeout(t, self.wait_stopped()).await.is_err() {
+				self.force_shutdown.notify_one();
+				self.wait_stopped().await;
+			}
+		} else {
+			self.wait_stopped().await;
+		}
+
+		debug!("shutdown complete");
+		Ok(())
+	}
+
+	pub(crate) async fn wait_stopped(&self) {
+		let mut status = self.status.clone();
+		while !matches!(*status.borrow(), Status::Stopped(_)) {
+			if status.changed().await.is_err() {
+				trace!("main task is gone, considering it stopped");
+				break;
+			}
+		}
+	}
+
+	/// Returns a future which resolves when the main task has finished.
+	///
+	/// Unlike the handle from [`main()`](Watchexec::main()), this can be obtained any number of
+	/// times, and cloned, so several parts of an application can await shutdown. It doesn't start
+	/// the runtime: until `main()` is called (or a shutdown requested), it stays pending.
+	///
+	/// If the main task finished with an error, this resolves to a
+	/// [`MainTaskStopped`](CriticalError::MainTaskStopped) with its message; the error itself is
+	/// returned by the main task handle. If the main task panicked or was aborted before it could
+	/// report, the message says so.
+	pub fn wait(&self) -> impl Future<Output = Result<(), CriticalError>> + Clone + Send + 'static {
+		let mut status = self.status.clone();
+		let stopped = async move {
+			loop {
+				if let Status::Stopped(res) = &*status.borrow() {
+					return res.clone();
+				}
+
+				if status.changed().await.is_err() {
+					return Err(String::from("main task ended without finishing"));
+				}
+			}
+		}
+		.boxed()
+		.shared();
+
+		Wait(stopped)
+	}
+
 	/// Start watchexec and obtain the handle to its main task.
 	///
-	/// This must only be called once.
+	/// When it stops gracefully, the main task resolves to the [reason](ShutdownReason) why.
+	///
+	/// This must only be called once; use [`wait()`](Watchexec::wait()) to await the runtime from
+	/// more than one place.
 	///
 	/// # Panics
 	/// Panics if called twice.
-	pub fn main(&self) -> JoinHandle<Result<(), CriticalError>> {
+	pub fn main(&self) -> JoinHandle<Result<ShutdownReason, CriticalError>> {
 		trace!("notifying start lock");
 		self.start_lock.notify_one();
 
@@ -160,6 +967,23 @@ impl Watchexec {
 	}
 }
 
+/// The future returned by [`Watchexec::wait()`].
+///
+/// The status is awaited in a [`Shared`] future so this can be cloned, but as [`CriticalError`]
+/// isn't `Clone`, each clone makes its own error from the message.
+#[derive(Clone)]
+struct Wait(Shared<BoxFuture<'static, Result<(), String>>>);
+
+impl Future for Wait {
+	type Output = Result<(), CriticalError>;
+
+	fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+		self.0
+			.poll_unpin(cx)
+			.map(|res| res.map_err(CriticalError::MainTaskStopped))
+	}
+}
+
 #[inline]
 fn flatten(join_res: Result<Result<(), CriticalError>, JoinError>) -> Result<(), CriticalError> {
 	join_res
@@ -167,26 +991,223 @@ fn flatten(join_res: Result<Result<(), CriticalError>, JoinError>) -> Result<(),
 		.and_then(|x| x)
 }
 
+/// Checks the configuration for mistakes which would otherwise only surface later, in the workers.
+fn validate(init: &InitConfig, runtime: &RuntimeConfig) -> Result<(), CriticalError> {
+	let mut problems = Vec::new();
+	if init.error_channel_size == 0 {
+		problems.push("error_channel_size must be greater than zero".to_string());
+	}
+	if init.event_channel_size == 0 {
+		problems.push("event_channel_size must be greater than zero".to_string());
+	}
+	if init.saturation_warning == Some(Duration::ZERO) {
+		problems.push("saturation_warning must be greater than zero".to_string());
+	}
+	problems.extend(runtime.action.problems());
+	problems.extend(runtime.fs.problems());
+
+	if problems.is_empty() {
+		Ok(())
+	} else {
+		Err(CriticalError::ConfigInvalid(problems.join("; ")))
+	}
+}
+
+/// Adds the program of the command to the pathset, if enabled and possible.
+fn watch_command_binary(config: &mut RuntimeConfig) {
+	if !config.action.watch_command_binary || config.action.command.is_empty() {
+		return;
+	}
+
+	match config
+		.action
+		.shell
+		.program_path(&config.action.command)
+		.map(WatchedPath::file)
+	{
+		Some(path) if config.fs.pathset.contains(&path) => {}
+		Some(path) => {
+			debug!(?path, "adding command program to the pathset");
+			config.fs.pathset.push(path);
+		}
+		None => warn!(
+			shell=?config.action.shell,
+			"cannot determine the program of the command, not watching it"
+		),
+	}
+}
+
+/// Sends a [`Lifecycle::WatcherRebuilt`] for each watcher reinit event the action worker receives.
+async fn watch_rebuilds(
+	mut events: broadcast::Receiver<Event>,
+	lifecycle: broadcast::Sender<Lifecycle>,
+) {
+	loop {
+		match events.recv().await {
+			Ok(event) if event.is_fs_lifecycle() => {
+				lifecycle.send(Lifecycle::WatcherRebuilt).ok();
+			}
+			Ok(_) => {}
+			Err(broadcast::error::RecvError::Lagged(missed)) => {
+				debug!(%missed, "lifecycle watcher lagged, may miss watcher rebuilds");
+			}
+			Err(broadcast::error::RecvError::Closed) => break,
+		}
+	}
+}
+
+/// Tenths of the event channel which must be taken for it to be considered nearly full.
+const SATURATION_THRESHOLD: usize = 9;
+
+/// Tracks how long the event channel has been nearly full.
+#[derive(Debug, Default)]
+struct Saturation {
+	since: Option<Instant>,
+	reported: bool,
+}
+
+impl Saturation {
+	/// Records a sample of the channel depth.
+	///
+	/// Returns how long the channel has been nearly full, the first time that's `window` or more.
+	fn sample(
+		&mut self,
+		depth: usize,
+		size: usize,
+		now: Instant,
+		window: Duration,
+	) -> Option<Duration> {
+		if depth.saturating_mul(10) < size.saturating_mul(SATURATION_THRESHOLD) {
+			*self = Self::default();
+			return None;
+		}
+
+		let duration = now.duration_since(*self.since.get_or_insert(now));
+		if self.reported || duration < window {
+			return None;
+		}
+
+		self.reported = true;
+		Some(duration)
+	}
+}
+
+/// Samples the depth of the event channel, and warns when it stays nearly full.
+///
+/// This only warns: the channel isn't resized, as its size is fixed once created. The warning
+/// suggests a size for the operator to set instead.
+///
+/// See [`InitConfig::saturation_warning`](InitConfig#structfield.saturation_warning).
+async fn watch_saturation(
+	events: mpsc::Sender<Event>,
+	size: usize,
+	window: Duration,
+	errors: mpsc::Sender<RuntimeError>,
+) {
+	let mut ticks = interval((window / 10).max(Duration::from_millis(10)));
+	ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+	let mut saturation = Saturation::default();
+	loop {
+		ticks.tick().await;
+		let depth = size.saturating_sub(events.capacity());
+		if let Some(duration) = saturation.sample(depth, size, Instant::now(), window) {
+			let err = RuntimeError::ChannelSaturated {
+				size,
+				depth,
+				duration,
+			};
+			if errors.send(err).await.is_err() {
+				break;
+			}
+		}
+	}
+}
+
+/// Stand-in for a disabled subtask: holds onto `_keep` until shutdown.
+async fn idle<T>(_keep: T, mut shutdown: watch::Receiver<bool>) -> Result<(), CriticalError> {
+	shutdown.changed().await.ok();
+	Ok(())
+}
+
 async fn error_hook(
 	mut errors: mpsc::Receiver<RuntimeError>,
-	mut handler: Box<dyn Handler<RuntimeError> + Send>,
+	handlers: Vec<Box<dyn Handler<Arc<RuntimeError>> + Send>>,
+	failure_limit: usize,
 ) -> Result<(), CriticalError> {
+	// each handler with its count of consecutive failures
+	let mut handlers: Vec<_> = handlers.into_iter().map(|h| (h, 0_usize)).collect();
 	while let Some(err) = errors.recv().await {
 		if matches!(err, RuntimeError::Exit) {
 			trace!("got graceful exit request via runtime error, upgrading to crit");
-			return Err(CriticalError::Exit);
+			return Err(CriticalError::Exit(ShutdownReason::Requested));
 		}
 
 		error!(%err, "runtime error");
-		if let Err(err) = handler.handle(err) {
-			error!(%err, "error while handling error");
-			handler
-				.handle(rte("error hook", err))
-				.unwrap_or_else(|err| {
+		let err = Arc::new(err);
+		let mut failures = Vec::new();
+		for (handler, failed) in &mut handlers {
+			if let Err(err) = handler.handle(err.clone()) {
+				error!(%err, "error while handling error");
+				failures.push(Arc::new(rte("error hook", err)));
+				*failed += 1;
+			} else {
+				*failed = 0;
+			}
+		}
+
+		for failure in failures {
+			for (handler, _) in &mut handlers {
+				handler.handle(failure.clone()).unwrap_or_else(|err| {
 					error!(%err, "error while handling error of handling error");
 				});
+			}
+		}
+
+		if failure_limit > 0 {
+			let before = handlers.len();
+			handlers.retain(|(_, failed)| *failed < failure_limit);
+			if handlers.len() < before {
+				error!(
+					disabled = before - handlers.len(),
+					remaining = handlers.len(),
+					"error handler failed {} times in a row, disabling it; runtime errors are still logged",
+					failure_limit
+				);
+			}
 		}
 	}
 
 	Ok(())
 }
+
+#[cfg(test)]
+mod test {
+	use std::time::{Duration, Instant};
+
+	use super::Saturation;
+
+	#[test]
+	fn saturation_is_reported_once_per_stretch() {
+		let window = Duration::from_secs(1);
+		let start = Instant::now();
+		let at = |ms| start + Duration::from_millis(ms);
+		let mut saturation = Saturation::default();
+
+		assert_eq!(saturation.sample(8, 10, at(0), window), None);
+		assert_eq!(saturation.sample(9, 10, at(0), window), None);
+		assert_eq!(saturation.sample(10, 10, at(500), window), None);
+		assert_eq!(
+			saturation.sample(9, 10, at(1200), window),
+			Some(Duration::from_millis(1200))
+		);
+		assert_eq!(saturation.sample(10, 10, at(5000), window), None);
+
+		assert_eq!(saturation.sample(3, 10, at(5100), window), None);
+		assert_eq!(saturation.sample(10, 10, at(5200), window), None);
+		assert_eq!(
+			saturation.sample(10, 10, at(6200), window),
+			Some(Duration::from_secs(1))
+		);
+	}
+}
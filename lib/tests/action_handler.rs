@@ -0,0 +1,818 @@
+use std::{
+	convert::Infallible,
+	sync::{
+		atomic::{AtomicUsize, Ordering},
+		Arc,
+	},
+	time::Duration,
+};
+
+use tokio::{
+	sync::mpsc,
+	time::{sleep, timeout, Instant},
+};
+use watchexec::{
+	action::{Action, Outcome},
+	config::{InitConfig, RuntimeConfig},
+	error::RuntimeError,
+	event::{
+		filekind::{FileEventKind, ModifyKind},
+		Event, FileType, Source, Tag,
+	},
+	filter::expr::Filter,
+	signal::source::MainSignal,
+	Status, Watchexec,
+};
+
+#[tokio::test(flavor = "multi_thread")]
+async fn handler_sees_batched_events() {
+	let (ac_s, mut ac_r) = mpsc::channel(8);
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.action_throttle(Duration::from_millis(200));
+	runtime.on_action(move |action: Action| {
+		let ac_s = ac_s.clone();
+		async move {
+			ac_s.send(action.events.len()).await.ok();
+			action.outcome(Outcome::DoNothing);
+			Ok::<(), Infallible>(())
+		}
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+
+	let event = Event {
+		tags: vec![Tag::Source(Source::Internal)],
+		metadata: Default::default(),
+	};
+	wx.send_event(event.clone()).await.unwrap();
+	wx.send_event(event).await.unwrap();
+
+	assert_eq!(
+		timeout(Duration::from_secs(5), ac_r.recv()).await.unwrap(),
+		Some(2)
+	);
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn exit_outcome_stops_main() {
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.on_action(|action: Action| async move {
+		action.outcome(Outcome::Exit);
+		Ok::<(), Infallible>(())
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+
+	wx.send_event(Event::default()).await.unwrap();
+
+	timeout(Duration::from_secs(5), main)
+		.await
+		.expect("main task did not resolve after exit outcome")
+		.unwrap()
+		.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn exit_outcome_is_not_an_error() {
+	let (er_s, mut er_r) = mpsc::channel(8);
+	let mut init = InitConfig::default();
+	init.on_error(move |err: std::sync::Arc<RuntimeError>| {
+		let er_s = er_s.clone();
+		async move {
+			er_s.send(err.to_string()).await.ok();
+			Ok::<(), Infallible>(())
+		}
+	});
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.on_action(|action: Action| async move {
+		action.outcome(Outcome::both(Outcome::DoNothing, Outcome::Exit));
+		Ok::<(), Infallible>(())
+	});
+
+	let wx = Watchexec::new(init, runtime).unwrap();
+	let main = wx.main();
+	wx.send_event(Event::default()).await.unwrap();
+
+	timeout(Duration::from_secs(5), main)
+		.await
+		.expect("main task did not resolve after exit outcome")
+		.unwrap()
+		.unwrap();
+	assert_eq!(wx.status(), Status::Stopped(Ok(())));
+	assert_eq!(er_r.try_recv().ok(), None);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn run_on_start_triggers_initial_action() {
+	let (ac_s, mut ac_r) = mpsc::channel(8);
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.run_on_start(true);
+	runtime.on_action(move |action: Action| {
+		let ac_s = ac_s.clone();
+		async move {
+			ac_s.send(action.events.iter().all(Event::is_empty))
+				.await
+				.ok();
+			Ok::<(), Infallible>(())
+		}
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+
+	assert_eq!(
+		timeout(Duration::from_secs(5), ac_r.recv()).await.unwrap(),
+		Some(true)
+	);
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn no_initial_action_by_default() {
+	let (ac_s, mut ac_r) = mpsc::channel::<()>(8);
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.on_action(move |_: Action| {
+		let ac_s = ac_s.clone();
+		async move {
+			ac_s.send(()).await.ok();
+			Ok::<(), Infallible>(())
+		}
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+
+	assert!(timeout(Duration::from_millis(300), ac_r.recv())
+		.await
+		.is_err());
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn predicate_rejects_events() {
+	let (ac_s, mut ac_r) = mpsc::channel(8);
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.filter_predicate(|event: &Event| {
+		!event
+			.tags
+			.iter()
+			.any(|t| matches!(t, Tag::Source(Source::Keyboard)))
+	});
+	runtime.on_action(move |action: Action| {
+		let ac_s = ac_s.clone();
+		async move {
+			ac_s.send(action.events.as_ref().clone()).await.ok();
+			Ok::<(), Infallible>(())
+		}
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+
+	let rejected = Event {
+		tags: vec![Tag::Source(Source::Keyboard)],
+		metadata: Default::default(),
+	};
+	let accepted = Event {
+		tags: vec![Tag::Source(Source::Internal)],
+		metadata: Default::default(),
+	};
+	wx.send_event(rejected).await.unwrap();
+	wx.send_event(accepted.clone()).await.unwrap();
+
+	assert_eq!(
+		timeout(Duration::from_secs(5), ac_r.recv()).await.unwrap(),
+		Some(vec![accepted])
+	);
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn async_predicate_rejects_events_and_passes_on_timeout() {
+	let (ac_s, mut ac_r) = mpsc::channel(8);
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.action_throttle(Duration::from_millis(500));
+	runtime.filter_predicate_timeout(Duration::from_millis(100));
+	runtime.filter_predicate_async(|event: Event| {
+		Box::pin(async move {
+			if event.sources().any(|s| s == Source::Os) {
+				std::future::pending::<()>().await;
+			}
+			sleep(Duration::from_millis(10)).await;
+			event.sources().all(|s| s != Source::Keyboard)
+		})
+	});
+	runtime.on_action(move |action: Action| {
+		let ac_s = ac_s.clone();
+		async move {
+			ac_s.send(action.events.as_ref().clone()).await.ok();
+			Ok::<(), Infallible>(())
+		}
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+
+	let rejected = Event {
+		tags: vec![Tag::Source(Source::Keyboard)],
+		metadata: Default::default(),
+	};
+	let timed_out = Event {
+		tags: vec![Tag::Source(Source::Os)],
+		metadata: Default::default(),
+	};
+	let accepted = Event {
+		tags: vec![Tag::Source(Source::Internal)],
+		metadata: Default::default(),
+	};
+	wx.send_event(rejected).await.unwrap();
+	wx.send_event(timed_out.clone()).await.unwrap();
+	wx.send_event(accepted.clone()).await.unwrap();
+
+	assert_eq!(
+		timeout(Duration::from_secs(5), ac_r.recv()).await.unwrap(),
+		Some(vec![timed_out, accepted])
+	);
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn constructed_events_are_filtered_like_real_ones() {
+	let (ac_s, mut ac_r) = mpsc::channel(8);
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.filter_predicate(|event: &Event| {
+		event
+			.paths()
+			.all(|(path, _)| path.extension() == Some("rs".as_ref()))
+	});
+	runtime.on_action(move |action: Action| {
+		let ac_s = ac_s.clone();
+		async move {
+			for event in action.events.iter() {
+				ac_s.send(event.clone()).await.ok();
+			}
+			Ok::<(), Infallible>(())
+		}
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+
+	let modified = |path| Event::from_path(path, FileEventKind::Modify(ModifyKind::Any));
+	wx.send_event(modified("/src/lib.o")).await.unwrap();
+	wx.send_event(modified("/src/lib.rs")).await.unwrap();
+	wx.send_event(Event::signal(MainSignal::Hangup))
+		.await
+		.unwrap();
+
+	let mut received = Vec::new();
+	while received.len() < 2 {
+		received.push(
+			timeout(Duration::from_secs(5), ac_r.recv())
+				.await
+				.unwrap()
+				.unwrap(),
+		);
+	}
+	assert_eq!(
+		received,
+		vec![modified("/src/lib.rs"), Event::signal(MainSignal::Hangup)]
+	);
+	assert_eq!(
+		received[0].sources().collect::<Vec<_>>(),
+		[Source::Filesystem]
+	);
+	assert_eq!(received[1].sources().collect::<Vec<_>>(), [Source::Os]);
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn event_mapper_runs_before_filtering() {
+	let (ac_s, mut ac_r) = mpsc::channel(8);
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.event_mapper(|mut event: Event| {
+		if event.sources().any(|s| s == Source::Keyboard) {
+			return None;
+		}
+
+		for tag in &mut event.tags {
+			if let Tag::Path { path, .. } = tag {
+				if let Ok(rest) = path.strip_prefix("/build") {
+					*path = std::path::Path::new("/src").join(rest);
+				}
+			}
+		}
+		Some(event)
+	});
+	runtime.filter_predicate(|event: &Event| event.paths().all(|(p, _)| p.starts_with("/src")));
+	runtime.on_action(move |action: Action| {
+		let ac_s = ac_s.clone();
+		async move {
+			ac_s.send(action.events.as_ref().clone()).await.ok();
+			Ok::<(), Infallible>(())
+		}
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+
+	let path = |path: &str| Event {
+		tags: vec![Tag::Path {
+			path: path.into(),
+			file_type: None,
+		}],
+		metadata: Default::default(),
+	};
+	wx.send_event(Event {
+		tags: vec![Tag::Source(Source::Keyboard)],
+		metadata: Default::default(),
+	})
+	.await
+	.unwrap();
+	wx.send_event(path("/build/main.o")).await.unwrap();
+
+	assert_eq!(
+		timeout(Duration::from_secs(5), ac_r.recv()).await.unwrap(),
+		Some(vec![path("/src/main.o")])
+	);
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn filter_expressions_apply_after_reconfigure() {
+	let (ac_s, mut ac_r) = mpsc::channel(8);
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.filters([
+		"*.rs".parse::<Filter>().unwrap(),
+		Filter::ignore("target/**"),
+	]);
+	runtime.on_action(move |action: Action| {
+		let ac_s = ac_s.clone();
+		async move {
+			let paths: Vec<_> = action
+				.events
+				.iter()
+				.flat_map(|e| e.paths().map(|(p, _)| p.to_owned()))
+				.collect();
+			ac_s.send(paths).await.ok();
+			Ok::<(), Infallible>(())
+		}
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime.clone()).unwrap();
+	let main = wx.main();
+
+	let modify = |path: &str| Event::from_path(path, FileEventKind::Modify(ModifyKind::Any));
+	wx.send_event(modify("/target/build.rs")).await.unwrap();
+	wx.send_event(modify("/README.md")).await.unwrap();
+	wx.send_event(modify("/src/lib.rs")).await.unwrap();
+	assert_eq!(
+		timeout(Duration::from_secs(5), ac_r.recv()).await.unwrap(),
+		Some(vec!["/src/lib.rs".into()])
+	);
+
+	runtime.filters([Filter::select("*.md")]);
+	wx.reconfigure(runtime).unwrap();
+	wx.send_event(modify("/src/lib.rs")).await.unwrap();
+	wx.send_event(modify("/README.md")).await.unwrap();
+	assert_eq!(
+		timeout(Duration::from_secs(5), ac_r.recv()).await.unwrap(),
+		Some(vec!["/README.md".into()])
+	);
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn handler_can_branch_on_source() {
+	let (ac_s, mut ac_r) = mpsc::channel(8);
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.on_action(move |action: Action| {
+		let ac_s = ac_s.clone();
+		async move {
+			let sources: Vec<Source> = action.events.iter().flat_map(|e| e.sources()).collect();
+			ac_s.send(sources).await.ok();
+			Ok::<(), Infallible>(())
+		}
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+
+	wx.send_event(Event {
+		tags: vec![Tag::Source(Source::Keyboard)],
+		metadata: Default::default(),
+	})
+	.await
+	.unwrap();
+
+	assert_eq!(
+		timeout(Duration::from_secs(5), ac_r.recv()).await.unwrap(),
+		Some(vec![Source::Keyboard])
+	);
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rate_limit_fires_leading_then_trailing() {
+	let (ac_s, mut ac_r) = mpsc::channel(8);
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime
+		.action_throttle(Duration::from_secs(10))
+		.action_rate_limit(Some(Duration::from_millis(500)));
+	runtime.on_action(move |action: Action| {
+		let ac_s = ac_s.clone();
+		async move {
+			ac_s.send(action.events.len()).await.ok();
+			action.outcome(Outcome::DoNothing);
+			Ok::<(), Infallible>(())
+		}
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+
+	let event = Event {
+		tags: vec![Tag::Source(Source::Internal)],
+		metadata: Default::default(),
+	};
+
+	// the first event is actioned straight away, despite the long throttle
+	wx.send_event(event.clone()).await.unwrap();
+	assert_eq!(
+		timeout(Duration::from_millis(300), ac_r.recv())
+			.await
+			.unwrap(),
+		Some(1)
+	);
+
+	// events within the window are collapsed into one run at its edge
+	wx.send_event(event.clone()).await.unwrap();
+	wx.send_event(event.clone()).await.unwrap();
+	wx.send_event(event).await.unwrap();
+	assert!(timeout(Duration::from_millis(100), ac_r.recv())
+		.await
+		.is_err());
+	assert_eq!(
+		timeout(Duration::from_secs(5), ac_r.recv()).await.unwrap(),
+		Some(3)
+	);
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn debounce_coalesces_every_source() {
+	let (ac_s, mut ac_r) = mpsc::channel(8);
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime
+		.action_throttle(Duration::from_millis(10))
+		.action_debounce(Some(Duration::from_millis(300)));
+	runtime.on_action(move |action: Action| {
+		let ac_s = ac_s.clone();
+		async move {
+			let sources: Vec<Source> = action.events.iter().flat_map(|e| e.sources()).collect();
+			ac_s.send(sources).await.ok();
+			action.outcome(Outcome::DoNothing);
+			Ok::<(), Infallible>(())
+		}
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+
+	// each event comes well after the throttle, but within the debounce of the previous one
+	wx.send_event(Event::from_path(
+		"/src/lib.rs",
+		FileEventKind::Modify(ModifyKind::Any),
+	))
+	.await
+	.unwrap();
+	sleep(Duration::from_millis(100)).await;
+	wx.send_event(Event::signal(MainSignal::Hangup))
+		.await
+		.unwrap();
+	sleep(Duration::from_millis(100)).await;
+	wx.send_event(Event {
+		tags: vec![Tag::Source(Source::Keyboard)],
+		metadata: Default::default(),
+	})
+	.await
+	.unwrap();
+
+	assert_eq!(
+		timeout(Duration::from_secs(5), ac_r.recv()).await.unwrap(),
+		Some(vec![Source::Filesystem, Source::Os, Source::Keyboard])
+	);
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn last_outcome_is_published() {
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.action_throttle(Duration::from_millis(10));
+	runtime.on_action(move |action: Action| async move {
+		action.outcome(Outcome::if_running(Outcome::Stop, Outcome::DoNothing));
+		Ok::<(), Infallible>(())
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let mut last = wx.last_outcome();
+	assert_eq!(*last.borrow(), None);
+
+	let main = wx.main();
+	wx.send_event(Event::default()).await.unwrap();
+
+	timeout(Duration::from_secs(5), last.changed())
+		.await
+		.unwrap()
+		.unwrap();
+	// the published outcome is the resolved one
+	assert_eq!(*last.borrow(), Some(Outcome::DoNothing));
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn callback_outcome_runs_in_process() {
+	let (cb_s, mut cb_r) = mpsc::channel(8);
+	let (er_s, mut er_r) = mpsc::channel(8);
+
+	let mut init = InitConfig::default();
+	init.on_error(move |err: std::sync::Arc<RuntimeError>| {
+		let er_s = er_s.clone();
+		async move {
+			er_s.send(err.to_string()).await.ok();
+			Ok::<(), Infallible>(())
+		}
+	});
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.action_throttle(Duration::from_millis(10));
+	runtime.on_action(move |action: Action| {
+		let cb_s = cb_s.clone();
+		async move {
+			action.outcome(Outcome::callback(move |events| {
+				let cb_s = cb_s.clone();
+				let count = events.len();
+				Box::pin(async move {
+					cb_s.send(count).await.ok();
+					Err(RuntimeError::External("callback failed".into()))
+				})
+			}));
+			Ok::<(), Infallible>(())
+		}
+	});
+
+	let wx = Watchexec::new(init, runtime).unwrap();
+	let main = wx.main();
+	wx.send_event(Event::default()).await.unwrap();
+
+	assert_eq!(
+		timeout(Duration::from_secs(5), cb_r.recv()).await.unwrap(),
+		Some(1)
+	);
+	assert_eq!(
+		timeout(Duration::from_secs(5), er_r.recv()).await.unwrap(),
+		Some(RuntimeError::External("callback failed".into()).to_string())
+	);
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn delayed_outcome_fires_unless_superseded() {
+	let (cb_s, mut cb_r) = mpsc::channel(8);
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.action_throttle(Duration::from_millis(10));
+	let actions = Arc::new(AtomicUsize::new(0));
+	runtime.on_action(move |action: Action| {
+		let cb_s = cb_s.clone();
+		let n = actions.fetch_add(1, Ordering::SeqCst) + 1;
+		async move {
+			action.outcome(Outcome::delay(
+				Duration::from_millis(300),
+				Outcome::callback(move |_| {
+					let cb_s = cb_s.clone();
+					Box::pin(async move {
+						cb_s.send((n, Instant::now())).await.ok();
+						Ok(())
+					})
+				}),
+			));
+			Ok::<(), Infallible>(())
+		}
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+
+	let sent = Instant::now();
+	wx.send_event(Event::default()).await.unwrap();
+	let (n, fired) = timeout(Duration::from_secs(5), cb_r.recv())
+		.await
+		.unwrap()
+		.unwrap();
+	assert_eq!(n, 1);
+	assert!(fired - sent >= Duration::from_millis(300));
+
+	wx.send_event(Event::default()).await.unwrap();
+	sleep(Duration::from_millis(100)).await;
+	wx.send_event(Event::default()).await.unwrap();
+	assert_eq!(
+		timeout(Duration::from_secs(5), cb_r.recv())
+			.await
+			.unwrap()
+			.map(|(n, _)| n),
+		Some(3)
+	);
+	assert!(timeout(Duration::from_millis(500), cb_r.recv())
+		.await
+		.is_err());
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn vanished_paths_are_dropped() {
+	let (ac_s, mut ac_r) = mpsc::channel(8);
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.action_throttle(Duration::from_millis(100));
+	runtime.drop_vanished(true);
+	runtime.on_action(move |action: Action| {
+		let ac_s = ac_s.clone();
+		async move {
+			ac_s.send(action.events.to_vec()).await.ok();
+			Ok::<(), Infallible>(())
+		}
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+
+	let present = std::env::current_dir().unwrap().join("Cargo.toml");
+	let ghost = std::env::current_dir()
+		.unwrap()
+		.join("does-not-exist-for-watchexec-tests");
+	let path_event = |paths: &[&std::path::PathBuf]| Event {
+		tags: paths
+			.iter()
+			.map(|path| Tag::Path {
+				path: path.to_path_buf(),
+				file_type: None,
+			})
+			.collect(),
+		metadata: Default::default(),
+	};
+
+	// an action with only ghosts doesn't happen
+	wx.send_event(path_event(&[&ghost])).await.unwrap();
+	sleep(Duration::from_millis(300)).await;
+	assert!(ac_r.try_recv().is_err());
+
+	wx.send_event(path_event(&[&ghost])).await.unwrap();
+	wx.send_event(path_event(&[&present, &ghost]))
+		.await
+		.unwrap();
+	let events = timeout(Duration::from_secs(5), ac_r.recv())
+		.await
+		.unwrap()
+		.unwrap();
+	assert_eq!(
+		events,
+		vec![Event {
+			tags: vec![Tag::Path {
+				path: present,
+				file_type: Some(FileType::File),
+			}],
+			metadata: Default::default(),
+		}]
+	);
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn duplicate_events_are_dropped_from_batch() {
+	use watchexec::event::filekind::{CreateKind, DataChange, FileEventKind, ModifyKind};
+
+	async fn batch_with(dedupe: bool) -> Vec<Event> {
+		let (ac_s, mut ac_r) = mpsc::channel(8);
+
+		let mut runtime = RuntimeConfig::default();
+		runtime.enable_fs(false).enable_signal(false);
+		runtime.action_throttle(Duration::from_millis(200));
+		runtime.dedupe(dedupe);
+		runtime.on_action(move |action: Action| {
+			let ac_s = ac_s.clone();
+			async move {
+				ac_s.send(action.events.to_vec()).await.ok();
+				Ok::<(), Infallible>(())
+			}
+		});
+
+		let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+		let main = wx.main();
+
+		let event = |path: &str, kind| Event {
+			tags: vec![
+				Tag::Source(Source::Filesystem),
+				Tag::Path {
+					path: path.into(),
+					file_type: None,
+				},
+				Tag::FileEventKind(kind),
+			],
+			metadata: Default::default(),
+		};
+		let modify = FileEventKind::Modify(ModifyKind::Data(DataChange::Content));
+		let create = FileEventKind::Create(CreateKind::File);
+		for e in [
+			event("/a", modify.clone()),
+			event("/a", modify.clone()),
+			event("/b", modify.clone()),
+			event("/a", create),
+			event("/b", modify),
+		] {
+			wx.send_event(e).await.unwrap();
+		}
+
+		let events = timeout(Duration::from_secs(5), ac_r.recv())
+			.await
+			.unwrap()
+			.unwrap();
+		wx.shutdown(None).await.unwrap();
+		main.await.unwrap().unwrap();
+		events
+	}
+
+	let deduped: Vec<_> = batch_with(true)
+		.await
+		.into_iter()
+		.map(|e| e.paths().map(|(p, _)| p.to_owned()).collect::<Vec<_>>())
+		.collect();
+	assert_eq!(
+		deduped,
+		vec![vec!["/a".into()], vec!["/b".into()], vec!["/a".into()]]
+			as Vec<Vec<std::path::PathBuf>>
+	);
+	assert_eq!(batch_with(false).await.len(), 5);
+}
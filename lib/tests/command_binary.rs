@@ -0,0 +1,96 @@
+#![cfg(unix)]
+
+use std::{convert::Infallible, fs, os::unix::fs::PermissionsExt, path::Path, time::Duration};
+
+use tokio::time::{sleep, timeout};
+use watchexec::{
+	action::{Action, Outcome},
+	config::{InitConfig, RuntimeConfig},
+	event::{Event, Source, Tag},
+	Watchexec,
+};
+
+fn write_program(path: &Path) {
+	fs::write(path, "#!/bin/sh\nwhile true; do sleep 0.1; done\n").unwrap();
+	fs::set_permissions(path, fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn replacing_the_program_restarts_it() {
+	let base = std::env::temp_dir().join(format!("watchexec-test-binary-{}", std::process::id()));
+	fs::create_dir_all(&base).unwrap();
+	let base = dunce::canonicalize(base).unwrap();
+	let program = base.join("program");
+	write_program(&program);
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_signal(false);
+	runtime.command([program.display().to_string()]);
+	runtime.command_grouped(false).command_watch_binary(true);
+	runtime.on_action(|action: Action| async move {
+		let initial = action
+			.events
+			.iter()
+			.any(|e| e.tags.contains(&Tag::Source(Source::Internal)));
+		// fs events are left to the implicit restart
+		action.outcome(if initial {
+			Outcome::Start
+		} else {
+			Outcome::DoNothing
+		});
+		Ok::<(), Infallible>(())
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+
+	timeout(Duration::from_secs(5), async {
+		while !wx.watched_paths().contains(&program) {
+			sleep(Duration::from_millis(10)).await;
+		}
+	})
+	.await
+	.expect("program was not watched");
+
+	wx.send_event(Event {
+		tags: vec![Tag::Source(Source::Internal)],
+		metadata: Default::default(),
+	})
+	.await
+	.unwrap();
+	let first = timeout(Duration::from_secs(5), async {
+		loop {
+			if let Some(pid) = wx.current_child_pid() {
+				break pid;
+			}
+			sleep(Duration::from_millis(10)).await;
+		}
+	})
+	.await
+	.expect("command did not start");
+
+	// replace atomically, as installers do
+	let staging = base.join(".program.tmp");
+	write_program(&staging);
+	fs::rename(&staging, &program).unwrap();
+
+	let second = timeout(Duration::from_secs(5), async {
+		loop {
+			match wx.current_child_pid() {
+				Some(pid) if pid != first => break pid,
+				_ => sleep(Duration::from_millis(10)).await,
+			}
+		}
+	})
+	.await
+	.expect("command was not restarted");
+
+	std::process::Command::new("kill")
+		.arg("-9")
+		.arg(second.to_string())
+		.status()
+		.unwrap();
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+	fs::remove_dir_all(&base).ok();
+}
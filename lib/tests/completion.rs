@@ -0,0 +1,244 @@
+#![cfg(unix)]
+
+use std::{convert::Infallible, time::Duration};
+
+use tokio::{sync::mpsc, time::timeout};
+use watchexec::{
+	action::{Action, Outcome},
+	config::{InitConfig, RuntimeConfig},
+	event::{Event, ProcessEnd, Tag},
+	signal::process::SubSignal,
+	Watchexec,
+};
+
+#[tokio::test(flavor = "multi_thread")]
+async fn completion_restart_loop_is_guarded() {
+	let (ac_s, mut ac_r) = mpsc::channel(8);
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.command(["true"]);
+	runtime.on_action(move |action: Action| {
+		let ac_s = ac_s.clone();
+		async move {
+			for event in action.events.iter().filter(|e| e.is_completion()) {
+				ac_s.send(event.clone()).await.ok();
+			}
+			action.outcome(Outcome::Start);
+			Ok::<(), Infallible>(())
+		}
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+
+	wx.send_event(Event::default()).await.unwrap();
+
+	for _ in 0..2 {
+		let event = timeout(Duration::from_secs(5), ac_r.recv())
+			.await
+			.expect("no completion event")
+			.unwrap();
+		assert!(event
+			.tags
+			.iter()
+			.any(|t| matches!(t, Tag::ProcessCompletion(Some(ProcessEnd::Success), _))));
+	}
+
+	assert!(
+		timeout(Duration::from_millis(500), ac_r.recv())
+			.await
+			.is_err(),
+		"completion loop was not broken"
+	);
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn signal_outcome_reaches_command() {
+	use std::sync::{
+		atomic::{AtomicUsize, Ordering},
+		Arc,
+	};
+	use watchexec::command::Shell;
+
+	let (ac_s, mut ac_r) = mpsc::channel(8);
+	let actions = Arc::new(AtomicUsize::new(0));
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.command_shell(Shell::Unix("sh".into()));
+	runtime.command_grouped(false);
+	runtime.command(["trap 'exit 3' TERM; while true; do sleep 0.1; done"]);
+	runtime.on_action(move |action: Action| {
+		let ac_s = ac_s.clone();
+		let n = actions.fetch_add(1, Ordering::SeqCst);
+		async move {
+			for event in action.events.iter() {
+				for end in event.completions() {
+					ac_s.send(end).await.ok();
+				}
+			}
+
+			action.outcome(match n {
+				0 => Outcome::Start,
+				1 => Outcome::Signal(SubSignal::Terminate),
+				_ => Outcome::DoNothing,
+			});
+			Ok::<(), Infallible>(())
+		}
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+
+	wx.send_event(Event::default()).await.unwrap();
+	tokio::time::sleep(Duration::from_millis(300)).await;
+	wx.send_event(Event::default()).await.unwrap();
+
+	let end = timeout(Duration::from_secs(5), ac_r.recv())
+		.await
+		.expect("command did not end after signal")
+		.unwrap();
+	assert_eq!(end, Some(ProcessEnd::ExitError(3.try_into().unwrap())));
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn child_pid_tracks_running_command() {
+	use watchexec::command::Shell;
+
+	let (end_s, mut end_r) = mpsc::channel(8);
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.command_shell(Shell::Unix("sh".into()));
+	runtime.command(["sleep 0.5"]);
+	runtime.on_action(move |action: Action| {
+		let end_s = end_s.clone();
+		async move {
+			let completed = action.events.iter().any(|e| e.is_completion());
+			action.outcome(if completed {
+				end_s.send(()).await.ok();
+				Outcome::DoNothing
+			} else {
+				Outcome::Start
+			});
+			Ok::<(), Infallible>(())
+		}
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+	assert_eq!(wx.current_child_pid(), None);
+
+	wx.send_event(Event::default()).await.unwrap();
+	tokio::time::sleep(Duration::from_millis(200)).await;
+	assert!(wx.current_child_pid().is_some());
+
+	timeout(Duration::from_secs(5), end_r.recv())
+		.await
+		.expect("command did not complete")
+		.unwrap();
+	assert_eq!(wx.current_child_pid(), None);
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn command_timeout_stops_overrunning_command() {
+	let (ac_s, mut ac_r) = mpsc::channel(8);
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime
+		.command(["sleep", "10"])
+		.command_timeout(Some(Duration::from_millis(200)))
+		.command_stop_signal(SubSignal::Terminate);
+	runtime.on_action(move |action: Action| {
+		let ac_s = ac_s.clone();
+		async move {
+			for event in action.events.iter() {
+				if event.is_timeout() || event.is_completion() {
+					ac_s.send(event.clone()).await.ok();
+				}
+			}
+			if action.events.iter().any(|e| e.is_empty()) {
+				action.outcome(Outcome::Start);
+			}
+			Ok::<(), Infallible>(())
+		}
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+	wx.send_event(Event::default()).await.unwrap();
+
+	let timed_out = timeout(Duration::from_secs(5), ac_r.recv())
+		.await
+		.expect("no timeout event")
+		.unwrap();
+	assert!(timed_out.is_timeout() && !timed_out.is_completion());
+
+	let completion = timeout(Duration::from_secs(5), ac_r.recv())
+		.await
+		.expect("command was not stopped")
+		.unwrap();
+	assert!(completion.is_completion() && completion.is_timeout());
+	assert_eq!(
+		completion.metadata.get("stopped-by"),
+		Some(&vec!["signal:Terminate".to_string()])
+	);
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn command_timeout_is_cancelled_on_exit() {
+	let (ac_s, mut ac_r) = mpsc::channel(8);
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime
+		.command(["true"])
+		.command_timeout(Some(Duration::from_millis(300)));
+	runtime.on_action(move |action: Action| {
+		let ac_s = ac_s.clone();
+		async move {
+			for event in action.events.iter() {
+				if event.is_timeout() || event.is_completion() {
+					ac_s.send(event.clone()).await.ok();
+				}
+			}
+			if action.events.iter().any(|e| e.is_empty()) {
+				action.outcome(Outcome::Start);
+			}
+			Ok::<(), Infallible>(())
+		}
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+	wx.send_event(Event::default()).await.unwrap();
+
+	let completion = timeout(Duration::from_secs(5), ac_r.recv())
+		.await
+		.expect("no completion event")
+		.unwrap();
+	assert!(completion.is_completion() && !completion.is_timeout());
+	assert!(
+		timeout(Duration::from_millis(600), ac_r.recv())
+			.await
+			.is_err(),
+		"timeout fired after the command exited"
+	);
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
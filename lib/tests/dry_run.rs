@@ -0,0 +1,80 @@
+use std::{convert::Infallible, time::Duration};
+
+use futures::StreamExt;
+use tokio::time::timeout;
+use watchexec::{
+	action::{Action, Outcome},
+	command::Shell,
+	config::{InitConfig, RuntimeConfig},
+	event::{Event, Tag},
+	Watchexec,
+};
+
+fn would_have_run(event: &Event) -> Option<Outcome> {
+	event.tags.iter().find_map(|tag| match tag {
+		Tag::WouldHaveRun(outcome) => Some(outcome.clone()),
+		_ => None,
+	})
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn dry_run_reports_instead_of_running() {
+	let marker =
+		std::env::temp_dir().join(format!("watchexec-test-dry-run-{}", std::process::id()));
+	std::fs::remove_file(&marker).ok();
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime
+		.command_shell(Shell::Unix("sh".into()))
+		.command_grouped(false)
+		.command([format!("touch '{}'", marker.display())])
+		.dry_run(true);
+	runtime.on_action(|action: Action| async move {
+		action.outcome(Outcome::both(Outcome::Clear, Outcome::Start));
+		Ok::<(), Infallible>(())
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let mut stream = Box::pin(
+		wx.event_stream()
+			.filter_map(|e| async move { would_have_run(&e) }),
+	);
+	let main = wx.main();
+
+	wx.send_event(Event::default()).await.unwrap();
+	assert_eq!(
+		timeout(Duration::from_secs(5), stream.next())
+			.await
+			.unwrap(),
+		Some(Outcome::both(Outcome::Clear, Outcome::Start))
+	);
+
+	tokio::time::sleep(Duration::from_millis(200)).await;
+	assert!(!marker.exists(), "command ran in a dry run");
+	assert_eq!(wx.current_child_pid(), None);
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn dry_run_still_exits() {
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.dry_run(true);
+	runtime.on_action(|action: Action| async move {
+		action.outcome(Outcome::Exit);
+		Ok::<(), Infallible>(())
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+
+	wx.send_event(Event::default()).await.unwrap();
+	timeout(Duration::from_secs(5), main)
+		.await
+		.expect("exit was not applied in a dry run")
+		.unwrap()
+		.unwrap();
+}
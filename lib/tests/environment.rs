@@ -0,0 +1,203 @@
+use std::{
+	collections::HashMap,
+	convert::Infallible,
+	ffi::{OsStr, OsString},
+	time::Duration,
+};
+
+use tokio::{sync::mpsc, time::timeout};
+use watchexec::{
+	action::{Action, Outcome, PreSpawn},
+	config::{InitConfig, RuntimeConfig},
+	event::{
+		filekind::{CreateKind, FileEventKind},
+		Event, Tag,
+	},
+	Watchexec,
+};
+
+async fn envs_for(event: Event, environment: bool) -> HashMap<OsString, Option<OsString>> {
+	let (env_s, mut env_r) = mpsc::channel(8);
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.command(["true"]);
+	runtime.command_environment(environment);
+	runtime.command_env_prefix("TEST_");
+	runtime.on_action(|action: Action| async move {
+		let outcome = if action.events.iter().any(|e| e.is_completion()) {
+			Outcome::DoNothing
+		} else {
+			Outcome::Start
+		};
+		action.outcome(outcome);
+		Ok::<(), Infallible>(())
+	});
+	runtime.on_pre_spawn(move |prespawn: PreSpawn| {
+		let env_s = env_s.clone();
+		async move {
+			if let Some(command) = prespawn.command().await {
+				let envs: HashMap<_, _> = command
+					.as_std()
+					.get_envs()
+					.map(|(k, v)| (k.to_owned(), v.map(OsStr::to_owned)))
+					.collect();
+				env_s.send(envs).await.ok();
+			}
+			Ok::<(), Infallible>(())
+		}
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+
+	wx.send_event(event).await.unwrap();
+	let envs = timeout(Duration::from_secs(5), env_r.recv())
+		.await
+		.expect("command was not spawned")
+		.unwrap();
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+	envs
+}
+
+fn created(path: &str) -> Event {
+	Event {
+		tags: vec![
+			Tag::FileEventKind(FileEventKind::Create(CreateKind::File)),
+			Tag::Path {
+				path: path.into(),
+				file_type: None,
+			},
+		],
+		metadata: Default::default(),
+	}
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn sets_prefixed_path_variables() {
+	let envs = envs_for(created("/tmp/project/file.txt"), true).await;
+	assert_eq!(
+		envs.get(OsStr::new("TEST_CREATED_PATH")),
+		Some(&Some("file.txt".into()))
+	);
+	assert_eq!(
+		envs.get(OsStr::new("TEST_COMMON_PATH")),
+		Some(&Some("/tmp/project".into()))
+	);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn no_variables_when_disabled() {
+	let envs = envs_for(created("/tmp/project/file.txt"), false).await;
+	assert!(envs.is_empty(), "{:?}", envs);
+}
+
+#[cfg(unix)]
+#[tokio::test(flavor = "multi_thread")]
+async fn non_utf8_paths_are_preserved() {
+	use std::os::unix::ffi::OsStrExt;
+
+	let name = OsStr::from_bytes(b"/tmp/project/caf\xe9");
+	let event = Event {
+		tags: vec![
+			Tag::FileEventKind(FileEventKind::Create(CreateKind::File)),
+			Tag::Path {
+				path: name.into(),
+				file_type: None,
+			},
+		],
+		metadata: Default::default(),
+	};
+
+	let envs = envs_for(event, true).await;
+	assert_eq!(
+		envs.get(OsStr::new("TEST_CREATED_PATH")),
+		Some(&Some(OsStr::from_bytes(b"caf\xe9").to_owned()))
+	);
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test(flavor = "multi_thread")]
+async fn non_utf8_file_reaches_argv_and_env() {
+	use std::{fs, os::unix::ffi::OsStrExt, path::Path};
+	use watchexec::command::Shell;
+
+	let base = std::env::temp_dir().join(format!("watchexec-test-non-utf8-{}", std::process::id()));
+	let watched = base.join("watched");
+	fs::create_dir_all(&watched).unwrap();
+	let watched = dunce::canonicalize(watched).unwrap();
+	let out = base.join("out");
+	let name = OsStr::from_bytes(b"caf\xe9");
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_signal(false).pathset([&watched]);
+	runtime.command_shell(Shell::None).command_environment(true);
+	runtime.command_env_prefix("TEST_");
+	runtime.command([
+		"sh".to_string(),
+		"-c".to_string(),
+		r#"printf %s "$1" > "$0.arg"; printf %s "$TEST_CREATED_PATH" > "$0.env""#.to_string(),
+		out.display().to_string(),
+	]);
+
+	fn our_file<'a>(events: &'a [Event], name: &OsStr) -> Option<&'a Path> {
+		events
+			.iter()
+			.flat_map(Event::paths)
+			.map(|(path, _)| path)
+			.find(|path| path.file_name() == Some(name))
+	}
+
+	runtime.on_action(move |action: Action| async move {
+		let outcome = if our_file(&action.events, name).is_some() {
+			Outcome::if_running(Outcome::DoNothing, Outcome::Start)
+		} else {
+			Outcome::DoNothing
+		};
+		action.outcome(outcome);
+		Ok::<(), Infallible>(())
+	});
+	runtime.on_pre_spawn(move |prespawn: PreSpawn| async move {
+		if let Some(path) = our_file(&prespawn.events, name).map(Path::to_owned) {
+			if let Some(mut command) = prespawn.command().await {
+				command.arg(path);
+			}
+		}
+		Ok::<(), Infallible>(())
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+	timeout(Duration::from_secs(5), async {
+		while wx.watched_paths().is_empty() {
+			tokio::time::sleep(Duration::from_millis(10)).await;
+		}
+	})
+	.await
+	.expect("path was not watched");
+
+	let file = watched.join(name);
+	fs::write(&file, "").unwrap();
+
+	let read = |ext: &str| {
+		let mut path = out.clone().into_os_string();
+		path.push(ext);
+		fs::read(path).unwrap_or_default()
+	};
+	timeout(Duration::from_secs(5), async {
+		while read(".env").is_empty() {
+			tokio::time::sleep(Duration::from_millis(10)).await;
+		}
+	})
+	.await
+	.expect("command did not run");
+
+	assert_eq!(read(".arg"), file.as_os_str().as_bytes());
+	assert_eq!(read(".env"), name.as_bytes());
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+	fs::remove_dir_all(&base).ok();
+}
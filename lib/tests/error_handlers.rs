@@ -0,0 +1,222 @@
+use std::{convert::Infallible, io, sync::Arc, time::Duration};
+
+use tokio::{sync::mpsc, time::timeout};
+use watchexec::{
+	action::{Action, Outcome},
+	config::{InitConfig, RuntimeConfig},
+	error::RuntimeError,
+	event::Event,
+	Watchexec,
+};
+
+#[tokio::test(flavor = "multi_thread")]
+async fn every_handler_runs_despite_failures() {
+	let (er_s, mut er_r) = mpsc::channel(8);
+
+	let mut init = InitConfig::default();
+	let first = er_s.clone();
+	init.on_error(move |err: Arc<RuntimeError>| {
+		let first = first.clone();
+		async move {
+			let nested = matches!(*err, RuntimeError::Handler { .. });
+			first.send(("first", nested)).await.ok();
+			if nested {
+				Ok(())
+			} else {
+				Err(io::Error::new(io::ErrorKind::Other, "first handler failed"))
+			}
+		}
+	});
+	init.add_error_handler(move |err: Arc<RuntimeError>| {
+		let second = er_s.clone();
+		async move {
+			let nested = matches!(*err, RuntimeError::Handler { .. });
+			second.send(("second", nested)).await.ok();
+			Ok::<(), Infallible>(())
+		}
+	});
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.command(["watchexec-test-command-which-does-not-exist"]);
+	runtime.on_action(|action: Action| async move {
+		action.outcome(Outcome::Start);
+		Ok::<(), Infallible>(())
+	});
+
+	let wx = Watchexec::new(init, runtime).unwrap();
+	let main = wx.main();
+	wx.send_event(Event::default()).await.unwrap();
+
+	let mut calls = Vec::new();
+	for _ in 0..4 {
+		calls.push(
+			timeout(Duration::from_secs(5), er_r.recv())
+				.await
+				.expect("error handlers were not all called")
+				.unwrap(),
+		);
+	}
+	assert_eq!(
+		calls,
+		vec![
+			("first", false),
+			("second", false),
+			("first", true),
+			("second", true)
+		]
+	);
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn channel_handler_forwards_and_reports_closed() {
+	let (er_s, mut er_r) = mpsc::channel(8);
+	let (fb_s, mut fb_r) = mpsc::channel(8);
+
+	let mut init = InitConfig::default();
+	init.on_error(er_s);
+	init.add_error_handler(move |err: Arc<RuntimeError>| {
+		let fb_s = fb_s.clone();
+		async move {
+			fb_s.send(err.to_string()).await.ok();
+			Ok::<(), Infallible>(())
+		}
+	});
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.command(["watchexec-test-command-which-does-not-exist"]);
+	runtime.on_action(|action: Action| async move {
+		action.outcome(Outcome::Start);
+		Ok::<(), Infallible>(())
+	});
+
+	let wx = Watchexec::new(init, runtime).unwrap();
+	let main = wx.main();
+
+	wx.send_event(Event::default()).await.unwrap();
+	let err = timeout(Duration::from_secs(5), er_r.recv())
+		.await
+		.expect("error was not forwarded")
+		.unwrap();
+	assert!(matches!(*err, RuntimeError::IoError { .. }), "{:?}", err);
+	timeout(Duration::from_secs(5), fb_r.recv())
+		.await
+		.expect("fallback handler was not called")
+		.unwrap();
+
+	drop(er_r);
+	wx.send_event(Event::default()).await.unwrap();
+	let closed = timeout(Duration::from_secs(5), async {
+		while let Some(msg) = fb_r.recv().await {
+			if msg.contains("channel closed") {
+				return msg;
+			}
+		}
+		panic!("fallback handler went away");
+	})
+	.await
+	.expect("closed channel was not reported");
+	assert!(closed.contains("error hook"), "{}", closed);
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn persistently_failing_handler_is_disabled() {
+	let (er_s, mut er_r) = mpsc::channel(32);
+
+	let mut init = InitConfig::default();
+	init.error_handler_failure_limit(2);
+	let first = er_s.clone();
+	init.on_error(move |err: Arc<RuntimeError>| {
+		let first = first.clone();
+		async move {
+			if matches!(*err, RuntimeError::Handler { .. }) {
+				return Ok(());
+			}
+			first.send("first").await.ok();
+			Err(io::Error::new(io::ErrorKind::Other, "first handler failed"))
+		}
+	});
+	init.add_error_handler(move |err: Arc<RuntimeError>| {
+		let second = er_s.clone();
+		async move {
+			if !matches!(*err, RuntimeError::Handler { .. }) {
+				second.send("second").await.ok();
+			}
+			Ok::<(), Infallible>(())
+		}
+	});
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.command(["watchexec-test-command-which-does-not-exist"]);
+	runtime.on_action(|action: Action| async move {
+		action.outcome(Outcome::Start);
+		Ok::<(), Infallible>(())
+	});
+
+	let wx = Watchexec::new(init, runtime).unwrap();
+	let main = wx.main();
+
+	let mut calls = Vec::new();
+	for _ in 0..3 {
+		wx.send_event(Event::default()).await.unwrap();
+		loop {
+			let call = timeout(Duration::from_secs(5), er_r.recv())
+				.await
+				.expect("error handlers were not called")
+				.unwrap();
+			calls.push(call);
+			if call == "second" {
+				break;
+			}
+		}
+	}
+	assert_eq!(calls, vec!["first", "second", "first", "second", "second"]);
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn sync_handler_panic_is_delivered() {
+	let (er_s, mut er_r) = mpsc::channel(8);
+
+	let mut init = InitConfig::default();
+	init.on_error_sync(move |err: Arc<RuntimeError>| -> Result<(), Infallible> {
+		match &*err {
+			RuntimeError::HandlerPanic(msg) => {
+				er_s.try_send(msg.clone()).ok();
+				Ok(())
+			}
+			_ => panic!("handler broke"),
+		}
+	});
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.command(["watchexec-test-command-which-does-not-exist"]);
+	runtime.on_action(|action: Action| async move {
+		action.outcome(Outcome::Start);
+		Ok::<(), Infallible>(())
+	});
+
+	let wx = Watchexec::new(init, runtime).unwrap();
+	let main = wx.main();
+	wx.send_event(Event::default()).await.unwrap();
+
+	let msg = timeout(Duration::from_secs(5), er_r.recv())
+		.await
+		.expect("panic was not delivered as such")
+		.unwrap();
+	assert_eq!(msg, "handler broke");
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
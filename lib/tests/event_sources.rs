@@ -0,0 +1,461 @@
+use std::time::Duration;
+
+use futures::StreamExt;
+use tokio::{
+	sync::mpsc::{self, error::TrySendError},
+	time::timeout,
+};
+use watchexec::{
+	action::Action,
+	config::{InitConfig, RuntimeConfig},
+	event::{Event, Source, Tag},
+	fs::{KindCategory, Watcher, WhilePaused},
+	handler::SyncFnHandler,
+	Watchexec,
+};
+
+#[tokio::test(flavor = "multi_thread")]
+async fn disabled_sources_still_reconfigure() {
+	let (ac_s, mut ac_r) = mpsc::channel(8);
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.on_action(move |action: Action| {
+		let ac_s = ac_s.clone();
+		async move {
+			ac_s.send(action.events.len()).await.ok();
+			Ok::<(), std::convert::Infallible>(())
+		}
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime.clone()).unwrap();
+	let main = wx.main();
+
+	runtime.pathset(["."]);
+	wx.reconfigure(runtime).unwrap();
+
+	wx.send_event(Event::default()).await.unwrap();
+	assert_eq!(
+		timeout(Duration::from_secs(5), ac_r.recv()).await.unwrap(),
+		Some(1)
+	);
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn send_event_from_blocking_thread() {
+	let (ac_s, mut ac_r) = mpsc::channel(8);
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.on_action(move |action: Action| {
+		let ac_s = ac_s.clone();
+		async move {
+			ac_s.send(action.events.len()).await.ok();
+			Ok::<(), std::convert::Infallible>(())
+		}
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+
+	let w = wx.clone();
+	std::thread::spawn(move || w.send_event_blocking(Event::default()))
+		.join()
+		.unwrap()
+		.unwrap();
+
+	assert_eq!(
+		timeout(Duration::from_secs(5), ac_r.recv()).await.unwrap(),
+		Some(1)
+	);
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn watcher_reinit_is_reported() {
+	let (ac_s, mut ac_r) = mpsc::channel(8);
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_signal(false);
+	runtime.on_action(move |action: Action| {
+		let ac_s = ac_s.clone();
+		async move {
+			if action.events.iter().any(|e| e.is_fs_lifecycle()) {
+				ac_s.send(()).await.ok();
+			}
+			Ok::<(), std::convert::Infallible>(())
+		}
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime.clone()).unwrap();
+	let main = wx.main();
+
+	runtime.pathset(["src"]);
+	wx.reconfigure(runtime.clone()).unwrap();
+	tokio::time::sleep(Duration::from_millis(100)).await;
+
+	runtime.file_watcher(Watcher::Poll(Duration::from_secs(1)));
+	wx.reconfigure(runtime).unwrap();
+
+	assert_eq!(
+		timeout(Duration::from_secs(5), ac_r.recv()).await.unwrap(),
+		Some(())
+	);
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn try_send_event_reports_full_channel() {
+	let (in_s, mut in_r) = mpsc::channel(8);
+	let (release_s, release_r) = std::sync::mpsc::channel::<()>();
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.on_action(SyncFnHandler::from(move |_: Action| {
+		in_s.try_send(()).ok();
+		release_r.recv().ok();
+		Ok::<(), std::convert::Infallible>(())
+	}));
+
+	let init = InitConfig::builder().event_channel_size(1).build();
+	let wx = Watchexec::new(init, runtime).unwrap();
+	let main = wx.main();
+
+	wx.try_send_event(Event::default()).unwrap();
+	timeout(Duration::from_secs(5), in_r.recv())
+		.await
+		.expect("action handler was not called");
+
+	// the worker is busy in the handler, so only one more event fits
+	wx.try_send_event(Event::default()).unwrap();
+	assert!(matches!(
+		wx.try_send_event(Event::default()),
+		Err(TrySendError::Full(_))
+	));
+
+	release_s.send(()).unwrap();
+	release_s.send(()).unwrap();
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn event_stream_sees_filtered_events_and_ends() {
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.filter_predicate(|_: &Event| false);
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let mut stream = Box::pin(wx.event_stream());
+	let dropped = wx.event_stream();
+	drop(dropped);
+	let main = wx.main();
+
+	let event = Event {
+		tags: vec![Tag::Source(Source::Internal)],
+		metadata: Default::default(),
+	};
+	wx.send_event(event.clone()).await.unwrap();
+	assert_eq!(
+		timeout(Duration::from_secs(5), stream.next())
+			.await
+			.unwrap(),
+		Some(event)
+	);
+
+	wx.shutdown(None).await.unwrap();
+	assert_eq!(
+		timeout(Duration::from_secs(5), stream.next())
+			.await
+			.unwrap(),
+		None
+	);
+	main.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn immediate_kinds_bypass_debounce() {
+	let base =
+		std::env::temp_dir().join(format!("watchexec-test-immediate-{}", std::process::id()));
+	std::fs::create_dir_all(&base).unwrap();
+	let base = dunce::canonicalize(base).unwrap();
+	let file = base.join("doomed.txt");
+	std::fs::write(&file, "bye").unwrap();
+
+	let (ac_s, mut ac_r) = mpsc::channel(8);
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_signal(false);
+	runtime.pathset([&base]);
+	runtime
+		.fs_debounce(Duration::from_secs(30))
+		.fs_immediate_kinds([KindCategory::Remove]);
+	runtime.on_action(move |action: Action| {
+		let ac_s = ac_s.clone();
+		async move {
+			ac_s.send(action.events.len()).await.ok();
+			Ok::<(), std::convert::Infallible>(())
+		}
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+	tokio::time::sleep(Duration::from_millis(200)).await;
+
+	std::fs::remove_file(&file).unwrap();
+	timeout(Duration::from_secs(5), ac_r.recv())
+		.await
+		.expect("removal was held by the debounce");
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+	std::fs::remove_dir_all(&base).ok();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn settle_drops_events_after_watcher_creation() {
+	let base = std::env::temp_dir().join(format!("watchexec-test-settle-{}", std::process::id()));
+	std::fs::create_dir_all(&base).unwrap();
+	let base = dunce::canonicalize(base).unwrap();
+	let file = base.join("churn.txt");
+
+	let (ac_s, mut ac_r) = mpsc::channel(64);
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_signal(false);
+	runtime.pathset([&base]);
+	runtime
+		.fs_debounce(Duration::from_millis(10))
+		.fs_settle(Duration::from_millis(1500));
+	runtime.on_action(move |action: Action| {
+		let ac_s = ac_s.clone();
+		async move {
+			let reinit = action.events.iter().any(|e| e.is_fs_lifecycle());
+			let paths = action.events.iter().any(|e| e.paths().next().is_some());
+			if reinit || paths {
+				ac_s.send(reinit).await.ok();
+			}
+			Ok::<(), std::convert::Infallible>(())
+		}
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime.clone()).unwrap();
+	let main = wx.main();
+	timeout(Duration::from_secs(5), async {
+		while wx.watched_paths().is_empty() {
+			tokio::time::sleep(Duration::from_millis(10)).await;
+		}
+	})
+	.await
+	.expect("watcher was not created");
+
+	for round in 0..2 {
+		std::fs::write(&file, "early").unwrap();
+		assert!(
+			timeout(Duration::from_millis(500), ac_r.recv())
+				.await
+				.is_err(),
+			"event went through while settling in round {}",
+			round
+		);
+
+		timeout(Duration::from_secs(10), async {
+			let mut n = 0;
+			loop {
+				n += 1;
+				std::fs::write(&file, "later".repeat(n)).unwrap();
+				if let Ok(Some(false)) = timeout(Duration::from_millis(200), ac_r.recv()).await {
+					break;
+				}
+			}
+		})
+		.await
+		.unwrap_or_else(|_| {
+			panic!(
+				"events were still dropped after settling in round {}",
+				round
+			)
+		});
+		tokio::time::sleep(Duration::from_millis(100)).await;
+		while ac_r.try_recv().is_ok() {}
+
+		// re-creating the watcher settles again
+		runtime.file_watcher(Watcher::Poll(Duration::from_millis(50)));
+		wx.reconfigure(runtime.clone()).unwrap();
+		if round == 0 {
+			assert_eq!(
+				timeout(Duration::from_secs(5), ac_r.recv()).await.unwrap(),
+				Some(true),
+				"watcher was not re-created"
+			);
+		}
+	}
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+	std::fs::remove_dir_all(&base).ok();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn injected_batch_is_one_action() {
+	let (ac_s, mut ac_r) = mpsc::channel(8);
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.action_throttle(Duration::ZERO);
+	runtime.on_action(move |action: Action| {
+		let ac_s = ac_s.clone();
+		async move {
+			ac_s.send(action.events.to_vec()).await.ok();
+			Ok::<(), std::convert::Infallible>(())
+		}
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+
+	let batch: Vec<Event> = (0..20)
+		.map(|n| Event {
+			tags: vec![Tag::Path {
+				path: format!("/batch/{}", n).into(),
+				file_type: None,
+			}],
+			metadata: Default::default(),
+		})
+		.collect();
+	wx.inject_events(batch.clone()).await.unwrap();
+
+	let events = timeout(Duration::from_secs(5), ac_r.recv())
+		.await
+		.unwrap()
+		.unwrap();
+	assert_eq!(events, batch);
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test(flavor = "multi_thread")]
+async fn renames_carry_both_paths() {
+	let base = std::env::temp_dir().join(format!("watchexec-test-rename-{}", std::process::id()));
+	std::fs::create_dir_all(&base).unwrap();
+	let base = dunce::canonicalize(base).unwrap();
+	let from = base.join("before.txt");
+	let to = base.join("after.txt");
+	std::fs::write(&from, "moving").unwrap();
+
+	let (ac_s, mut ac_r) = mpsc::channel(8);
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_signal(false);
+	runtime.pathset([&base]);
+	runtime.on_action(move |action: Action| {
+		let ac_s = ac_s.clone();
+		async move {
+			for event in action.events.iter() {
+				for tag in &event.tags {
+					if let Tag::Rename { from, to } = tag {
+						ac_s.send((from.clone(), to.clone())).await.ok();
+					}
+				}
+			}
+			Ok::<(), std::convert::Infallible>(())
+		}
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+	timeout(Duration::from_secs(5), async {
+		while wx.watched_paths().is_empty() {
+			tokio::time::sleep(Duration::from_millis(10)).await;
+		}
+	})
+	.await
+	.expect("watcher was not created");
+
+	std::fs::rename(&from, &to).unwrap();
+	assert_eq!(
+		timeout(Duration::from_secs(5), ac_r.recv())
+			.await
+			.expect("no rename event"),
+		Some((from, to))
+	);
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+	std::fs::remove_dir_all(&base).ok();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn paused_events_are_held_or_dropped() {
+	for while_paused in [WhilePaused::Coalesce, WhilePaused::Discard] {
+		let base = std::env::temp_dir().join(format!(
+			"watchexec-test-pause-{:?}-{}",
+			while_paused,
+			std::process::id()
+		));
+		std::fs::create_dir_all(&base).unwrap();
+		let base = dunce::canonicalize(base).unwrap();
+
+		let (ac_s, mut ac_r) = mpsc::channel(8);
+		let mut runtime = RuntimeConfig::default();
+		runtime.enable_signal(false);
+		runtime.pathset([&base]).fs_while_paused(while_paused);
+		runtime.on_action(move |action: Action| {
+			let ac_s = ac_s.clone();
+			async move {
+				let mut paths: Vec<_> = action
+					.events
+					.iter()
+					.flat_map(|e| e.paths().map(|(p, _)| p.to_owned()))
+					.collect();
+				paths.sort();
+				paths.dedup();
+				if !paths.is_empty() {
+					ac_s.send(paths).await.ok();
+				}
+				Ok::<(), std::convert::Infallible>(())
+			}
+		});
+
+		let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+		let main = wx.main();
+		timeout(Duration::from_secs(5), async {
+			while wx.watched_paths().is_empty() {
+				tokio::time::sleep(Duration::from_millis(10)).await;
+			}
+		})
+		.await
+		.expect("watcher was not created");
+
+		wx.pause();
+		assert!(wx.is_paused());
+		std::fs::write(base.join("a"), "a").unwrap();
+		std::fs::write(base.join("b"), "b").unwrap();
+		assert!(
+			timeout(Duration::from_millis(500), ac_r.recv())
+				.await
+				.is_err(),
+			"event went through while paused"
+		);
+
+		wx.resume();
+		let resumed = timeout(Duration::from_secs(1), ac_r.recv()).await;
+		match while_paused {
+			WhilePaused::Coalesce => assert_eq!(
+				resumed.expect("held events were not emitted"),
+				Some(vec![base.join("a"), base.join("b")])
+			),
+			_ => assert!(resumed.is_err(), "dropped events came through"),
+		}
+
+		wx.shutdown(None).await.unwrap();
+		main.await.unwrap().unwrap();
+		std::fs::remove_dir_all(&base).ok();
+	}
+}
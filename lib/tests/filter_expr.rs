@@ -0,0 +1,157 @@
+use std::path::PathBuf;
+
+use watchexec::{
+	event::{
+		filekind::{CreateKind, DataChange, FileEventKind, MetadataKind, ModifyKind, RemoveKind},
+		Event, FileType, Source, Tag,
+	},
+	filter::expr::{ExprFilterer, Filter, FilterKind},
+};
+
+mod helpers;
+use helpers::expr::*;
+
+fn check_kind(filterer: &ExprFilterer, path: &str, kind: FileEventKind) -> bool {
+	filterer.check(&Event {
+		tags: vec![
+			Tag::Path {
+				path: PathBuf::from(path),
+				file_type: Some(FileType::File),
+			},
+			Tag::FileEventKind(kind),
+		],
+		metadata: Default::default(),
+	})
+}
+
+#[test]
+fn no_filters_pass_everything() {
+	let filterer = filt(&[]);
+
+	filterer.file_does_pass("Cargo.toml");
+	filterer.dir_does_pass("/a/folder");
+	filterer.source_does_pass(Source::Keyboard);
+}
+
+#[test]
+fn filters_are_ored() {
+	let filterer = filt(&["*.rs", "Cargo.toml"]);
+
+	filterer.file_does_pass("src/lib.rs");
+	filterer.file_does_pass("/test/foo/bar/Cargo.toml");
+	filterer.file_doesnt_pass("Cargo.lock");
+	filterer.file_doesnt_pass("README.md");
+}
+
+#[test]
+fn ignores_override_filters() {
+	let filterer = filt(&["*.rs", "-**/tests/**", "-*_test.rs"]);
+
+	filterer.file_does_pass("src/lib.rs");
+	filterer.file_doesnt_pass("tests/helpers.rs");
+	filterer.file_doesnt_pass("src/parse_test.rs");
+	filterer.file_doesnt_pass("src/lib.toml");
+}
+
+#[test]
+fn relative_globs_match_in_any_directory() {
+	let filterer = filt(&["-target/**"]);
+
+	filterer.file_doesnt_pass("target/debug/watchexec");
+	filterer.file_doesnt_pass("/test/crates/lib/target/debug/watchexec");
+	filterer.file_does_pass("src/target.rs");
+}
+
+#[test]
+fn negated_filters_reverse_the_match() {
+	let filterer = filt(&["!*.md"]);
+
+	filterer.file_does_pass("src/lib.rs");
+	filterer.file_doesnt_pass("README.md");
+
+	let filterer = filt(&["-!src/**"]);
+
+	filterer.file_does_pass("src/lib.rs");
+	filterer.file_doesnt_pass("README.md");
+}
+
+#[test]
+fn non_path_events_skip_path_filters() {
+	let filterer = filt(&["*.rs", "-target/**"]);
+
+	filterer.source_does_pass(Source::Keyboard);
+	filterer.source_does_pass(Source::Internal);
+}
+
+#[test]
+fn kinds_are_matched_by_glob() {
+	let filterer = filt(&["*.rs on create", "*.rs on Modify(Data(*))"]);
+
+	assert!(check_kind(
+		&filterer,
+		"/src/lib.rs",
+		FileEventKind::Create(CreateKind::File)
+	));
+	assert!(check_kind(
+		&filterer,
+		"/src/lib.rs",
+		FileEventKind::Modify(ModifyKind::Data(DataChange::Content))
+	));
+	assert!(!check_kind(
+		&filterer,
+		"/src/lib.rs",
+		FileEventKind::Modify(ModifyKind::Metadata(MetadataKind::Permissions))
+	));
+	assert!(!check_kind(
+		&filterer,
+		"/README.md",
+		FileEventKind::Create(CreateKind::File)
+	));
+}
+
+#[test]
+fn kind_only_ignore() {
+	let filterer = filt(&["-on remove"]);
+
+	assert!(!check_kind(
+		&filterer,
+		"/src/lib.rs",
+		FileEventKind::Remove(RemoveKind::File)
+	));
+	assert!(check_kind(
+		&filterer,
+		"/src/lib.rs",
+		FileEventKind::Create(CreateKind::File)
+	));
+	filterer.file_does_pass("src/lib.rs");
+}
+
+#[test]
+fn parse_string_form() {
+	assert_eq!("*.rs".parse::<Filter>().unwrap(), Filter::select("*.rs"));
+	assert_eq!(
+		"-target/**".parse::<Filter>().unwrap(),
+		Filter::ignore("target/**")
+	);
+	assert_eq!(
+		"\\-weird".parse::<Filter>().unwrap(),
+		Filter::select("-weird")
+	);
+	assert_eq!(
+		"-!on create".parse::<Filter>().unwrap(),
+		Filter {
+			path_glob: None,
+			kind: FilterKind::Ignore,
+			negate: true,
+			on_kind: Some("create".into()),
+		}
+	);
+}
+
+#[test]
+fn parse_errors() {
+	assert!("".parse::<Filter>().is_err());
+	assert!("-!".parse::<Filter>().is_err());
+	assert!("*.rs on ".parse::<Filter>().is_err());
+	assert!("src/[".parse::<Filter>().is_err());
+}
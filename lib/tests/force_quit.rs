@@ -0,0 +1,77 @@
+#![cfg(unix)]
+
+use std::{convert::Infallible, time::Duration};
+
+use nix::{
+	sys::signal::{kill, Signal},
+	unistd::Pid,
+};
+use tokio::time::{sleep, timeout};
+use watchexec::{
+	action::{Action, Outcome},
+	command::Shell,
+	config::{InitConfig, RuntimeConfig},
+	signal::{process::SubSignal, source::MainSignal},
+	Watchexec,
+};
+
+#[tokio::test(flavor = "multi_thread")]
+async fn second_interrupt_force_quits() {
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false);
+	runtime.command_shell(Shell::Unix("sh".into()));
+	runtime.command_grouped(false);
+	runtime.command(["trap '' INT; while true; do sleep 0.1; done"]);
+	runtime
+		.command_stop_signal(SubSignal::Interrupt)
+		.command_stop_timeout(Duration::from_secs(60));
+	runtime.on_action(|action: Action| async move {
+		let interrupted = action
+			.events
+			.iter()
+			.any(|e| e.signals().any(|s| s == MainSignal::Interrupt));
+		action.outcome(if interrupted {
+			Outcome::both(Outcome::Stop, Outcome::Exit)
+		} else {
+			Outcome::Start
+		});
+		Ok::<(), Infallible>(())
+	});
+
+	let init = InitConfig::builder()
+		.force_quit_window(Some(Duration::from_secs(5)))
+		.build();
+	let wx = Watchexec::new(init, runtime).unwrap();
+	let main = wx.main();
+
+	wx.send_event(Default::default()).await.unwrap();
+	let pid = timeout(Duration::from_secs(5), async {
+		loop {
+			if let Some(pid) = wx.current_child_pid() {
+				return pid;
+			}
+			sleep(Duration::from_millis(10)).await;
+		}
+	})
+	.await
+	.expect("command did not start");
+
+	let us = Pid::this();
+	kill(us, Signal::SIGINT).unwrap();
+	sleep(Duration::from_millis(500)).await;
+	assert!(
+		kill(Pid::from_raw(pid as _), None).is_ok(),
+		"command did not ignore the first interrupt"
+	);
+
+	kill(us, Signal::SIGINT).unwrap();
+	timeout(Duration::from_secs(5), main)
+		.await
+		.expect("second interrupt did not force-quit")
+		.unwrap()
+		.unwrap();
+	assert!(
+		kill(Pid::from_raw(pid as _), None).is_err(),
+		"command was not killed"
+	);
+}
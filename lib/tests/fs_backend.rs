@@ -0,0 +1,220 @@
+use std::{
+	io::ErrorKind,
+	path::{Path, PathBuf},
+	sync::{
+		atomic::{AtomicUsize, Ordering},
+		Arc, Mutex,
+	},
+	time::Duration,
+};
+
+use tokio::{sync::mpsc, time::timeout};
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use watchexec::{
+	action::Action,
+	config::{InitConfig, RuntimeConfig},
+	event::{FsEventKind, Tag},
+	fs::{BackendWatcher, FsBackend, RawEvents},
+	Watchexec,
+};
+
+/// A backend fed from a channel, as if tunnelled from elsewhere.
+#[derive(Debug)]
+struct ChannelBackend {
+	events: Mutex<Option<mpsc::UnboundedReceiver<Result<notify::Event, notify::Error>>>>,
+	watched: Arc<Mutex<Vec<(PathBuf, bool)>>>,
+	/// How many times watching fails before it works.
+	failures: Arc<AtomicUsize>,
+}
+
+struct ChannelWatcher(Arc<Mutex<Vec<(PathBuf, bool)>>>, Arc<AtomicUsize>);
+
+impl BackendWatcher for ChannelWatcher {
+	fn watch(&mut self, path: &Path, recursive: bool) -> Result<(), notify::Error> {
+		let failing = self
+			.1
+			.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+			.is_ok();
+		if failing {
+			return Err(notify::Error::io(ErrorKind::PermissionDenied.into()));
+		}
+
+		self.0.lock().unwrap().push((path.to_owned(), recursive));
+		Ok(())
+	}
+
+	fn unwatch(&mut self, path: &Path) -> Result<(), notify::Error> {
+		self.0.lock().unwrap().retain(|(p, _)| p != path);
+		Ok(())
+	}
+}
+
+impl FsBackend for ChannelBackend {
+	fn create(&self) -> Result<(Box<dyn BackendWatcher>, RawEvents), notify::Error> {
+		let events = self
+			.events
+			.lock()
+			.unwrap()
+			.take()
+			.ok_or_else(|| notify::Error::generic("backend already created"))?;
+		Ok((
+			Box::new(ChannelWatcher(self.watched.clone(), self.failures.clone())),
+			Box::pin(UnboundedReceiverStream::new(events)),
+		))
+	}
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn custom_backend_feeds_events() {
+	let (raw_s, raw_r) = mpsc::unbounded_channel();
+	let watched = Arc::new(Mutex::new(Vec::new()));
+	let (ac_s, mut ac_r) = mpsc::channel(8);
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_signal(false);
+	runtime.pathset(["/remote/project"]);
+	runtime.fs_backend(ChannelBackend {
+		events: Mutex::new(Some(raw_r)),
+		watched: watched.clone(),
+		failures: Default::default(),
+	});
+	runtime.on_action(move |action: Action| {
+		let ac_s = ac_s.clone();
+		async move {
+			let paths: Vec<PathBuf> = action
+				.events
+				.iter()
+				.flat_map(|e| e.paths().map(|(p, _)| p.to_owned()))
+				.collect();
+			ac_s.send(paths).await.ok();
+			Ok::<(), std::convert::Infallible>(())
+		}
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+
+	timeout(Duration::from_secs(5), async {
+		while watched.lock().unwrap().is_empty() {
+			tokio::time::sleep(Duration::from_millis(10)).await;
+		}
+	})
+	.await
+	.expect("backend was not told to watch");
+	assert_eq!(
+		*watched.lock().unwrap(),
+		vec![(PathBuf::from("/remote/project"), true)]
+	);
+
+	raw_s
+		.send(Ok(notify::Event::new(notify::EventKind::Any)
+			.add_path("/remote/project/src/main.rs".into())))
+		.unwrap();
+	assert_eq!(
+		timeout(Duration::from_secs(5), ac_r.recv()).await.unwrap(),
+		Some(vec![PathBuf::from("/remote/project/src/main.rs")])
+	);
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn raw_events_are_attached_when_enabled() {
+	let (raw_s, raw_r) = mpsc::unbounded_channel();
+	let watched = Arc::new(Mutex::new(Vec::new()));
+	let (ac_s, mut ac_r) = mpsc::channel(8);
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_signal(false);
+	runtime.pathset(["/remote/project"]);
+	runtime.fs_raw_events(true);
+	runtime.fs_backend(ChannelBackend {
+		events: Mutex::new(Some(raw_r)),
+		watched: watched.clone(),
+		failures: Default::default(),
+	});
+	runtime.on_action(move |action: Action| {
+		let ac_s = ac_s.clone();
+		async move {
+			let raw: Vec<notify::Event> = action
+				.events
+				.iter()
+				.flat_map(|e| e.raw_events().cloned())
+				.collect();
+			ac_s.send(raw).await.ok();
+			Ok::<(), std::convert::Infallible>(())
+		}
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+
+	timeout(Duration::from_secs(5), async {
+		while watched.lock().unwrap().is_empty() {
+			tokio::time::sleep(Duration::from_millis(10)).await;
+		}
+	})
+	.await
+	.expect("backend was not told to watch");
+
+	let event = notify::Event::new(notify::EventKind::Any)
+		.add_path("/remote/project/src/main.rs".into())
+		.set_tracker(42);
+	raw_s.send(Ok(event.clone())).unwrap();
+	let raw = timeout(Duration::from_secs(5), ac_r.recv())
+		.await
+		.unwrap()
+		.unwrap();
+	assert_eq!(raw, vec![event]);
+	assert_eq!(raw[0].attrs.tracker(), Some(42));
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn failed_watch_is_retried() {
+	let (_raw_s, raw_r) = mpsc::unbounded_channel();
+	let watched = Arc::new(Mutex::new(Vec::new()));
+	let (ac_s, mut ac_r) = mpsc::channel(8);
+	let dir = std::env::temp_dir();
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_signal(false);
+	runtime.pathset([&dir]);
+	runtime.fs_watch_retries(3);
+	runtime.fs_backend(ChannelBackend {
+		events: Mutex::new(Some(raw_r)),
+		watched: watched.clone(),
+		failures: Arc::new(AtomicUsize::new(2)),
+	});
+	runtime.on_action(move |action: Action| {
+		let ac_s = ac_s.clone();
+		async move {
+			for event in action.events.iter() {
+				if event
+					.tags
+					.contains(&Tag::FileSystem(FsEventKind::WatchRecovered))
+				{
+					let paths: Vec<PathBuf> = event.paths().map(|(p, _)| p.to_owned()).collect();
+					ac_s.send(paths).await.ok();
+				}
+			}
+			Ok::<(), std::convert::Infallible>(())
+		}
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+
+	assert_eq!(
+		timeout(Duration::from_secs(5), ac_r.recv()).await.unwrap(),
+		Some(vec![dir.clone()])
+	);
+	assert_eq!(*watched.lock().unwrap(), vec![(dir.clone(), true)]);
+	assert_eq!(wx.watched_paths(), vec![dir]);
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
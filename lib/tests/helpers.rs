@@ -5,12 +5,14 @@ use std::{
 	path::{Path, PathBuf},
 	str::FromStr,
 	sync::Arc,
+	time::Duration,
 };
 
 use watchexec::{
 	error::RuntimeError,
 	event::{filekind::FileEventKind, Event, FileType, ProcessEnd, Source, Tag},
 	filter::{
+		expr::ExprFilterer,
 		globset::GlobsetFilterer,
 		tagged::{files::FilterFile, Filter, Matcher, Op, Pattern, TaggedFilterer},
 		Filterer,
@@ -34,6 +36,12 @@ pub mod globset {
 	pub use super::PathHarness;
 }
 
+pub mod expr {
+	pub use super::expr_filt as filt;
+	pub use super::PathHarness;
+	pub use super::TaggedHarness;
+}
+
 pub mod tagged {
 	pub use super::ig_file as file;
 	pub use super::tagged_filt as filt;
@@ -120,6 +128,7 @@ pub trait PathHarness: Filterer {
 impl PathHarness for GlobsetFilterer {}
 impl PathHarness for TaggedFilterer {}
 impl PathHarness for IgnoreFilterer {}
+impl PathHarness for ExprFilterer {}
 
 pub trait TaggedHarness {
 	fn check_tag(&self, tag: Tag) -> std::result::Result<bool, RuntimeError>;
@@ -169,11 +178,11 @@ pub trait TaggedHarness {
 	}
 
 	fn complete_does_pass(&self, exit: Option<ProcessEnd>) {
-		self.tag_pass(Tag::ProcessCompletion(exit), true);
+		self.tag_pass(Tag::ProcessCompletion(exit, Duration::ZERO), true);
 	}
 
 	fn complete_doesnt_pass(&self, exit: Option<ProcessEnd>) {
-		self.tag_pass(Tag::ProcessCompletion(exit), false);
+		self.tag_pass(Tag::ProcessCompletion(exit, Duration::ZERO), false);
 	}
 }
 
@@ -188,6 +197,17 @@ impl TaggedHarness for TaggedFilterer {
 	}
 }
 
+impl TaggedHarness for ExprFilterer {
+	fn check_tag(&self, tag: Tag) -> std::result::Result<bool, RuntimeError> {
+		let event = Event {
+			tags: vec![tag],
+			metadata: Default::default(),
+		};
+
+		self.check_event(&event)
+	}
+}
+
 fn tracing_init() {
 	use tracing_subscriber::{
 		fmt::{format::FmtSpan, Subscriber},
@@ -221,6 +241,17 @@ pub async fn globset_filt(
 	.expect("making filterer")
 }
 
+pub fn expr_filt(filters: &[&str]) -> ExprFilterer {
+	tracing_init();
+	ExprFilterer::new(
+		filters
+			.iter()
+			.map(|f| f.parse().expect("parsing filter"))
+			.collect(),
+	)
+	.expect("making filterer")
+}
+
 pub async fn ignore_filt(origin: &str, ignore_files: &[IgnoreFile]) -> IgnoreFilterer {
 	tracing_init();
 	let origin = dunce::canonicalize(".").unwrap().join(origin);
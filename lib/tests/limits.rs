@@ -0,0 +1,80 @@
+#![cfg(unix)]
+
+use std::{convert::Infallible, time::Duration};
+
+use nix::sys::signal::Signal;
+use tokio::{sync::mpsc, time::timeout};
+use watchexec::{
+	action::{Action, Outcome},
+	command::{ResourceLimits, Shell},
+	config::{InitConfig, RuntimeConfig},
+	event::{Event, ProcessEnd},
+	signal::process::SubSignal,
+	Watchexec,
+};
+
+async fn end_with_limits(script: &str, limits: ResourceLimits) -> ProcessEnd {
+	let (end_s, mut end_r) = mpsc::channel(8);
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.command_shell(Shell::Unix("sh".into()));
+	runtime.command_grouped(false);
+	runtime.command([script]);
+	runtime.command_rlimits(limits);
+	runtime.on_action(move |action: Action| {
+		let end_s = end_s.clone();
+		async move {
+			let mut completed = false;
+			for event in action.events.iter() {
+				for end in event.completions().flatten() {
+					completed = true;
+					end_s.send(end).await.ok();
+				}
+			}
+
+			action.outcome(if completed {
+				Outcome::DoNothing
+			} else {
+				Outcome::Start
+			});
+			Ok::<(), Infallible>(())
+		}
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+	wx.send_event(Event::default()).await.unwrap();
+
+	let end = timeout(Duration::from_secs(10), end_r.recv())
+		.await
+		.expect("command did not end")
+		.unwrap();
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+	end
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn cpu_limit_sends_sigxcpu() {
+	let end = end_with_limits(
+		"while :; do :; done",
+		ResourceLimits::default().cpu_seconds(1),
+	)
+	.await;
+	assert_eq!(
+		end,
+		ProcessEnd::ExitSignal(SubSignal::from_nix(Signal::SIGXCPU))
+	);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn address_space_limit_applies() {
+	let end = end_with_limits(
+		"test \"$(ulimit -v)\" = 65536 && exit 7",
+		ResourceLimits::default().address_space(64 * 1024 * 1024),
+	)
+	.await;
+	assert_eq!(end, ProcessEnd::ExitError(7.try_into().unwrap()));
+}
@@ -0,0 +1,50 @@
+use std::{convert::Infallible, sync::mpsc, time::Duration};
+
+use tracing::level_filters::LevelFilter;
+use watchexec::{
+	action::{Action, Outcome},
+	config::{InitConfig, RuntimeConfig},
+	error::RuntimeError,
+	event::Event,
+	Watchexec,
+};
+
+#[tokio::test(flavor = "multi_thread")]
+async fn log_level_goes_to_the_handler() {
+	let (ll_s, ll_r) = mpsc::channel();
+	let mut init = InitConfig::default();
+	init.on_log_level(ll_s);
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.on_action(|action: Action| async move {
+		action.outcome(Outcome::LogLevel(LevelFilter::WARN));
+		Ok::<(), Infallible>(())
+	});
+
+	let wx = Watchexec::new(init, runtime.clone()).unwrap();
+	let main = wx.main();
+
+	wx.set_log_level(LevelFilter::TRACE).unwrap();
+	assert_eq!(ll_r.try_recv().ok(), Some(LevelFilter::TRACE));
+
+	// the handler is kept across reconfigures
+	wx.reconfigure_force(runtime).unwrap();
+	wx.send_event(Event::default()).await.unwrap();
+	assert_eq!(
+		ll_r.recv_timeout(Duration::from_secs(5)).ok(),
+		Some(LevelFilter::WARN)
+	);
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn log_level_errors_without_a_handler() {
+	let wx = Watchexec::new(InitConfig::default(), RuntimeConfig::default()).unwrap();
+	assert!(matches!(
+		wx.set_log_level(LevelFilter::DEBUG),
+		Err(RuntimeError::NoLogLevelHandler)
+	));
+}
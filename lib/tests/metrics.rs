@@ -0,0 +1,197 @@
+use std::{convert::Infallible, sync::Arc, time::Duration};
+
+use tokio::{
+	sync::{mpsc, watch},
+	time::{sleep, timeout},
+};
+use watchexec::{
+	action::Action,
+	config::{InitConfig, RuntimeConfig},
+	error::RuntimeError,
+	event::{Event, Source, Tag},
+	handler::SyncFnHandler,
+	Watchexec,
+};
+
+#[tokio::test(flavor = "multi_thread")]
+async fn counts_events_and_actions() {
+	let (ac_s, mut ac_r) = mpsc::channel(8);
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.action_throttle(Duration::from_millis(200));
+	runtime.filter_predicate(|event: &Event| !event.tags.contains(&Tag::Source(Source::Os)));
+	runtime.on_action(move |action: Action| {
+		let ac_s = ac_s.clone();
+		async move {
+			ac_s.send(action.events.len()).await.ok();
+			Ok::<(), Infallible>(())
+		}
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+
+	let event = |source| Event {
+		tags: vec![Tag::Source(source)],
+		metadata: Default::default(),
+	};
+	wx.send_event(event(Source::Internal)).await.unwrap();
+	wx.send_event(event(Source::Os)).await.unwrap();
+	wx.send_event(event(Source::Internal)).await.unwrap();
+
+	assert_eq!(
+		timeout(Duration::from_secs(5), ac_r.recv()).await.unwrap(),
+		Some(2)
+	);
+
+	let metrics = wx.metrics();
+	assert_eq!(metrics.events_received, 3);
+	assert_eq!(metrics.events_filtered, 1);
+	assert_eq!(metrics.actions, 1);
+	assert_eq!(metrics.actions_suppressed, 0);
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn counts_coalesced_fs_events() {
+	let base = std::env::temp_dir().join(format!("watchexec-test-metrics-{}", std::process::id()));
+	std::fs::create_dir_all(&base).unwrap();
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_signal(false);
+	runtime.fs_debounce(Duration::from_millis(300));
+	runtime.pathset([&base]);
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+	timeout(Duration::from_secs(5), async {
+		while wx.watched_paths().is_empty() {
+			sleep(Duration::from_millis(10)).await;
+		}
+	})
+	.await
+	.expect("path was not watched");
+
+	for n in 0..5 {
+		std::fs::write(base.join(format!("file{}", n)), "data").unwrap();
+	}
+
+	timeout(Duration::from_secs(5), async {
+		while wx.metrics().events_received == 0 {
+			sleep(Duration::from_millis(10)).await;
+		}
+	})
+	.await
+	.expect("no event emitted");
+
+	let metrics = wx.metrics();
+	// five writes within the debounce window make (at least) five raw events, emitted as one
+	assert!(metrics.fs_events >= 5, "{:?}", metrics);
+	assert!(metrics.fs_events_coalesced >= 4, "{:?}", metrics);
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+	std::fs::remove_dir_all(&base).ok();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn event_queue_depth_follows_backlog() {
+	let (in_s, mut in_r) = mpsc::channel(8);
+	let (release_s, release_r) = std::sync::mpsc::channel::<()>();
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.on_action(SyncFnHandler::from(move |_: Action| {
+		in_s.try_send(()).ok();
+		release_r.recv().ok();
+		Ok::<(), Infallible>(())
+	}));
+
+	let init = InitConfig::builder().event_channel_size(8).build();
+	let wx = Watchexec::new(init, runtime).unwrap();
+	assert_eq!(wx.event_queue_depth(), 0);
+	let main = wx.main();
+
+	wx.send_event(Event::default()).await.unwrap();
+	timeout(Duration::from_secs(5), in_r.recv())
+		.await
+		.expect("action handler was not called");
+
+	// the worker is busy in the handler, so these wait in the channel
+	for _ in 0..3 {
+		wx.send_event(Event::default()).await.unwrap();
+	}
+	assert_eq!(wx.event_queue_depth(), 3);
+
+	for _ in 0..4 {
+		release_s.send(()).unwrap();
+	}
+	timeout(Duration::from_secs(5), async {
+		while wx.event_queue_depth() > 0 {
+			sleep(Duration::from_millis(10)).await;
+		}
+	})
+	.await
+	.expect("event channel did not drain");
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn sustained_saturation_is_reported() {
+	let (in_s, mut in_r) = mpsc::channel(8);
+	let (release_s, release_r) = watch::channel(false);
+	let (er_s, mut er_r) = mpsc::channel(8);
+
+	// this handler waits asynchronously, so the runtime can still check on the channel
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.on_action(move |_: Action| {
+		let in_s = in_s.clone();
+		let mut release = release_r.clone();
+		async move {
+			in_s.try_send(()).ok();
+			while !*release.borrow() {
+				release.changed().await.ok();
+			}
+			Ok::<(), Infallible>(())
+		}
+	});
+
+	let init = InitConfig::builder()
+		.event_channel_size(2)
+		.saturation_warning(Some(Duration::from_millis(200)))
+		.on_error(move |err: Arc<RuntimeError>| {
+			let er_s = er_s.clone();
+			async move {
+				if let RuntimeError::ChannelSaturated { size, depth, .. } = *err {
+					er_s.send((size, depth)).await.ok();
+				}
+				Ok::<(), Infallible>(())
+			}
+		})
+		.build();
+	let wx = Watchexec::new(init, runtime).unwrap();
+	let main = wx.main();
+
+	wx.send_event(Event::default()).await.unwrap();
+	timeout(Duration::from_secs(5), in_r.recv())
+		.await
+		.expect("action handler was not called");
+	for _ in 0..2 {
+		wx.send_event(Event::default()).await.unwrap();
+	}
+
+	assert_eq!(
+		timeout(Duration::from_secs(5), er_r.recv()).await.unwrap(),
+		Some((2, 2))
+	);
+
+	release_s.send(true).unwrap();
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
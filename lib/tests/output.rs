@@ -0,0 +1,236 @@
+#![cfg(unix)]
+
+use std::{convert::Infallible, time::Duration};
+
+use tokio::{sync::mpsc, time::timeout};
+use watchexec::{
+	action::{Action, CapturedOutput, Outcome},
+	command::Shell,
+	config::{InitConfig, RuntimeConfig},
+	event::{Event, OutputStream, ProcessEnd, Source, Tag},
+	Watchexec,
+};
+
+async fn captured(script: &str, cr_lines: bool) -> Vec<(OutputStream, String)> {
+	let (out_s, mut out_r) = mpsc::channel(64);
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.action_throttle(Duration::from_millis(10));
+	runtime.command_shell(Shell::Unix("sh".into()));
+	runtime.command([script]);
+	runtime
+		.command_capture_output(true)
+		.command_output_cr_lines(cr_lines);
+	runtime.on_action(move |action: Action| {
+		let out_s = out_s.clone();
+		async move {
+			let mut start = false;
+			for event in action.events.iter() {
+				if event.is_output() {
+					for (stream, line) in event.output_lines() {
+						out_s.send(Some((stream, line.to_owned()))).await.ok();
+					}
+				} else if event.is_completion() {
+					out_s.send(None).await.ok();
+				} else {
+					start = true;
+				}
+			}
+			action.outcome(if start {
+				Outcome::Start
+			} else {
+				Outcome::DoNothing
+			});
+			Ok::<(), Infallible>(())
+		}
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+	wx.send_event(Event {
+		tags: vec![Tag::Source(Source::Internal)],
+		metadata: Default::default(),
+	})
+	.await
+	.unwrap();
+
+	// the last lines may come after the completion
+	let mut lines = Vec::new();
+	let mut completed = false;
+	while let Ok(Some(next)) = timeout(
+		Duration::from_secs(if completed { 1 } else { 5 }),
+		out_r.recv(),
+	)
+	.await
+	{
+		match next {
+			Some(line) => lines.push(line),
+			None => completed = true,
+		}
+	}
+	assert!(completed, "command did not complete");
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+	lines
+}
+
+fn only(lines: &[(OutputStream, String)], stream: OutputStream) -> Vec<&str> {
+	lines
+		.iter()
+		.filter(|(s, _)| *s == stream)
+		.map(|(_, l)| l.as_str())
+		.collect()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn output_is_split_into_lines_per_stream() {
+	let lines = captured(
+		r"printf 'one\ntwo\n'; printf 'oops\n' >&2; printf 'partial'",
+		false,
+	)
+	.await;
+	assert_eq!(
+		only(&lines, OutputStream::Stdout),
+		vec!["one", "two", "partial"]
+	);
+	assert_eq!(only(&lines, OutputStream::Stderr), vec!["oops"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn carriage_returns_can_end_lines() {
+	let lines = captured(r"printf '10%%\r50%%\r100%%\n'", true).await;
+	assert_eq!(
+		only(&lines, OutputStream::Stdout),
+		vec!["10%", "50%", "100%"]
+	);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn stdin_outcome_writes_to_command() {
+	let (out_s, mut out_r) = mpsc::channel(64);
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.action_throttle(Duration::from_millis(10));
+	runtime.command_shell(Shell::Unix("sh".into()));
+	runtime.command([r#"read a; echo "got $a"; read b; echo "then $b""#]);
+	runtime
+		.command_capture_output(true)
+		.command_keep_stdin(true);
+	runtime.on_action(move |action: Action| {
+		let out_s = out_s.clone();
+		async move {
+			let mut outcome = Outcome::DoNothing;
+			for event in action.events.iter() {
+				if event.is_output() {
+					for (_, line) in event.output_lines() {
+						out_s.send(Some(line.to_owned())).await.ok();
+					}
+				} else if event.is_completion() {
+					out_s.send(None).await.ok();
+				} else if let Some(key) = event.keys().next() {
+					outcome = Outcome::Stdin(format!("{}\n", key).into_bytes());
+				} else {
+					outcome = Outcome::Start;
+				}
+			}
+			action.outcome(outcome);
+			Ok::<(), Infallible>(())
+		}
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+	let key = |c| Event {
+		tags: vec![Tag::Keyboard(c)],
+		metadata: Default::default(),
+	};
+	wx.send_event(Event {
+		tags: vec![Tag::Source(Source::Internal)],
+		metadata: Default::default(),
+	})
+	.await
+	.unwrap();
+	tokio::time::sleep(Duration::from_millis(100)).await;
+	wx.send_event(key('a')).await.unwrap();
+	assert_eq!(
+		timeout(Duration::from_secs(5), out_r.recv()).await.unwrap(),
+		Some(Some("got a".into()))
+	);
+	wx.send_event(key('b')).await.unwrap();
+	assert_eq!(
+		timeout(Duration::from_secs(5), out_r.recv()).await.unwrap(),
+		Some(Some("then b".into()))
+	);
+	assert_eq!(
+		timeout(Duration::from_secs(5), out_r.recv()).await.unwrap(),
+		Some(None),
+		"command did not complete"
+	);
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
+
+async fn captured_output(script: &str, limit: usize) -> CapturedOutput {
+	let (out_s, mut out_r) = mpsc::channel(8);
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.action_throttle(Duration::from_millis(10));
+	runtime.command_shell(Shell::Unix("sh".into()));
+	runtime.command([script]);
+	runtime.command_capture_limit(limit);
+	runtime.on_action(move |action: Action| {
+		let out_s = out_s.clone();
+		async move {
+			if action.events.iter().any(|e| !e.is_completion()) {
+				action.outcome(Outcome::capture(move |output| {
+					let out_s = out_s.clone();
+					Box::pin(async move {
+						out_s.send(output).await.ok();
+						Ok(())
+					})
+				}));
+			}
+			Ok::<(), Infallible>(())
+		}
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+	wx.send_event(Event {
+		tags: vec![Tag::Source(Source::Internal)],
+		metadata: Default::default(),
+	})
+	.await
+	.unwrap();
+
+	let output = timeout(Duration::from_secs(5), out_r.recv())
+		.await
+		.expect("output was not captured")
+		.unwrap();
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+	output
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn capture_outcome_returns_output_and_status() {
+	let output = captured_output(r"printf 'one\ntwo\n'; printf 'oops' >&2; exit 3", 1024).await;
+	assert_eq!(output.stdout, b"one\ntwo\n");
+	assert_eq!(output.stderr, b"oops");
+	assert!(!output.truncated);
+	assert!(matches!(output.status, Some(ProcessEnd::ExitError(code)) if code.get() == 3));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn capture_outcome_is_bounded() {
+	let output = captured_output("yes | head -c 100000", 10).await;
+	assert_eq!(output.stdout, b"y\ny\ny\ny\ny\n");
+	assert!(output.truncated);
+	assert_eq!(output.status, Some(ProcessEnd::Success));
+}
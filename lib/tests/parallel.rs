@@ -0,0 +1,117 @@
+#![cfg(unix)]
+
+use std::{
+	convert::Infallible,
+	sync::{
+		atomic::{AtomicUsize, Ordering},
+		Arc,
+	},
+	time::{Duration, Instant},
+};
+
+use tokio::{sync::mpsc, time::timeout};
+use watchexec::{
+	action::{Action, Outcome},
+	command::Shell,
+	config::{InitConfig, RuntimeConfig},
+	event::{Event, ProcessEnd, Tag},
+	Watchexec,
+};
+
+fn runtime(command: &str, parallel: &[&str], completions: mpsc::Sender<Event>) -> RuntimeConfig {
+	let actions = Arc::new(AtomicUsize::new(0));
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.command_shell(Shell::Unix("sh".into()));
+	runtime.command_grouped(false);
+	runtime.command([command]);
+	runtime.parallel_commands(parallel.iter().map(|command| [command]));
+	runtime.on_action(move |action: Action| {
+		let completions = completions.clone();
+		let n = actions.fetch_add(1, Ordering::SeqCst);
+		async move {
+			let mut completed = false;
+			for event in action.events.iter().filter(|e| e.is_completion()) {
+				completed = true;
+				completions.send(event.clone()).await.ok();
+			}
+
+			action.outcome(match n {
+				_ if completed => Outcome::DoNothing,
+				0 => Outcome::Start,
+				_ => Outcome::Stop,
+			});
+			Ok::<(), Infallible>(())
+		}
+	});
+	runtime
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn parallel_commands_complete_together() {
+	let (cm_s, mut cm_r) = mpsc::channel(8);
+	let runtime = runtime("true", &["sleep 0.2; exit 3", "exit 4"], cm_s);
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+
+	wx.send_event(Event::default()).await.unwrap();
+	let event = timeout(Duration::from_secs(5), cm_r.recv())
+		.await
+		.expect("no completion event")
+		.unwrap();
+
+	let mut ends: Vec<_> = event.completions().collect();
+	ends.sort_by_key(|end| format!("{:?}", end));
+	assert_eq!(
+		ends,
+		vec![
+			Some(ProcessEnd::ExitError(3.try_into().unwrap())),
+			Some(ProcessEnd::ExitError(4.try_into().unwrap())),
+			Some(ProcessEnd::Success),
+		]
+	);
+	assert_eq!(
+		event
+			.tags
+			.iter()
+			.filter(|tag| matches!(tag, Tag::Process(_)))
+			.count(),
+		3
+	);
+	assert!(
+		timeout(Duration::from_millis(300), cm_r.recv())
+			.await
+			.is_err(),
+		"got more than one completion event"
+	);
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn stop_applies_to_every_parallel_command() {
+	let (cm_s, mut cm_r) = mpsc::channel(8);
+	let runtime = runtime("sleep 10", &["sleep 10"], cm_s);
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+
+	wx.send_event(Event::default()).await.unwrap();
+	tokio::time::sleep(Duration::from_millis(300)).await;
+	let stopped = Instant::now();
+	wx.send_event(Event::default()).await.unwrap();
+
+	let event = timeout(Duration::from_secs(5), cm_r.recv())
+		.await
+		.expect("commands did not stop")
+		.unwrap();
+	assert_eq!(event.completions().count(), 2);
+	assert_eq!(event.metadata["stopped-by"], vec!["kill", "kill"]);
+	assert!(stopped.elapsed() < Duration::from_secs(5));
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
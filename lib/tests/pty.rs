@@ -0,0 +1,52 @@
+#![cfg(any(target_os = "linux", target_os = "android"))]
+
+use std::{convert::Infallible, time::Duration};
+
+use tokio::{sync::mpsc, time::timeout};
+use watchexec::{
+	action::{Action, Outcome},
+	command::Shell,
+	config::{InitConfig, RuntimeConfig},
+	event::{Event, ProcessEnd},
+	Watchexec,
+};
+
+#[tokio::test(flavor = "multi_thread")]
+async fn command_output_is_a_terminal() {
+	let (end_s, mut end_r) = mpsc::channel(8);
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.command_shell(Shell::Unix("sh".into()));
+	runtime.command_grouped(false);
+	runtime.command(["test -t 1 && test -t 2"]);
+	runtime.command_pty(true);
+	runtime.on_action(move |action: Action| {
+		let end_s = end_s.clone();
+		async move {
+			let ends: Vec<_> = action.events.iter().flat_map(Event::completions).collect();
+			if ends.is_empty() {
+				action.outcome(Outcome::Start);
+			} else {
+				for end in ends {
+					end_s.send(end).await.ok();
+				}
+				action.outcome(Outcome::DoNothing);
+			}
+			Ok::<(), Infallible>(())
+		}
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+
+	wx.send_event(Event::default()).await.unwrap();
+	let end = timeout(Duration::from_secs(5), end_r.recv())
+		.await
+		.expect("command did not complete")
+		.unwrap();
+	assert_eq!(end, Some(ProcessEnd::Success));
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
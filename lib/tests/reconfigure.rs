@@ -0,0 +1,292 @@
+use std::{convert::Infallible, path::PathBuf, time::Duration};
+
+use tokio::{
+	sync::mpsc,
+	time::{sleep, timeout},
+};
+use watchexec::{
+	action::{Action, Outcome, PostSpawn},
+	command::Shell,
+	config::{InitConfig, RuntimeConfig},
+	event::{Event, Tag},
+	ReconfigReport, Watchexec,
+};
+
+fn report(action: bool, fs: bool) -> ReconfigReport {
+	let mut report = ReconfigReport::default();
+	report.action = action;
+	report.fs = fs;
+	report
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reconfigure_only_updates_what_changed() {
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_signal(false).pathset(["src"]);
+
+	let wx = Watchexec::new(InitConfig::default(), runtime.clone()).unwrap();
+	let main = wx.main();
+
+	assert_eq!(
+		wx.reconfigure(runtime.clone()).unwrap(),
+		report(false, false)
+	);
+
+	runtime.action_throttle(Duration::from_millis(10));
+	assert_eq!(
+		wx.reconfigure(runtime.clone()).unwrap(),
+		report(true, false)
+	);
+
+	runtime.pathset(["src", "tests"]);
+	assert_eq!(
+		wx.reconfigure(runtime.clone()).unwrap(),
+		report(false, true)
+	);
+
+	runtime.filter_predicate(|_: &watchexec::event::Event| true);
+	assert_eq!(wx.reconfigure(runtime).unwrap(), report(true, false));
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reconfigure_force_updates_everything() {
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_signal(false);
+
+	let wx = Watchexec::new(InitConfig::default(), runtime.clone()).unwrap();
+	let main = wx.main();
+
+	let mut everything = report(true, true);
+	everything.timer = true;
+	assert_eq!(wx.reconfigure_force(runtime).unwrap(), everything);
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reconfigure_async_waits_for_the_watcher() {
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_signal(false);
+
+	let wx = Watchexec::new(InitConfig::default(), runtime.clone()).unwrap();
+	let main = wx.main();
+
+	runtime.pathset(["src"]);
+	let report = timeout(
+		Duration::from_secs(5),
+		wx.reconfigure_async(runtime.clone()),
+	)
+	.await
+	.expect("reconfigure was not applied")
+	.unwrap();
+	assert!(report.fs);
+	assert_eq!(wx.watched_paths(), vec![PathBuf::from("src")]);
+
+	runtime.pathset(["src", "tests"]);
+	timeout(Duration::from_secs(5), wx.reconfigure_async(runtime))
+		.await
+		.expect("reconfigure was not applied")
+		.unwrap();
+	assert_eq!(
+		wx.watched_paths(),
+		vec![PathBuf::from("src"), PathBuf::from("tests")]
+	);
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reconfigure_async_without_fs_does_not_wait() {
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+
+	let wx = Watchexec::new(InitConfig::default(), runtime.clone()).unwrap();
+	let main = wx.main();
+
+	runtime.pathset(["src"]);
+	timeout(Duration::from_secs(5), wx.reconfigure_async(runtime))
+		.await
+		.expect("reconfigure waited for a disabled worker")
+		.unwrap();
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn set_command_changes_only_the_command() {
+	let (sp_s, mut sp_r) = mpsc::channel(8);
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.command_shell(Shell::None);
+	runtime.command_grouped(false);
+	runtime.command(["true", "one"]);
+	runtime.on_action(|action: Action| async move {
+		if action.events.iter().any(|e| e.is_empty()) {
+			action.outcome(Outcome::Start);
+		}
+		Ok::<(), Infallible>(())
+	});
+	runtime.on_post_spawn(move |spawn: PostSpawn| {
+		let sp_s = sp_s.clone();
+		async move {
+			sp_s.send(spawn.command).await.ok();
+			Ok::<(), Infallible>(())
+		}
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+	assert_eq!(wx.command(), vec!["true", "one"]);
+
+	wx.send_event(Event::default()).await.unwrap();
+	assert_eq!(
+		timeout(Duration::from_secs(5), sp_r.recv()).await.unwrap(),
+		Some(vec!["true".to_owned(), "one".to_owned()])
+	);
+
+	wx.set_command(["true", "two"]).unwrap();
+	assert_eq!(wx.command(), vec!["true", "two"]);
+
+	wx.send_event(Event::default()).await.unwrap();
+	assert_eq!(
+		timeout(Duration::from_secs(5), sp_r.recv()).await.unwrap(),
+		Some(vec!["true".to_owned(), "two".to_owned()])
+	);
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
+
+fn key(c: char) -> Event {
+	Event {
+		tags: vec![Tag::Keyboard(c)],
+		metadata: Default::default(),
+	}
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reconfigure_atomic_refilters_pending_events() {
+	let (ac_s, mut ac_r) = mpsc::channel(8);
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.action_throttle(Duration::from_millis(300));
+	runtime.filter_predicate(|event: &Event| event.keys().any(|k| k == 'a'));
+	runtime.on_action(move |action: Action| {
+		let ac_s = ac_s.clone();
+		async move {
+			let keys: Vec<char> = action.events.iter().flat_map(|e| e.keys()).collect();
+			ac_s.send(keys).await.ok();
+			Ok::<(), Infallible>(())
+		}
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime.clone()).unwrap();
+	let main = wx.main();
+
+	wx.send_event(key('a')).await.unwrap();
+	runtime.filter_predicate(|event: &Event| event.keys().any(|k| k == 'b'));
+	let swapped = timeout(Duration::from_secs(5), wx.reconfigure_atomic(runtime))
+		.await
+		.expect("reconfigure was not applied")
+		.unwrap();
+	assert_eq!(swapped, report(true, false));
+	wx.send_event(key('b')).await.unwrap();
+
+	assert_eq!(
+		timeout(Duration::from_secs(5), ac_r.recv()).await.unwrap(),
+		Some(vec!['b'])
+	);
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reconfigure_atomic_refilters_when_more_events_come_after() {
+	let (ac_s, mut ac_r) = mpsc::channel(8);
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.action_throttle(Duration::from_millis(500));
+	runtime.filter_predicate(|event: &Event| event.keys().any(|k| k == 'a'));
+	runtime.on_action(move |action: Action| {
+		let ac_s = ac_s.clone();
+		async move {
+			let keys: Vec<char> = action.events.iter().flat_map(|e| e.keys()).collect();
+			ac_s.send(keys).await.ok();
+			Ok::<(), Infallible>(())
+		}
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime.clone()).unwrap();
+	let main = wx.main();
+
+	// let the first event be filtered with the old configuration before swapping
+	wx.send_event(key('a')).await.unwrap();
+	sleep(Duration::from_millis(100)).await;
+	runtime.filter_predicate(|event: &Event| event.keys().any(|k| k == 'b'));
+	timeout(Duration::from_secs(5), wx.reconfigure_atomic(runtime))
+		.await
+		.expect("reconfigure was not applied")
+		.unwrap();
+	wx.send_event(key('b')).await.unwrap();
+
+	assert_eq!(
+		timeout(Duration::from_secs(5), ac_r.recv()).await.unwrap(),
+		Some(vec!['b'])
+	);
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reconfigure_atomic_waits_for_the_action() {
+	let (cb_s, mut cb_r) = mpsc::channel(8);
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.action_throttle(Duration::from_millis(10));
+	runtime.on_action(move |action: Action| {
+		let cb_s = cb_s.clone();
+		async move {
+			action.outcome(Outcome::callback(move |_| {
+				let cb_s = cb_s.clone();
+				Box::pin(async move {
+					cb_s.send("started").await.ok();
+					sleep(Duration::from_millis(300)).await;
+					cb_s.send("done").await.ok();
+					Ok(())
+				})
+			}));
+			Ok::<(), Infallible>(())
+		}
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime.clone()).unwrap();
+	let main = wx.main();
+
+	wx.send_event(Event::default()).await.unwrap();
+	assert_eq!(
+		timeout(Duration::from_secs(5), cb_r.recv()).await.unwrap(),
+		Some("started")
+	);
+
+	runtime.action_throttle(Duration::from_millis(20));
+	timeout(Duration::from_secs(5), wx.reconfigure_atomic(runtime))
+		.await
+		.expect("reconfigure was not applied")
+		.unwrap();
+	assert_eq!(cb_r.try_recv().ok(), Some("done"));
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
@@ -0,0 +1,63 @@
+use std::{path::PathBuf, sync::Arc, time::Duration};
+
+use tokio::time::{sleep, timeout};
+use watchexec::{
+	action::Action,
+	config::{InitConfig, RuntimeConfig},
+	error::ReconfigError,
+	event::{Event, Source, Tag},
+	signal::source::MainSignal,
+	Watchexec,
+};
+
+#[tokio::test(flavor = "multi_thread")]
+async fn hangup_reconfigures_from_handler() {
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_signal(false).pathset(["src"]);
+
+	let wx = Watchexec::new(InitConfig::default(), runtime.clone()).unwrap();
+	let main = wx.main();
+
+	let w = Arc::downgrade(&wx);
+	let config = runtime.clone();
+	runtime.on_action(move |action: Action| {
+		let mut config = config.clone();
+		let w = w.clone();
+		async move {
+			let hangup = action
+				.events
+				.iter()
+				.any(|e| e.signals().any(|s| s == MainSignal::Hangup));
+
+			if let (true, Some(w)) = (hangup, w.upgrade()) {
+				config.pathset(["tests"]).keep_action();
+				w.reconfigure(config)?;
+			}
+
+			Ok::<(), ReconfigError>(())
+		}
+	});
+	wx.reconfigure(runtime).unwrap();
+
+	wx.send_event(Event {
+		tags: vec![
+			Tag::Source(Source::Internal),
+			Tag::Signal(MainSignal::Hangup),
+		],
+		metadata: Default::default(),
+	})
+	.await
+	.unwrap();
+
+	let expected: Vec<PathBuf> = vec!["tests".into()];
+	timeout(Duration::from_secs(5), async {
+		while wx.watched_paths() != expected {
+			sleep(Duration::from_millis(10)).await;
+		}
+	})
+	.await
+	.expect("handler did not reconfigure on hangup");
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
@@ -0,0 +1,120 @@
+#![cfg(feature = "serde")]
+
+use std::{convert::Infallible, time::Duration};
+
+use tokio::{
+	sync::mpsc,
+	time::{sleep, timeout, Instant},
+};
+use watchexec::{
+	action::Action,
+	config::{InitConfig, RuntimeConfig},
+	event::{Event, Source, Tag},
+	replay::{record, replay, Pace},
+	Watchexec,
+};
+
+fn keyboard(key: char) -> Event {
+	Event {
+		tags: vec![Tag::Source(Source::Keyboard), Tag::Keyboard(key)],
+		metadata: Default::default(),
+	}
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn recorded_events_replay_with_their_timing() {
+	let log = std::env::temp_dir().join(format!(
+		"watchexec-test-replay-{}.jsonl",
+		std::process::id()
+	));
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let recorder = tokio::spawn(record(wx.event_stream(), log.clone()));
+	let main = wx.main();
+
+	wx.send_event(keyboard('a')).await.unwrap();
+	sleep(Duration::from_millis(300)).await;
+	wx.send_event(keyboard('b')).await.unwrap();
+	sleep(Duration::from_millis(50)).await;
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+	assert_eq!(
+		timeout(Duration::from_secs(5), recorder)
+			.await
+			.expect("recording did not end with the runtime")
+			.unwrap()
+			.unwrap(),
+		2
+	);
+
+	let (ac_s, mut ac_r) = mpsc::channel(8);
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.action_throttle(Duration::from_millis(10));
+	runtime.on_action(move |action: Action| {
+		let ac_s = ac_s.clone();
+		async move {
+			ac_s.send((action.events.as_ref().clone(), Instant::now()))
+				.await
+				.ok();
+			Ok::<(), Infallible>(())
+		}
+	});
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+
+	assert_eq!(replay(&wx, &log, Pace::Original).await.unwrap(), 2);
+	let (first, at_first) = timeout(Duration::from_secs(5), ac_r.recv())
+		.await
+		.unwrap()
+		.unwrap();
+	let (second, at_second) = timeout(Duration::from_secs(5), ac_r.recv())
+		.await
+		.unwrap()
+		.unwrap();
+	assert_eq!(first, vec![keyboard('a')]);
+	assert_eq!(second, vec![keyboard('b')]);
+	assert!(at_second - at_first >= Duration::from_millis(250));
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+	std::fs::remove_file(&log).ok();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn malformed_log_replays_nothing() {
+	let log = std::env::temp_dir().join(format!(
+		"watchexec-test-replay-bad-{}.jsonl",
+		std::process::id()
+	));
+	std::fs::write(
+		&log,
+		"{\"offset\":{\"secs\":0,\"nanos\":0},\"event\":{\"tags\":[],\"metadata\":{}}}\nnot json\n",
+	)
+	.unwrap();
+
+	let (ac_s, mut ac_r) = mpsc::channel(8);
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.on_action(move |action: Action| {
+		let ac_s = ac_s.clone();
+		async move {
+			ac_s.send(action.events.len()).await.ok();
+			Ok::<(), Infallible>(())
+		}
+	});
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+
+	let err = replay(&wx, &log, Pace::Immediate).await.unwrap_err();
+	assert!(err.to_string().contains("line 2"), "{}", err);
+	assert!(timeout(Duration::from_millis(200), ac_r.recv())
+		.await
+		.is_err());
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+	std::fs::remove_file(&log).ok();
+}
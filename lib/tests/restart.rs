@@ -0,0 +1,309 @@
+#![cfg(unix)]
+
+use std::{
+	convert::Infallible,
+	sync::{
+		atomic::{AtomicUsize, Ordering},
+		Arc,
+	},
+	time::Duration,
+};
+
+use futures::StreamExt;
+use tokio::{sync::mpsc, time::timeout};
+use watchexec::{
+	action::{Action, OnBusyUpdate, Outcome, PostSpawn},
+	command::Shell,
+	config::{InitConfig, RuntimeConfig},
+	error::RuntimeError,
+	event::{Event, ProcessEnd, Tag},
+	signal::{process::SubSignal, source::MainSignal},
+	Watchexec,
+};
+
+async fn restart_with(script: &str, restart: Outcome) -> (Option<ProcessEnd>, usize) {
+	let (end_s, mut end_r) = mpsc::channel(8);
+	let actions = Arc::new(AtomicUsize::new(0));
+	let spawns = Arc::new(AtomicUsize::new(0));
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.command_shell(Shell::Unix("sh".into()));
+	runtime.command_grouped(false);
+	runtime.command([script]);
+	runtime.on_action(move |action: Action| {
+		let end_s = end_s.clone();
+		let n = actions.fetch_add(1, Ordering::SeqCst);
+		let restart = restart.clone();
+		async move {
+			for event in action.events.iter() {
+				for end in event.completions() {
+					end_s.send(end).await.ok();
+				}
+			}
+
+			action.outcome(match n {
+				0 => Outcome::Start,
+				1 => restart,
+				2 => Outcome::Stop,
+				_ => Outcome::DoNothing,
+			});
+			Ok::<(), Infallible>(())
+		}
+	});
+	let counter = spawns.clone();
+	runtime.on_post_spawn(move |_: PostSpawn| {
+		counter.fetch_add(1, Ordering::SeqCst);
+		async { Ok::<(), Infallible>(()) }
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+
+	wx.send_event(Event::default()).await.unwrap();
+	tokio::time::sleep(Duration::from_millis(300)).await;
+	wx.send_event(Event::default()).await.unwrap();
+
+	let end = timeout(Duration::from_secs(5), end_r.recv())
+		.await
+		.expect("old command did not end on restart")
+		.unwrap();
+
+	wx.send_event(Event::default()).await.unwrap();
+	timeout(Duration::from_secs(5), end_r.recv())
+		.await
+		.expect("new command did not stop")
+		.unwrap();
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+
+	(end, spawns.load(Ordering::SeqCst))
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn restart_graceful() {
+	let (end, spawns) = restart_with(
+		"trap 'exit 3' TERM; while true; do sleep 0.1; done",
+		Outcome::restart_with_grace(Duration::from_secs(5)),
+	)
+	.await;
+
+	assert_eq!(end, Some(ProcessEnd::ExitError(3.try_into().unwrap())));
+	assert_eq!(spawns, 2);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn restart_forced_after_grace() {
+	let (end, spawns) = restart_with(
+		"trap '' TERM; while true; do sleep 0.1; done",
+		Outcome::restart_with_grace(Duration::from_millis(200)),
+	)
+	.await;
+
+	assert!(matches!(end, Some(ProcessEnd::ExitSignal(_))), "{:?}", end);
+	assert_eq!(spawns, 2);
+}
+
+async fn busy_with(script: &str, on_busy: OnBusyUpdate) -> (Option<ProcessEnd>, usize) {
+	let (end_s, mut end_r) = mpsc::channel(8);
+	let actions = Arc::new(AtomicUsize::new(0));
+	let spawns = Arc::new(AtomicUsize::new(0));
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.command_shell(Shell::Unix("sh".into()));
+	runtime.command_grouped(false);
+	runtime.command([script]);
+	runtime.on_busy_update(on_busy);
+	runtime.on_action(move |action: Action| {
+		let end_s = end_s.clone();
+		let n = actions.fetch_add(1, Ordering::SeqCst);
+		async move {
+			for event in action.events.iter() {
+				for end in event.completions() {
+					end_s.send(end).await.ok();
+				}
+			}
+
+			action.outcome(if n < 2 {
+				Outcome::Start
+			} else {
+				Outcome::DoNothing
+			});
+			Ok::<(), Infallible>(())
+		}
+	});
+	let counter = spawns.clone();
+	runtime.on_post_spawn(move |_: PostSpawn| {
+		counter.fetch_add(1, Ordering::SeqCst);
+		async { Ok::<(), Infallible>(()) }
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+
+	wx.send_event(Event::default()).await.unwrap();
+	tokio::time::sleep(Duration::from_millis(300)).await;
+	wx.send_event(Event::default()).await.unwrap();
+
+	let end = timeout(Duration::from_secs(5), end_r.recv())
+		.await
+		.expect("first command did not end")
+		.unwrap();
+	tokio::time::sleep(Duration::from_millis(300)).await;
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+
+	(end, spawns.load(Ordering::SeqCst))
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn busy_queue_starts_after() {
+	let (end, spawns) = busy_with("sleep 0.5", OnBusyUpdate::Queue).await;
+	assert_eq!(end, Some(ProcessEnd::Success));
+	assert_eq!(spawns, 2);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn busy_queue_still_exits() {
+	let spawns = Arc::new(AtomicUsize::new(0));
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.command_shell(Shell::Unix("sh".into()));
+	runtime.command_grouped(false);
+	runtime.command(["sleep 10"]);
+	runtime.on_busy_update(OnBusyUpdate::Queue);
+	runtime.on_action(|action: Action| async move {
+		let signalled = action.events.iter().any(|e| e.signals().next().is_some());
+		action.outcome(if signalled {
+			Outcome::Exit
+		} else {
+			Outcome::Start
+		});
+		Ok::<(), Infallible>(())
+	});
+	let counter = spawns.clone();
+	runtime.on_post_spawn(move |_: PostSpawn| {
+		counter.fetch_add(1, Ordering::SeqCst);
+		async { Ok::<(), Infallible>(()) }
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+
+	wx.send_event(Event::default()).await.unwrap();
+	tokio::time::sleep(Duration::from_millis(300)).await;
+	wx.send_event(Event::default()).await.unwrap();
+	tokio::time::sleep(Duration::from_millis(300)).await;
+	wx.send_event(Event::signal(MainSignal::Interrupt))
+		.await
+		.unwrap();
+
+	timeout(Duration::from_secs(3), main)
+		.await
+		.expect("main task did not resolve while a start was queued")
+		.unwrap()
+		.unwrap();
+	assert_eq!(spawns.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn busy_restart_replaces() {
+	let (end, spawns) = busy_with("sleep 10", OnBusyUpdate::Restart).await;
+	assert!(matches!(end, Some(ProcessEnd::ExitSignal(_))), "{:?}", end);
+	assert_eq!(spawns, 2);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn busy_do_nothing_ignores() {
+	let (end, spawns) = busy_with("sleep 0.5", OnBusyUpdate::DoNothing).await;
+	assert_eq!(end, Some(ProcessEnd::Success));
+	assert_eq!(spawns, 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn busy_signal_signals() {
+	let (end, spawns) = busy_with(
+		"trap 'exit 4' USR1; while true; do sleep 0.1; done",
+		OnBusyUpdate::Signal(SubSignal::User1),
+	)
+	.await;
+	assert_eq!(end, Some(ProcessEnd::ExitError(4.try_into().unwrap())));
+	assert_eq!(spawns, 1);
+}
+
+async fn crash_loop_with(start: Outcome) {
+	let (er_s, mut er_r) = mpsc::channel(8);
+	let mut init = InitConfig::default();
+	init.on_error(er_s);
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.command_shell(Shell::Unix("sh".into()));
+	runtime.command_grouped(false);
+	runtime.command(["exit 1"]);
+	runtime.action_throttle(Duration::ZERO);
+	runtime
+		.max_restarts(Some(1))
+		.restart_window(Duration::from_millis(1500));
+	runtime.on_action(move |action: Action| {
+		let start = start.clone();
+		async move {
+			if action.events.iter().any(|e| e.is_empty()) {
+				action.outcome(start);
+			}
+			Ok::<(), Infallible>(())
+		}
+	});
+
+	let wx = Watchexec::new(init, runtime).unwrap();
+	let mut stream = Box::pin(wx.event_stream());
+	let main = wx.main();
+
+	let mut backoffs = Vec::new();
+	for _ in 0..3 {
+		wx.send_event(Event::default()).await.unwrap();
+		timeout(Duration::from_secs(5), async {
+			while let Some(event) = stream.next().await {
+				for tag in &event.tags {
+					if let Tag::RestartBackoff(delay) = tag {
+						backoffs.push(*delay);
+					}
+				}
+				if event.completions().next().is_some() {
+					break;
+				}
+			}
+		})
+		.await
+		.expect("command did not run");
+	}
+	assert_eq!(backoffs, vec![Duration::from_secs(1)]);
+
+	wx.send_event(Event::default()).await.unwrap();
+	let err = timeout(Duration::from_secs(5), er_r.recv())
+		.await
+		.expect("crash loop was not reported")
+		.unwrap();
+	assert!(
+		matches!(*err, RuntimeError::CrashLoop { starts: 3, .. }),
+		"{:?}",
+		err
+	);
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn crash_loop_backs_off_then_gives_up() {
+	crash_loop_with(Outcome::Start).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn crash_loop_guards_delayed_starts() {
+	crash_loop_with(Outcome::delay(Duration::from_millis(50), Outcome::Start)).await;
+}
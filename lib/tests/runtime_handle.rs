@@ -0,0 +1,48 @@
+use std::{convert::Infallible, sync::mpsc, thread, time::Duration};
+
+use tokio::runtime::Builder;
+use watchexec::{
+	action::{Action, Outcome},
+	config::{InitConfig, RuntimeConfig},
+	event::Event,
+	Watchexec,
+};
+
+#[test]
+fn runs_on_the_given_runtime() {
+	let rt = Builder::new_multi_thread()
+		.enable_all()
+		.thread_name("watchexec-io")
+		.build()
+		.unwrap();
+
+	let (ac_s, ac_r) = mpsc::channel();
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.on_action(move |action: Action| {
+		let ac_s = ac_s.clone();
+		async move {
+			ac_s.send(thread::current().name().map(String::from)).ok();
+			action.outcome(Outcome::DoNothing);
+			Ok::<(), Infallible>(())
+		}
+	});
+
+	// there's no ambient runtime here
+	let wx =
+		Watchexec::new_with_handle(InitConfig::default(), runtime, rt.handle().clone()).unwrap();
+	let main = wx.main();
+
+	wx.send_event_blocking(Event::default()).unwrap();
+	assert_eq!(
+		ac_r.recv_timeout(Duration::from_secs(5))
+			.unwrap()
+			.as_deref(),
+		Some("watchexec-io")
+	);
+
+	rt.block_on(async {
+		wx.shutdown(None).await.unwrap();
+		main.await.unwrap().unwrap();
+	});
+}
@@ -0,0 +1,181 @@
+#![cfg(feature = "serde")]
+
+use std::{collections::HashMap, convert::Infallible, time::Duration};
+
+use notify::event::Flag;
+use tokio::{sync::mpsc, time::timeout};
+use watchexec::{
+	action::{Action, Outcome},
+	command::Shell,
+	config::{InitConfig, RuntimeConfig},
+	event::{
+		filekind::{AccessKind, AccessMode, CreateKind, FileEventKind, ModifyKind, RenameMode},
+		Event, FileType, FsEventKind, OutputStream, ProcessEnd, Source, Tag,
+	},
+	signal::{process::SubSignal, source::MainSignal},
+	Watchexec,
+};
+
+fn roundtrip(event: &Event) -> Event {
+	let json = serde_json::to_string(event).unwrap();
+	serde_json::from_str(&json).unwrap()
+}
+
+#[test]
+fn every_tag_roundtrips() {
+	let event = Event {
+		tags: vec![
+			Tag::Source(Source::Filesystem),
+			Tag::FileEventKind(FileEventKind::Create(CreateKind::File)),
+			Tag::Path {
+				path: "/a/b".into(),
+				file_type: Some(FileType::File),
+			},
+			Tag::Process(1234),
+			Tag::Signal(MainSignal::Hangup),
+			Tag::ProcessCompletion(
+				Some(ProcessEnd::ExitSignal(SubSignal::Custom(31))),
+				Duration::from_millis(1500),
+			),
+			Tag::ProcessCompletion(None, Duration::ZERO),
+			Tag::FileSystem(FsEventKind::WatcherReinit),
+			Tag::Keyboard('r'),
+			Tag::Output {
+				stream: OutputStream::Stderr,
+				line: "warning: unused".into(),
+			},
+		],
+		metadata: HashMap::from([("stopped-by".to_owned(), vec!["kill".to_owned()])]),
+	};
+
+	assert_eq!(roundtrip(&event), event);
+}
+
+#[test]
+fn file_event_kinds_keep_notify_representation() {
+	let kind = FileEventKind::Modify(ModifyKind::Name(RenameMode::Both));
+	let json = serde_json::to_value(Tag::FileEventKind(kind.clone())).unwrap();
+	assert_eq!(
+		json,
+		serde_json::json!({
+			"kind": "file-event-kind",
+			"value": { "modify": { "kind": "rename", "mode": "both" } }
+		})
+	);
+
+	let mut raw = notify::Event::new(FileEventKind::Access(AccessKind::Close(AccessMode::Write)))
+		.add_path("/a".into());
+	raw.attrs.set_tracker(7);
+	raw.attrs.set_flag(Flag::Rescan);
+	let event = Event {
+		tags: vec![Tag::FileEventKind(kind), Tag::RawEvent(Box::new(raw))],
+		metadata: HashMap::new(),
+	};
+	assert_eq!(roundtrip(&event), event);
+}
+
+#[test]
+fn tags_have_a_kind_and_value() {
+	let json = serde_json::to_value(Tag::Source(Source::Keyboard)).unwrap();
+	assert_eq!(
+		json,
+		serde_json::json!({ "kind": "source", "value": "keyboard" })
+	);
+
+	let json = serde_json::to_value(&Tag::Path {
+		path: "/a".into(),
+		file_type: None,
+	})
+	.unwrap();
+	assert_eq!(
+		json,
+		serde_json::json!({ "kind": "path", "value": { "path": "/a", "file_type": null } })
+	);
+}
+
+#[cfg(unix)]
+#[test]
+fn non_utf8_paths_roundtrip() {
+	use std::{ffi::OsStr, os::unix::ffi::OsStrExt, path::PathBuf};
+
+	let path = PathBuf::from(OsStr::from_bytes(b"/tmp/\xff\xfe"));
+	let event = Event {
+		tags: vec![Tag::Path {
+			path: path.clone(),
+			file_type: None,
+		}],
+		metadata: Default::default(),
+	};
+
+	let json = serde_json::to_value(&event).unwrap();
+	assert_eq!(
+		json["tags"][0]["value"]["path"],
+		serde_json::json!({ "bytes": [47, 116, 109, 112, 47, 255, 254] })
+	);
+	assert_eq!(roundtrip(&event), event);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn events_file_is_written_and_removed() {
+	let base =
+		std::env::temp_dir().join(format!("watchexec-test-events-file-{}", std::process::id()));
+	std::fs::create_dir_all(&base).unwrap();
+	let copy = base.join("copy.json");
+	let name = base.join("name.txt");
+
+	let (done_s, mut done_r) = mpsc::channel(8);
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime
+		.command_shell(Shell::Unix("sh".into()))
+		.command_grouped(false)
+		.command_events_file(true)
+		.command([format!(
+			"cp \"$WATCHEXEC_EVENTS_FILE\" '{}' && printf %s \"$WATCHEXEC_EVENTS_FILE\" > '{}'",
+			copy.display(),
+			name.display()
+		)]);
+	runtime.on_action(move |action: Action| {
+		let done_s = done_s.clone();
+		async move {
+			if action.events.iter().any(|e| e.is_completion()) {
+				done_s.send(()).await.ok();
+				action.outcome(Outcome::DoNothing);
+			} else {
+				action.outcome(Outcome::Start);
+			}
+			Ok::<(), Infallible>(())
+		}
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+
+	let event = Event {
+		tags: vec![Tag::Path {
+			path: "/a/b".into(),
+			file_type: Some(FileType::File),
+		}],
+		metadata: HashMap::new(),
+	};
+	wx.send_event(event.clone()).await.unwrap();
+	timeout(Duration::from_secs(5), done_r.recv())
+		.await
+		.expect("command did not complete");
+
+	let written: Vec<Event> = serde_json::from_slice(&std::fs::read(&copy).unwrap()).unwrap();
+	assert_eq!(written, vec![event]);
+
+	let events_file = std::path::PathBuf::from(std::fs::read_to_string(&name).unwrap());
+	timeout(Duration::from_secs(5), async {
+		while events_file.exists() {
+			tokio::time::sleep(Duration::from_millis(10)).await;
+		}
+	})
+	.await
+	.expect("events file was not removed");
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+	std::fs::remove_dir_all(&base).ok();
+}
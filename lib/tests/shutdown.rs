@@ -0,0 +1,191 @@
+use std::time::Duration;
+
+use tokio::time::timeout;
+use watchexec::{
+	action::{Action, Outcome},
+	config::{InitConfig, RuntimeConfig},
+	error::RuntimeError,
+	event::{Event, Tag},
+	fs::Watcher,
+	signal::source::MainSignal,
+	Lifecycle, ShutdownReason, Status, Watchexec,
+};
+
+#[tokio::test(flavor = "multi_thread")]
+async fn shutdown_after_start() {
+	let wx = Watchexec::new(InitConfig::default(), RuntimeConfig::default()).unwrap();
+	let main = wx.main();
+
+	wx.shutdown(Some(Duration::from_secs(5))).await.unwrap();
+
+	timeout(Duration::from_secs(1), main)
+		.await
+		.expect("main task did not resolve after shutdown")
+		.unwrap()
+		.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn shutdown_before_start() {
+	let wx = Watchexec::new(InitConfig::default(), RuntimeConfig::default()).unwrap();
+
+	wx.shutdown(None).await.unwrap();
+
+	timeout(Duration::from_secs(1), wx.main())
+		.await
+		.expect("main task did not resolve after shutdown")
+		.unwrap()
+		.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn shutdown_twice() {
+	let wx = Watchexec::new(InitConfig::default(), RuntimeConfig::default()).unwrap();
+	let main = wx.main();
+
+	wx.shutdown(None).await.unwrap();
+	wx.shutdown(None).await.unwrap();
+
+	main.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn status_follows_lifecycle() {
+	let wx = Watchexec::new(InitConfig::default(), RuntimeConfig::default()).unwrap();
+	assert_eq!(wx.status(), Status::NotStarted);
+
+	let main = wx.main();
+	timeout(Duration::from_secs(5), async {
+		while wx.status() == Status::NotStarted {
+			tokio::time::sleep(Duration::from_millis(10)).await;
+		}
+	})
+	.await
+	.expect("main task did not start");
+	assert_eq!(wx.status(), Status::Running);
+
+	wx.shutdown(None).await.unwrap();
+	assert_eq!(wx.status(), Status::Stopped(Ok(())));
+	main.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn wait_resolves_for_every_waiter() {
+	let wx = Watchexec::new(InitConfig::default(), RuntimeConfig::default()).unwrap();
+	let wait = wx.wait();
+	let waiters: Vec<_> = (0..3).map(|_| tokio::spawn(wait.clone())).collect();
+	let later = tokio::spawn(wx.wait());
+	let main = wx.main();
+
+	wx.shutdown(None).await.unwrap();
+	for waiter in waiters {
+		timeout(Duration::from_secs(1), waiter)
+			.await
+			.expect("waiter did not resolve after shutdown")
+			.unwrap()
+			.unwrap();
+	}
+	later.await.unwrap().unwrap();
+	wait.await.unwrap();
+	wx.wait().await.unwrap();
+	main.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn main_resolves_to_the_shutdown_reason() {
+	let wx = Watchexec::new(InitConfig::default(), RuntimeConfig::default()).unwrap();
+	let main = wx.main();
+	wx.shutdown(None).await.unwrap();
+	assert_eq!(main.await.unwrap().unwrap(), ShutdownReason::Requested);
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.on_action(|action: Action| async move {
+		action.outcome(Outcome::Exit);
+		Ok::<(), std::convert::Infallible>(())
+	});
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+	wx.send_event(Event {
+		tags: vec![Tag::Signal(MainSignal::Interrupt)],
+		metadata: Default::default(),
+	})
+	.await
+	.unwrap();
+	assert_eq!(
+		timeout(Duration::from_secs(5), main)
+			.await
+			.expect("main task did not exit")
+			.unwrap()
+			.unwrap(),
+		ShutdownReason::Signal(MainSignal::Interrupt)
+	);
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.on_action(|_: Action| async { Err(RuntimeError::Exit) });
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+	wx.send_event(Event::default()).await.unwrap();
+	assert_eq!(
+		timeout(Duration::from_secs(5), main)
+			.await
+			.expect("main task did not exit")
+			.unwrap()
+			.unwrap(),
+		ShutdownReason::HandlerError
+	);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn lifecycle_events_are_broadcast() {
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_signal(false).pathset(["src"]);
+	let wx = Watchexec::new(InitConfig::default(), runtime.clone()).unwrap();
+	let mut lifecycle = wx.lifecycle();
+	let main = wx.main();
+
+	assert_eq!(
+		timeout(Duration::from_secs(5), lifecycle.recv())
+			.await
+			.unwrap()
+			.unwrap(),
+		Lifecycle::MainStarted
+	);
+
+	timeout(Duration::from_secs(5), async {
+		while wx.watched_paths().is_empty() {
+			tokio::time::sleep(Duration::from_millis(10)).await;
+		}
+	})
+	.await
+	.expect("watcher was not created");
+
+	runtime.file_watcher(Watcher::Poll(Duration::from_secs(1)));
+	wx.reconfigure(runtime).unwrap();
+	match timeout(Duration::from_secs(5), lifecycle.recv())
+		.await
+		.unwrap()
+		.unwrap()
+	{
+		Lifecycle::Reconfigured(report) => assert!(report.fs && !report.action),
+		other => panic!("expected a reconfigure, got {:?}", other),
+	}
+	assert_eq!(
+		timeout(Duration::from_secs(5), lifecycle.recv())
+			.await
+			.unwrap()
+			.unwrap(),
+		Lifecycle::WatcherRebuilt
+	);
+
+	wx.shutdown(None).await.unwrap();
+	assert_eq!(
+		timeout(Duration::from_secs(5), lifecycle.recv())
+			.await
+			.unwrap()
+			.unwrap(),
+		Lifecycle::MainStopping
+	);
+	main.await.unwrap().unwrap();
+}
@@ -0,0 +1,125 @@
+#![cfg(unix)]
+
+use std::{convert::Infallible, time::Duration};
+
+use tokio::{sync::mpsc, time::timeout};
+use watchexec::{
+	action::{Action, Outcome, UnmappedSignals},
+	command::Shell,
+	config::{InitConfig, RuntimeConfig},
+	event::{Event, ProcessEnd, Source, Tag},
+	signal::source::MainSignal,
+	Watchexec,
+};
+
+fn signal(sig: MainSignal) -> Event {
+	Event {
+		tags: vec![Tag::Source(Source::Os), Tag::Signal(sig)],
+		metadata: Default::default(),
+	}
+}
+
+/// Starts the script, sends the signals, and returns the end of the command and whether the
+/// action handler saw any signal.
+async fn signals_with(
+	script: &str,
+	configure: impl FnOnce(&mut RuntimeConfig),
+	signals: &[MainSignal],
+) -> (Option<ProcessEnd>, bool) {
+	let (end_s, mut end_r) = mpsc::channel(8);
+	let (sig_s, mut sig_r) = mpsc::channel(8);
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.command_shell(Shell::Unix("sh".into()));
+	runtime.command_grouped(false);
+	runtime.command([script]);
+	configure(&mut runtime);
+	runtime.on_action(move |action: Action| {
+		let end_s = end_s.clone();
+		let sig_s = sig_s.clone();
+		async move {
+			let mut completed = false;
+			for event in action.events.iter() {
+				if event.signals().next().is_some() {
+					sig_s.send(()).await.ok();
+				}
+				for end in event.completions() {
+					completed = true;
+					end_s.send(end).await.ok();
+				}
+			}
+
+			action.outcome(if completed {
+				Outcome::DoNothing
+			} else {
+				Outcome::if_running(Outcome::DoNothing, Outcome::Start)
+			});
+			Ok::<(), Infallible>(())
+		}
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+
+	wx.send_event(Event::default()).await.unwrap();
+	tokio::time::sleep(Duration::from_millis(300)).await;
+	for sig in signals {
+		wx.send_event(signal(*sig)).await.unwrap();
+	}
+
+	let end = timeout(Duration::from_secs(5), end_r.recv())
+		.await
+		.expect("command did not end")
+		.unwrap();
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+	(end, sig_r.try_recv().is_ok())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn mapped_signal_applies_outcome() {
+	let (end, handler_saw) = signals_with(
+		"sleep 10",
+		|rt| {
+			rt.signal_outcome(
+				MainSignal::User1,
+				Outcome::if_running(Outcome::Stop, Outcome::DoNothing),
+			);
+		},
+		&[MainSignal::User1],
+	)
+	.await;
+	assert!(matches!(end, Some(ProcessEnd::ExitSignal(_))), "{:?}", end);
+	assert!(!handler_saw);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn unmapped_signal_is_forwarded() {
+	let (end, handler_saw) = signals_with(
+		"trap 'exit 5' USR2; while true; do sleep 0.1; done",
+		|rt| {
+			rt.unmapped_signals(UnmappedSignals::Forward);
+		},
+		&[MainSignal::User2],
+	)
+	.await;
+	assert_eq!(end, Some(ProcessEnd::ExitError(5.try_into().unwrap())));
+	assert!(!handler_saw);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn unmapped_signal_is_ignored() {
+	let (end, handler_saw) = signals_with(
+		"trap 'exit 6' USR1; trap 'exit 5' USR2; while true; do sleep 0.1; done",
+		|rt| {
+			rt.signal_outcome(MainSignal::User1, Outcome::Signal(MainSignal::User1.into()))
+				.unmapped_signals(UnmappedSignals::Ignore);
+		},
+		&[MainSignal::User2, MainSignal::User1],
+	)
+	.await;
+	assert_eq!(end, Some(ProcessEnd::ExitError(6.try_into().unwrap())));
+	assert!(!handler_saw);
+}
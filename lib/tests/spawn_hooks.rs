@@ -0,0 +1,117 @@
+use std::{convert::Infallible, time::Duration};
+
+use tokio::{sync::mpsc, time::timeout};
+use watchexec::{
+	action::{Action, Outcome, PostExit, PostSpawn, PreSpawn},
+	command::Shell,
+	config::{InitConfig, RuntimeConfig},
+	event::{Event, ProcessEnd},
+	Watchexec,
+};
+
+fn runtime(command: &str) -> RuntimeConfig {
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime
+		.command_shell(Shell::Unix("sh".into()))
+		.command_grouped(false)
+		.command([command]);
+	runtime.on_action(|action: Action| async move {
+		let outcome = if action.events.iter().any(|e| e.is_completion()) {
+			Outcome::DoNothing
+		} else {
+			Outcome::Start
+		};
+		action.outcome(outcome);
+		Ok::<(), Infallible>(())
+	});
+	runtime
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn post_exit_gets_status() {
+	let (exit_s, mut exit_r) = mpsc::channel(8);
+	let (spawn_s, mut spawn_r) = mpsc::channel(8);
+
+	let mut runtime = runtime("exit 3");
+	runtime.on_post_spawn(move |post: PostSpawn| {
+		let spawn_s = spawn_s.clone();
+		async move {
+			spawn_s.send(post.id).await.ok();
+			Ok::<(), Infallible>(())
+		}
+	});
+	runtime.on_post_exit(move |post: PostExit| {
+		let exit_s = exit_s.clone();
+		async move {
+			exit_s.send(post).await.ok();
+			Ok::<(), Infallible>(())
+		}
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+
+	wx.send_event(Event::default()).await.unwrap();
+	let id = timeout(Duration::from_secs(5), spawn_r.recv())
+		.await
+		.expect("command was not spawned")
+		.unwrap();
+	let exited = timeout(Duration::from_secs(5), exit_r.recv())
+		.await
+		.expect("post-exit handler was not called")
+		.unwrap();
+
+	assert_eq!(exited.id, id);
+	assert_eq!(exited.command, vec!["exit 3".to_string()]);
+	assert_eq!(exited.events.len(), 1);
+	assert!(
+		matches!(exited.status, Some(ProcessEnd::ExitError(code)) if code.get() == 3),
+		"{:?}",
+		exited.status
+	);
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pre_spawn_can_veto() {
+	let (spawn_s, mut spawn_r) = mpsc::channel(8);
+	let (pre_s, mut pre_r) = mpsc::channel(8);
+
+	let mut runtime = runtime("true");
+	runtime.on_pre_spawn(move |pre: PreSpawn| {
+		let pre_s = pre_s.clone();
+		async move {
+			pre.veto();
+			pre_s.send(()).await.ok();
+			Ok::<(), Infallible>(())
+		}
+	});
+	runtime.on_post_spawn(move |_: PostSpawn| {
+		let spawn_s = spawn_s.clone();
+		async move {
+			spawn_s.send(()).await.ok();
+			Ok::<(), Infallible>(())
+		}
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+
+	wx.send_event(Event::default()).await.unwrap();
+	timeout(Duration::from_secs(5), pre_r.recv())
+		.await
+		.expect("pre-spawn handler was not called");
+	assert!(
+		timeout(Duration::from_millis(300), spawn_r.recv())
+			.await
+			.is_err(),
+		"command was spawned despite the veto"
+	);
+	assert_eq!(wx.current_child_pid(), None);
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
@@ -0,0 +1,149 @@
+#![cfg(unix)]
+
+use std::{convert::Infallible, time::Duration};
+
+use tokio::{sync::mpsc, time::timeout};
+use watchexec::{
+	action::{Action, Outcome},
+	command::Shell,
+	config::{InitConfig, RuntimeConfig},
+	event::{Event, ProcessEnd},
+	signal::process::SubSignal,
+	Watchexec,
+};
+
+async fn stop_with(script: &str, stop_timeout: Duration) -> (Option<ProcessEnd>, Vec<String>) {
+	let (end_s, mut end_r) = mpsc::channel(8);
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.command_shell(Shell::Unix("sh".into()));
+	runtime.command_grouped(false);
+	runtime.command([script]);
+	runtime
+		.command_stop_signal(SubSignal::Terminate)
+		.command_stop_timeout(stop_timeout);
+	runtime.on_action(move |action: Action| {
+		let end_s = end_s.clone();
+		async move {
+			let mut completed = false;
+			for event in action.events.iter() {
+				for end in event.completions() {
+					completed = true;
+					let by = event
+						.metadata
+						.get("stopped-by")
+						.cloned()
+						.unwrap_or_default();
+					end_s.send((end, by)).await.ok();
+				}
+			}
+
+			action.outcome(if completed {
+				Outcome::DoNothing
+			} else {
+				Outcome::if_running(Outcome::Stop, Outcome::Start)
+			});
+			Ok::<(), Infallible>(())
+		}
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+
+	wx.send_event(Event::default()).await.unwrap();
+	tokio::time::sleep(Duration::from_millis(300)).await;
+	wx.send_event(Event::default()).await.unwrap();
+
+	let end = timeout(Duration::from_secs(5), end_r.recv())
+		.await
+		.expect("command did not stop")
+		.unwrap();
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+	end
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn stop_with_soft_signal() {
+	let (end, by) = stop_with(
+		"trap 'exit 3' TERM; while true; do sleep 0.1; done",
+		Duration::from_secs(5),
+	)
+	.await;
+
+	assert_eq!(end, Some(ProcessEnd::ExitError(3.try_into().unwrap())));
+	assert_eq!(by, vec!["signal:Terminate".to_owned()]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn stop_escalates_after_timeout() {
+	let (end, by) = stop_with(
+		"trap '' TERM; while true; do sleep 0.1; done",
+		Duration::from_millis(200),
+	)
+	.await;
+
+	assert!(matches!(end, Some(ProcessEnd::ExitSignal(_))), "{:?}", end);
+	assert_eq!(by, vec!["kill".to_owned()]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn stop_reaches_grandchildren_when_grouped() {
+	let log = std::env::temp_dir().join(format!("watchexec-grandchild-{}", std::process::id()));
+	std::fs::remove_file(&log).ok();
+	let (end_s, mut end_r) = mpsc::channel(8);
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.command_shell(Shell::Unix("sh".into()));
+	runtime.command_grouped(true);
+	runtime.command([format!(
+		"(while true; do echo tick >> '{}'; sleep 0.05; done) & wait",
+		log.display()
+	)]);
+	runtime.on_action(move |action: Action| {
+		let end_s = end_s.clone();
+		async move {
+			let completed = action.events.iter().any(|event| event.is_completion());
+			if completed {
+				end_s.send(()).await.ok();
+			}
+
+			action.outcome(if completed {
+				Outcome::DoNothing
+			} else {
+				Outcome::if_running(Outcome::Stop, Outcome::Start)
+			});
+			Ok::<(), Infallible>(())
+		}
+	});
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+
+	let size = || std::fs::metadata(&log).map_or(0, |m| m.len());
+	wx.send_event(Event::default()).await.unwrap();
+	timeout(Duration::from_secs(5), async {
+		while size() == 0 {
+			tokio::time::sleep(Duration::from_millis(10)).await;
+		}
+	})
+	.await
+	.expect("grandchild did not start");
+
+	wx.send_event(Event::default()).await.unwrap();
+	timeout(Duration::from_secs(5), end_r.recv())
+		.await
+		.expect("command did not stop")
+		.unwrap();
+
+	let stopped = size();
+	tokio::time::sleep(Duration::from_millis(300)).await;
+	assert_eq!(size(), stopped, "grandchild is still running");
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+	std::fs::remove_file(&log).ok();
+}
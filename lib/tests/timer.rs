@@ -0,0 +1,90 @@
+use std::{convert::Infallible, time::Duration};
+
+use tokio::{
+	sync::mpsc,
+	time::{timeout, Instant},
+};
+use watchexec::{
+	action::{Action, Outcome},
+	config::{InitConfig, RuntimeConfig},
+	event::{Source, Tag},
+	Watchexec,
+};
+
+fn runtime(timer: Option<Duration>, ticks: mpsc::Sender<Duration>) -> RuntimeConfig {
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.action_throttle(Duration::from_millis(10));
+	runtime.timer(timer);
+	runtime.on_action(move |action: Action| {
+		let ticks = ticks.clone();
+		async move {
+			for event in action.events.iter() {
+				assert!(event.sources().any(|s| s == Source::Time));
+				for tag in &event.tags {
+					if let Tag::Timer(period) = tag {
+						ticks.send(*period).await.ok();
+					}
+				}
+			}
+			action.outcome(Outcome::DoNothing);
+			Ok::<(), Infallible>(())
+		}
+	});
+	runtime
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn timer_fires_every_interval() {
+	let (tk_s, mut tk_r) = mpsc::channel(8);
+	let period = Duration::from_millis(200);
+
+	let wx = Watchexec::new(InitConfig::default(), runtime(Some(period), tk_s)).unwrap();
+	let started = Instant::now();
+	let main = wx.main();
+
+	for _ in 0..2 {
+		assert_eq!(
+			timeout(Duration::from_secs(5), tk_r.recv()).await.unwrap(),
+			Some(period)
+		);
+	}
+	assert!(started.elapsed() >= period * 2);
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reconfiguring_the_timer_resets_it() {
+	let (tk_s, mut tk_r) = mpsc::channel(8);
+
+	let mut runtime = runtime(None, tk_s);
+	let wx = Watchexec::new(InitConfig::default(), runtime.clone()).unwrap();
+	let main = wx.main();
+
+	assert!(timeout(Duration::from_millis(300), tk_r.recv())
+		.await
+		.is_err());
+
+	let period = Duration::from_millis(100);
+	runtime.timer(Some(period));
+	assert!(wx.reconfigure(runtime.clone()).unwrap().timer);
+	assert_eq!(
+		timeout(Duration::from_secs(5), tk_r.recv()).await.unwrap(),
+		Some(period)
+	);
+
+	runtime.timer(None);
+	assert!(wx.reconfigure(runtime).unwrap().timer);
+	while timeout(Duration::from_millis(50), tk_r.recv())
+		.await
+		.is_ok()
+	{}
+	assert!(timeout(Duration::from_millis(300), tk_r.recv())
+		.await
+		.is_err());
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
@@ -0,0 +1,90 @@
+use watchexec::{
+	command::Shell,
+	config::{InitConfig, RuntimeConfig, RuntimeConfigPatch},
+	error::CriticalError,
+	filter::expr::Filter,
+	Watchexec,
+};
+
+fn invalid(init: InitConfig, runtime: RuntimeConfig) -> String {
+	match Watchexec::new(init, runtime) {
+		Err(CriticalError::ConfigInvalid(msg)) => msg,
+		Err(err) => panic!("expected ConfigInvalid, got {:?}", err),
+		Ok(_) => panic!("expected ConfigInvalid, got a Watchexec"),
+	}
+}
+
+#[tokio::test]
+async fn zero_sized_channels_are_rejected() {
+	let mut init = InitConfig::default();
+	init.event_channel_size(0).error_channel_size(0);
+	let msg = invalid(init, RuntimeConfig::default());
+	assert!(msg.contains("event_channel_size"), "{}", msg);
+	assert!(msg.contains("error_channel_size"), "{}", msg);
+}
+
+#[tokio::test]
+async fn command_with_nothing_to_run_is_rejected() {
+	let mut runtime = RuntimeConfig::default();
+	runtime.command(["", "arg"]).command_shell(Shell::None);
+	let msg = invalid(InitConfig::default(), runtime.clone());
+	assert!(msg.contains("command program is empty"), "{}", msg);
+
+	runtime
+		.command([" "])
+		.command_shell(Shell::Unix("sh".into()));
+	let msg = invalid(InitConfig::default(), runtime.clone());
+	assert!(msg.contains("command is blank"), "{}", msg);
+
+	runtime
+		.command(["true"])
+		.command_shell(Shell::Unix("".into()));
+	let msg = invalid(InitConfig::default(), runtime.clone());
+	assert!(msg.contains("shell program is empty"), "{}", msg);
+
+	runtime
+		.command_shell(Shell::Unix("sh".into()))
+		.parallel_commands([vec![" "], vec![]]);
+	let msg = invalid(InitConfig::default(), runtime);
+	assert!(msg.contains("parallel command is blank"), "{}", msg);
+	assert!(msg.contains("parallel command is empty"), "{}", msg);
+}
+
+#[tokio::test]
+async fn malformed_globs_are_rejected() {
+	let mut patch = RuntimeConfigPatch::default();
+	patch.ignore_globs(["*.tmp", "a["]).watch_globs(["b{"]);
+	let mut runtime = RuntimeConfig::default();
+	runtime.merge(patch);
+	let msg = invalid(InitConfig::default(), runtime);
+	assert!(
+		msg.contains("ignore_globs") && msg.contains("a["),
+		"{}",
+		msg
+	);
+	assert!(msg.contains("watch_globs") && msg.contains("b{"), "{}", msg);
+}
+
+#[tokio::test]
+async fn empty_command_is_valid() {
+	let mut runtime = RuntimeConfig::default();
+	runtime.command(Vec::<String>::new());
+	Watchexec::new(InitConfig::default(), runtime).unwrap();
+}
+
+#[cfg(not(feature = "serde"))]
+#[tokio::test]
+async fn events_file_needs_serde() {
+	let mut runtime = RuntimeConfig::default();
+	runtime.command_events_file(true);
+	let msg = invalid(InitConfig::default(), runtime);
+	assert!(msg.contains("serde feature"), "{}", msg);
+}
+
+#[tokio::test]
+async fn invalid_filter_globs_are_rejected() {
+	let mut runtime = RuntimeConfig::default();
+	runtime.filters([Filter::select("*.rs"), Filter::ignore("src/[")]);
+	let msg = invalid(InitConfig::default(), runtime);
+	assert!(msg.contains("filters: invalid glob `src/[`"), "{}", msg);
+}
@@ -0,0 +1,248 @@
+use std::{convert::Infallible, path::PathBuf, sync::Arc, time::Duration};
+
+use tokio::{
+	sync::mpsc,
+	time::{sleep, timeout},
+};
+use watchexec::{
+	action::Action,
+	config::{InitConfig, RuntimeConfig},
+	error::RuntimeError,
+	fs::WatchedPath,
+	Lifecycle, Watchexec,
+};
+
+async fn wait_for_watched(wx: &Watchexec, expected: Vec<PathBuf>) {
+	timeout(Duration::from_secs(5), async {
+		while wx.watched_paths() != expected {
+			sleep(Duration::from_millis(10)).await;
+		}
+	})
+	.await
+	.unwrap_or_else(|_| panic!("watched paths {:?} != {:?}", wx.watched_paths(), expected));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn watched_paths_follow_reconfigure() {
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_signal(false);
+
+	let wx = Watchexec::new(InitConfig::default(), runtime.clone()).unwrap();
+	let main = wx.main();
+	assert!(wx.watched_paths().is_empty());
+
+	runtime.pathset(["src", "tests"]);
+	wx.reconfigure(runtime.clone()).unwrap();
+	wait_for_watched(&wx, vec!["src".into(), "tests".into()]).await;
+
+	runtime.pathset(["tests"]);
+	wx.reconfigure(runtime.clone()).unwrap();
+	wait_for_watched(&wx, vec!["tests".into()]).await;
+
+	runtime.pathset(Vec::<PathBuf>::new());
+	wx.reconfigure(runtime).unwrap();
+	wait_for_watched(&wx, Vec::new()).await;
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pathset_changes_keep_the_watcher() {
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_signal(false).pathset(["src"]);
+
+	let wx = Watchexec::new(InitConfig::default(), runtime.clone()).unwrap();
+	let mut lifecycle = wx.lifecycle();
+	let main = wx.main();
+	wait_for_watched(&wx, vec!["src".into()]).await;
+
+	runtime.pathset(["src", "tests"]);
+	wx.reconfigure_async(runtime.clone()).await.unwrap();
+	runtime.pathset(Vec::<PathBuf>::new());
+	wx.reconfigure_async(runtime.clone()).await.unwrap();
+	assert!(wx.watched_paths().is_empty());
+	runtime.pathset(["tests"]);
+	wx.reconfigure_async(runtime).await.unwrap();
+	assert_eq!(wx.watched_paths(), vec![PathBuf::from("tests")]);
+
+	// give a reinit event the time to go through the action worker
+	sleep(Duration::from_millis(100)).await;
+	while let Ok(event) = lifecycle.try_recv() {
+		assert_ne!(event, Lifecycle::WatcherRebuilt);
+	}
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn watched_paths_omit_missing() {
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_signal(false);
+
+	let wx = Watchexec::new(InitConfig::default(), runtime.clone()).unwrap();
+	let main = wx.main();
+
+	runtime.pathset(["src", "does-not-exist-for-watchexec-tests"]);
+	wx.reconfigure(runtime).unwrap();
+	wait_for_watched(&wx, vec!["src".into()]).await;
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn missing_path_is_reported_then_watched_once_created() {
+	let base = std::env::temp_dir().join(format!("watchexec-test-missing-{}", std::process::id()));
+	let present = base.join("present");
+	let optional = base.join("optional");
+	std::fs::create_dir_all(&present).unwrap();
+
+	let (er_s, mut er_r) = mpsc::channel(8);
+	let mut init = InitConfig::default();
+	init.on_error(move |err: Arc<RuntimeError>| {
+		let er_s = er_s.clone();
+		async move {
+			if let RuntimeError::FsWatcherPathAdd { path, .. } = &*err {
+				er_s.send(path.clone()).await.ok();
+			}
+			Ok::<(), Infallible>(())
+		}
+	});
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_signal(false);
+
+	let wx = Watchexec::new(init, runtime.clone()).unwrap();
+	let main = wx.main();
+
+	runtime.pathset([&present, &optional]);
+	wx.reconfigure(runtime.clone()).unwrap();
+	wait_for_watched(&wx, vec![present.clone()]).await;
+	assert_eq!(
+		timeout(Duration::from_secs(5), er_r.recv()).await.unwrap(),
+		Some(optional.clone())
+	);
+
+	std::fs::create_dir_all(&optional).unwrap();
+	wx.reconfigure_force(runtime).unwrap();
+	wait_for_watched(&wx, vec![optional.clone(), present.clone()]).await;
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+	std::fs::remove_dir_all(&base).ok();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn non_recursive_path_ignores_descendants() {
+	let base = std::env::temp_dir().join(format!("watchexec-test-nonrec-{}", std::process::id()));
+	let nested = base.join("nested");
+	std::fs::create_dir_all(&nested).unwrap();
+	let base = dunce::canonicalize(base).unwrap();
+	let nested = base.join("nested");
+
+	let (ev_s, mut ev_r) = mpsc::unbounded_channel();
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_signal(false);
+	runtime.on_action(move |action: Action| {
+		let ev_s = ev_s.clone();
+		async move {
+			for event in action.events.iter() {
+				for (path, _) in event.paths() {
+					ev_s.send(path.to_owned()).ok();
+				}
+			}
+			Ok::<(), Infallible>(())
+		}
+	});
+	runtime
+		.pathset(Vec::<PathBuf>::new())
+		.add_path(WatchedPath::non_recursive(&base));
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+	wait_for_watched(&wx, vec![base.clone()]).await;
+
+	let deep = nested.join("deep.txt");
+	let top = base.join("top.txt");
+	std::fs::write(&deep, "deep").unwrap();
+	sleep(Duration::from_millis(200)).await;
+	std::fs::write(&top, "top").unwrap();
+
+	let seen = timeout(Duration::from_secs(5), async {
+		let mut seen = Vec::new();
+		while let Some(path) = ev_r.recv().await {
+			let done = path == top;
+			seen.push(path);
+			if done {
+				break;
+			}
+		}
+		seen
+	})
+	.await
+	.expect("no event for the direct child");
+	assert!(!seen.contains(&deep), "got events for {:?}", seen);
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+	std::fs::remove_dir_all(&base).ok();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn missing_path_is_watched_when_created_without_reconfigure() {
+	let base = std::env::temp_dir().join(format!("watchexec-test-pending-{}", std::process::id()));
+	std::fs::create_dir_all(&base).unwrap();
+	let base = dunce::canonicalize(base).unwrap();
+	let dist = base.join("dist").join("out");
+
+	let (ev_s, mut ev_r) = mpsc::unbounded_channel();
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_signal(false);
+	runtime.on_action(move |action: Action| {
+		let ev_s = ev_s.clone();
+		async move {
+			for event in action.events.iter() {
+				for (path, _) in event.paths() {
+					ev_s.send(path.to_owned()).ok();
+				}
+			}
+			Ok::<(), Infallible>(())
+		}
+	});
+	runtime.pathset([&dist]);
+
+	let wx = Watchexec::new(InitConfig::default(), runtime).unwrap();
+	let main = wx.main();
+	sleep(Duration::from_millis(200)).await;
+	assert!(wx.watched_paths().is_empty());
+
+	let sibling = base.join("sibling.txt");
+	std::fs::write(&sibling, "unrelated").unwrap();
+	std::fs::create_dir(base.join("dist")).unwrap();
+	sleep(Duration::from_millis(200)).await;
+	std::fs::create_dir(&dist).unwrap();
+	wait_for_watched(&wx, vec![dist.clone()]).await;
+
+	let inside = dist.join("bundle.js");
+	std::fs::write(&inside, "built").unwrap();
+	let seen = timeout(Duration::from_secs(5), async {
+		let mut seen = Vec::new();
+		while let Some(path) = ev_r.recv().await {
+			let done = path == inside;
+			seen.push(path);
+			if done {
+				break;
+			}
+		}
+		seen
+	})
+	.await
+	.expect("no event from inside the created path");
+	assert!(!seen.contains(&sibling), "got events for {:?}", seen);
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+	std::fs::remove_dir_all(&base).ok();
+}
@@ -0,0 +1,114 @@
+#![cfg(unix)]
+
+use std::{
+	convert::Infallible,
+	path::{Path, PathBuf},
+	sync::Arc,
+	time::Duration,
+};
+
+use tokio::{sync::mpsc, time::timeout};
+use watchexec::{
+	action::{Action, Outcome},
+	config::{InitConfig, RuntimeConfig},
+	error::RuntimeError,
+	event::{Event, ProcessEnd},
+	Watchexec,
+};
+
+fn temp_dir(name: &str) -> PathBuf {
+	let dir = std::env::temp_dir().join(format!("watchexec-test-{}-{}", name, std::process::id()));
+	std::fs::create_dir_all(&dir).unwrap();
+	dunce::canonicalize(dir).unwrap()
+}
+
+/// Runs a command checking it's in `expected`, returning its end or the error from the spawn.
+async fn run_in(
+	workdir: Option<PathBuf>,
+	action_workdir: Option<PathBuf>,
+	expected: &Path,
+) -> Result<Option<ProcessEnd>, Arc<RuntimeError>> {
+	let (end_s, mut end_r) = mpsc::channel(8);
+
+	let er_s = end_s.clone();
+	let mut init = InitConfig::default();
+	init.on_error(move |err: Arc<RuntimeError>| {
+		let er_s = er_s.clone();
+		async move {
+			er_s.send(Err(err)).await.ok();
+			Ok::<(), Infallible>(())
+		}
+	});
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.enable_fs(false).enable_signal(false);
+	runtime.command([
+		"sh".to_string(),
+		"-c".to_string(),
+		format!("test \"$(pwd -P)\" = '{}'", expected.display()),
+	]);
+	runtime.command_grouped(false).command_workdir(workdir);
+	runtime.on_action(move |action: Action| {
+		let end_s = end_s.clone();
+		let action_workdir = action_workdir.clone();
+		async move {
+			let ends: Vec<_> = action.events.iter().flat_map(Event::completions).collect();
+			if ends.is_empty() {
+				if let Some(dir) = action_workdir {
+					action.workdir(dir);
+				}
+				action.outcome(Outcome::Start);
+			} else {
+				for end in ends {
+					end_s.send(Ok(end)).await.ok();
+				}
+				action.outcome(Outcome::DoNothing);
+			}
+			Ok::<(), Infallible>(())
+		}
+	});
+
+	let wx = Watchexec::new(init, runtime).unwrap();
+	let main = wx.main();
+
+	wx.send_event(Event::default()).await.unwrap();
+	let res = timeout(Duration::from_secs(5), end_r.recv())
+		.await
+		.expect("command did not complete")
+		.unwrap();
+
+	wx.shutdown(None).await.unwrap();
+	main.await.unwrap().unwrap();
+	res
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn command_runs_in_workdir() {
+	let dir = temp_dir("workdir");
+	assert_eq!(
+		run_in(Some(dir.clone()), None, &dir).await.unwrap(),
+		Some(ProcessEnd::Success)
+	);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn action_overrides_workdir() {
+	let dir = temp_dir("workdir-override");
+	assert_eq!(
+		run_in(Some(std::env::temp_dir()), Some(dir.clone()), &dir)
+			.await
+			.unwrap(),
+		Some(ProcessEnd::Success)
+	);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn missing_workdir_is_reported() {
+	let dir = std::env::temp_dir().join("watchexec-test-workdir-does-not-exist");
+	let err = run_in(Some(dir.clone()), None, &dir).await.unwrap_err();
+	assert!(
+		matches!(&*err, RuntimeError::CommandWorkdir { path, .. } if path == &dir),
+		"unexpected error: {:?}",
+		err
+	);
+}